@@ -0,0 +1,201 @@
+// Project Modules
+use ln_ms_lib::LnSimulation;
+use ln_ms_lib::sim_results::SimResults;
+use ln_ms_lib::sim_scenario::SimScenario;
+
+// Standard Modules
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+
+// External Modules
+use anyhow::{Result, anyhow};
+use clap::{Parser, Subcommand, ValueEnum};
+
+/*
+ * This is the main entry point for the simulation command line tool. This module exposes the ln_ms_lib library api to scripts and CI pipelines.
+ */
+
+#[derive(Parser)]
+#[command(name = "ln-ms", about = "Lightning Network Modeling and Simulation Framework")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a simulation scenario and write the results to a directory
+    Run {
+        /// The scenario file to run (json, toml or yaml)
+        scenario: String,
+        /// The directory to write the results to
+        #[arg(short, long, default_value = "results")]
+        output: String,
+        /// Start and stop bitcoind with nigiri
+        #[arg(long)]
+        nigiri: bool,
+        /// Check that the balances in the results add up after every event (slow)
+        #[arg(long)]
+        check_invariants: bool,
+        /// Save the balances and channels that each node reports every this many seconds
+        #[arg(long)]
+        snapshot_interval: Option<u64>
+    },
+    /// Check a simulation scenario for problems without running it
+    Validate {
+        /// The scenario file to check (json, toml or yaml)
+        scenario: String
+    },
+    /// Create a simulation scenario from the output of lncli describegraph, fwdinghistory and listpayments
+    Import {
+        /// The describegraph json file that defines the network
+        #[arg(short, long)]
+        graph: String,
+        /// The import map json file that assigns a profile to each node (uses the default map if not given)
+        #[arg(short, long, default_value = "")]
+        import_map: String,
+        /// The fwdinghistory or listpayments json files to create transaction events from
+        #[arg(short, long)]
+        transactions: Vec<String>,
+        /// The name of the simulation
+        #[arg(short, long, default_value = "imported")]
+        name: String,
+        /// The duration of the simulation in seconds
+        #[arg(short, long, default_value_t = 60)]
+        duration: u64,
+        /// The scenario json file to write
+        #[arg(short, long, default_value = "scenario.json")]
+        output: String
+    },
+    /// Render the results of a previous run as an html page, csv file or the fwdinghistory of a node
+    Report {
+        /// The results json file written by the run command
+        results: String,
+        /// The format of the report
+        #[arg(short, long, value_enum, default_value_t = ReportFormat::Html)]
+        format: ReportFormat,
+        /// The file to write the report to
+        #[arg(short, long)]
+        output: String,
+        /// The routing node to export the forwards of (fwdinghistory format only)
+        #[arg(long)]
+        node: Option<String>
+    }
+}
+
+#[derive(Clone, ValueEnum)]
+enum ReportFormat {
+    Html,
+    Csv,
+    Fwdinghistory
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Run { scenario, output, nigiri, check_invariants, snapshot_interval } => run(scenario, output, nigiri, check_invariants, snapshot_interval),
+        Command::Validate { scenario } => validate(scenario),
+        Command::Import { graph, import_map, transactions, name, duration, output } => import(graph, import_map, transactions, name, duration, output),
+        Command::Report { results, format, output, node } => report(results, format, output, node)
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/*
+ * Run a scenario and save the results as json, html and csv
+ */
+fn run(scenario_file: String, output: String, nigiri: bool, check_invariants: bool, snapshot_interval: Option<u64>) -> Result<()> {
+    let mut ln_sim = load_simulation(&scenario_file)?;
+    ln_sim.set_invariant_checks(check_invariants);
+    ln_sim.set_snapshot_interval(snapshot_interval);
+    let results = ln_sim.run(nigiri)?;
+
+    let output_dir = Path::new(&output);
+    fs::create_dir_all(output_dir)?;
+    fs::write(output_dir.join("results.json"), results.to_json()?)?;
+    fs::write(output_dir.join("results.html"), results.get_results_page())?;
+    fs::write(output_dir.join("results.csv"), results.get_results_csv())?;
+    let violations = results.get_invariant_violations();
+    if !violations.is_empty() {
+        eprintln!("{} invariant violation(s), see the results for the events that caused them", violations.len());
+    }
+    println!("Results written to {}", output);
+    Ok(())
+}
+
+/*
+ * Check a scenario and print each problem that was found
+ */
+fn validate(scenario_file: String) -> Result<()> {
+    load_simulation(&scenario_file)?;
+    println!("{} is valid", scenario_file);
+    Ok(())
+}
+
+/*
+ * Create a scenario from lncli output files
+ */
+fn import(graph: String, import_map: String, transactions: Vec<String>, name: String, duration: u64, output: String) -> Result<()> {
+    let mut ln_sim = LnSimulation::new(name, duration, 0);
+    ln_sim.import_network(graph, import_map);
+    for t in transactions {
+        ln_sim.import_transactions(t);
+    }
+
+    let scenario = ln_sim.get_scenario();
+    check_scenario(&scenario)?;
+    scenario.save(&output)?;
+    println!("Scenario written to {}", output);
+    Ok(())
+}
+
+/*
+ * Render saved results as an html page, csv file or the fwdinghistory of a node
+ */
+fn report(results_file: String, format: ReportFormat, output: String, node: Option<String>) -> Result<()> {
+    let results = SimResults::from_json(&fs::read_to_string(&results_file)?)?;
+    let report = match format {
+        ReportFormat::Html => results.get_results_page(),
+        ReportFormat::Csv => results.get_results_csv(),
+        ReportFormat::Fwdinghistory => {
+            let node = node.ok_or(anyhow!("the fwdinghistory format needs a --node"))?;
+            results.get_forwarding_history(&node)?
+        }
+    };
+    fs::write(&output, report)?;
+    println!("Report written to {}", output);
+    Ok(())
+}
+
+/*
+ * Load a scenario file and make sure that it is valid
+ */
+fn load_simulation(scenario_file: &String) -> Result<LnSimulation> {
+    let ln_sim = LnSimulation::from_scenario_file(scenario_file.clone())?;
+    check_scenario(&ln_sim.get_scenario())?;
+    Ok(ln_sim)
+}
+
+/*
+ * Print the problems with a scenario and return an error if there are any
+ */
+fn check_scenario(scenario: &SimScenario) -> Result<()> {
+    let problems = scenario.validate();
+    for p in &problems {
+        eprintln!("  {}", p);
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!("scenario {} has {} problem(s)", scenario.name, problems.len()))
+    }
+}
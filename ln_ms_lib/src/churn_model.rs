@@ -0,0 +1,161 @@
+// Project Modules
+use crate::sim_event::SimulationEvent;
+
+// External Modules
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand_distr::{Distribution, Exp};
+use serde::{Serialize, Deserialize};
+
+/*
+ * How the nodes go offline and come back online during the simulation
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "model", rename_all = "snake_case")]
+pub enum ChurnModel {
+    Exponential { mean_uptime: f64, mean_downtime: f64 }, // each node goes on and offline on its own, the length of each online and offline period (seconds) is exponentially distributed
+    Diurnal { period: u64, online_fraction: f64 }, // each node is online for the same part of every period starting at a random offset, like a mobile wallet that is only open during the day
+    CorrelatedOutage { rate: f64, mean_duration: f64 } // outages arrive with poisson arrivals at the rate (per second) and take all the nodes offline at once for an exponentially distributed duration (seconds)
+}
+
+/*
+ * The configuration of the availability of a set of nodes
+ * The nodes are expected to be online at the start time, any node that is offline at the end time is started again
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChurnConfig {
+    #[serde(flatten)]
+    pub model: ChurnModel,
+    pub nodes: Vec<String>, // the nodes that go on and offline
+    pub start: u64, // the first second of the simulation that the model is applied
+    pub end: u64, // the last second of the simulation that the model is applied
+    pub seed: u64 // the seed for the random number generator so that the same events are generated each run
+}
+
+impl ChurnConfig {
+    /*
+     * Get the stop and start node events that this model creates and the simulation time of each one
+     */
+    pub fn generate_events(&self) -> Vec<(u64, SimulationEvent)> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut events: Vec<(u64, SimulationEvent)> = Vec::new();
+        if self.end <= self.start {
+            return events;
+        }
+
+        match &self.model {
+            ChurnModel::Exponential { mean_uptime, mean_downtime } => {
+                for node in &self.nodes {
+                    let mut time = self.start;
+                    let mut online = true;
+                    loop {
+                        let mean = if online { *mean_uptime } else { *mean_downtime };
+                        time = time + ChurnConfig::sample_duration(&mut rng, mean);
+                        if time >= self.end {
+                            break;
+                        }
+                        events.push((time, ChurnConfig::status_event(node, !online)));
+                        online = !online;
+                    }
+                    if !online {
+                        events.push((self.end, ChurnConfig::status_event(node, true)));
+                    }
+                }
+            },
+            ChurnModel::Diurnal { period, online_fraction } => {
+                if *period == 0 {
+                    return events;
+                }
+                let online_time = (*period as f64 * online_fraction).round() as u64;
+                for node in &self.nodes {
+                    // The node comes online at the offset in every period
+                    let offset = rng.gen_range(0..*period);
+                    let mut online = true;
+                    for time in self.start..self.end {
+                        let should_be_online = (time + period - offset) % period < online_time;
+                        if should_be_online != online {
+                            events.push((time, ChurnConfig::status_event(node, should_be_online)));
+                            online = should_be_online;
+                        }
+                    }
+                    if !online {
+                        events.push((self.end, ChurnConfig::status_event(node, true)));
+                    }
+                }
+            },
+            ChurnModel::CorrelatedOutage { rate, mean_duration } => {
+                if *rate <= 0.0 {
+                    return events;
+                }
+                let mut time = self.start;
+                loop {
+                    time = time + ChurnConfig::sample_duration(&mut rng, 1.0 / rate);
+                    if time >= self.end {
+                        break;
+                    }
+                    let recovered = (time + ChurnConfig::sample_duration(&mut rng, *mean_duration)).min(self.end);
+                    for node in &self.nodes {
+                        events.push((time, ChurnConfig::status_event(node, false)));
+                        events.push((recovered, ChurnConfig::status_event(node, true)));
+                    }
+                    time = recovered;
+                }
+            }
+        }
+
+        events
+    }
+
+    /*
+     * Get a list of problems with the parameters of this model (empty if it is valid)
+     */
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+        match &self.model {
+            ChurnModel::Exponential { mean_uptime, mean_downtime } => {
+                if *mean_uptime <= 0.0 || *mean_downtime <= 0.0 {
+                    problems.push(String::from("mean_uptime and mean_downtime must be greater than 0"));
+                }
+            },
+            ChurnModel::Diurnal { period, online_fraction } => {
+                if *period == 0 {
+                    problems.push(String::from("period must be greater than 0"));
+                }
+                if *online_fraction < 0.0 || *online_fraction > 1.0 {
+                    problems.push(String::from("online_fraction must be between 0 and 1"));
+                }
+            },
+            ChurnModel::CorrelatedOutage { rate, mean_duration } => {
+                if *rate <= 0.0 || *mean_duration <= 0.0 {
+                    problems.push(String::from("rate and mean_duration must be greater than 0"));
+                }
+            }
+        }
+        if self.end <= self.start {
+            problems.push(format!("end time {} must be after the start time {}", self.end, self.start));
+        }
+
+        problems
+    }
+
+    /*
+     * Pick the length of a period in whole seconds (at least 1) from an exponential distribution with this mean
+     */
+    fn sample_duration(rng: &mut StdRng, mean: f64) -> u64 {
+        match Exp::new(1.0 / mean) {
+            Ok(d) => (d.sample(rng).round() as u64).max(1),
+            Err(_) => u64::MAX / 2
+        }
+    }
+
+    /*
+     * Create the event that starts or stops a node
+     */
+    fn status_event(node: &String, online: bool) -> SimulationEvent {
+        if online {
+            SimulationEvent::StartNodeEvent(node.clone())
+        } else {
+            SimulationEvent::StopNodeEvent(node.clone())
+        }
+    }
+}
@@ -0,0 +1,129 @@
+// Project Modules
+use crate::sim_event::{SimulationEvent, SimFeeMarketBlock};
+
+// Standard Modules
+use std::fs;
+
+// External Modules
+use anyhow::{Result, anyhow};
+use serde::{Serialize, Deserialize};
+
+/*
+ * The market feerate from a point in time until the next point in the schedule
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FeeratePoint {
+    pub time: u64, // sim time in seconds
+    pub feerate: u64 // sats per vbyte
+}
+
+/*
+ * The configuration of the on-chain fee market
+ * A block is mined every block_interval seconds and only includes the transactions that pay at least the market feerate at that time
+ * Filler transactions are broadcast at the market feerate before each block so that the fee estimates of the nodes follow the schedule
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FeeMarketConfig {
+    pub schedule: Vec<FeeratePoint>, // the market feerate over time, sorted by time
+    pub block_interval: u64, // the number of seconds between blocks
+    pub filler_txs: u64, // the number of filler transactions that are broadcast before each block
+    pub start: u64, // the first second of the simulation that the fee market is running
+    pub end: u64 // the last second of the simulation that the fee market is running, the last block confirms every transaction that is still waiting
+}
+
+impl FeeMarketConfig {
+    /*
+     * Get the market feerate at a time in the simulation, the first point of the schedule is used before it starts
+     */
+    pub fn feerate_at(&self, time: u64) -> u64 {
+        match self.schedule.iter().rev().find(|p| p.time <= time) {
+            Some(p) => p.feerate,
+            None => self.schedule.first().map_or(1, |p| p.feerate)
+        }
+    }
+
+    /*
+     * Get the block events that the fee market creates and the simulation time of each one
+     */
+    pub fn generate_events(&self) -> Vec<(u64, SimulationEvent)> {
+        let mut events: Vec<(u64, SimulationEvent)> = Vec::new();
+        if self.block_interval == 0 || self.end < self.start {
+            return events;
+        }
+
+        let mut times: Vec<u64> = (self.start..=self.end).step_by(self.block_interval as usize).collect();
+        if times.last() != Some(&self.end) {
+            times.push(self.end);
+        }
+        for time in &times {
+            let block = SimFeeMarketBlock {
+                feerate_sat_per_vbyte: self.feerate_at(*time),
+                filler_txs: self.filler_txs,
+                last: *time == self.end,
+                confirmed_txs: None,
+                mempool_txs: None
+            };
+            events.push((*time, SimulationEvent::FeeMarketBlockEvent(block)));
+        }
+
+        events
+    }
+
+    /*
+     * Get a list of problems with the parameters of the fee market (empty if it is valid)
+     */
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+        if self.schedule.is_empty() {
+            problems.push(String::from("the schedule needs at least one feerate"));
+        }
+        if self.schedule.iter().any(|p| p.feerate == 0) {
+            problems.push(String::from("every feerate in the schedule must be greater than 0"));
+        }
+        if self.schedule.windows(2).any(|w| w[1].time < w[0].time) {
+            problems.push(String::from("the schedule must be sorted by time"));
+        }
+        if self.block_interval == 0 {
+            problems.push(String::from("block interval must be greater than 0"));
+        }
+        if self.end < self.start {
+            problems.push(format!("end time {} must not be before the start time {}", self.end, self.start));
+        }
+
+        problems
+    }
+
+    /*
+     * Load a feerate schedule from a historical mempool profile, a csv file with a unix timestamp and a feerate (sats per vbyte) on each line
+     * The first timestamp is sim time = 0 and the time between the points is divided by speedup, lines that can not be parsed (like a header) are skipped
+     */
+    pub fn load_profile(filename: &String, speedup: f64) -> Result<Vec<FeeratePoint>> {
+        if speedup <= 0.0 {
+            return Err(anyhow!("speedup must be greater than 0"));
+        }
+        let contents = fs::read_to_string(filename)?;
+        let mut rows: Vec<(f64, f64)> = Vec::new();
+        for line in contents.lines() {
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            if fields.len() < 2 {
+                continue;
+            }
+            match (fields[0].parse::<f64>(), fields[1].parse::<f64>()) {
+                (Ok(timestamp), Ok(feerate)) => rows.push((timestamp, feerate)),
+                _ => continue
+            }
+        }
+        if rows.is_empty() {
+            return Err(anyhow!("no feerates found in {}", filename));
+        }
+
+        rows.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let first = rows[0].0;
+        let schedule = rows.iter().map(|(timestamp, feerate)| FeeratePoint {
+            time: ((timestamp - first) / speedup).round() as u64,
+            feerate: (feerate.round() as u64).max(1)
+        }).collect();
+
+        Ok(schedule)
+    }
+}
@@ -0,0 +1,126 @@
+// Project Modules
+use crate::sim_event::{SimulationEvent, SimHeldPayment};
+
+// External Modules
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand_distr::{Distribution, Exp};
+use serde::{Serialize, Deserialize};
+
+/*
+ * How the attacker jams the channels between its two nodes
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "attack", rename_all = "snake_case")]
+pub enum JammingAttack {
+    Slow { htlcs: u64 }, // the attacker keeps a number of HTLCs held at its destination, each one is held until the destination fails it back a few blocks before its cltv expiry and it is sent again right away
+    Fast { rate: f64 } // the attacker sends small payments with poisson arrivals at the rate (per second) and its destination fails each one back after a second, so the HTLC slots and liquidity are always in use
+}
+
+/*
+ * The configuration of a jamming attack
+ * The attacker sends payments from src to dest, both are attacker controlled nodes, dest holds each HTLC because it does not know the preimage
+ * The hold relies on the event handler of the dest node ignoring a received payment that it has no preimage for, instead of failing it back right away
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JammingConfig {
+    #[serde(flatten)]
+    pub attack: JammingAttack,
+    pub src: String, // the attacker node that sends the payments
+    pub dest: String, // the attacker node that holds the payments
+    pub amount: u64, // the amount of each payment in sats
+    pub start: u64, // the first second of the simulation that the attack is running
+    pub end: u64, // the last second of the simulation that the attack is running, every payment that is still held is released at this time
+    pub seed: u64 // the seed for the random number generator so that the same events are generated each run
+}
+
+impl JammingConfig {
+    /*
+     * Get the hold and release payment events that this attack creates and the simulation time of each one
+     * Each held payment gets an id, starting at first_id, that is used to release it
+     */
+    pub fn generate_events(&self, first_id: u64) -> Vec<(u64, SimulationEvent)> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut events: Vec<(u64, SimulationEvent)> = Vec::new();
+        if self.end <= self.start {
+            return events;
+        }
+
+        let mut id = first_id;
+        match &self.attack {
+            JammingAttack::Slow { htlcs } => {
+                // The release time depends on the blocks that are mined, the network analyzer sends each payment again when it expires until the attack ends
+                for _ in 0..*htlcs {
+                    let mut payment = self.held_payment(id);
+                    payment.resend_until = Some(self.end);
+                    events.push((self.start, SimulationEvent::HoldPaymentEvent(payment)));
+                    events.push((self.end, SimulationEvent::ReleaseHeldPaymentEvent(id)));
+                    id = id + 1;
+                }
+            },
+            JammingAttack::Fast { rate } => {
+                let arrivals = match Exp::new(*rate) {
+                    Ok(d) => d,
+                    Err(_) => return events
+                };
+                let mut time = self.start as f64;
+                loop {
+                    time = time + arrivals.sample(&mut rng);
+                    if time.round() as u64 >= self.end {
+                        break;
+                    }
+                    let sent = time.round() as u64;
+                    events.push((sent, SimulationEvent::HoldPaymentEvent(self.held_payment(id))));
+                    events.push((sent + 1, SimulationEvent::ReleaseHeldPaymentEvent(id)));
+                    id = id + 1;
+                }
+            }
+        }
+
+        events
+    }
+
+    /*
+     * Get a list of problems with the parameters of this attack (empty if it is valid)
+     */
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+        match &self.attack {
+            JammingAttack::Slow { htlcs } => {
+                if *htlcs == 0 {
+                    problems.push(String::from("htlcs must be greater than 0"));
+                }
+            },
+            JammingAttack::Fast { rate } => {
+                if *rate <= 0.0 {
+                    problems.push(String::from("rate must be greater than 0"));
+                }
+            }
+        }
+        if self.src == self.dest {
+            problems.push(String::from("src and dest must be different nodes"));
+        }
+        if self.amount == 0 {
+            problems.push(String::from("amount must be greater than 0"));
+        }
+        if self.end <= self.start {
+            problems.push(format!("end time {} must be after the start time {}", self.end, self.start));
+        }
+
+        problems
+    }
+
+    /*
+     * Create a payment from the attacker's source to its destination
+     */
+    fn held_payment(&self, id: u64) -> SimHeldPayment {
+        SimHeldPayment {
+            id: id,
+            src_node: self.src.clone(),
+            dest_node: self.dest.clone(),
+            amount_sats: self.amount,
+            payment_id: None,
+            resend_until: None
+        }
+    }
+}
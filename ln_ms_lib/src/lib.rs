@@ -1,21 +1,36 @@
 // Project Modules
-mod sim_node;
-mod sim_transaction;
-mod sim_event;
 mod sim_event_manager;
 mod sim_runtime_graph;
 mod sim_utils;
 mod sensei_controller;
 mod nigiri_controller;
 mod network_analyzer;
-mod sim_node_status;
 mod ln_event_processor;
+mod node_set_profile;
+mod network_importer;
 pub mod sim_results;
 pub mod sim_channel;
+pub mod sim_node;
+pub mod sim_transaction;
+pub mod sim_event;
+pub mod sim_scenario;
+pub mod scenario_template;
+pub mod traffic_generator;
+pub mod churn_model;
+pub mod jamming;
+pub mod probing;
+pub mod fee_market;
+pub mod trigger;
+pub mod topology_generator;
+pub mod network_metrics;
+pub mod sim_node_status;
+pub mod sim_control;
+pub mod sim_handle;
 
 use sim_node::SimNode;
 use sim_event_manager::SimEventManager;
 use sim_channel::SimChannel;
+use sim_channel::SimChannelPolicy;
 use sim_transaction::SimTransaction;
 use sim_event::SimulationEvent;
 use sim_event::SimEvent;
@@ -28,6 +43,22 @@ use network_analyzer::NetworkAnalyzer;
 use sim_results::SimResults;
 use sim_transaction::SimTransactionStatus;
 use ln_event_processor::LnEventProcessor;
+use node_set_profile::NodeSetProfile;
+use sim_scenario::SimScenario;
+use sim_scenario::ScenarioEvent;
+use scenario_template::ScenarioTemplate;
+use traffic_generator::{TrafficGenerator, SimTrafficGenerator, TrafficConfig, TrafficModel, AmountDistribution};
+use topology_generator::TopologyModel;
+use churn_model::{ChurnConfig, ChurnModel};
+use jamming::{JammingConfig, JammingAttack};
+use probing::ProbingConfig;
+use fee_market::{FeeMarketConfig, FeeratePoint};
+use trigger::TriggerConfig;
+use sim_event::SimProbe;
+use sim_event::SimOnChainTx;
+use sim_event::SimReorg;
+use sim_control::SimControl;
+use sim_handle::{SimulationHandle, RunConfig};
 
 // Standard Modules
 use std::collections::HashMap;
@@ -42,7 +73,9 @@ use anyhow::Result;
 use tokio::runtime::Builder;
 use tokio::sync::broadcast;
 use sea_orm::{Database, ConnectOptions};
-use serde_json::Map;
+use serde_json::Value;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 
 // Sensei Modules
 use migration::{Migrator, MigratorTrait};
@@ -54,10 +87,14 @@ use senseicore::{
     services::admin::AdminService
 };
 
+// The import map that is used when none is given, it is built into the library so it is found no matter where the simulation is run from
+const DEFAULT_IMPORT_MAP: &str = include_str!("import_maps/default.json");
+
 /*
  *    LnSimulation is the public facing API for users of this library.
  *    A user will define the initial state of the network by adding nodes, channels, events, etc...
  *    After the LnSimulation is defined it will be run using the run() function and the events will take place on the defined network
+ *    start() runs it without blocking the caller and returns a SimulationHandle to follow and control it
  */ 
 pub struct LnSimulation {
     name: String,
@@ -66,7 +103,22 @@ pub struct LnSimulation {
     user_events: HashMap<u64, Vec<SimulationEvent>>,
     user_nodes: HashMap<String, SimNode>,
     user_channels: Vec<SimChannel>,
-    network_graph: RuntimeNetworkGraph
+    network_graph: Arc<Mutex<RuntimeNetworkGraph>>, // shared with the SimulationHandle so the graph can be read while the simulation runs
+    results_events: broadcast::Sender<SimResultsEvent>, // the results events of every run are sent here, see SimulationHandle::subscribe_events()
+    imported_nodes: HashMap<String, String>, // pubkey to node name map for nodes created by import_network
+    imported_channels: HashMap<String, (String, String)>, // node implementation channel id to node pubkeys map for channels created by import_network
+    rng: StdRng, // random number generator used when building the network, seeded with set_seed() for reproducible simulations
+    node_groups: HashMap<String, Vec<String>>, // group name to node names map, used to pick nodes by role (node sets are added as a group)
+    traffic_configs: Vec<TrafficConfig>, // the built-in traffic generators that create background payments during the simulation
+    traffic_generators: Vec<Box<dyn TrafficGenerator>>, // user defined traffic generators
+    churn_configs: Vec<ChurnConfig>, // the availability models that stop and start nodes during the simulation
+    jamming_configs: Vec<JammingConfig>, // the jamming attacks that hold payments during the simulation
+    probing_configs: Vec<ProbingConfig>, // the probing agents that infer channel balances during the simulation
+    fee_market: Option<FeeMarketConfig>, // the on-chain fee market that mines the blocks during the simulation, see set_fee_market()
+    trigger_configs: Vec<TriggerConfig>, // the rules that add events when a condition is met during the simulation
+    invariant_checks: bool, // check that the results still add up after every event, see set_invariant_checks()
+    snapshot_interval: Option<u64>, // the number of seconds between snapshots of the state that the nodes report, see set_snapshot_interval()
+    control: SimControl // pauses, resumes and steps the simulation while it runs, see get_control()
 }
 
 impl LnSimulation {
@@ -78,16 +130,170 @@ impl LnSimulation {
             user_events: HashMap::new(),
             user_nodes: HashMap::new(),
             user_channels: Vec::new(),
-            network_graph: RuntimeNetworkGraph::new()
+            network_graph: Arc::new(Mutex::new(RuntimeNetworkGraph::new())),
+            results_events: broadcast::channel(1024).0,
+            imported_nodes: HashMap::new(),
+            imported_channels: HashMap::new(),
+            rng: StdRng::from_entropy(),
+            node_groups: HashMap::new(),
+            traffic_configs: Vec::new(),
+            traffic_generators: Vec::new(),
+            churn_configs: Vec::new(),
+            jamming_configs: Vec::new(),
+            probing_configs: Vec::new(),
+            fee_market: None,
+            trigger_configs: Vec::new(),
+            invariant_checks: false,
+            snapshot_interval: None,
+            control: SimControl::new()
         };
 
         sim
     }
 
     /*
-     * Run the Lightning Network Simulation
+     * Create a simulation from a scenario file
+     * Json files are loaded as a saved scenario, toml and yaml files are loaded as a scenario template
+     */
+    pub fn from_scenario_file(filename: String) -> Result<Self> {
+        if filename.ends_with(".json") {
+            Ok(LnSimulation::from_scenario(SimScenario::from_file(&filename)?))
+        } else {
+            ScenarioTemplate::load(&filename)?.compile()
+        }
+    }
+
+    /*
+     * Seed the random number generator so that the randomly generated parts of the network are the same each time
+     */
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /*
+     * Check the balance invariants after every event: no funds are created or lost and the channel balances match what the nodes report
+     * The invariants that do not hold are saved in the results with the event that broke them, this slows down the simulation
+     */
+    pub fn set_invariant_checks(&mut self, check: bool) {
+        self.invariant_checks = check;
+    }
+
+    /*
+     * Query every running node for its balances and channels every interval seconds and save what they report in the results
+     * The snapshots can be compared to the results that are inferred from the events, None turns the snapshots off
+     */
+    pub fn set_snapshot_interval(&mut self, interval: Option<u64>) {
+        self.snapshot_interval = interval;
+    }
+
+    /*
+     * Get a handle that can pause, resume, step, stop and add events to the simulation from another thread while run() is blocking
+     * While it is paused the runtime network graph can be inspected and the nodes keep running so other tools can connect to them
+     */
+    pub fn get_control(&self) -> SimControl {
+        self.control.clone()
+    }
+
+    /*
+     * Get the name of the simulation
+     */
+    pub fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    /*
+     * Create a simulation from a scenario definition
+     */
+    pub fn from_scenario(scenario: SimScenario) -> Self {
+        let mut sim = LnSimulation::new(scenario.name, scenario.duration, scenario.num_sim_nodes);
+        for n in scenario.nodes {
+            sim.create_node(n.name, n.initial_balance, n.running);
+        }
+
+        for c in scenario.channels {
+            sim.create_channel(c.src_node.clone(), c.dest_node.clone(), c.src_balance_sats, c.id);
+            if let Some(p) = c.src_policy {
+                sim.set_channel_policy(c.src_node, c.id, p);
+            }
+            if let Some(p) = c.dest_policy {
+                sim.set_channel_policy(c.dest_node, c.id, p);
+            }
+        }
+
+        for e in scenario.events {
+            sim.add_event(e.event, e.time);
+        }
+
+        sim.traffic_configs = scenario.traffic;
+        sim.churn_configs = scenario.churn;
+        sim.jamming_configs = scenario.jamming;
+        sim.probing_configs = scenario.probing;
+        sim.fee_market = scenario.fee_market;
+        sim.trigger_configs = scenario.triggers;
+
+        sim
+    }
+
+    /*
+     * Get the scenario definition of this simulation so that it can be saved and run again later
+     */
+    pub fn get_scenario(&self) -> SimScenario {
+        let mut nodes: Vec<SimNode> = self.user_nodes.values().cloned().collect();
+        nodes.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut times: Vec<&u64> = self.user_events.keys().collect();
+        times.sort();
+        let mut events: Vec<ScenarioEvent> = Vec::new();
+        for time in times {
+            for e in &self.user_events[time] {
+                events.push(ScenarioEvent { time: time.clone(), event: e.clone() });
+            }
+        }
+
+        SimScenario {
+            name: self.name.clone(),
+            duration: self.duration,
+            num_sim_nodes: self.num_sim_nodes,
+            nodes: nodes,
+            channels: self.user_channels.clone(),
+            events: events,
+            traffic: self.traffic_configs.clone(),
+            churn: self.churn_configs.clone(),
+            jamming: self.jamming_configs.clone(),
+            probing: self.probing_configs.clone(),
+            fee_market: self.fee_market.clone(),
+            triggers: self.trigger_configs.clone()
+        }
+    }
+
+    /*
+     * Run the Lightning Network Simulation, this blocks until the simulation has ended
      */
     pub fn run(&mut self, nigiri: bool) -> Result<SimResults> {
+        let placeholder = LnSimulation::new(self.name.clone(), self.duration, self.num_sim_nodes);
+        let sim = std::mem::replace(self, placeholder);
+        let (sim, results) = sim.start(RunConfig { nigiri: nigiri }).join();
+
+        // Put the simulation back before passing on a panic so the caller does not lose it
+        *self = sim;
+        match results {
+            Ok(results) => results,
+            Err(e) => std::panic::resume_unwind(e)
+        }
+    }
+
+    /*
+     * Start the Lightning Network Simulation on its own thread without blocking the caller
+     * The returned handle owns the simulation until it ends, see SimulationHandle
+     */
+    pub fn start(self, config: RunConfig) -> SimulationHandle {
+        SimulationHandle::new(self, config)
+    }
+
+    /*
+     * Run the simulation on the current thread, called from the thread that start() spawns
+     */
+    pub(crate) fn execute(&mut self, nigiri: bool) -> Result<SimResults> {
         println!("[=== LnSimulation === {}] Starting simulation: {} for {} seconds", get_current_time(), self.name, self.duration);
         let d = self.duration.clone();
 
@@ -241,7 +447,7 @@ impl LnSimulation {
             );
 
             // Create the network analyzer
-            let mut network_analyzer = NetworkAnalyzer::new(analyzer_runtime_handle, bitcoind_client.clone());
+            let mut network_analyzer = NetworkAnalyzer::new(analyzer_runtime_handle, Some(bitcoind_client.clone()), self.control.clone());
 
             // Create the ln event processor
             let ln_event_proc = LnEventProcessor::new(ln_event_runtime_handle);
@@ -250,7 +456,28 @@ impl LnSimulation {
             let mut sensei_controller = SenseiController::new(sensei_admin_service, sensei_runtime_handle);
 
             // Create the event manager
-            let event_manager = SimEventManager::new(self.user_events.clone());
+            let mut traffic_generators: Vec<Box<dyn TrafficGenerator>> = Vec::new();
+            for config in &self.traffic_configs {
+                traffic_generators.push(Box::new(SimTrafficGenerator::new(config.clone())));
+            }
+            let num_built_in_generators = traffic_generators.len();
+            traffic_generators.append(&mut self.traffic_generators);
+            let capacities = self.get_node_capacities();
+            for g in traffic_generators.iter_mut() {
+                g.initialize(&capacities);
+            }
+            let generated_events = self.get_generated_events();
+            let mut revoked_state_targets: Vec<(String, u64)> = Vec::new();
+            for events in generated_events.values() {
+                for e in events {
+                    match e {
+                        SimulationEvent::BroadcastRevokedStateEvent(node, id) => revoked_state_targets.push((node.clone(), *id)),
+                        _ => {}
+                    }
+                }
+            }
+            sensei_controller.set_revoked_state_targets(revoked_state_targets);
+            let event_manager = Arc::new(SimEventManager::new(generated_events, traffic_generators, self.snapshot_interval, self.control.clone()));
 
             // Create the initial state of the network (nodes, channels, balances, etc...)
             /* 
@@ -265,11 +492,20 @@ impl LnSimulation {
 
             // Set up the initial runtime network graph
             println!("[=== LnSimulation === {}] Initializing the runtime network graph", get_current_time());
-            self.network_graph.update(&self.user_nodes, &self.user_channels, self.num_sim_nodes);
+            let mut network_graph = RuntimeNetworkGraph::new();
+            network_graph.update(&self.user_nodes, &self.user_channels, self.num_sim_nodes);
 
             // Set up the network analyzer
             println!("[=== LnSimulation === {}] Initializing the network analyzer", get_current_time());
-            network_analyzer.initialize_network(&self.network_graph, &sensei_controller).await;
+            network_analyzer.initialize_network(&network_graph, &sensei_controller).await;
+            *self.network_graph.lock().unwrap() = network_graph;
+            let sensei_controller_arc = Arc::new(sensei_controller);
+            if self.invariant_checks {
+                network_analyzer.enable_invariant_checks(sensei_controller_arc.clone());
+            }
+            if !self.trigger_configs.is_empty() {
+                network_analyzer.enable_triggers(self.trigger_configs.clone());
+            }
 
             // Create the channels for threads to communicate over
 
@@ -280,7 +516,7 @@ impl LnSimulation {
             let ln_event_sim_receiver = sim_event_sender.subscribe();
             
             // Used for sending results to the network analyzer: Receivers will be network analyzer
-            let (sim_results_event_sender, _): (broadcast::Sender<SimResultsEvent>, broadcast::Receiver<SimResultsEvent>) = broadcast::channel(1024);
+            let sim_results_event_sender = self.results_events.clone();
             let ln_results_event_sender = sim_results_event_sender.clone();
             let network_analyzer_receiver = sim_results_event_sender.subscribe();
 
@@ -301,7 +537,6 @@ impl LnSimulation {
 
                 // Start the SenseiController
                 println!("[=== LnSimulation === {}] Starting the sensei controller", get_current_time());
-                let sensei_controller_arc = Arc::new(sensei_controller);
                 let sensei_controller_handle = s.spawn(move || {
                     sensei_controller_arc.process_events(sensei_event_receiver, sim_results_event_sender);
                 });
@@ -318,12 +553,11 @@ impl LnSimulation {
                  * - currently for simplicity in order to create a proof of concept and demonstrate the use case of this project
                  *   the duration is denoted in seconds and will run in real time... eventually this will need to be changed to a purely event driven
                  *   time concept that will allow the simulations to be run in a faster-than-real-time mode
-                 * - allow for a use case where the user wants to spin up a large network and connect a user-controlled node to perform manual testing
                  * - mining, on-chain transactions, block updates all need to be considered, we do not want to wait 10 min for a block to be added
                  *   need to model an event driven on-chain process that will mine blocks at faster than real time.
                  */
                 println!("[=== LnSimulation === {}] Starting the event manager", get_current_time());
-                let event_manager_arc = Arc::new(event_manager);
+                let event_manager_arc = event_manager.clone();
                 let event_manager_handle = s.spawn(move || {
                     event_manager_arc.run(d, sim_event_sender);
                 });
@@ -354,9 +588,11 @@ impl LnSimulation {
             // Get the results of the simulation
             let results = network_analyzer.get_sim_results();
 
+            // Keep the user defined traffic generators so the simulation can be run again
+            self.traffic_generators = event_manager.take_traffic_generators().split_off(num_built_in_generators);
+
             // Clear the runtime network graph
-            self.network_graph.nodes.clear();
-            self.network_graph.channels.clear();
+            *self.network_graph.lock().unwrap() = RuntimeNetworkGraph::new();
 
             // Stop bitcoind with nigiri
             if nigiri {
@@ -378,39 +614,109 @@ impl LnSimulation {
      * Get the current network graph for the simulation
      */
     pub fn get_runtime_network_graph(&self) -> String {
-        let serialized_nodes = serde_json::to_string(&self.network_graph.nodes).unwrap();
-        let serialized_channels = serde_json::to_string(&self.network_graph.channels).unwrap();
-        let mut map = Map::new();
-        map.insert(String::from("nodes"), serde_json::Value::String(serialized_nodes));
-        map.insert(String::from("channels"), serde_json::Value::String(serialized_channels));
-        serde_json::to_string(&map).unwrap()
+        self.network_graph.lock().unwrap().to_json()
     }
 
     /*
      * Parse a file that contains a definition of a LN topology
      * This definition could be from a project like Polar or from dumping the network information from the mainnet (lncli describegraph)
      * The filename param is the json file of the network topology and import_map is the json file that maps nodes to a profile when importing
-     * TODO: Implement
+     * If import_map is empty the default import map is used
      */
-    pub fn import_network(&self, filename: String, import_map: String) {
+    pub fn import_network(&mut self, filename: String, import_map: String) {
         println!("[=== LnSimulation === {}] Importing network definition from {}, with import map: {}", get_current_time(), filename, import_map);
+        let (nodes, channels) = match network_importer::parse_graph(&filename) {
+            Ok(g) => g,
+            Err(e) => {
+                println!("could not import network: {:?}", e);
+                return;
+            }
+        };
+
+        // Read the import map that assigns a profile to each node
+        let import_map_contents = if import_map.is_empty() { Ok(String::from(DEFAULT_IMPORT_MAP)) } else { std::fs::read_to_string(&import_map) };
+        let profiles: HashMap<String, String> = match import_map_contents {
+            Ok(contents) => {
+                let map: Value = serde_json::from_str(&contents).unwrap_or(Value::Null);
+                let mut profiles = HashMap::new();
+                for n in map["nodes"].as_array().unwrap_or(&Vec::new()) {
+                    match (n["pub_key"].as_str(), n["profile"].as_str()) {
+                        (Some(k), Some(p)) => { profiles.insert(String::from(k), String::from(p)); },
+                        _ => {}
+                    }
+                }
+                profiles
+            },
+            Err(e) => {
+                println!("could not read import map: {:?}", e);
+                return;
+            }
+        };
+
+        // Create each node that is in the import map, the initial balance is the amount needed to fund its channels plus the minimum on-chain balance of its profile
+        for n in &nodes {
+            let profile_name = match profiles.get(&n.pub_key).or(profiles.get("*")) {
+                Some(p) => p,
+                None => continue
+            };
+            let on_chain = match NodeSetProfile::load(profile_name).and_then(|p| p.get_initial_on_chain_range()) {
+                Ok((min, _)) => min,
+                Err(e) => {
+                    println!("could not load profile {}: {:?}", profile_name, e);
+                    0
+                }
+            };
+            let funding: u64 = channels.iter().filter(|c| c.node1_pub == n.pub_key).map(|c| c.capacity).sum();
+            let name = if n.alias.is_empty() || self.user_nodes.contains_key(&n.alias) { n.pub_key.clone() } else { n.alias.clone() };
+            self.imported_nodes.insert(n.pub_key.clone(), name.clone());
+            self.create_node(name, funding + on_chain, true);
+        }
+
+        // Create each channel between two imported nodes, the first node in the graph is treated as the node that opened the channel
+        let mut id = self.user_channels.iter().map(|c| c.id).max().unwrap_or(0);
+        for c in &channels {
+            match (self.imported_nodes.get(&c.node1_pub).cloned(), self.imported_nodes.get(&c.node2_pub).cloned()) {
+                (Some(src), Some(dest)) => {
+                    id = id + 1;
+                    self.imported_channels.insert(c.chan_id.clone(), (c.node1_pub.clone(), c.node2_pub.clone()));
+                    self.create_channel(src, dest, c.capacity, id);
+                },
+                _ => {}
+            }
+        }
     }
 
     /*
      * Export the network to a json file that can be loaded later
-     * TODO: Implement
      */
     pub fn export_network(&self, filename: String) {
         println!("[=== LnSimulation === {}] Exporting network definition from {}", get_current_time(), filename);
+        match self.get_scenario().save(&filename) {
+            Ok(()) => {},
+            Err(e) => println!("could not export network: {:?}", e)
+        }
     }
 
     /*
      * Parse a file that contains transactions
-     * This could be from payment information from the mainnet (lncli fwdinghistory)
-     * TODO: Implement
+     * This could be from payment information from the mainnet (lncli fwdinghistory or lncli listpayments)
+     * The network that the transactions took place on must be imported with import_network first
      */
-    pub fn import_transactions(&self, filename: String) {
+    pub fn import_transactions(&mut self, filename: String) {
         println!("[=== LnSimulation === {}] Importing transactions from {}", get_current_time(), filename);
+        match network_importer::parse_transactions(&filename, &self.imported_channels) {
+            Ok(txs) => {
+                for t in txs {
+                    match (self.imported_nodes.get(&t.src_pub).cloned(), self.imported_nodes.get(&t.dest_pub).cloned()) {
+                        (Some(src), Some(dest)) => {
+                            self.create_transaction_event(src, dest, t.amount_sats, t.time);
+                        },
+                        _ => println!("transaction not created: node not found in the imported network")
+                    }
+                }
+            },
+            Err(e) => println!("could not import transactions: {:?}", e)
+        }
     }
 
     /* 
@@ -429,10 +735,99 @@ impl LnSimulation {
 
     /*
      * Create a set of nodes with pre-defined properties
-     * TODO: Implement
+     * The nodes are named {name}1..{name}N and their initial balances are picked from the profile, returns the names of the new nodes
+     */
+    pub fn create_node_set(&mut self, name: String, number_of_nodes: u64, profile: String) -> Vec<String> {
+        println!("[=== LnSimulation === {}] Create Node Set: {}, {}, {}", get_current_time(), name, number_of_nodes, profile);
+        let mut names: Vec<String> = Vec::new();
+        let (min, max) = match NodeSetProfile::load(&profile).and_then(|p| p.get_initial_on_chain_range()) {
+            Ok(r) => r,
+            Err(e) => {
+                println!("node set not created: could not load profile {}: {:?}", profile, e);
+                return names;
+            }
+        };
+
+        for number in 1..(number_of_nodes + 1) {
+            let node_name = name.clone() + &number.to_string();
+            let balance = self.rng.gen_range(min..=max);
+            self.create_node(node_name.clone(), balance, true);
+            self.add_node_to_group(node_name.clone(), name.clone());
+            names.push(node_name);
+        }
+
+        names
+    }
+
+    /*
+     * Create a random network of nodes and the channels between them from a graph model (see TopologyModel)
+     * The nodes are named {name}1..{name}N and added to the group {name}, for the hub-and-spoke model the hubs are also added to the group {name}_hubs
+     * Each node gets an initial balance that funds the channels it opens plus an on-chain balance picked from the profile, returns the names of the new nodes
+     */
+    pub fn create_random_network(&mut self, name: String, number_of_nodes: u64, model: TopologyModel, channel_amount: AmountDistribution, profile: String) -> Vec<String> {
+        println!("[=== LnSimulation === {}] Create Random Network: {}, {}, {:?}", get_current_time(), name, number_of_nodes, model);
+        let mut names: Vec<String> = Vec::new();
+        let (min, max) = match NodeSetProfile::load(&profile).and_then(|p| p.get_initial_on_chain_range()) {
+            Ok(r) => r,
+            Err(e) => {
+                println!("network not created: could not load profile {}: {:?}", profile, e);
+                return names;
+            }
+        };
+        let channels = match topology_generator::generate(&model, number_of_nodes, &channel_amount, &mut self.rng) {
+            Ok(c) => c,
+            Err(e) => {
+                println!("network not created: {:?}", e);
+                return names;
+            }
+        };
+
+        // Create the nodes with enough of a balance to open their channels
+        let mut funding: Vec<u64> = vec![0; number_of_nodes as usize];
+        for (src, _, capacity) in &channels {
+            funding[*src] += capacity;
+        }
+        for (index, f) in funding.iter().enumerate() {
+            let node_name = name.clone() + &(index + 1).to_string();
+            let balance = f + self.rng.gen_range(min..=max);
+            self.create_node(node_name.clone(), balance, true);
+            self.add_node_to_group(node_name.clone(), name.clone());
+            names.push(node_name);
+        }
+        if let TopologyModel::HubAndSpoke { hubs } = model {
+            for hub in names.iter().take(hubs as usize) {
+                self.add_node_to_group(hub.clone(), name.clone() + "_hubs");
+            }
+        }
+
+        // Create the channels
+        let mut id = self.user_channels.iter().map(|c| c.id).max().unwrap_or(0);
+        for (src, dest, capacity) in channels {
+            id = id + 1;
+            self.create_channel(names[src].clone(), names[dest].clone(), capacity, id);
+        }
+
+        names
+    }
+
+    /*
+     * Add a node to a group, groups are used to pick nodes by role (for example merchants and consumers)
+     */
+    pub fn add_node_to_group(&mut self, node: String, group: String) {
+        let members = self.node_groups.entry(group).or_insert(Vec::new());
+        if !members.contains(&node) {
+            members.push(node);
+        }
+    }
+
+    /*
+     * Get the names of the nodes in a group
      */
-    pub fn create_node_set(&mut self, number_of_nodes: i32, profile: String) {
-        println!("[=== LnSimulation === {}] Create Node Set: {}, {}", get_current_time(), number_of_nodes, profile);
+    pub fn get_group_nodes(&self, group: &String) -> Vec<String> {
+        match self.node_groups.get(group) {
+            Some(members) => members.clone(),
+            None => Vec::new()
+        }
     }
 
     /*
@@ -485,7 +880,9 @@ impl LnSimulation {
             short_id: None,
             run_time_id: None,
             funding_tx: None,
-            penalty_reserve_sats: None
+            penalty_reserve_sats: None,
+            src_policy: None,
+            dest_policy: None
         };
         self.user_channels.push(channel.clone());
 
@@ -523,7 +920,9 @@ impl LnSimulation {
             short_id: None,
             run_time_id: None,
             funding_tx: None,
-            penalty_reserve_sats: None
+            penalty_reserve_sats: None,
+            src_policy: None,
+            dest_policy: None
         };
         let event = SimulationEvent::OpenChannelEvent(channel.clone());
         self.add_event(event, time);
@@ -538,6 +937,119 @@ impl LnSimulation {
         self.add_event(event, time);
     }
 
+    /*
+     * Create an event that will unilaterally close a channel, the node broadcasts its latest commitment transaction
+     * The node's own balance is timelocked for the channel's to_self_delay, use a MineBlocksEvent to get past it
+     */
+    pub fn create_force_close_channel_event(&mut self, node: String, channel_id: u64, time: u64) {
+        println!("[=== LnSimulation === {}] Add ForceCloseChannelEvent for: {} at {} seconds", get_current_time(), channel_id, time);
+        let event = SimulationEvent::ForceCloseChannelEvent(node, channel_id);
+        self.add_event(event, time);
+    }
+
+    /*
+     * Create an event where a node cheats by broadcasting the commitment transaction it had when the channel was opened
+     * If the channel has been used since then the commitment is revoked and the counterparty can take the whole channel balance as a penalty
+     * The event fails if the channel has not been used yet, or if it was not scheduled before the simulation started (the commitment is only saved for scheduled events)
+     */
+    pub fn create_broadcast_revoked_state_event(&mut self, node: String, channel_id: u64, time: u64) {
+        println!("[=== LnSimulation === {}] Add BroadcastRevokedStateEvent for: {} at {} seconds", get_current_time(), channel_id, time);
+        let event = SimulationEvent::BroadcastRevokedStateEvent(node, channel_id);
+        self.add_event(event, time);
+    }
+
+    /*
+     * Create an event that will mine a number of blocks
+     */
+    pub fn create_mine_blocks_event(&mut self, number: u64, time: u64) {
+        println!("[=== LnSimulation === {}] Add MineBlocksEvent for: {} blocks at {} seconds", get_current_time(), number, time);
+        let event = SimulationEvent::MineBlocksEvent(number);
+        self.add_event(event, time);
+    }
+
+    /*
+     * Create an event that replaces the last blocks of the chain with a longer competing chain
+     * The transactions in the replaced blocks go back to the mempool and are confirmed again by the next blocks that are mined
+     */
+    pub fn create_reorg_event(&mut self, depth: u64, time: u64) {
+        println!("[=== LnSimulation === {}] Add ReorgEvent for: {} blocks at {} seconds", get_current_time(), depth, time);
+        let event = SimulationEvent::ReorgEvent(
+            SimReorg {
+                depth: depth,
+                unconfirmed_txs: Vec::new(),
+                unusable_channels: Vec::new()
+            }
+        );
+        self.add_event(event, time);
+    }
+
+    /*
+     * Create an on-chain payment from a node's wallet, the destination can be another node or a bitcoin address outside of the simulation
+     */
+    pub fn create_send_on_chain_event(&mut self, src: String, dest: String, amount_sats: u64, feerate_sat_per_vbyte: u64, time: u64) {
+        println!("[=== LnSimulation === {}] Add SendOnChainEvent for: {} -> {} at {} seconds", get_current_time(), src, dest, time);
+        let event = SimulationEvent::SendOnChainEvent(
+            SimOnChainTx {
+                src_node: src,
+                dest: dest,
+                amount_sats: amount_sats,
+                feerate_sat_per_vbyte: feerate_sat_per_vbyte,
+                txid: None
+            }
+        );
+        self.add_event(event, time);
+    }
+
+    /*
+     * Create an event that sends funds from outside of the simulation to a node's on-chain wallet
+     */
+    pub fn create_fund_node_event(&mut self, node: String, amount_sats: u64, time: u64) {
+        println!("[=== LnSimulation === {}] Add FundNodeEvent for: {} at {} seconds", get_current_time(), node, time);
+        let event = SimulationEvent::FundNodeEvent(node, amount_sats);
+        self.add_event(event, time);
+    }
+
+    /*
+     * Set the routing policy of a node for a channel, the channel can be open at the start of the simulation or opened by an OpenChannelEvent
+     * The policy is applied when the channel is opened
+     */
+    pub fn set_channel_policy(&mut self, node: String, channel_id: u64, policy: SimChannelPolicy) {
+        println!("[=== LnSimulation === {}] Set Channel Policy: {} for channel {}", get_current_time(), node, channel_id);
+        for c in &mut self.user_channels {
+            if c.id == channel_id {
+                if !c.set_policy(&node, policy) {
+                    println!("channel policy not set: node is not in this channel");
+                }
+                return;
+            }
+        }
+
+        for events in self.user_events.values_mut() {
+            for e in events.iter_mut() {
+                match e {
+                    SimulationEvent::OpenChannelEvent(c) if c.id == channel_id => {
+                        if !c.set_policy(&node, policy) {
+                            println!("channel policy not set: node is not in this channel");
+                        }
+                        return;
+                    },
+                    _ => {}
+                }
+            }
+        }
+
+        println!("channel policy not set: channel not found with the given id");
+    }
+
+    /*
+     * Create an event that will change the routing policy of a node for a channel
+     */
+    pub fn create_update_channel_policy_event(&mut self, node: String, channel_id: u64, policy: SimChannelPolicy, time: u64) {
+        println!("[=== LnSimulation === {}] Add UpdateChannelPolicyEvent for: {} on {} at {} seconds", get_current_time(), node, channel_id, time);
+        let event = SimulationEvent::UpdateChannelPolicyEvent(node, channel_id, policy);
+        self.add_event(event, time);
+    }
+
     /*
      * Create a transaction for a given amount between two nodes
      */
@@ -549,89 +1061,341 @@ impl LnSimulation {
                 src_node: src,
                 dest_node: dest,
                 amount_sats: amount_sats,
-                status: SimTransactionStatus::NONE
+                status: SimTransactionStatus::NONE,
+                keysend: false
             }
         );
         self.add_event(event, time);
     }
 
     /*
-     * Add a SimulationEvent to the list of events to execute
+     * Create a spontaneous (keysend) payment for a given amount between two nodes, the payment is sent to the destination's pubkey without an invoice
      */
-    fn add_event(&mut self, event: SimulationEvent, time: u64) {
-        if self.user_events.contains_key(&time) {
-            let current_events = self.user_events.get_mut(&time).unwrap();
-            current_events.push(event);
-        } else {
-            let mut current_events: Vec<SimulationEvent> = Vec::new();
-            current_events.push(event);
-            self.user_events.insert(time, current_events);
-        }
+    pub fn create_keysend_event(&mut self, src: String, dest: String, amount_sats: u64, time: u64) {
+        println!("[=== LnSimulation === {}] Add KeysendEvent for: {} at {} seconds", get_current_time(), src, time);
+        let event = SimulationEvent::KeysendEvent(
+            SimTransaction {
+                id: None,
+                src_node: src,
+                dest_node: dest,
+                amount_sats: amount_sats,
+                status: SimTransactionStatus::NONE,
+                keysend: true
+            }
+        );
+        self.add_event(event, time);
     }
 
     /*
-     * Listens for simulation events and updates the runtime network graph
+     * Create an event that disconnects two running nodes from each other, their channels can not be used until a HealPartitionEvent
      */
-    fn listen(&mut self, mut event_channel: broadcast::Receiver<SimEvent>, runtime_handle: tokio::runtime::Handle) {
-        tokio::task::block_in_place(move || {
-            runtime_handle.block_on(async move {
-                let mut running = true;
-                while running {
-                    let event = event_channel.recv().await.unwrap();
-                    match event.event {
-                        SimulationEvent::StopNodeEvent(name) => {
-                            println!("[=== LnSimulation === {}] NodeOfflineEvent, updating network graph", get_current_time());
-                            for node in &mut self.network_graph.nodes {
-                                if node.name == name {
-                                    node.running = false;
-                                    break;
-                                }
-                            }
-                        },
-                        SimulationEvent::StartNodeEvent(name) => {
-                            println!("[=== LnSimulation === {}] NodeOnlineEvent, updating network graph", get_current_time());
-                            for node in &mut self.network_graph.nodes {
-                                if node.name == name {
-                                    node.running = true;
-                                    break;
-                                }
-                            }
-                        },
-                        SimulationEvent::CloseChannelEvent(_, id) => {
-                            println!("[=== LnSimulation === {}] CloseChannelEvent, updating network graph", get_current_time());
-                            self.network_graph.channels.retain(|c| c.id != id);
-                        },
-                        SimulationEvent::OpenChannelEvent(channel) => {
-                            println!("[=== LnSimulation === {}] OpenChannelEvent, updating network graph", get_current_time());
-                            self.network_graph.channels.push(channel);
-                        },
-                        SimulationEvent::SimulationEndedEvent => {
-                            println!("[=== LnSimulation === {}] SimulationEnded", get_current_time());
-                            running = false;
-                        },
-                        _ => {
-                            // Ignore all other events
-                        }
-                    }
-                }
-            });
-        });
+    pub fn create_disconnect_peers_event(&mut self, node: String, peer: String, time: u64) {
+        println!("[=== LnSimulation === {}] Add DisconnectPeersEvent for: {} and {} at {} seconds", get_current_time(), node, peer, time);
+        let event = SimulationEvent::DisconnectPeersEvent(node, peer);
+        self.add_event(event, time);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use serial_test::serial;
-    use super::*;
-
-    #[test]
-    #[serial]
-    fn status_test() {
-        let mut ln_sim = LnSimulation::new(String::from("test"), 10, 0);
-        
-        ln_sim.create_node(String::from("node1"), 0, true);
+    /*
+     * Create an event that splits the network in two, no node in one set can connect to a node in the other set until a HealPartitionEvent
+     * The sets can contain node names or group names (all the nodes in the group are used)
+     */
+    pub fn create_partition_event(&mut self, nodes_a: Vec<String>, nodes_b: Vec<String>, time: u64) {
+        println!("[=== LnSimulation === {}] Add PartitionEvent at {} seconds", get_current_time(), time);
+        let event = SimulationEvent::PartitionEvent(self.expand_groups(nodes_a), self.expand_groups(nodes_b));
+        self.add_event(event, time);
+    }
 
-        ln_sim.create_stop_node_event(String::from("node1"), 2);
+    /*
+     * Create an event that reconnects all the nodes that were disconnected by a DisconnectPeersEvent or a PartitionEvent
+     */
+    pub fn create_heal_partition_event(&mut self, time: u64) {
+        println!("[=== LnSimulation === {}] Add HealPartitionEvent at {} seconds", get_current_time(), time);
+        let event = SimulationEvent::HealPartitionEvent;
+        self.add_event(event, time);
+    }
+
+    /*
+     * Create a traffic generator that sends background payments between nodes while the simulation is running
+     * Senders and receivers can be node names or group names (all the nodes in the group are used)
+     */
+    pub fn create_traffic_generator(&mut self, model: TrafficModel, senders: Vec<String>, receivers: Vec<String>, rate: f64, amount: AmountDistribution, start: u64, end: u64) {
+        println!("[=== LnSimulation === {}] Add {:?} traffic generator from {} seconds to {} seconds", get_current_time(), model, start, end);
+        let config = TrafficConfig {
+            model: model,
+            senders: self.expand_groups(senders),
+            receivers: self.expand_groups(receivers),
+            rate: rate,
+            amount: amount,
+            start: start,
+            end: end,
+            seed: self.rng.gen()
+        };
+        self.traffic_configs.push(config);
+    }
+
+    /*
+     * Add a user defined traffic generator that sends background payments while the simulation is running
+     */
+    pub fn add_traffic_generator(&mut self, generator: Box<dyn TrafficGenerator>) {
+        self.traffic_generators.push(generator);
+    }
+
+    /*
+     * Create an availability model that stops and starts nodes during the simulation
+     * Nodes can be node names or group names (all the nodes in the group are used), the nodes should be online at the start time
+     */
+    pub fn create_churn_model(&mut self, model: ChurnModel, nodes: Vec<String>, start: u64, end: u64) {
+        println!("[=== LnSimulation === {}] Add {:?} churn model from {} seconds to {} seconds", get_current_time(), model, start, end);
+        let config = ChurnConfig {
+            model: model,
+            nodes: self.expand_groups(nodes),
+            start: start,
+            end: end,
+            seed: self.rng.gen()
+        };
+        self.churn_configs.push(config);
+    }
+
+    /*
+     * Create a jamming attack where the src node sends payments to the dest node and dest holds them, both nodes are controlled by the attacker
+     * The attacker needs channels that route through the channels it wants to jam, the amount of each payment is in sats
+     */
+    pub fn create_jamming_attack(&mut self, attack: JammingAttack, src: String, dest: String, amount_sats: u64, start: u64, end: u64) {
+        println!("[=== LnSimulation === {}] Add {:?} jamming attack from {} to {} from {} seconds to {} seconds", get_current_time(), attack, src, dest, start, end);
+        let config = JammingConfig {
+            attack: attack,
+            src: src,
+            dest: dest,
+            amount: amount_sats,
+            start: start,
+            end: end,
+            seed: self.rng.gen()
+        };
+        self.jamming_configs.push(config);
+    }
+
+    /*
+     * Create an event where the prober infers the balance of a channel within precision sats by sending probes that must fail
+     * The prober needs a channel to one end of the probed channel, the balance of that end is probed
+     */
+    pub fn create_probe_event(&mut self, prober: String, channel_id: u64, precision_sats: u64, time: u64) {
+        println!("[=== LnSimulation === {}] Add ProbeEvent for: {} from {} at {} seconds", get_current_time(), channel_id, prober, time);
+        let event = SimulationEvent::ProbeEvent(SimProbe {
+            prober: prober,
+            channel_id: channel_id,
+            precision_sats: precision_sats,
+            result: None
+        });
+        self.add_event(event, time);
+    }
+
+    /*
+     * Create a probing agent that probes the balances of a list of channels every interval seconds
+     */
+    pub fn create_probing_agent(&mut self, prober: String, channels: Vec<u64>, interval: u64, precision_sats: u64, start: u64, end: u64) {
+        println!("[=== LnSimulation === {}] Add probing agent for {} from {} seconds to {} seconds", get_current_time(), prober, start, end);
+        let config = ProbingConfig {
+            prober: prober,
+            channels: channels,
+            interval: interval,
+            precision: precision_sats,
+            start: start,
+            end: end
+        };
+        self.probing_configs.push(config);
+    }
+
+    /*
+     * Run an on-chain fee market from start to end, the market feerate follows the schedule and a block is mined every block_interval seconds
+     * Each block only includes the transactions that pay at least the market feerate, so channel opens, closes and on-chain sends wait while the feerate is higher than they pay
+     * A channel that is opened while the market runs is open in the results once its funding transaction is in a block
+     */
+    pub fn set_fee_market(&mut self, schedule: Vec<FeeratePoint>, block_interval: u64, filler_txs: u64, start: u64, end: u64) {
+        println!("[=== LnSimulation === {}] Set fee market from {} seconds to {} seconds", get_current_time(), start, end);
+        let config = FeeMarketConfig {
+            schedule: schedule,
+            block_interval: block_interval,
+            filler_txs: filler_txs,
+            start: start,
+            end: end
+        };
+        self.fee_market = Some(config);
+    }
+
+    /*
+     * Create a trigger that adds events to the simulation when its condition is met instead of at a fixed time
+     * The condition is checked against the results after every event, so a trigger reacts to what has happened so far in the simulation
+     */
+    pub fn create_trigger(&mut self, config: TriggerConfig) {
+        println!("[=== LnSimulation === {}] Add trigger {}", get_current_time(), config.name);
+        self.trigger_configs.push(config);
+    }
+
+    /*
+     * Get the user defined events and the events that the churn models, jamming attacks, probing agents and fee market create
+     */
+    fn get_generated_events(&self) -> HashMap<u64, Vec<SimulationEvent>> {
+        let mut events = self.user_events.clone();
+        for config in &self.churn_configs {
+            for (time, event) in config.generate_events() {
+                events.entry(time).or_insert(Vec::new()).push(event);
+            }
+        }
+
+        for config in &self.probing_configs {
+            for (time, event) in config.generate_events() {
+                events.entry(time).or_insert(Vec::new()).push(event);
+            }
+        }
+
+        match &self.fee_market {
+            Some(config) => {
+                for (time, event) in config.generate_events() {
+                    events.entry(time).or_insert(Vec::new()).push(event);
+                }
+            },
+            None => {}
+        }
+
+        // Each held payment needs an id that is unique across all the attacks
+        let mut next_id: u64 = 0;
+        for config in &self.jamming_configs {
+            for (time, event) in config.generate_events(next_id) {
+                if let SimulationEvent::HoldPaymentEvent(_) = event {
+                    next_id = next_id + 1;
+                }
+                events.entry(time).or_insert(Vec::new()).push(event);
+            }
+        }
+
+        events
+    }
+
+    /*
+     * Replace any group names in a list of nodes with the nodes in that group
+     */
+    fn expand_groups(&self, names: Vec<String>) -> Vec<String> {
+        let mut nodes: Vec<String> = Vec::new();
+        for n in names {
+            match self.node_groups.get(&n) {
+                Some(members) => nodes.extend(members.iter().cloned()),
+                None => nodes.push(n)
+            }
+        }
+
+        nodes
+    }
+
+    /*
+     * Get the total capacity of the channels that each node has at the start of the simulation
+     */
+    fn get_node_capacities(&self) -> HashMap<String, u64> {
+        let mut capacities: HashMap<String, u64> = HashMap::new();
+        for c in &self.user_channels {
+            let capacity = c.src_balance_sats + c.dest_balance_sats;
+            *capacities.entry(c.src_node.clone()).or_insert(0) += capacity;
+            *capacities.entry(c.dest_node.clone()).or_insert(0) += capacity;
+        }
+
+        capacities
+    }
+
+    /*
+     * Add a SimulationEvent to the list of events to execute
+     */
+    fn add_event(&mut self, event: SimulationEvent, time: u64) {
+        if self.user_events.contains_key(&time) {
+            let current_events = self.user_events.get_mut(&time).unwrap();
+            current_events.push(event);
+        } else {
+            let mut current_events: Vec<SimulationEvent> = Vec::new();
+            current_events.push(event);
+            self.user_events.insert(time, current_events);
+        }
+    }
+
+    /*
+     * Listens for simulation events and updates the runtime network graph
+     */
+    fn listen(&mut self, mut event_channel: broadcast::Receiver<SimEvent>, runtime_handle: tokio::runtime::Handle) {
+        tokio::task::block_in_place(move || {
+            runtime_handle.block_on(async move {
+                let mut running = true;
+                while running {
+                    let event = event_channel.recv().await.unwrap();
+                    let mut network_graph = self.network_graph.lock().unwrap();
+                    match event.event {
+                        SimulationEvent::StopNodeEvent(name) => {
+                            println!("[=== LnSimulation === {}] NodeOfflineEvent, updating network graph", get_current_time());
+                            for node in &mut network_graph.nodes {
+                                if node.name == name {
+                                    node.running = false;
+                                    break;
+                                }
+                            }
+                        },
+                        SimulationEvent::StartNodeEvent(name) => {
+                            println!("[=== LnSimulation === {}] NodeOnlineEvent, updating network graph", get_current_time());
+                            for node in &mut network_graph.nodes {
+                                if node.name == name {
+                                    node.running = true;
+                                    break;
+                                }
+                            }
+                        },
+                        SimulationEvent::CloseChannelEvent(_, id) => {
+                            println!("[=== LnSimulation === {}] CloseChannelEvent, updating network graph", get_current_time());
+                            network_graph.channels.retain(|c| c.id != id);
+                        },
+                        SimulationEvent::ForceCloseChannelEvent(_, id) | SimulationEvent::BroadcastRevokedStateEvent(_, id) => {
+                            println!("[=== LnSimulation === {}] ForceCloseChannelEvent, updating network graph", get_current_time());
+                            network_graph.channels.retain(|c| c.id != id);
+                        },
+                        SimulationEvent::OpenChannelEvent(channel) => {
+                            println!("[=== LnSimulation === {}] OpenChannelEvent, updating network graph", get_current_time());
+                            network_graph.channels.push(channel);
+                        },
+                        SimulationEvent::UpdateChannelPolicyEvent(node, id, policy) => {
+                            println!("[=== LnSimulation === {}] UpdateChannelPolicyEvent, updating network graph", get_current_time());
+                            for channel in &mut network_graph.channels {
+                                if channel.id == id {
+                                    channel.set_policy(&node, policy);
+                                    break;
+                                }
+                            }
+                        },
+                        SimulationEvent::SimulationEndedEvent => {
+                            println!("[=== LnSimulation === {}] SimulationEnded", get_current_time());
+                            running = false;
+                        },
+                        _ => {
+                            // Ignore all other events
+                        }
+                    }
+                }
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+    use super::*;
+    use crate::sim_results::{ForceClose, CloseType, Sweep, Tx, FailedAttempt, Forward, FailedForward, ForwardingStats, InvariantViolation, Invariant, Partition, JammedHtlc, BalanceProbe, FeeMarketBlock, ChannelFee, ChannelFeeType, MAX_ACCEPTED_HTLCS};
+    use crate::sim_transaction::SimFailureReason;
+    use crate::sim_event::{SimPaymentPath, PathHop, SimFeeMarketBlock, SimPaymentFailure, SimForward, SimSweep, SimHeldPayment, SimProbeResult};
+    use crate::trigger::{Trigger, TriggerAction};
+    use crate::sim_node_status::{SimNodeStatus, SimNodeBalance};
+
+    #[test]
+    #[serial]
+    fn status_test() {
+        let mut ln_sim = LnSimulation::new(String::from("test"), 10, 0);
+        
+        ln_sim.create_node(String::from("node1"), 0, true);
+
+        ln_sim.create_stop_node_event(String::from("node1"), 2);
         ln_sim.create_start_node_event(String::from("node1"), 6);
         ln_sim.create_stop_node_event(String::from("node1"), 9);
 
@@ -1140,4 +1904,1707 @@ mod tests {
             }
         }
     }
+
+    /*
+     * Get a channel whose short channel id is its id and whose node implementation id is its id as a string
+     */
+    fn test_channel(id: u64, src: &str, dest: &str, src_balance: u64, dest_balance: u64) -> SimChannel {
+        SimChannel {
+            id: id, src_node: String::from(src), dest_node: String::from(dest), src_balance_sats: src_balance, dest_balance_sats: dest_balance,
+            short_id: Some(id), run_time_id: Some(id.to_string()), funding_tx: None, penalty_reserve_sats: None, src_policy: None, dest_policy: None
+        }
+    }
+
+    /*
+     * Get the results of a network whose nodes are online with an off-chain balance and whose channels are open at time 0
+     */
+    fn test_results(nodes: Vec<(&str, u64)>, channels: Vec<SimChannel>) -> SimResults {
+        let mut results = SimResults::new();
+        for (n, off_chain) in nodes {
+            results.status.nodes.insert(String::from(n), HashMap::from([(0, true)]));
+            results.balance.on_chain.insert(String::from(n), HashMap::from([(0, 0)]));
+            results.balance.off_chain.insert(String::from(n), HashMap::from([(0, off_chain)]));
+        }
+        results.channels.open_channels.insert(0, channels);
+        results.event_times.push(0);
+        results
+    }
+
+    /*
+     * Get a network analyzer that starts from the results, the events are given to it with process_event the way the simulation sends them
+     */
+    fn test_analyzer(results: SimResults, control: SimControl) -> NetworkAnalyzer {
+        let runtime = Builder::new_current_thread().build().unwrap();
+        let mut analyzer = NetworkAnalyzer::new(runtime.handle().clone(), None, control);
+        analyzer.initialize_results(results);
+        analyzer
+    }
+
+    /*
+     * Get a successful results event at a sim time, events that the nodes report on their own do not have a sim time
+     */
+    fn test_event(sim_time: Option<u64>, event: SimulationEvent) -> SimResultsEvent {
+        SimResultsEvent { sim_time: sim_time, success: true, event: event }
+    }
+
+    #[test]
+    fn import_test() {
+        let mut ln_sim = LnSimulation::new(String::from("test"), 10, 0);
+
+        ln_sim.import_network(String::from("../example_imports/describegraph.json"), String::from(""));
+        ln_sim.import_transactions(String::from("../example_imports/fwdinghistory.json"));
+        ln_sim.import_transactions(String::from("../example_imports/listpayments.json"));
+
+        let scenario = ln_sim.get_scenario();
+        assert_eq!(scenario.nodes.len(), 3);
+        assert_eq!(scenario.channels.len(), 2);
+        assert_eq!(scenario.events.len(), 2);
+        assert!(scenario.validate().is_empty());
+
+        // Save and load the scenario and make sure it defines the same simulation
+        let filename = String::from("import_test_scenario.json");
+        ln_sim.export_network(filename.clone());
+        let loaded = SimScenario::from_file(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+        assert_eq!(loaded.nodes.len(), 3);
+        assert_eq!(loaded.channels.len(), 2);
+        assert_eq!(loaded.events.len(), 2);
+    }
+
+    #[test]
+    fn node_set_test() {
+        // The default profile is built into the library and does not depend on the working directory
+        let mut ln_sim = LnSimulation::new(String::from("test"), 10, 0);
+        let names = ln_sim.create_node_set(String::from("consumer"), 3, String::from("default"));
+        assert_eq!(names, vec![String::from("consumer1"), String::from("consumer2"), String::from("consumer3")]);
+        let scenario = ln_sim.get_scenario();
+        assert!(scenario.nodes.iter().all(|n| n.initial_balance >= 50000 && n.initial_balance <= 100000));
+
+        // Any other profile is read from a file and a profile that does not exist creates no nodes
+        let filename = String::from("node_set_test_profile.json");
+        std::fs::write(&filename, r#"{"initial_on_chain": "1000", "number_channels": "1", "outbound_percentage": 100, "routing_fee": ""}"#).unwrap();
+        let custom = ln_sim.create_node_set(String::from("custom"), 1, filename.clone());
+        std::fs::remove_file(&filename).unwrap();
+        assert_eq!(custom.len(), 1);
+        assert!(ln_sim.create_node_set(String::from("missing"), 1, String::from("missing")).is_empty());
+    }
+
+    #[test]
+    fn scenario_template_test() {
+        let toml_sim = LnSimulation::from_scenario_file(String::from("../example_scenarios/merchant_payments.toml")).unwrap();
+        let yaml_sim = LnSimulation::from_scenario_file(String::from("../example_scenarios/merchant_payments.yaml")).unwrap();
+
+        let scenario = toml_sim.get_scenario();
+        assert_eq!(scenario.nodes.len(), 5); // alice, bob and 3 merchants
+        assert_eq!(scenario.channels.len(), 3);
+        assert_eq!(scenario.channels[0].src_balance_sats, 200000); // the variable is overridden by the scenario that includes the topology
+        assert_eq!(scenario.events.len(), 60); // 59 payments (every 5 seconds from 10 to 300) and 1 stop node event
+        assert!(scenario.validate().is_empty());
+
+        // The same seed creates the same simulation from both formats
+        let toml_json = serde_json::to_string(&scenario).unwrap();
+        let yaml_json = serde_json::to_string(&yaml_sim.get_scenario()).unwrap();
+        assert_eq!(toml_json, yaml_json);
+    }
+
+    #[test]
+    fn traffic_generator_test() {
+        let sim = LnSimulation::from_scenario_file(String::from("../example_scenarios/background_traffic.toml")).unwrap();
+        let scenario = sim.get_scenario();
+        assert_eq!(scenario.traffic.len(), 2);
+        assert_eq!(scenario.traffic[0].senders, vec![String::from("alice"), String::from("bob")]); // the consumer group is expanded
+        assert_eq!(scenario.traffic[1].start, 60);
+        assert_eq!(scenario.traffic[1].end, 300);
+        assert!(scenario.validate().is_empty());
+
+        // The same seed generates the same payments, within the configured amounts and times
+        let capacities = sim.get_node_capacities();
+        let mut generated: Vec<Vec<SimTransaction>> = Vec::new();
+        for _ in 0..2 {
+            let mut txs: Vec<SimTransaction> = Vec::new();
+            for config in &scenario.traffic {
+                let mut generator = SimTrafficGenerator::new(config.clone());
+                generator.initialize(&capacities);
+                for time in 0..(scenario.duration + 1) {
+                    for tx in generator.generate(time) {
+                        assert!(time >= config.start && time <= config.end);
+                        assert!(tx.amount_sats >= 1 && tx.amount_sats <= 50000);
+                        assert!(tx.src_node != tx.dest_node);
+                        txs.push(tx);
+                    }
+                }
+            }
+            generated.push(txs);
+        }
+        assert!(!generated[0].is_empty());
+        assert_eq!(serde_json::to_string(&generated[0]).unwrap(), serde_json::to_string(&generated[1]).unwrap());
+
+        // An amount range that can not be sampled is a problem with the scenario instead of a panic when the payments are generated
+        let mut bad = scenario.clone();
+        bad.traffic[0].amount = AmountDistribution::Uniform { min: 5000, max: 1000 };
+        bad.traffic[1].end = 30;
+        assert_eq!(bad.validate().len(), 2);
+        assert!(topology_generator::generate(&TopologyModel::HubAndSpoke { hubs: 1 }, 3, &bad.traffic[0].amount, &mut StdRng::seed_from_u64(1)).is_err());
+    }
+
+    #[test]
+    fn topology_generator_test() {
+        let mut ln_sim = LnSimulation::new(String::from("test"), 10, 0);
+        ln_sim.set_seed(1);
+        let amount = AmountDistribution::Uniform { min: 50000, max: 500000 };
+
+        // 3 initial channels between the first 3 nodes and 2 for each of the other 97 nodes
+        let ba = ln_sim.create_random_network(String::from("ba"), 100, TopologyModel::BarabasiAlbert { edges_per_node: 2 }, amount.clone(), String::from("default"));
+        assert_eq!(ba.len(), 100);
+        assert_eq!(ln_sim.get_scenario().channels.len(), 3 + 97 * 2);
+
+        // 20 nodes with 2 neighbors on each side
+        ln_sim.create_random_network(String::from("ws"), 20, TopologyModel::WattsStrogatz { neighbors: 4, rewire_probability: 0.2 }, amount.clone(), String::from("default"));
+        assert_eq!(ln_sim.get_scenario().channels.len(), 197 + 40);
+
+        // 1 channel between the 2 hubs and 1 channel to each of the 18 spokes
+        ln_sim.create_random_network(String::from("lsp"), 20, TopologyModel::HubAndSpoke { hubs: 2 }, amount.clone(), String::from("default"));
+        assert_eq!(ln_sim.get_scenario().channels.len(), 237 + 19);
+        assert_eq!(ln_sim.get_group_nodes(&String::from("lsp_hubs")), vec![String::from("lsp1"), String::from("lsp2")]);
+
+        ln_sim.create_random_network(String::from("er"), 20, TopologyModel::ErdosRenyi { probability: 0.5 }, amount.clone(), String::from("default"));
+        ln_sim.create_random_network(String::from("mainnet"), 20, TopologyModel::MainnetLike { graph: String::from("../example_imports/describegraph.json") }, amount.clone(), String::from("default"));
+        assert_eq!(ln_sim.get_group_nodes(&String::from("mainnet")).len(), 20);
+
+        // Every channel is between two different nodes, can be funded and has a unique id
+        let scenario = ln_sim.get_scenario();
+        for c in &scenario.channels {
+            assert!(c.src_node != c.dest_node);
+            assert!(c.src_balance_sats >= 20000);
+        }
+        assert!(scenario.validate().is_empty());
+
+        // Topologies in a scenario file
+        let scale_free = LnSimulation::from_scenario_file(String::from("../example_scenarios/scale_free.toml")).unwrap().get_scenario();
+        assert_eq!(scale_free.nodes.len(), 1052);
+        assert_eq!(scale_free.channels.len(), (3 + 997 * 2) + (1 + 50));
+        assert!(scale_free.validate().is_empty());
+    }
+
+    #[test]
+    fn channel_policy_test() {
+        let sim = LnSimulation::from_scenario_file(String::from("../example_scenarios/fee_strategy.toml")).unwrap();
+        let scenario = sim.get_scenario();
+        assert!(scenario.validate().is_empty());
+
+        // The policies are set on the channel, missing values use the defaults
+        let channel = scenario.channels.iter().find(|c| c.id == 4).unwrap();
+        let src_policy = channel.get_policy(&String::from("merchant1")).unwrap();
+        assert_eq!(src_policy.fee_rate_ppm, 100);
+        assert_eq!(src_policy.base_fee_msat, 0);
+        let dest_policy = channel.get_policy(&String::from("merchant3")).unwrap();
+        assert_eq!(dest_policy.cltv_expiry_delta, 144);
+        assert_eq!((dest_policy.htlc_minimum_msat, dest_policy.htlc_maximum_msat), (Some(1000), Some(25000000)));
+        assert_eq!(dest_policy.get_max_in_flight_percent(channel.src_balance_sats + channel.dest_balance_sats), Some(50));
+        assert!(scenario.channels.iter().find(|c| c.id == 1).unwrap().src_policy.is_none());
+
+        // The policy updates are scheduled
+        let updates: Vec<u32> = scenario.events.iter().filter_map(|e| match &e.event {
+            SimulationEvent::UpdateChannelPolicyEvent(node, 4, p) if node == "merchant1" => Some(p.fee_rate_ppm),
+            _ => None
+        }).collect();
+        assert_eq!(updates, vec![1000, 2500]);
+
+        // Only the node that opens a channel can choose htlc limits, so they can only be on the dest policy and can not be updated
+        let mut bad = scenario.clone();
+        for c in &mut bad.channels {
+            if c.id == 4 {
+                c.src_policy = c.dest_policy.clone();
+            }
+        }
+        for e in &mut bad.events {
+            match &mut e.event {
+                SimulationEvent::UpdateChannelPolicyEvent(_, _, p) => p.htlc_minimum_msat = Some(5000),
+                _ => {}
+            }
+        }
+        assert_eq!(bad.validate().len(), 3);
+    }
+
+    #[test]
+    fn channel_policy_serde_test() {
+        // A policy without htlc limits uses the LDK defaults and a misspelled field is an error instead of being ignored
+        let policy: SimChannelPolicy = serde_json::from_str(r#"{"base_fee_msat": 0}"#).unwrap();
+        assert!(!policy.has_htlc_limits());
+        assert!(serde_json::from_str::<SimChannelPolicy>(r#"{"base_fee_msat": 0, "htlc_max_msat": 25000000}"#).is_err());
+
+        // The htlc maximum is rounded down to a whole percent of the channel capacity, between 1 and 100
+        let max = |msat: u64| SimChannelPolicy { htlc_maximum_msat: Some(msat), ..SimChannelPolicy::default() };
+        assert_eq!(max(25000000).get_max_in_flight_percent(100000), Some(25));
+        assert_eq!(max(25999999).get_max_in_flight_percent(100000), Some(25));
+        assert_eq!(max(1000).get_max_in_flight_percent(100000), Some(1));
+        assert_eq!(max(500000000).get_max_in_flight_percent(100000), Some(100));
+        assert_eq!(SimChannelPolicy::default().get_max_in_flight_percent(100000), None);
+    }
+
+    /*
+     * Remove the keys from every json object in the value, the way the results looked before the fields with those keys were added
+     */
+    fn remove_keys(value: &mut Value, keys: &[&str]) {
+        match value {
+            Value::Object(map) => {
+                for key in keys {
+                    map.remove(*key);
+                }
+                for v in map.values_mut() {
+                    remove_keys(v, keys);
+                }
+            },
+            Value::Array(list) => {
+                for v in list {
+                    remove_keys(v, keys);
+                }
+            },
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn results_serde_test() {
+        // Every field that was added to the results is saved with them
+        let mut channel = test_channel(1, "alice", "bob", 100000, 0);
+        channel.set_policy(&String::from("alice"), SimChannelPolicy { base_fee_msat: 0, fee_rate_ppm: 100, cltv_expiry_delta: 72, ..SimChannelPolicy::default() });
+        let mut results = test_results(vec![("alice", 100000), ("bob", 0)], vec![channel]);
+        let keysend = SimTransaction { id: Some(String::from("keysend")), src_node: String::from("alice"), dest_node: String::from("bob"), amount_sats: 1000, status: SimTransactionStatus::SUCCESSFUL, keysend: true };
+        results.transactions.txs.push(Tx { time: 10, transaction: keysend, parts: Vec::new(), failed_attempts: Vec::new() });
+        let failed = SimTransaction { id: Some(String::from("failed")), src_node: String::from("alice"), dest_node: String::from("bob"), amount_sats: 1000, status: SimTransactionStatus::FAILED(SimFailureReason::PermanentChannelFailure), keysend: false };
+        let attempt = FailedAttempt { time: 20, path: Vec::new(), short_channel_id: Some(1), reason: SimFailureReason::PermanentChannelFailure, permanent: true };
+        results.transactions.txs.push(Tx { time: 20, transaction: failed, parts: Vec::new(), failed_attempts: vec![attempt] });
+        let mut status = SimNodeStatus::new();
+        status.balance = SimNodeBalance { total: 100000, onchain: 0, offchain: 100000 };
+        results.snapshots.insert(5, HashMap::from([(String::from("alice"), status)]));
+        results.probes.push(BalanceProbe { time: 30, finished: Some(31), prober: String::from("bob"), node: String::from("alice"), channel_id: 1, lower_sats: 99000, upper_sats: 100000, probes: 7, actual_sats: 100000, capacity_sats: 100000 });
+        results.fee_market.push(FeeMarketBlock { time: 60, feerate_sat_per_vbyte: 2, filler_txs: 10, confirmed_txs: 10, mempool_txs: 0, last: true });
+        results.invariant_violations.push(InvariantViolation { time: 2, invariant: Invariant::SupplyConservation, event: test_event(Some(2), SimulationEvent::SimulationEndedEvent), description: String::from("test") });
+        let json = results.to_json().unwrap();
+        let loaded = SimResults::from_json(&json).unwrap();
+        assert_eq!(loaded.channels.open_channels[&0][0].src_policy.as_ref().unwrap().fee_rate_ppm, 100);
+        assert!(loaded.transactions.txs[0].transaction.keysend);
+        assert!(matches!(loaded.transactions.txs[1].transaction.status, SimTransactionStatus::FAILED(SimFailureReason::PermanentChannelFailure)));
+        assert_eq!(loaded.get_node_snapshot(5, &String::from("alice")).unwrap().balance.offchain, 100000);
+        assert_eq!(loaded.probes[0].finished, Some(31));
+        assert!(loaded.get_fee_market_blocks()[0].last);
+        let violation = &loaded.get_invariant_violations()[0];
+        assert_eq!((violation.time, violation.invariant, violation.description.as_str()), (2, Invariant::SupplyConservation, "test"));
+
+        // Results saved before the fields were added still load with the defaults
+        let mut old: Value = serde_json::from_str(&json).unwrap();
+        remove_keys(&mut old, &["src_policy", "dest_policy", "keysend", "invariant_violations", "snapshots", "finished", "last"]);
+        // A failed status was saved without a reason and the failure reasons were renamed
+        old["transactions"]["txs"][1]["transaction"]["status"] = Value::from("FAILED");
+        old["transactions"]["txs"][1]["failed_attempts"][0]["reason"] = Value::from("UnknownNextPeer");
+        let old = SimResults::from_json(&old.to_string()).unwrap();
+        assert!(old.channels.open_channels[&0][0].src_policy.is_none());
+        assert!(!old.transactions.txs[0].transaction.keysend);
+        assert!(matches!(old.transactions.txs[1].transaction.status, SimTransactionStatus::FAILED(SimFailureReason::Unknown)));
+        assert_eq!(old.transactions.txs[1].failed_attempts[0].reason, SimFailureReason::PermanentChannelFailure);
+        assert!(old.get_invariant_violations().is_empty());
+        assert!(old.snapshots.is_empty());
+        assert!(old.probes[0].finished.is_none());
+        assert!(!old.get_fee_market_blocks()[0].last);
+    }
+
+    #[test]
+    fn channel_policy_events_test() {
+        // a raises its fee on channel 1 at 20, b has htlc limits and keeps its default fees
+        let mut channel = test_channel(1, "a", "b", 60000, 40000);
+        let (a, b) = (String::from("a"), String::from("b"));
+        let initial = SimChannelPolicy { base_fee_msat: 1000, fee_rate_ppm: 100, cltv_expiry_delta: 72, ..SimChannelPolicy::default() };
+        let raised = SimChannelPolicy { base_fee_msat: 1000, fee_rate_ppm: 2500, cltv_expiry_delta: 72, ..SimChannelPolicy::default() };
+        let limited = SimChannelPolicy { htlc_minimum_msat: Some(1000), htlc_maximum_msat: Some(20000000), ..SimChannelPolicy::default() };
+        channel.set_policy(&a, initial.clone());
+        channel.set_policy(&b, limited.clone());
+        let mut analyzer = test_analyzer(test_results(vec![("a", 60000), ("b", 40000)], vec![channel]), SimControl::new());
+        analyzer.process_event(&test_event(Some(20), SimulationEvent::UpdateChannelPolicyEvent(a.clone(), 1, raised.clone())));
+
+        // The new policy is used from the time of the event and the update is recorded
+        let results = analyzer.get_sim_results();
+        assert_eq!(results.get_channel_policy(10, 1, &a), Some(initial));
+        assert_eq!(results.get_channel_policy(20, 1, &a), Some(raised.clone()));
+        assert_eq!(results.get_channel_policy(20, 1, &b), Some(limited.clone()));
+        assert_eq!(results.get_open_channels(20).unwrap()[0].src_balance_sats, 60000);
+        let updates = results.get_policy_updates();
+        assert_eq!(updates.len(), 1);
+        assert_eq!((updates[0].time, updates[0].node.clone(), updates[0].channel_id), (20, a.clone(), 1));
+
+        // An update that the node rejected is a failed event and the policy does not change
+        analyzer.process_event(&SimResultsEvent { sim_time: Some(30), success: false, event: SimulationEvent::UpdateChannelPolicyEvent(a.clone(), 1, SimChannelPolicy::default()) });
+        let results = analyzer.get_sim_results();
+        assert_eq!(results.get_channel_policy(30, 1, &a), Some(raised));
+        assert_eq!(results.get_policy_updates().len(), 1);
+        assert_eq!(results.failed_events.len(), 1);
+
+        // The htlc limits are set when the channel is opened, so they are kept when b changes its fees
+        analyzer.process_event(&test_event(Some(40), SimulationEvent::UpdateChannelPolicyEvent(b.clone(), 1, raised.clone())));
+        let policy = analyzer.get_sim_results().get_channel_policy(40, 1, &b).unwrap();
+        assert_eq!(policy.fee_rate_ppm, 2500);
+        assert_eq!((policy.htlc_minimum_msat, policy.htlc_maximum_msat), (limited.htlc_minimum_msat, limited.htlc_maximum_msat));
+    }
+
+    #[test]
+    fn channel_policy_export_test() {
+        let mut ln_sim = LnSimulation::new(String::from("test"), 10, 0);
+        ln_sim.create_node(String::from("node1"), 200000, true);
+        ln_sim.create_node(String::from("node2"), 0, true);
+        ln_sim.create_channel(String::from("node1"), String::from("node2"), 40000, 1);
+        let policy = SimChannelPolicy { base_fee_msat: 0, fee_rate_ppm: 250, cltv_expiry_delta: 40, ..SimChannelPolicy::default() };
+        ln_sim.set_channel_policy(String::from("node2"), 1, policy.clone());
+
+        // The policies of the channels are kept when the network is exported and imported again
+        let filename = String::from("channel_policy_export_test.json");
+        ln_sim.export_network(filename.clone());
+        let loaded = LnSimulation::from_scenario_file(filename.clone()).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+        let channel = loaded.get_scenario().channels[0].clone();
+        assert!(channel.src_policy.is_none());
+        assert_eq!(channel.get_policy(&String::from("node2")), Some(policy));
+    }
+
+    #[test]
+    fn force_close_test() {
+        let sim = LnSimulation::from_scenario_file(String::from("../example_scenarios/force_close.toml")).unwrap();
+        let scenario = sim.get_scenario();
+        // Broadcasting a revoked state is only valid when the test signing of revoked commitments is turned on
+        let revoked_problems = if cfg!(feature = "revoked_state") { 0 } else { 1 };
+        assert_eq!(scenario.validate().len(), revoked_problems);
+        assert!(scenario.events.iter().any(|e| matches!(&e.event, SimulationEvent::ForceCloseChannelEvent(node, 1) if node == "alice")));
+        assert!(scenario.events.iter().any(|e| matches!(&e.event, SimulationEvent::BroadcastRevokedStateEvent(node, 3) if node == "bob")));
+        assert!(scenario.events.iter().any(|e| matches!(&e.event, SimulationEvent::MineBlocksEvent(150))));
+
+        // A force close of a channel that does not exist is not valid
+        let mut bad_sim = LnSimulation::from_scenario_file(String::from("../example_scenarios/force_close.toml")).unwrap();
+        bad_sim.create_force_close_channel_event(String::from("alice"), 99, 50);
+        assert_eq!(bad_sim.get_scenario().validate().len(), 1 + revoked_problems);
+
+        // The initiator's funds are locked until its delayed output is swept
+        let sweep = |time: u64, node: &str, delayed: bool, penalty: bool| Sweep { time: time, node: String::from(node), amount_sats: 1000, delayed: delayed, penalty: penalty };
+        let mut force_close = ForceClose {
+            channel_id: 1,
+            run_time_id: None,
+            close_type: CloseType::Force,
+            initiator: String::from("alice"),
+            counterparty: String::from("merchant1"),
+            time: 30,
+            closing_tx: None,
+            closing_fee_sats: None,
+            to_self_delay: Some(144),
+            sweeps: vec![sweep(31, "merchant1", false, false)]
+        };
+        assert_eq!(force_close.get_locked_time(), None);
+        assert_eq!(force_close.is_penalized(), None);
+        force_close.sweeps.push(sweep(61, "alice", true, false));
+        assert_eq!(force_close.get_locked_time(), Some(31));
+        assert_eq!(force_close.is_penalized(), None);
+
+        // A revoked state is penalized when the counterparty claims the outputs with a justice transaction
+        force_close.close_type = CloseType::RevokedState;
+        assert_eq!(force_close.is_penalized(), Some(false));
+        force_close.sweeps = vec![sweep(31, "merchant1", false, false)];
+        assert_eq!(force_close.is_penalized(), None);
+        force_close.sweeps.push(sweep(32, "merchant1", false, true));
+        assert_eq!(force_close.is_penalized(), Some(true));
+
+        // The analyzer closes the channel when alice force closes it at 30 and records each output as it is swept
+        let control = SimControl::new();
+        control.start(100);
+        let mut analyzer = test_analyzer(test_results(vec![("alice", 60000), ("merchant1", 40000)], vec![test_channel(1, "alice", "merchant1", 60000, 40000)]), control.clone());
+        let swept = |node: &str, amount: u64, to_self_delay: Option<u16>| SimulationEvent::SweepEvent(String::from(node), SimSweep { spent_txid: String::from("commitment"), amount_sats: amount, to_self_delay: to_self_delay });
+        analyzer.process_event(&test_event(Some(30), SimulationEvent::ForceCloseChannelEvent(String::from("alice"), 1)));
+        // The commitment is found on-chain by process_on_chain_event
+        let mut results = analyzer.get_sim_results();
+        results.force_closes[0].closing_tx = Some(String::from("commitment"));
+        analyzer.initialize_results(results);
+        control.wait(31);
+        analyzer.process_event(&test_event(None, swept("merchant1", 40000, None)));
+        control.wait(61);
+        analyzer.process_event(&test_event(None, swept("alice", 59000, Some(144))));
+
+        // An output that does not come from the commitment (e.g. from a cooperative close once it has 6 confirmations) is not added to the force close or the balance
+        control.wait(70);
+        analyzer.process_event(&test_event(None, SimulationEvent::SweepEvent(String::from("alice"), SimSweep { spent_txid: String::from("cooperative close"), amount_sats: 1000, to_self_delay: None })));
+        control.end();
+
+        let results = analyzer.get_sim_results();
+        assert!(results.channels.closed_channels[&30].iter().any(|c| c.id == 1));
+        let force_close = &results.force_closes[0];
+        assert_eq!((force_close.initiator.as_str(), force_close.counterparty.as_str(), force_close.run_time_id.clone()), ("alice", "merchant1", Some(String::from("1"))));
+        assert_eq!(force_close.to_self_delay, Some(144));
+        assert_eq!(force_close.get_locked_time(), Some(31));
+        assert_eq!(results.balance.on_chain["merchant1"][&31], 40000);
+        assert_eq!(results.balance.on_chain["alice"][&61], 59000);
+        assert!(results.event_times.contains(&61));
+        assert_eq!(force_close.sweeps.len(), 2);
+        assert!(!results.event_times.contains(&70));
+        assert_eq!(results.get_on_chain_bal(70, &String::from("alice")), Some(59000));
+    }
+
+    #[test]
+    fn keysend_test() {
+        let sim = LnSimulation::from_scenario_file(String::from("../example_scenarios/donations.toml")).unwrap();
+        let scenario = sim.get_scenario();
+        assert!(scenario.validate().is_empty());
+
+        // A keysend is sent every 10 seconds from a consumer to merchant3
+        let keysends: Vec<&SimTransaction> = scenario.events.iter().filter_map(|e| match &e.event {
+            SimulationEvent::KeysendEvent(tx) => Some(tx),
+            _ => None
+        }).collect();
+        assert_eq!(keysends.len(), 12);
+        assert!(keysends.iter().all(|tx| tx.keysend && tx.dest_node == "merchant3" && (tx.src_node == "alice" || tx.src_node == "bob")));
+        assert!(keysends.iter().all(|tx| tx.amount_sats >= 500 && tx.amount_sats <= 2000));
+    }
+
+    #[test]
+    fn keysend_events_test() {
+        // alice sends a keysend to merchant3 through bob without an invoice, bob earns a 1 sat fee
+        let channels = vec![test_channel(1, "alice", "bob", 50000, 0), test_channel(2, "bob", "merchant3", 50000, 0)];
+        let mut analyzer = test_analyzer(test_results(vec![("alice", 50000), ("bob", 50000), ("merchant3", 0)], channels), SimControl::new());
+        let keysend = |id: &str| SimTransaction { id: Some(String::from(id)), src_node: String::from("alice"), dest_node: String::from("merchant3"), amount_sats: 2000, status: SimTransactionStatus::PENDING, keysend: true };
+        let path = SimPaymentPath { payment_id: String::from("k1"), path: vec![
+            PathHop { short_channel_id: 1, amount: 1, amount_msat: 1000, node_pub_key: String::from("bob") },
+            PathHop { short_channel_id: 2, amount: 2000, amount_msat: 2000000, node_pub_key: String::from("merchant3") }
+        ] };
+        analyzer.process_event(&test_event(Some(10), SimulationEvent::KeysendEvent(keysend("k1"))));
+        analyzer.process_event(&test_event(None, SimulationEvent::PaymentSuccessEvent(String::from("k1"), 1000)));
+        analyzer.process_event(&test_event(None, SimulationEvent::PaymentPathSuccessful(path)));
+
+        // The keysend is a transaction like a payment to an invoice and the balances move along its path
+        let results = analyzer.get_sim_results();
+        assert_eq!(results.transactions.txs.len(), 1);
+        assert!(results.transactions.txs[0].transaction.keysend);
+        assert!(matches!(results.transactions.txs[0].transaction.status, SimTransactionStatus::SUCCESSFUL));
+        assert_eq!(results.get_off_chain_bal(10, &String::from("alice")), Some(47999));
+        assert_eq!(results.get_off_chain_bal(10, &String::from("bob")), Some(50001));
+        assert_eq!(results.get_off_chain_bal(10, &String::from("merchant3")), Some(2000));
+        assert_eq!(results.get_open_channels(10).unwrap()[1].dest_balance_sats, 2000);
+
+        // A keysend that could not be sent is a failed event and one that was not received fails without changing any balances
+        analyzer.process_event(&SimResultsEvent { sim_time: Some(20), success: false, event: SimulationEvent::KeysendEvent(keysend("k2")) });
+        analyzer.process_event(&test_event(Some(30), SimulationEvent::KeysendEvent(keysend("k3"))));
+        analyzer.process_event(&test_event(None, SimulationEvent::PaymentFailedEvent(String::from("k3"))));
+        let results = analyzer.get_sim_results();
+        assert_eq!(results.failed_events.len(), 1);
+        assert_eq!(results.transactions.txs.len(), 2);
+        assert!(matches!(results.transactions.txs[1].transaction.status, SimTransactionStatus::FAILED(_)));
+        assert_eq!(results.get_off_chain_bal(30, &String::from("alice")), Some(47999));
+    }
+
+    #[test]
+    fn multi_path_payment_test() {
+        // A 30000 sat payment split into two parts, one direct and one forwarded by n1 for 3.5 sats and by n2 for 2 sats
+        let hop = |scid: u64, amount_msat: u64, node: &str| PathHop { short_channel_id: scid, amount: amount_msat / 1000, amount_msat: amount_msat, node_pub_key: String::from(node) };
+        let direct = SimPaymentPath { payment_id: String::from("id"), path: vec![hop(1, 10000000, "dest")] };
+        let forwarded = SimPaymentPath { payment_id: String::from("id"), path: vec![hop(2, 3500, "n1"), hop(3, 2000, "n2"), hop(4, 20000000, "dest")] };
+        assert_eq!(direct.get_amount(), 10000);
+        assert_eq!(direct.get_fee(), 0);
+        assert_eq!(direct.get_forwarded_amount(0), 10000);
+        assert_eq!(forwarded.get_amount(), 20000);
+        assert_eq!(forwarded.get_fee(), 5);
+        assert_eq!(forwarded.get_forwarded_amount(0), 20005);
+        assert_eq!(forwarded.get_forwarded_amount(1), 20002);
+        assert_eq!(forwarded.get_forwarded_amount(2), 20000);
+        assert_eq!(forwarded.get_forwarded_amount_msat(0), 20005500);
+
+        // The payment is sent at 10, then the sender reports the fee and each part that was received
+        let channels = vec![test_channel(1, "src", "dest", 50000, 0), test_channel(2, "src", "n1", 50000, 0), test_channel(3, "n1", "n2", 50000, 0), test_channel(4, "n2", "dest", 50000, 0)];
+        let mut analyzer = test_analyzer(test_results(vec![("src", 200000), ("n1", 50000), ("n2", 50000), ("dest", 0)], channels), SimControl::new());
+        let tx = SimTransaction { id: Some(String::from("id")), src_node: String::from("src"), dest_node: String::from("dest"), amount_sats: 30000, status: SimTransactionStatus::PENDING, keysend: false };
+        analyzer.process_event(&test_event(Some(10), SimulationEvent::TransactionEvent(tx)));
+        analyzer.process_event(&test_event(None, SimulationEvent::PaymentSuccessEvent(String::from("id"), 5500)));
+        for p in [&direct, &forwarded] {
+            analyzer.process_event(&test_event(None, SimulationEvent::PaymentPathSuccessful(p.clone())));
+        }
+
+        // The transaction keeps each part
+        let results = analyzer.get_sim_results();
+        let tx = &results.transactions.txs[0];
+        assert!(matches!(tx.transaction.status, SimTransactionStatus::SUCCESSFUL));
+        assert!(tx.is_multi_path());
+        assert_eq!(tx.get_parts().iter().map(|p| p.amount_sats).sum::<u64>(), tx.transaction.amount_sats);
+        assert_eq!(tx.get_fee_sats(), 5);
+
+        // Each channel moved the amount of its part plus the fees of the rest of the path, at the time the payment was sent
+        let balances: Vec<(u64, u64, u64)> = results.channels.open_channels[&10].iter().map(|c| (c.id, c.src_balance_sats, c.dest_balance_sats)).collect();
+        assert_eq!(balances, vec![(1, 40000, 10000), (2, 29994, 20005), (3, 29998, 20002), (4, 30000, 20000)]);
+
+        // The sender paid the amount and the fees, each forwarding node earned its fee and the destination got both parts
+        let off_chain = |n: &str| results.balance.off_chain[n][&10];
+        assert_eq!(off_chain("src"), 169994);
+        assert_eq!(off_chain("n1"), 50003);
+        assert_eq!(off_chain("n2"), 50002);
+        assert_eq!(off_chain("dest"), 30000);
+        assert_eq!(results.forwarding.forwards.iter().map(|f| (f.node.as_str(), f.channel_in, f.channel_out)).collect::<Vec<_>>(), vec![("n1", Some(2), Some(3)), ("n2", Some(3), Some(4))]);
+    }
+
+    #[test]
+    fn failed_payment_test() {
+        let attempt = |scid: u64, reason: SimFailureReason| FailedAttempt { time: 10, path: Vec::new(), short_channel_id: Some(scid), reason: reason, permanent: false };
+        let tx = |status: SimTransactionStatus, attempts: Vec<FailedAttempt>| Tx {
+            time: 10,
+            transaction: SimTransaction { id: Some(String::from("id")), src_node: String::from("src"), dest_node: String::from("dest"), amount_sats: 1000, status: status, keysend: false },
+            parts: Vec::new(),
+            failed_attempts: attempts
+        };
+
+        // A payment that succeeded after two failed attempts was retried twice, a payment that failed was not retried after its last attempt
+        let succeeded = tx(SimTransactionStatus::SUCCESSFUL, vec![attempt(1, SimFailureReason::ChannelUpdateReceived), attempt(2, SimFailureReason::FeeInsufficient)]);
+        let failed = tx(SimTransactionStatus::FAILED(SimFailureReason::PermanentChannelFailure), vec![attempt(1, SimFailureReason::ChannelUpdateReceived), attempt(3, SimFailureReason::PermanentChannelFailure)]);
+        assert_eq!(succeeded.get_retry_count(), 2);
+        assert_eq!(failed.get_retry_count(), 1);
+        assert_eq!(tx(SimTransactionStatus::FAILED(SimFailureReason::Unknown), Vec::new()).get_retry_count(), 0);
+
+        // The results count the failures by reason and by channel
+        let mut results = SimResults::new();
+        results.transactions.txs = vec![succeeded, failed];
+        assert_eq!(results.get_failed_transactions().len(), 1);
+        let reasons = results.get_failure_reason_counts();
+        assert_eq!(reasons.get(&SimFailureReason::ChannelUpdateReceived), Some(&2));
+        assert_eq!(reasons.get(&SimFailureReason::FeeInsufficient), Some(&1));
+        assert_eq!(results.get_failing_channel_counts().get(&1), Some(&2));
+
+        // A status that does not exist is an error
+        assert!(serde_json::from_str::<SimTransactionStatus>(r#""DONE""#).is_err());
+
+        // The analyzer records each failed attempt at the current sim time, n1 failed to forward the first one to channel 2
+        let control = SimControl::new();
+        control.start(100);
+        let mut analyzer = test_analyzer(test_results(vec![("src", 5000), ("n1", 5000), ("dest", 0)], vec![test_channel(1, "src", "n1", 5000, 0), test_channel(2, "n1", "dest", 5000, 0)]), control.clone());
+        let tx = SimTransaction { id: Some(String::from("id")), src_node: String::from("src"), dest_node: String::from("dest"), amount_sats: 1000, status: SimTransactionStatus::PENDING, keysend: false };
+        analyzer.process_event(&test_event(Some(10), SimulationEvent::TransactionEvent(tx)));
+        control.wait(12);
+        let path = vec![PathHop { short_channel_id: 1, amount: 1, amount_msat: 1000, node_pub_key: String::from("n1") }, PathHop { short_channel_id: 2, amount: 1000, amount_msat: 1000000, node_pub_key: String::from("dest") }];
+        let failure = |scid: u64, reason: SimFailureReason, permanent: bool| SimulationEvent::PaymentPathFailedEvent(SimPaymentFailure { payment_id: String::from("id"), path: path.clone(), short_channel_id: Some(scid), reason: reason, permanent: permanent });
+        analyzer.process_event(&test_event(None, failure(2, SimFailureReason::ChannelUpdateReceived, false)));
+        analyzer.process_event(&test_event(None, failure(1, SimFailureReason::PermanentChannelFailure, true)));
+        analyzer.process_event(&test_event(None, SimulationEvent::PaymentFailedEvent(String::from("id"))));
+        control.end();
+
+        let results = analyzer.get_sim_results();
+        let tx = &results.transactions.txs[0];
+        assert!(matches!(tx.transaction.status, SimTransactionStatus::FAILED(SimFailureReason::PermanentChannelFailure)));
+        assert_eq!(tx.failed_attempts.iter().map(|a| (a.time, a.short_channel_id, a.permanent)).collect::<Vec<_>>(), vec![(12, Some(2), false), (12, Some(1), true)]);
+        assert_eq!(tx.get_retry_count(), 1);
+        assert_eq!(results.forwarding.failed_forwards.len(), 1);
+        let failed_forward = &results.forwarding.failed_forwards[0];
+        assert_eq!((failed_forward.node.as_str(), failed_forward.channel_in, failed_forward.channel_out, failed_forward.amount_sats), ("n1", Some(1), Some(2), 1000));
+
+        // A failed payment does not move any balances
+        assert_eq!(results.balance.off_chain["src"].len(), 1);
+        assert!(results.channels.open_channels.get(&10).is_none());
+    }
+
+    #[test]
+    fn forwarding_stats_test() {
+        // merchant1 forwards two payments from channel 1 (scid 100) to channel 2 (scid 200) and fails to forward one
+        let forward = |time: u64, amount: u64, fee: u64, fee_msat: Option<u64>| Forward {
+            time: time, node: String::from("merchant1"), short_channel_id_in: 100, short_channel_id_out: 200, channel_in: Some(1), channel_out: Some(2),
+            amount_in_sats: amount + fee, amount_out_sats: amount, fee_sats: fee, fee_msat: fee_msat
+        };
+        let mut results = SimResults::new();
+        results.event_times = vec![0, 10, 20];
+        results.forwarding.forwards = vec![forward(10, 5000, 1, Some(1050)), forward(20, 3000, 2, None)];
+        results.forwarding.failed_forwards.push(FailedForward {
+            time: 20, node: String::from("merchant1"), short_channel_id_in: 100, short_channel_id_out: 200, channel_in: Some(1), channel_out: Some(2),
+            amount_sats: 9000, reason: SimFailureReason::ChannelUpdateReceived
+        });
+
+        let node = String::from("merchant1");
+        assert_eq!(results.get_node_forwarding_stats(10, &node), ForwardingStats { forwards: 1, volume_sats: 5000, fee_sats: 1, failed_forwards: 0 });
+        assert_eq!(results.get_node_forwarding_stats(20, &node), ForwardingStats { forwards: 2, volume_sats: 8000, fee_sats: 3, failed_forwards: 1 });
+        assert_eq!(results.get_channel_forwarding_stats(20, 1), ForwardingStats { forwards: 2, volume_sats: 8003, fee_sats: 0, failed_forwards: 0 });
+        assert_eq!(results.get_channel_forwarding_stats(20, 2).fee_sats, 3);
+        assert_eq!(results.get_node_fee_revenue(&node), vec![(0, 0), (10, 1), (20, 3)]);
+
+        // The export has the same fields as lncli fwdinghistory
+        let history: Value = serde_json::from_str(&results.get_forwarding_history(&node).unwrap()).unwrap();
+        let events = history["forwarding_events"].as_array().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["chan_id_in"], "100");
+        assert_eq!(events[0]["amt_in"], "5001");
+        assert_eq!(events[0]["fee_msat"], "1050");
+        assert_eq!(events[1]["fee_msat"], "2000");
+        assert_eq!(history["last_offset_index"], 2);
+
+        // The analyzer records the forwards from the payment paths, n1 reports the fee of the first before the sender reports its path and the fee of the second after
+        let mut analyzer = test_analyzer(test_results(vec![("src", 50000), ("n1", 50000), ("dest", 0)], vec![test_channel(1, "src", "n1", 50000, 0), test_channel(2, "n1", "dest", 50000, 0)]), SimControl::new());
+        let forwarded = |fee_msat: u64| SimulationEvent::PaymentForwardedEvent(String::from("n1"), SimForward { prev_channel_id: Some(String::from("1")), next_channel_id: Some(String::from("2")), fee_earned_msat: Some(fee_msat) });
+        let payment = |id: &str, amount: u64, fee_msat: u64| {
+            let tx = SimTransaction { id: Some(String::from(id)), src_node: String::from("src"), dest_node: String::from("dest"), amount_sats: amount, status: SimTransactionStatus::PENDING, keysend: false };
+            let path = vec![PathHop { short_channel_id: 1, amount: fee_msat / 1000, amount_msat: fee_msat, node_pub_key: String::from("n1") }, PathHop { short_channel_id: 2, amount: amount, amount_msat: amount * 1000, node_pub_key: String::from("dest") }];
+            (SimulationEvent::TransactionEvent(tx), SimulationEvent::PaymentSuccessEvent(String::from(id), fee_msat), SimulationEvent::PaymentPathSuccessful(SimPaymentPath { payment_id: String::from(id), path: path }))
+        };
+        let (sent, success, path) = payment("p1", 5000, 1500);
+        analyzer.process_event(&test_event(Some(10), sent));
+        analyzer.process_event(&test_event(None, forwarded(1500)));
+        analyzer.process_event(&test_event(None, success));
+        analyzer.process_event(&test_event(None, path));
+        let (sent, success, path) = payment("p2", 3000, 2000);
+        analyzer.process_event(&test_event(Some(20), sent));
+        analyzer.process_event(&test_event(None, success));
+        analyzer.process_event(&test_event(None, path));
+        analyzer.process_event(&test_event(None, forwarded(2000)));
+
+        let results = analyzer.get_sim_results();
+        let forwards: Vec<(u64, u64, u64, Option<u64>)> = results.forwarding.forwards.iter().map(|f| (f.time, f.amount_in_sats, f.fee_sats, f.fee_msat)).collect();
+        assert_eq!(forwards, vec![(10, 5001, 1, Some(1500)), (20, 3002, 2, Some(2000))]);
+        assert_eq!(results.get_node_forwarding_stats(20, &String::from("n1")), ForwardingStats { forwards: 2, volume_sats: 8000, fee_sats: 3, failed_forwards: 0 });
+        assert_eq!(results.balance.off_chain["n1"][&20], 50003);
+    }
+
+    #[test]
+    fn network_metrics_test() {
+        // A line a - b - c - d with an extra channel b - e
+        let channels = vec![test_channel(1, "a", "b", 100000, 0), test_channel(2, "b", "c", 50000, 50000), test_channel(3, "c", "d", 20000, 0), test_channel(4, "b", "e", 30000, 10000)];
+        let nodes: Vec<String> = ["a", "b", "c", "d", "e"].iter().map(|n| String::from(*n)).collect();
+
+        let metrics = network_metrics::compute_metrics(&nodes, &channels);
+        assert_eq!(metrics.degree.get("b"), Some(&3));
+        assert_eq!(metrics.components, 1);
+        assert_eq!(metrics.largest_component, 5);
+        assert_eq!(metrics.diameter, 3);
+        // b is on the shortest paths a-c, a-d, a-e, c-e, d-e and c is on the shortest paths a-d, b-d, d-e
+        assert_eq!(metrics.betweenness.get("b"), Some(&5.0));
+        assert_eq!(metrics.betweenness.get("c"), Some(&3.0));
+        assert_eq!(metrics.betweenness.get("a"), Some(&0.0));
+        assert_eq!(metrics.capacity.channels, 4);
+        assert_eq!(metrics.capacity.median_sats, 70000);
+        assert_eq!(metrics.balance_ratio.get(&2), Some(&0.5));
+        assert_eq!(metrics.balance_ratio.get(&4), Some(&0.75));
+
+        // When c goes offline the network splits into a - b - e and d
+        let online: Vec<String> = nodes.iter().filter(|n| n.as_str() != "c").cloned().collect();
+        let metrics = network_metrics::compute_metrics(&online, &channels);
+        assert_eq!(metrics.components, 2);
+        assert_eq!(metrics.largest_component, 3);
+        assert_eq!(metrics.diameter, 2);
+        assert_eq!(metrics.capacity.channels, 2);
+
+        // The metrics are stored as a time series
+        let mut results = SimResults::new();
+        results.metrics.insert(0, network_metrics::compute_metrics(&nodes, &channels));
+        results.metrics.insert(10, metrics);
+        assert_eq!(results.get_metric_series(|m| m.components), vec![(0, 1), (10, 2)]);
+        assert_eq!(results.get_network_metrics(5).unwrap().components, 1);
+
+        // The analyzer computes the metrics at every event time when the simulation ends, c is stopped at 10 and started again at 20
+        let mut analyzer = test_analyzer(test_results(nodes.iter().map(|n| (n.as_str(), 0)).collect(), channels.clone()), SimControl::new());
+        analyzer.process_event(&test_event(Some(10), SimulationEvent::StopNodeEvent(String::from("c"))));
+        analyzer.process_event(&test_event(Some(20), SimulationEvent::StartNodeEvent(String::from("c"))));
+        analyzer.process_event(&test_event(Some(30), SimulationEvent::SimulationEndedEvent));
+        let results = analyzer.get_sim_results();
+        assert_eq!(results.get_metric_series(|m| (m.components, m.diameter)), vec![(0, (1, 3)), (10, (2, 2)), (20, (1, 3)), (30, (1, 3))]);
+    }
+
+    #[test]
+    fn reachability_test() {
+        // a can send to c through b (limited by the 30000 in b -> c) and directly (10000), c can only send back through b
+        let results = test_results(vec![("a", 60000), ("b", 30000), ("c", 20000), ("d", 0)], vec![test_channel(1, "a", "b", 50000, 0), test_channel(2, "b", "c", 30000, 20000), test_channel(3, "a", "c", 10000, 0)]);
+
+        let (a, b, c, d) = (String::from("a"), String::from("b"), String::from("c"), String::from("d"));
+        let matrix = results.get_reachability(0, None, 0);
+        assert_eq!(matrix.get_num_pairs(), 12);
+        assert_eq!(matrix.get_max_flow(&a, &c), Some(40000));
+        assert_eq!(matrix.get_max_flow(&c, &a), Some(0));
+        assert_eq!(matrix.get_max_flow(&c, &b), Some(20000));
+        assert!(matrix.is_reachable(&a, &b));
+        assert!(!matrix.is_reachable(&a, &d));
+        // a->b, a->c, b->c and c->b can send 20000
+        assert_eq!(matrix.get_success_probability(20000), 4.0 / 12.0);
+
+        // Only the sampled pairs are analyzed and the same seed picks the same pairs
+        let sampled = results.get_reachability(0, Some(5), 7);
+        assert_eq!(sampled.get_num_pairs(), 5);
+        assert_eq!(sampled.max_flow, results.get_reachability(0, Some(5), 7).max_flow);
+
+        // When b goes offline a can only send to c directly, after a sends 4000 to c each of them can send the other what is on their side
+        let mut analyzer = test_analyzer(results, SimControl::new());
+        let tx = SimTransaction { id: Some(String::from("p")), src_node: a.clone(), dest_node: c.clone(), amount_sats: 4000, status: SimTransactionStatus::PENDING, keysend: false };
+        let path = SimPaymentPath { payment_id: String::from("p"), path: vec![PathHop { short_channel_id: 3, amount: 4000, amount_msat: 4000000, node_pub_key: c.clone() }] };
+        analyzer.process_event(&test_event(Some(10), SimulationEvent::StopNodeEvent(b.clone())));
+        analyzer.process_event(&test_event(Some(20), SimulationEvent::TransactionEvent(tx)));
+        analyzer.process_event(&test_event(None, SimulationEvent::PaymentSuccessEvent(String::from("p"), 0)));
+        analyzer.process_event(&test_event(None, SimulationEvent::PaymentPathSuccessful(path)));
+        let results = analyzer.get_sim_results();
+        assert_eq!(results.get_reachability(10, None, 0).get_max_flow(&a, &c), Some(10000));
+        let matrix = results.get_reachability(20, None, 0);
+        assert_eq!(matrix.get_max_flow(&a, &c), Some(6000));
+        assert_eq!(matrix.get_max_flow(&c, &a), Some(4000));
+        assert_eq!(matrix.get_num_pairs(), 6);
+    }
+
+    #[test]
+    fn churn_model_test() {
+        let sim = LnSimulation::from_scenario_file(String::from("../example_scenarios/mobile_churn.toml")).unwrap();
+        let scenario = sim.get_scenario();
+        assert!(scenario.validate().is_empty());
+        assert_eq!(scenario.churn.len(), 3);
+        assert_eq!(scenario.churn[0].nodes, vec![String::from("alice"), String::from("bob")]); // the consumer group is expanded
+        assert_eq!(scenario.churn[1].end, 400);
+        assert_eq!(scenario.churn[2].start, 400);
+
+        // Each node alternates between stopping and starting, inside the time range of the model, and ends up online
+        let events = sim.get_generated_events();
+        for config in &scenario.churn {
+            let generated = config.generate_events();
+            assert!(!generated.is_empty());
+            assert_eq!(generated.len(), config.generate_events().len()); // the same seed creates the same events
+            for node in &config.nodes {
+                let mut node_events: Vec<&(u64, SimulationEvent)> = generated.iter().filter(|(_, e)| match e {
+                    SimulationEvent::StartNodeEvent(n) | SimulationEvent::StopNodeEvent(n) => n == node,
+                    _ => false
+                }).collect();
+                node_events.sort_by_key(|(t, e)| (*t, matches!(e, SimulationEvent::StartNodeEvent(_))));
+                for (i, (time, event)) in node_events.iter().enumerate() {
+                    assert!(*time >= config.start && *time <= config.end);
+                    assert_eq!(matches!(event, SimulationEvent::StopNodeEvent(_)), i % 2 == 0);
+                    assert!(events[time].iter().any(|e| e.to_string() == event.to_string()));
+                }
+                assert_eq!(node_events.len() % 2, 0);
+            }
+        }
+
+        // A diurnal node is online for half of each period
+        let diurnal = ChurnConfig { model: ChurnModel::Diurnal { period: 100, online_fraction: 0.5 }, nodes: vec![String::from("a")], start: 0, end: 1000, seed: 1 };
+        let stops = diurnal.generate_events().iter().filter(|(_, e)| matches!(e, SimulationEvent::StopNodeEvent(_))).count();
+        assert!(stops == 10 || stops == 11);
+
+        // The parameters and nodes of a churn model are checked
+        let mut bad = scenario.clone();
+        bad.churn[0].model = ChurnModel::Exponential { mean_uptime: 0.0, mean_downtime: 10.0 };
+        bad.churn[1].nodes.push(String::from("nobody"));
+        assert_eq!(bad.validate().len(), 2);
+    }
+
+    #[test]
+    fn churn_events_test() {
+        // a goes offline at 25 and comes back at 50, the simulation ends at 100
+        let mut analyzer = test_analyzer(test_results(vec![("a", 10000), ("b", 0)], vec![test_channel(1, "a", "b", 10000, 0)]), SimControl::new());
+        analyzer.process_event(&test_event(Some(25), SimulationEvent::StopNodeEvent(String::from("a"))));
+        analyzer.process_event(&test_event(Some(50), SimulationEvent::StartNodeEvent(String::from("a"))));
+
+        // A node that could not be stopped is a failed event and stays online
+        analyzer.process_event(&SimResultsEvent { sim_time: Some(60), success: false, event: SimulationEvent::StopNodeEvent(String::from("b")) });
+        analyzer.process_event(&test_event(Some(100), SimulationEvent::SimulationEndedEvent));
+
+        // The results report the percentage of the simulation that each node was online
+        let results = analyzer.get_sim_results();
+        assert!(!results.get_node_status(30, &String::from("a")));
+        assert_eq!(results.failed_events.len(), 1);
+        assert_eq!(results.get_node_availability(&String::from("a")), Some(75.0));
+        assert_eq!(results.get_availability()[&String::from("b")], 100.0);
+        assert!(results.get_node_availability(&String::from("c")).is_none());
+    }
+
+    #[test]
+    fn partition_test() {
+        let sim = LnSimulation::from_scenario_file(String::from("../example_scenarios/partition.toml")).unwrap();
+        let scenario = sim.get_scenario();
+        assert!(scenario.validate().is_empty());
+        let partition = scenario.events.iter().find(|e| e.time == 100).unwrap();
+        match &partition.event {
+            SimulationEvent::PartitionEvent(nodes_a, nodes_b) => {
+                assert_eq!(*nodes_a, vec![String::from("alice"), String::from("bob")]); // the groups are expanded
+                assert_eq!(nodes_b.len(), 3);
+            },
+            _ => panic!("expected a PartitionEvent at 100 seconds")
+        }
+
+        // A node can not be on both sides of a partition or disconnected from itself
+        let mut bad = scenario.clone();
+        for e in &mut bad.events {
+            match &mut e.event {
+                SimulationEvent::PartitionEvent(_, nodes_b) => nodes_b.push(String::from("alice")),
+                SimulationEvent::DisconnectPeersEvent(_, peer) => *peer = String::from("alice"),
+                _ => {}
+            }
+        }
+        assert_eq!(bad.validate().len(), 2);
+
+        // The results show which channels could not be used while the partition was active
+        let mut results = SimResults::new();
+        let channel = SimChannel {
+            src_node: String::from("alice"),
+            dest_node: String::from("merchant1"),
+            src_balance_sats: 100000,
+            dest_balance_sats: 0,
+            id: 1,
+            short_id: None,
+            run_time_id: None,
+            funding_tx: None,
+            penalty_reserve_sats: None,
+            src_policy: None,
+            dest_policy: None
+        };
+        results.channels.open_channels.insert(0, vec![channel]);
+        results.partitions.push(Partition { time: 60, healed: Some(200), links: vec![(String::from("merchant1"), String::from("alice"))] });
+        assert!(results.is_partitioned(100, &String::from("alice"), &String::from("merchant1")));
+        assert!(!results.is_partitioned(200, &String::from("alice"), &String::from("merchant1")));
+        assert_eq!(results.get_partitioned_channels(100).len(), 1);
+        assert!(results.get_partitioned_channels(30).is_empty());
+    }
+
+    #[test]
+    fn partition_events_test() {
+        // a is cut off from b and c at 10 and the partition is healed at 20
+        let results = test_results(vec![("a", 50000), ("b", 30000), ("c", 0)], vec![test_channel(1, "a", "b", 50000, 0), test_channel(2, "b", "c", 30000, 0)]);
+        let mut analyzer = test_analyzer(results, SimControl::new());
+        let (a, b, c) = (String::from("a"), String::from("b"), String::from("c"));
+        analyzer.process_event(&test_event(Some(10), SimulationEvent::PartitionEvent(vec![a.clone()], vec![b.clone(), c.clone()])));
+        let results = analyzer.get_sim_results();
+        assert_eq!(results.get_partitions()[0].links, vec![(a.clone(), b.clone()), (a.clone(), c.clone())]);
+        assert_eq!(results.get_partitions()[0].healed, None);
+
+        analyzer.process_event(&test_event(Some(20), SimulationEvent::HealPartitionEvent));
+        analyzer.process_event(&test_event(Some(30), SimulationEvent::SimulationEndedEvent));
+        let results = analyzer.get_sim_results();
+        assert_eq!(results.get_partitions()[0].healed, Some(20));
+        assert_eq!(results.get_partitioned_channels(10).len(), 1);
+
+        // The channel a - b can not be used while the partition is active
+        assert!(results.get_reachability(0, None, 0).is_reachable(&a, &b));
+        let matrix = results.get_reachability(10, None, 0);
+        assert!(!matrix.is_reachable(&a, &b));
+        assert!(!matrix.is_reachable(&a, &c));
+        assert!(matrix.is_reachable(&b, &c));
+        assert!(results.get_reachability(20, None, 0).is_reachable(&a, &c));
+        assert_eq!(results.get_metric_series(|m| m.components), vec![(0, 1), (10, 2), (20, 1), (30, 1)]);
+
+        // Nodes that fail to disconnect are a failed event and no partition is recorded
+        let mut analyzer = test_analyzer(test_results(vec![("a", 0), ("b", 0)], Vec::new()), SimControl::new());
+        analyzer.process_event(&SimResultsEvent { sim_time: Some(10), success: false, event: SimulationEvent::DisconnectPeersEvent(a.clone(), b.clone()) });
+        let results = analyzer.get_sim_results();
+        assert!(results.get_partitions().is_empty());
+        assert_eq!(results.failed_events.len(), 1);
+    }
+
+    #[test]
+    fn jamming_test() {
+        let sim = LnSimulation::from_scenario_file(String::from("../example_scenarios/jamming.toml")).unwrap();
+        let scenario = sim.get_scenario();
+        assert!(scenario.validate().is_empty());
+        assert_eq!(scenario.jamming.len(), 2);
+
+        // The slow attack holds 8 payments, they are sent again when they expire and the last ones are released when the attack ends
+        let slow = scenario.jamming[0].generate_events(0);
+        let holds: Vec<&(u64, SimulationEvent)> = slow.iter().filter(|(_, e)| matches!(e, SimulationEvent::HoldPaymentEvent(_))).collect();
+        assert_eq!(holds.len(), 8);
+        assert!(holds.iter().all(|(t, e)| *t == 60 && matches!(e, SimulationEvent::HoldPaymentEvent(p) if p.resend_until == Some(180))));
+        assert!(slow.iter().any(|(t, e)| *t == 180 && matches!(e, SimulationEvent::ReleaseHeldPaymentEvent(7))));
+
+        // Every fast jamming payment is released a second after it is sent and the ids are unique across the attacks
+        let events = sim.get_generated_events();
+        let mut ids: Vec<u64> = Vec::new();
+        for (time, list) in &events {
+            for e in list {
+                match e {
+                    SimulationEvent::HoldPaymentEvent(p) => {
+                        assert!(!ids.contains(&p.id));
+                        ids.push(p.id);
+                        if *time >= 200 {
+                            assert!(events[&(time + 1)].iter().any(|r| matches!(r, SimulationEvent::ReleaseHeldPaymentEvent(id) if *id == p.id)));
+                        }
+                    },
+                    _ => {}
+                }
+            }
+        }
+        assert!(ids.len() > 8);
+
+        // The attack parameters and nodes are checked
+        let mut bad = scenario.clone();
+        bad.jamming[0].dest = String::from("mallory");
+        bad.jamming[1].src = String::from("nobody");
+        assert_eq!(bad.validate().len(), 2);
+
+        // The results show the slots and liquidity that the held payments used and the failure rate of the honest payments
+        let mut results = SimResults::new();
+        results.event_times = vec![0, 60, 70, 120];
+        let hops = vec![
+            PathHop { short_channel_id: 1, amount: 1, amount_msat: 1000, node_pub_key: String::from("alice") },
+            PathHop { short_channel_id: 2, amount: 1000, amount_msat: 1000000, node_pub_key: String::from("merchant1") }
+        ];
+        for id in 0..2 {
+            results.jamming.htlcs.push(JammedHtlc { id: id, payment_id: id.to_string(), src_node: String::from("mallory"), dest_node: String::from("sink"), amount_sats: 1000, sent: 60, released: Some(120), path: hops.clone(), channels: vec![10, 1] });
+        }
+        results.jamming.htlcs.push(JammedHtlc { id: 2, payment_id: String::from("2"), src_node: String::from("mallory"), dest_node: String::from("sink"), amount_sats: 1000, sent: 60, released: Some(120), path: Vec::new(), channels: Vec::new() });
+        assert_eq!(results.get_htlc_slot_occupancy(70)[&1], 2);
+        assert!(results.get_htlc_slot_occupancy(120).is_empty());
+        assert_eq!(results.get_peak_htlc_slot_occupancy()[&10], 4.0);
+        assert_eq!(results.get_jammed_liquidity_series(), vec![(0, 0), (60, 4000), (70, 4000), (120, 0)]);
+        for (time, status) in [(10, SimTransactionStatus::SUCCESSFUL), (70, SimTransactionStatus::FAILED(SimFailureReason::Unknown)), (80, SimTransactionStatus::SUCCESSFUL), (90, SimTransactionStatus::PENDING)] {
+            let transaction = SimTransaction { id: None, src_node: String::from("alice"), dest_node: String::from("merchant1"), amount_sats: 1000, status: status, keysend: false };
+            results.transactions.txs.push(Tx { time: time, transaction: transaction, parts: Vec::new(), failed_attempts: Vec::new() });
+        }
+        assert_eq!(results.get_honest_failure_rate(60, 120), Some(50.0));
+        assert_eq!(results.get_honest_failure_rate(0, 59), Some(0.0));
+        assert!(results.get_honest_failure_rate(200, 300).is_none());
+    }
+
+    #[test]
+    fn jamming_events_test() {
+        // mallory holds payments to sink through alice until every HTLC slot of the alice - sink channel is used
+        let channels = vec![test_channel(1, "mallory", "alice", 1000000, 0), test_channel(2, "alice", "sink", 1000000, 0), test_channel(3, "bob", "alice", 100000, 0)];
+        let mut analyzer = test_analyzer(test_results(vec![("mallory", 1000000), ("alice", 1000000), ("sink", 0), ("bob", 100000)], channels), SimControl::new());
+        let hold = |id: u64| SimulationEvent::HoldPaymentEvent(SimHeldPayment { id: id, src_node: String::from("mallory"), dest_node: String::from("sink"), amount_sats: 1000, payment_id: Some(format!("jam{}", id)), resend_until: None });
+        let jam_path = vec![PathHop { short_channel_id: 1, amount: 0, amount_msat: 0, node_pub_key: String::from("alice") }, PathHop { short_channel_id: 2, amount: 1000, amount_msat: 1000000, node_pub_key: String::from("sink") }];
+        let fail_back = |id: u64, reason: SimFailureReason| SimulationEvent::PaymentPathFailedEvent(SimPaymentFailure { payment_id: format!("jam{}", id), path: jam_path.clone(), short_channel_id: Some(2), reason: reason, permanent: true });
+        for id in 0..MAX_ACCEPTED_HTLCS {
+            analyzer.process_event(&test_event(Some(10), hold(id)));
+        }
+
+        // An honest payment from bob fails at the full channel and the payment after the last slot is not held
+        let tx = SimTransaction { id: Some(String::from("honest")), src_node: String::from("bob"), dest_node: String::from("sink"), amount_sats: 1000, status: SimTransactionStatus::PENDING, keysend: false };
+        let honest_path = vec![PathHop { short_channel_id: 3, amount: 0, amount_msat: 0, node_pub_key: String::from("alice") }, PathHop { short_channel_id: 2, amount: 1000, amount_msat: 1000000, node_pub_key: String::from("sink") }];
+        analyzer.process_event(&test_event(Some(20), SimulationEvent::TransactionEvent(tx)));
+        analyzer.process_event(&test_event(None, SimulationEvent::PaymentPathFailedEvent(SimPaymentFailure { payment_id: String::from("honest"), path: honest_path, short_channel_id: Some(2), reason: SimFailureReason::ChannelUpdateReceived, permanent: true })));
+        analyzer.process_event(&test_event(None, SimulationEvent::PaymentFailedEvent(String::from("honest"))));
+        analyzer.process_event(&test_event(Some(20), hold(MAX_ACCEPTED_HTLCS)));
+        analyzer.process_event(&test_event(None, fail_back(MAX_ACCEPTED_HTLCS, SimFailureReason::ChannelUpdateReceived)));
+
+        // The held payments are released at 30, the destination rejects them so the path they were held along is known
+        for id in 0..MAX_ACCEPTED_HTLCS {
+            analyzer.process_event(&test_event(Some(30), SimulationEvent::ReleaseHeldPaymentEvent(id)));
+            analyzer.process_event(&test_event(None, fail_back(id, SimFailureReason::RecipientRejected)));
+        }
+        let results = analyzer.get_sim_results();
+        let htlcs = results.get_jammed_htlcs();
+        assert_eq!(htlcs.len(), MAX_ACCEPTED_HTLCS as usize + 1);
+        assert_eq!(htlcs[0].channels, vec![1, 2]);
+        assert_eq!(htlcs[0].released, Some(30));
+        assert!(htlcs[MAX_ACCEPTED_HTLCS as usize].path.is_empty());
+        assert!(!htlcs[MAX_ACCEPTED_HTLCS as usize].is_held(20));
+        assert_eq!(results.get_htlc_slot_occupancy(20)[&2], MAX_ACCEPTED_HTLCS);
+        assert_eq!(results.get_peak_htlc_slot_occupancy()[&2], 100.0);
+        assert!(results.get_htlc_slot_occupancy(30).is_empty());
+        assert_eq!(results.get_jammed_liquidity(20), MAX_ACCEPTED_HTLCS * 2000);
+
+        // The jamming payments are not transactions, only the honest payment failed and alice failed to forward it
+        assert_eq!(results.transactions.txs.len(), 1);
+        assert!(matches!(results.transactions.txs[0].transaction.status, SimTransactionStatus::FAILED(SimFailureReason::ChannelUpdateReceived)));
+        assert_eq!(results.get_honest_failure_rate(0, 100), Some(100.0));
+        assert_eq!(results.forwarding.failed_forwards.len(), 1);
+        assert_eq!(results.forwarding.failed_forwards[0].node, String::from("alice"));
+
+        // A payment that could not be held is a failed event
+        analyzer.process_event(&SimResultsEvent { sim_time: Some(40), success: false, event: hold(MAX_ACCEPTED_HTLCS + 1) });
+        let results = analyzer.get_sim_results();
+        assert_eq!(results.get_jammed_htlcs().len(), MAX_ACCEPTED_HTLCS as usize + 1);
+        assert_eq!(results.failed_events.len(), 1);
+
+        // A slow jamming payment that is about to expire is failed back by the destination and sent again with the same id until the attack ends
+        let control = SimControl::new();
+        control.start(100);
+        let mut analyzer = test_analyzer(test_results(vec![("mallory", 1000000), ("alice", 1000000), ("sink", 0)], vec![test_channel(1, "mallory", "alice", 1000000, 0), test_channel(2, "alice", "sink", 1000000, 0)]), control.clone());
+        let slow = |payment_id: &str| SimulationEvent::HoldPaymentEvent(SimHeldPayment { id: 0, src_node: String::from("mallory"), dest_node: String::from("sink"), amount_sats: 1000, payment_id: Some(String::from(payment_id)), resend_until: Some(50) });
+        let expire = |payment_id: &str| SimulationEvent::PaymentPathFailedEvent(SimPaymentFailure { payment_id: String::from(payment_id), path: jam_path.clone(), short_channel_id: None, reason: SimFailureReason::RecipientRejected, permanent: true });
+        analyzer.process_event(&test_event(Some(10), slow("slow1")));
+        control.wait(40);
+        analyzer.process_event(&test_event(None, expire("slow1")));
+        assert!(matches!(&control.take_events(40)[..], [SimulationEvent::HoldPaymentEvent(p)] if p.id == 0 && p.payment_id.is_none() && p.resend_until == Some(50)));
+
+        // The payment that was sent again is released when the attack ends and is not sent again
+        analyzer.process_event(&test_event(Some(41), slow("slow2")));
+        control.wait(50);
+        analyzer.process_event(&test_event(Some(50), SimulationEvent::ReleaseHeldPaymentEvent(0)));
+        analyzer.process_event(&test_event(None, expire("slow2")));
+        assert!(control.take_events(50).is_empty());
+        control.end();
+        let htlcs = analyzer.get_sim_results().jamming.htlcs;
+        assert_eq!(htlcs.iter().map(|h| (h.sent, h.released)).collect::<Vec<(u64, Option<u64>)>>(), vec![(10, Some(40)), (41, Some(50))]);
+        assert!(htlcs[0].is_held(39) && !htlcs[0].is_held(40) && htlcs[1].is_held(45));
+    }
+
+    #[test]
+    fn probing_test() {
+        let sim = LnSimulation::from_scenario_file(String::from("../example_scenarios/probing.toml")).unwrap();
+        let scenario = sim.get_scenario();
+        assert!(scenario.validate().is_empty());
+        assert_eq!(scenario.probing[0].precision, 1000);
+
+        // The agent probes every channel every interval and the single probe keeps its precision
+        let events = sim.get_generated_events();
+        let probes: Vec<(u64, SimProbe)> = events.iter().flat_map(|(t, list)| list.iter().filter_map(move |e| match e {
+            SimulationEvent::ProbeEvent(p) => Some((*t, p.clone())),
+            _ => None
+        })).collect();
+        assert_eq!(probes.len(), 16);
+        assert_eq!(probes.iter().filter(|(t, _)| *t == 270).count(), 3);
+        assert!(probes.iter().any(|(t, p)| *t == 200 && p.precision_sats == 100 && p.channel_id == 1));
+
+        // The prober and channels are checked
+        let mut bad = scenario.clone();
+        bad.probing[0].channels.push(99);
+        bad.probing[0].prober = String::from("nobody");
+        assert_eq!(bad.validate().len(), 2);
+
+        // The results compare the inferred balances with the actual balances
+        let mut results = SimResults::new();
+        assert!(results.get_probe_accuracy().is_none());
+        for (lower, upper, actual) in [(48000, 50000, 49000), (10000, 11000, 12000)] {
+            results.probes.push(BalanceProbe { time: 30, finished: Some(31), prober: String::from("eve"), node: String::from("alice"), channel_id: 1, lower_sats: lower, upper_sats: upper, probes: 7, actual_sats: actual, capacity_sats: 100000 });
+        }
+        assert_eq!(results.probes[1].get_error_sats(), -1500);
+        let accuracy = results.get_probe_accuracy().unwrap();
+        assert_eq!(accuracy.channels_probed, 2);
+        assert_eq!(accuracy.mean_error_sats, 750.0);
+        assert_eq!(accuracy.mean_error_percent, 0.75);
+        assert_eq!(accuracy.within_range_percent, 50.0);
+        assert_eq!(accuracy.mean_probes, 7.0);
+    }
+
+    #[test]
+    fn probing_events_test() {
+        // eve probes the alice - bob channel at 10, the last probe finishes at 16 after alice pays bob at 15
+        let results = test_results(vec![("eve", 50000), ("alice", 30000), ("bob", 20000)], vec![test_channel(1, "eve", "alice", 50000, 0), test_channel(2, "alice", "bob", 30000, 20000)]);
+        let control = SimControl::new();
+        control.start(100);
+        let mut analyzer = test_analyzer(results, control.clone());
+        let probe = |channel_id: u64, result: Option<SimProbeResult>| SimulationEvent::ProbeEvent(SimProbe { prober: String::from("eve"), channel_id: channel_id, precision_sats: 1000, result: result });
+        let found = SimProbeResult { node: String::from("alice"), lower_sats: 24000, upper_sats: 25500, probes: 6 };
+        let tx = SimTransaction { id: Some(String::from("p")), src_node: String::from("alice"), dest_node: String::from("bob"), amount_sats: 5000, status: SimTransactionStatus::PENDING, keysend: false };
+        let path = SimPaymentPath { payment_id: String::from("p"), path: vec![PathHop { short_channel_id: 2, amount: 5000, amount_msat: 5000000, node_pub_key: String::from("bob") }] };
+        analyzer.process_event(&test_event(Some(15), SimulationEvent::TransactionEvent(tx)));
+        analyzer.process_event(&test_event(None, SimulationEvent::PaymentSuccessEvent(String::from("p"), 0)));
+        analyzer.process_event(&test_event(None, SimulationEvent::PaymentPathSuccessful(path)));
+        control.wait(16);
+        analyzer.process_event(&test_event(Some(10), probe(2, Some(found.clone()))));
+
+        // The inferred range is compared to alice's balance when the last probe finished, after the payment, and the late result keeps the event times sorted
+        let results = analyzer.get_sim_results();
+        assert_eq!(results.probes.len(), 1);
+        assert_eq!((results.probes[0].time, results.probes[0].finished), (10, Some(16)));
+        assert_eq!(results.probes[0].actual_sats, 25000);
+        assert_eq!(results.probes[0].capacity_sats, 50000);
+        assert_eq!(results.probes[0].get_error_sats(), -250);
+        assert_eq!(results.get_probe_accuracy().unwrap().within_range_percent, 100.0);
+        assert_eq!(results.event_times, vec![0, 10, 15]);
+
+        // A probe that failed or a channel that is not open is a failed event and is left out of the accuracy
+        analyzer.process_event(&SimResultsEvent { sim_time: Some(20), success: false, event: probe(2, None) });
+        analyzer.process_event(&test_event(Some(20), probe(99, Some(found))));
+        control.end();
+        let results = analyzer.get_sim_results();
+        assert_eq!(results.probes.len(), 1);
+        assert_eq!(results.failed_events.len(), 2);
+        assert_eq!(results.get_probe_accuracy().unwrap().channels_probed, 1);
+    }
+
+    #[test]
+    fn on_chain_test() {
+        let sim = LnSimulation::from_scenario_file(String::from("../example_scenarios/on_chain.toml")).unwrap();
+        let scenario = sim.get_scenario();
+        assert!(scenario.validate().is_empty());
+
+        // The destination can be a node or an address and the feerate defaults to 1
+        let sends: Vec<SimOnChainTx> = scenario.events.iter().filter_map(|e| match &e.event {
+            SimulationEvent::SendOnChainEvent(tx) => Some(tx.clone()),
+            _ => None
+        }).collect();
+        assert_eq!(sends.len(), 3);
+        assert!(sends.iter().any(|tx| tx.src_node == "alice" && tx.dest == "bob" && tx.feerate_sat_per_vbyte == 5));
+        assert_eq!(sends.iter().filter(|tx| tx.dest.starts_with("bcrt1") && tx.src_node.starts_with("merchant")).count(), 2);
+        assert!(scenario.events.iter().any(|e| matches!(&e.event, SimulationEvent::FundNodeEvent(n, 500000) if n == "bob")));
+
+        // Only the source has to be a node
+        let mut bad = scenario.clone();
+        bad.events.push(ScenarioEvent { time: 5, event: SimulationEvent::SendOnChainEvent(SimOnChainTx { src_node: String::from("nobody"), dest: String::from("bob"), amount_sats: 0, feerate_sat_per_vbyte: 1, txid: None }) });
+        bad.events.push(ScenarioEvent { time: 5, event: SimulationEvent::FundNodeEvent(String::from("nobody"), 1000) });
+        assert_eq!(bad.validate().len(), 3);
+    }
+
+    #[test]
+    fn on_chain_events_test() {
+        // alice is funded from outside of the simulation and then pays bob and an address outside of the simulation
+        let mut analyzer = test_analyzer(test_results(vec![("alice", 0), ("bob", 0)], Vec::new()), SimControl::new());
+        let (alice, bob) = (String::from("alice"), String::from("bob"));
+        let address = String::from("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080");
+        let send = |src: &str, dest: &String, amount: u64, txid: Option<&str>| SimOnChainTx {
+            src_node: String::from(src), dest: dest.clone(), amount_sats: amount, feerate_sat_per_vbyte: 1, txid: txid.map(String::from)
+        };
+        analyzer.process_event(&test_event(Some(10), SimulationEvent::FundNodeEvent(alice.clone(), 500000)));
+        // The fee of a broadcast transaction is looked up on-chain by process_on_chain_event
+        for (time, tx, fee) in [(20, send("alice", &bob, 250000, Some("a")), 705), (30, send("alice", &address, 20000, Some("b")), 282)] {
+            analyzer.add_on_chain_tx(&test_event(Some(time), SimulationEvent::SendOnChainEvent(tx.clone())), &tx, fee);
+        }
+
+        // The sender pays the amount and the fee, the amount only stays in the simulation if it was sent to a node
+        let results = analyzer.get_sim_results();
+        assert_eq!(results.get_on_chain_bal(10, &alice), Some(500000));
+        assert_eq!(results.get_on_chain_bal(20, &alice), Some(249295));
+        assert_eq!(results.get_on_chain_bal(20, &bob), Some(250000));
+        assert_eq!(results.get_on_chain_bal(30, &alice), Some(229013));
+        assert_eq!(results.get_on_chain_bal(30, &bob), Some(250000));
+        assert_eq!(results.get_on_chain_txs().len(), 3);
+        assert!(results.get_on_chain_txs()[0].src_node.is_none());
+        assert_eq!(results.get_node_on_chain_txs(&bob).len(), 1);
+        assert_eq!(results.get_node_on_chain_txs(&alice)[1].fee_sats, 705);
+        assert!(results.get_on_chain_txs()[2].external);
+
+        // A transaction that was not broadcast and a node that could not be funded do not change any balances
+        analyzer.process_event(&SimResultsEvent { sim_time: Some(40), success: false, event: SimulationEvent::SendOnChainEvent(send("alice", &bob, 1000, None)) });
+        analyzer.process_event(&SimResultsEvent { sim_time: Some(50), success: false, event: SimulationEvent::FundNodeEvent(bob.clone(), 1000) });
+        let results = analyzer.get_sim_results();
+        assert_eq!(results.get_on_chain_bal(50, &alice), Some(229013));
+        assert_eq!(results.get_on_chain_bal(50, &bob), Some(250000));
+        assert_eq!(results.get_on_chain_txs().len(), 3);
+        assert_eq!(results.failed_events.len(), 2);
+        assert!(results.get_invariant_violations().is_empty());
+
+        // Sending more out of the simulation than the total balance that is tracked is a violation instead of a panic
+        let tx = send("carol", &address, 600000, Some("c"));
+        analyzer.add_on_chain_tx(&test_event(Some(60), SimulationEvent::SendOnChainEvent(tx.clone())), &tx, 300);
+        let violations = analyzer.get_sim_results().get_invariant_violations();
+        assert_eq!(violations.len(), 1);
+        assert_eq!((violations[0].time, violations[0].invariant), (60, Invariant::SupplyConservation));
+    }
+
+    #[test]
+    fn fee_market_test() {
+        let sim = LnSimulation::from_scenario_file(String::from("../example_scenarios/fee_market.toml")).unwrap();
+        let scenario = sim.get_scenario();
+        assert!(scenario.validate().is_empty());
+        let market = scenario.fee_market.clone().unwrap();
+        assert_eq!((market.start, market.end, market.filler_txs), (0, 300, 10));
+        assert_eq!(market.feerate_at(89), 2);
+        assert_eq!(market.feerate_at(90), 50);
+
+        // A block every interval at the scheduled feerate, the last one confirms everything
+        let events = sim.get_generated_events();
+        let blocks: Vec<(u64, SimFeeMarketBlock)> = events.iter().flat_map(|(t, list)| list.iter().filter_map(move |e| match e {
+            SimulationEvent::FeeMarketBlockEvent(b) => Some((*t, b.clone())),
+            _ => None
+        })).collect();
+        assert_eq!(blocks.len(), 11);
+        assert!(blocks.iter().any(|(t, b)| *t == 120 && b.feerate_sat_per_vbyte == 50 && !b.last));
+        assert!(blocks.iter().any(|(t, b)| *t == 300 && b.feerate_sat_per_vbyte == 5 && b.last));
+
+        // A historical profile starts at sim time 0 and is sped up
+        let schedule = FeeMarketConfig::load_profile(&String::from("../example_scenarios/mempool_profile.csv"), 60.0).unwrap();
+        assert_eq!(schedule.len(), 6);
+        assert_eq!((schedule[0].time, schedule[0].feerate), (0, 3));
+        assert_eq!((schedule[3].time, schedule[3].feerate), (180, 61));
+        let mut bad = scenario.clone();
+        bad.fee_market = Some(FeeMarketConfig { schedule: Vec::new(), block_interval: 0, filler_txs: 0, start: 0, end: 400 });
+        assert_eq!(bad.validate().len(), 3);
+
+        // The results show what each channel open and close cost and how long it waited at the market feerate
+        let mut results = SimResults::new();
+        assert!(results.get_market_feerate(100).is_none());
+        for (time, feerate) in [(60, 2), (90, 50), (210, 5)] {
+            results.fee_market.push(FeeMarketBlock { time: time, feerate_sat_per_vbyte: feerate, filler_txs: 10, confirmed_txs: 10, mempool_txs: 0, last: false });
+        }
+        assert_eq!(results.get_market_feerate(130), Some(50));
+        results.channel_fees.push(ChannelFee { channel_id: 1, fee_type: ChannelFeeType::Close, node: String::from("alice"), time: 130, confirmed: 210, fee_sats: 1500, market_feerate: Some(50), txid: None });
+        results.channel_fees.push(ChannelFee { channel_id: 3, fee_type: ChannelFeeType::Close, node: String::from("bob"), time: 30, confirmed: 60, fee_sats: 500, market_feerate: None, txid: None });
+        assert_eq!(results.get_mean_channel_fee(ChannelFeeType::Close), Some((1000.0, 55.0)));
+        assert!(results.get_mean_channel_fee(ChannelFeeType::Open).is_none());
+    }
+
+    #[test]
+    fn fee_market_events_test() {
+        // The fee market starts with a block at 60, the feerate goes up at 90 and the last block at 210 confirms everything and stops it
+        let mut analyzer = test_analyzer(test_results(vec![("alice", 0)], Vec::new()), SimControl::new());
+        let block = |feerate: u64, last: bool, confirmed: u64, waiting: u64| SimulationEvent::FeeMarketBlockEvent(SimFeeMarketBlock {
+            feerate_sat_per_vbyte: feerate, filler_txs: 10, last: last, confirmed_txs: Some(confirmed), mempool_txs: Some(waiting)
+        });
+        analyzer.process_event(&test_event(Some(60), block(2, false, 10, 0)));
+        analyzer.process_event(&test_event(Some(90), block(50, false, 4, 6)));
+        analyzer.process_event(&test_event(Some(210), block(5, true, 16, 0)));
+
+        // The market feerate is known while the market is running
+        let results = analyzer.get_sim_results();
+        assert_eq!(results.get_fee_market_blocks().len(), 3);
+        assert_eq!(results.get_fee_market_blocks()[1].mempool_txs, 6);
+        assert!(results.get_fee_market_blocks()[2].last);
+        assert!(results.get_market_feerate(30).is_none());
+        assert_eq!(results.get_market_feerate(60), Some(2));
+        assert_eq!(results.get_market_feerate(209), Some(50));
+        assert!(results.get_market_feerate(210).is_none());
+        assert_eq!(results.event_times, vec![0, 60, 90, 210]);
+
+        // A block that was mined without its filler transactions is recorded and is a failed event
+        analyzer.process_event(&SimResultsEvent { sim_time: Some(300), success: false, event: block(20, false, 0, 0) });
+        let results = analyzer.get_sim_results();
+        assert_eq!(results.get_fee_market_blocks().len(), 4);
+        assert_eq!(results.get_market_feerate(300), Some(20));
+        assert_eq!(results.failed_events.len(), 1);
+    }
+
+    #[test]
+    fn reorg_test() {
+        let sim = LnSimulation::from_scenario_file(String::from("../example_scenarios/reorg.toml")).unwrap();
+        let scenario = sim.get_scenario();
+        assert!(scenario.validate().is_empty());
+        assert!(scenario.events.iter().any(|e| e.time == 60 && matches!(&e.event, SimulationEvent::ReorgEvent(r) if r.depth == 20 && r.unconfirmed_txs.is_empty())));
+
+        let mut bad = scenario.clone();
+        bad.events.push(ScenarioEvent { time: 5, event: SimulationEvent::ReorgEvent(SimReorg { depth: 0, unconfirmed_txs: Vec::new(), unusable_channels: Vec::new() }) });
+        assert_eq!(bad.validate().len(), 1);
+    }
+
+    #[test]
+    fn reorg_events_test() {
+        // Channel 1 was opened and channel 3 was closed in the blocks that the reorg replaces
+        let mut results = test_results(vec![("a", 60000), ("b", 40000)], vec![test_channel(1, "a", "b", 60000, 40000)]);
+        let fee = |channel_id: u64, fee_type: ChannelFeeType, txid: &str| ChannelFee { channel_id: channel_id, fee_type: fee_type, node: String::from("a"), time: 10, confirmed: 10, fee_sats: 300, market_feerate: None, txid: Some(String::from(txid)) };
+        results.channel_fees = vec![fee(1, ChannelFeeType::Open, "open1"), fee(2, ChannelFeeType::Open, "open2"), fee(3, ChannelFeeType::Close, "close3")];
+        let mut analyzer = test_analyzer(results, SimControl::new());
+        let reorg = SimReorg { depth: 20, unconfirmed_txs: vec![String::from("open1"), String::from("close3"), String::from("other")], unusable_channels: vec![1] };
+        analyzer.process_event(&test_event(Some(60), SimulationEvent::ReorgEvent(reorg.clone())));
+
+        // Only the channel transactions are recorded and the balances do not change
+        let results = analyzer.get_sim_results();
+        let reorgs = results.get_reorgs();
+        assert_eq!(reorgs.len(), 1);
+        assert_eq!(reorgs[0].unconfirmed_txs.len(), 3);
+        assert_eq!(reorgs[0].channels.iter().map(|c| (c.channel_id, c.fee_type, c.unusable)).collect::<Vec<(u64, ChannelFeeType, bool)>>(), vec![(1, ChannelFeeType::Open, true), (3, ChannelFeeType::Close, false)]);
+        assert_eq!(results.get_unusable_channels(), vec![1]);
+        assert_eq!(results.get_off_chain_bal(60, &String::from("a")), Some(60000));
+
+        // The channel can be used again once its funding transaction is confirmed again, the first confirmation is kept
+        analyzer.process_event(&test_event(Some(90), SimulationEvent::TxConfirmedEvent(String::from("open1"))));
+        analyzer.process_event(&test_event(Some(100), SimulationEvent::TxConfirmedEvent(String::from("open1"))));
+        let results = analyzer.get_sim_results();
+        assert_eq!(results.get_reorgs()[0].channels[0].reconfirmed, Some(90));
+        assert_eq!(results.get_reorgs()[0].channels[1].reconfirmed, None);
+        assert!(results.get_unusable_channels().is_empty());
+
+        // A reorg that could not be done is a failed event
+        analyzer.process_event(&SimResultsEvent { sim_time: Some(120), success: false, event: SimulationEvent::ReorgEvent(reorg) });
+        let results = analyzer.get_sim_results();
+        assert_eq!(results.get_reorgs().len(), 1);
+        assert_eq!(results.failed_events.len(), 1);
+    }
+
+    #[test]
+    fn sim_control_test() {
+        let sim = LnSimulation::new(String::from("control"), 10, 0);
+        let control = sim.get_control();
+
+        // Stepping runs every second up to the step time and then pauses
+        control.step_to(2);
+        for t in 0..3 {
+            control.wait(t);
+        }
+        assert!(!control.is_paused());
+        let event_manager = sim.get_control();
+        let waiting = thread::spawn(move || {
+            event_manager.wait(3);
+        });
+        while !control.is_paused() {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(control.get_time(), 3);
+        assert!(!waiting.is_finished());
+
+        // Resuming lets the event manager continue
+        control.resume();
+        waiting.join().unwrap();
+        assert!(!control.is_paused());
+        control.pause();
+        assert!(control.is_paused());
+    }
+
+    #[test]
+    fn add_running_event_test() {
+        let control = SimControl::new();
+        let payment = || SimulationEvent::TransactionEvent(SimTransaction { id: None, src_node: String::from("alice"), dest_node: String::from("bob"), amount_sats: 5000, status: SimTransactionStatus::NONE, keysend: false });
+        assert!(control.add_event(payment(), None).is_err());
+
+        // Events are added as soon as possible or at a future time while the simulation is running
+        control.start(100);
+        assert_eq!(control.add_event(payment(), None).unwrap(), 0);
+        assert_eq!(control.add_event(payment(), Some(10)).unwrap(), 10);
+        assert!(control.add_event(payment(), Some(101)).is_err());
+        assert!(control.add_event(SimulationEvent::PaymentFailedEvent(String::from("id")), None).is_err());
+        // The revoked commitment is only saved for the events scheduled before the simulation started
+        assert!(control.add_event(SimulationEvent::BroadcastRevokedStateEvent(String::from("alice"), 1), None).is_err());
+        assert_eq!(control.take_events(5).len(), 1);
+        assert_eq!(control.take_events(10).len(), 1);
+
+        // A time that has already been sent is moved to the next second
+        assert_eq!(control.add_event(payment(), Some(3)).unwrap(), 11);
+        control.end();
+        assert!(control.add_event(payment(), None).is_err());
+    }
+
+    #[test]
+    fn stop_sim_test() {
+        let sim = LnSimulation::new(String::from("stop"), 100, 0);
+        let control = sim.get_control();
+
+        // Stopping a simulation that is not running does nothing
+        control.stop();
+        control.start(100);
+        assert!(control.wait(0));
+
+        // Stopping releases a paused event manager and no more events are sent
+        control.pause();
+        let event_manager = sim.get_control();
+        let waiting = thread::spawn(move || {
+            event_manager.wait(1)
+        });
+        while control.get_time() != 1 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        control.stop();
+        assert!(!waiting.join().unwrap());
+        assert!(!control.wait(2));
+
+        // The next run is not stopped
+        control.end();
+        control.resume();
+        control.start(100);
+        assert!(control.wait(0));
+        control.end();
+        assert!(sim.get_runtime_network_graph().contains("nodes"));
+    }
+
+    #[test]
+    fn trigger_test() {
+        let sim = LnSimulation::from_scenario_file(String::from("../example_scenarios/triggers.toml")).unwrap();
+        let scenario = sim.get_scenario();
+        assert!(scenario.validate().is_empty());
+        assert_eq!(scenario.triggers.len(), 3);
+        assert_eq!(scenario.triggers[1].delay, 60);
+
+        // A retry needs a failed payment and a channel id can only be opened once
+        let mut bad = scenario.clone();
+        bad.triggers[0].repeat = true;
+        bad.triggers[1].then = TriggerAction::RetryPayment { src: String::from("nobody") };
+        assert_eq!(bad.validate().len(), 3);
+
+        // The balance trigger fires once when bob's side of channel 3 drops below 20%
+        let mut results = SimResults::new();
+        let channel = |id: u64, src: &str, dest: &str, src_balance: u64| test_channel(id, src, dest, src_balance, 100000 - src_balance);
+        let mut refill = Trigger::new(scenario.triggers[0].clone());
+        results.channels.open_channels.insert(0, vec![channel(2, "alice", "merchant2", 100000), channel(3, "bob", "merchant3", 30000)]);
+        assert!(refill.update(&test_event(Some(50), SimulationEvent::HealPartitionEvent), &results).is_empty());
+        results.channels.open_channels.insert(50, vec![channel(2, "alice", "merchant2", 100000), channel(3, "bob", "merchant3", 16000)]);
+        let events = refill.update(&test_event(Some(50), SimulationEvent::HealPartitionEvent), &results);
+        assert!(matches!(&events[..], [SimulationEvent::OpenChannelEvent(c)] if c.id == 10 && c.src_balance_sats == 200000));
+        assert!(refill.update(&test_event(Some(50), SimulationEvent::HealPartitionEvent), &results).is_empty());
+
+        // The peers of an offline node close their channels with it
+        let mut abandon = Trigger::new(scenario.triggers[1].clone());
+        assert!(abandon.update(&test_event(Some(50), SimulationEvent::StopNodeEvent(String::from("merchant1"))), &results).is_empty());
+        let events = abandon.update(&test_event(Some(50), SimulationEvent::StopNodeEvent(String::from("merchant2"))), &results);
+        assert!(matches!(&events[..], [SimulationEvent::ForceCloseChannelEvent(n, 2)] if n == "alice"));
+
+        // A failed payment is sent again from bob
+        let mut retry = Trigger::new(scenario.triggers[2].clone());
+        let tx = SimTransaction { id: Some(String::from("p1")), src_node: String::from("alice"), dest_node: String::from("merchant3"), amount_sats: 5000, status: SimTransactionStatus::FAILED(SimFailureReason::Unknown), keysend: false };
+        results.transactions.txs.push(Tx { time: 180, transaction: tx, parts: Vec::new(), failed_attempts: Vec::new() });
+        let events = retry.update(&test_event(Some(50), SimulationEvent::PaymentFailedEvent(String::from("p1"))), &results);
+        assert!(matches!(&events[..], [SimulationEvent::TransactionEvent(t)] if t.src_node == "bob" && t.dest_node == "merchant3" && t.amount_sats == 5000));
+
+        // The analyzer adds the events of a trigger that fires to the running simulation at the time of the event plus the delay
+        let control = SimControl::new();
+        control.start(scenario.duration);
+        let nodes = vec![("alice", 0), ("bob", 0), ("merchant2", 0), ("merchant3", 0)];
+        let mut analyzer = test_analyzer(test_results(nodes, vec![channel(2, "alice", "merchant2", 100000), channel(3, "bob", "merchant3", 30000)]), control.clone());
+        analyzer.enable_triggers(scenario.triggers.clone());
+        let stopped = test_event(Some(50), SimulationEvent::StopNodeEvent(String::from("merchant2")));
+        analyzer.process_event(&stopped);
+        analyzer.fire_triggers(&stopped);
+        assert!(control.take_events(109).is_empty());
+        assert!(matches!(&control.take_events(110)[..], [SimulationEvent::ForceCloseChannelEvent(n, 2)] if n == "alice"));
+        let fired = analyzer.get_sim_results().triggers;
+        assert_eq!(fired.len(), 1);
+        assert_eq!((fired[0].name.as_str(), fired[0].time, fired[0].send_time), ("abandon_merchant2", 50, 110));
+        control.end();
+    }
+
+    #[test]
+    fn snapshot_test() {
+        let mut results = test_results(vec![("node1", 0)], Vec::new());
+        let node = String::from("node1");
+        results.balance.on_chain.insert(node.clone(), HashMap::from([(0, 100000), (5, 60000)]));
+        results.balance.off_chain.get_mut(&node).unwrap().insert(5, 39000);
+
+        // The node reports its balances every 4 seconds, it is stopped at 8
+        let status = |on_chain: u64, off_chain: u64| {
+            let mut s = SimNodeStatus::new();
+            s.balance = SimNodeBalance { total: on_chain + off_chain, onchain: on_chain, offchain: off_chain };
+            s
+        };
+        results.snapshots.insert(0, HashMap::from([(node.clone(), status(100000, 0))]));
+        results.snapshots.insert(4, HashMap::from([(node.clone(), status(59800, 40000))]));
+        results.snapshots.insert(8, HashMap::new());
+
+        assert_eq!(results.get_node_snapshot(3, &node).unwrap().balance.onchain, 100000);
+        assert_eq!(results.get_node_snapshot(6, &node).unwrap().balance.offchain, 40000);
+        assert!(results.get_node_snapshot(9, &node).is_none());
+        assert_eq!(results.get_snapshot_series(&node, |s| s.balance.offchain), vec![(0, 0), (4, 40000)]);
+
+        // At 4 the results have not been updated yet, at 8 there is nothing to compare
+        assert_eq!(results.get_balance_drift(&node), vec![(0, 0, 0), (4, -40200, 40000)]);
+
+        // Snapshots are not events on the timeline and are not allowed in a scenario
+        let mut ln_sim = LnSimulation::new(String::from("test"), 10, 0);
+        ln_sim.create_node(node.clone(), 100000, true);
+        ln_sim.add_event(SimulationEvent::SnapshotEvent(HashMap::new()), 5);
+        assert_eq!(ln_sim.get_scenario().validate().len(), 1);
+    }
+
+    #[test]
+    #[serial]
+    fn invariant_checks_test() {
+        let mut ln_sim = LnSimulation::new(String::from("test"), 10, 0);
+        ln_sim.set_invariant_checks(true);
+
+        ln_sim.create_node(String::from("node1"), 200000, true);
+        ln_sim.create_node(String::from("node2"), 0, true);
+
+        ln_sim.create_channel(String::from("node1"), String::from("node2"), 40000, 1);
+        ln_sim.create_transaction_event(String::from("node1"), String::from("node2"), 3000, 2);
+        ln_sim.create_close_channel_event(String::from("node1"), 1, 5);
+
+        let sim_results = ln_sim.run(true);
+        match sim_results {
+            Ok(res) => {
+                // A payment and a cooperative close do not create or lose any funds
+                assert!(res.get_invariant_violations().is_empty());
+            },
+            Err(e) => {
+                println!("Test failed due to error: {:?}", e);
+                assert_eq!(true, false);
+            }
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn cooperative_close_sweep_test() {
+        let mut ln_sim = LnSimulation::new(String::from("test"), 20, 0);
+        ln_sim.set_invariant_checks(true);
+
+        ln_sim.create_node(String::from("node1"), 200000, true);
+        ln_sim.create_node(String::from("node2"), 0, true);
+
+        ln_sim.create_channel(String::from("node1"), String::from("node2"), 40000, 1);
+        ln_sim.create_transaction_event(String::from("node1"), String::from("node2"), 3000, 2);
+        ln_sim.create_close_channel_event(String::from("node1"), 1, 5);
+        // The nodes can sweep the outputs of the cooperative close once it has 6 confirmations
+        ln_sim.create_mine_blocks_event(10, 10);
+
+        let sim_results = ln_sim.run(true);
+        match sim_results {
+            Ok(res) => {
+                // The outputs were already added to the on-chain balances when the channel closed so they are not counted again
+                assert!(res.get_invariant_violations().is_empty());
+                assert!(res.force_closes.is_empty());
+                assert_eq!(res.get_on_chain_bal(20, &String::from("node2")), Some(3000));
+            },
+            Err(e) => {
+                println!("Test failed due to error: {:?}", e);
+                assert_eq!(true, false);
+            }
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn force_close_channel_test() {
+        let mut ln_sim = LnSimulation::new(String::from("test"), 30, 0);
+        ln_sim.set_invariant_checks(true);
+
+        ln_sim.create_node(String::from("node1"), 200000, true);
+        ln_sim.create_node(String::from("node2"), 0, true);
+
+        ln_sim.create_channel(String::from("node1"), String::from("node2"), 40000, 1);
+        ln_sim.create_transaction_event(String::from("node1"), String::from("node2"), 3000, 2);
+        ln_sim.create_force_close_channel_event(String::from("node1"), 1, 5);
+        // Mine past the default to_self_delay of 144 blocks so that node1 can sweep its balance
+        ln_sim.create_mine_blocks_event(150, 10);
+
+        let sim_results = ln_sim.run(true);
+        match sim_results {
+            Ok(res) => {
+                // node2 swept its balance right away and node1 swept its balance once the delay was over
+                let force_closes = res.get_force_closes();
+                assert_eq!(force_closes.len(), 1);
+                assert!(matches!(force_closes[0].close_type, CloseType::Force));
+                assert_eq!(force_closes[0].initiator, String::from("node1"));
+                assert!(force_closes[0].closing_tx.is_some());
+                assert!(force_closes[0].to_self_delay.is_some());
+                assert!(force_closes[0].sweeps.iter().any(|s| s.node == "node1" && s.delayed && !s.penalty));
+                assert!(force_closes[0].sweeps.iter().any(|s| s.node == "node2" && !s.delayed && !s.penalty));
+
+                // The funds were locked until they were swept and none were created or lost
+                assert!(res.get_invariant_violations().is_empty());
+                assert_eq!(res.get_off_chain_bal(30, &String::from("node1")), Some(0));
+                assert_eq!(res.get_off_chain_bal(30, &String::from("node2")), Some(0));
+            },
+            Err(e) => {
+                println!("Test failed due to error: {:?}", e);
+                assert_eq!(true, false);
+            }
+        }
+    }
+
+    #[cfg(feature = "revoked_state")]
+    #[test]
+    #[serial]
+    fn broadcast_revoked_state_test() {
+        let mut ln_sim = LnSimulation::new(String::from("test"), 30, 0);
+        ln_sim.set_invariant_checks(true);
+
+        ln_sim.create_node(String::from("node1"), 200000, true);
+        ln_sim.create_node(String::from("node2"), 0, true);
+
+        // The payment revokes the commitment that was saved when the channel opened
+        ln_sim.create_channel(String::from("node1"), String::from("node2"), 40000, 1);
+        ln_sim.create_transaction_event(String::from("node1"), String::from("node2"), 3000, 2);
+        ln_sim.create_broadcast_revoked_state_event(String::from("node1"), 1, 5);
+        ln_sim.create_mine_blocks_event(10, 10);
+
+        let sim_results = ln_sim.run(true);
+        match sim_results {
+            Ok(res) => {
+                // node2 took the whole channel with a justice transaction and node1 swept nothing
+                let force_closes = res.get_force_closes();
+                assert_eq!(force_closes.len(), 1);
+                assert!(matches!(force_closes[0].close_type, CloseType::RevokedState));
+                assert!(force_closes[0].sweeps.iter().any(|s| s.node == "node2" && s.penalty));
+                assert!(force_closes[0].sweeps.iter().all(|s| s.node == "node2"));
+                assert!(res.get_invariant_violations().is_empty());
+            },
+            Err(e) => {
+                println!("Test failed due to error: {:?}", e);
+                assert_eq!(true, false);
+            }
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn keysend_payment_test() {
+        let mut ln_sim = LnSimulation::new(String::from("test"), 10, 0);
+        ln_sim.set_invariant_checks(true);
+
+        ln_sim.create_node(String::from("node1"), 200000, true);
+        ln_sim.create_node(String::from("node2"), 0, true);
+
+        ln_sim.create_channel(String::from("node1"), String::from("node2"), 40000, 1);
+        ln_sim.create_keysend_event(String::from("node1"), String::from("node2"), 3000, 2);
+
+        let sim_results = ln_sim.run(true);
+        match sim_results {
+            Ok(res) => {
+                // node2 accepted the payment without an invoice
+                let txs = res.get_all_transactions().unwrap();
+                assert_eq!(txs.len(), 1);
+                assert!(txs[0].transaction.keysend);
+                assert!(matches!(txs[0].transaction.status, SimTransactionStatus::SUCCESSFUL));
+                assert_eq!(res.get_off_chain_bal(10, &String::from("node1")), Some(37000));
+                assert_eq!(res.get_off_chain_bal(10, &String::from("node2")), Some(3000));
+                assert!(res.get_invariant_violations().is_empty());
+            },
+            Err(e) => {
+                println!("Test failed due to error: {:?}", e);
+                assert_eq!(true, false);
+            }
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn reorg_channel_open_test() {
+        let mut ln_sim = LnSimulation::new(String::from("test"), 20, 0);
+
+        ln_sim.create_node(String::from("node1"), 200000, true);
+        ln_sim.create_node(String::from("node2"), 0, true);
+
+        // Opening the channel mines 10 blocks, the reorg replaces them and the funding transaction is confirmed again by the next blocks
+        ln_sim.create_open_channel_event(String::from("node1"), String::from("node2"), 40000, 2, 1);
+        ln_sim.create_reorg_event(10, 5);
+        ln_sim.create_mine_blocks_event(6, 10);
+
+        let sim_results = ln_sim.run(true);
+        match sim_results {
+            Ok(res) => {
+                let reorgs = res.get_reorgs();
+                assert_eq!(reorgs.len(), 1);
+                assert!(!reorgs[0].unconfirmed_txs.is_empty());
+                assert_eq!(reorgs[0].channels.len(), 1);
+                assert_eq!((reorgs[0].channels[0].channel_id, reorgs[0].channels[0].fee_type), (1, ChannelFeeType::Open));
+                assert!(reorgs[0].channels[0].reconfirmed.unwrap() >= 10);
+                assert!(res.get_unusable_channels().is_empty());
+            },
+            Err(e) => {
+                println!("Test failed due to error: {:?}", e);
+                assert_eq!(true, false);
+            }
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn slow_jamming_test() {
+        let mut ln_sim = LnSimulation::new(String::from("test"), 50, 0);
+        ln_sim.set_snapshot_interval(Some(5));
+
+        ln_sim.create_node(String::from("mallory"), 200000, true);
+        ln_sim.create_node(String::from("alice"), 200000, true);
+        ln_sim.create_node(String::from("sink"), 0, true);
+
+        ln_sim.create_channel(String::from("mallory"), String::from("alice"), 100000, 1);
+        ln_sim.create_channel(String::from("alice"), String::from("sink"), 100000, 2);
+        ln_sim.create_jamming_attack(JammingAttack::Slow { htlcs: 1 }, String::from("mallory"), String::from("sink"), 5000, 5, 40);
+        // Mine past the expiry of the held payment so that sink fails it back and mallory sends it again
+        ln_sim.create_mine_blocks_event(500, 20);
+
+        let sim_results = ln_sim.run(true);
+        match sim_results {
+            Ok(res) => {
+                // The payment was held at sink until it was about to expire and the payment that was sent again was released when the attack ended
+                let htlcs = res.get_jammed_htlcs();
+                assert_eq!(htlcs.len(), 2);
+                assert!(htlcs.iter().all(|h| h.id == 0 && h.channels == vec![1, 2]));
+                assert!(htlcs[0].released.unwrap() >= 20);
+                assert_eq!(htlcs[1].released, Some(40));
+
+                // The HTLC stayed pending in mallory's channel the whole time and sink was never paid
+                let capacity = |time: u64| res.get_node_snapshot(time, &String::from("mallory")).unwrap().channels.iter().find(|c| c.id == 1).unwrap().outbound_capacity;
+                assert!(capacity(45) >= capacity(15) + 5000000);
+                assert!(capacity(45) >= capacity(35) + 5000000);
+                assert_eq!(res.get_node_snapshot(45, &String::from("sink")).unwrap().balance.offchain, 0);
+            },
+            Err(e) => {
+                println!("Test failed due to error: {:?}", e);
+                assert_eq!(true, false);
+            }
+        }
+    }
+
+    #[test]
+    fn traffic_pairs_test() {
+        // Every node sends to and receives from the others, a node never pays itself and no payments are dropped
+        let nodes = vec![String::from("a"), String::from("b"), String::from("c")];
+        let config = TrafficConfig { model: TrafficModel::Poisson, senders: nodes.clone(), receivers: nodes.clone(), rate: 0.5, amount: AmountDistribution::Fixed { amount: 1000 }, start: 0, end: 999, seed: 7 };
+        let mut generator = SimTrafficGenerator::new(config);
+        generator.initialize(&HashMap::new());
+        let mut count = 0;
+        for time in 0..1000 {
+            for tx in generator.generate(time) {
+                assert!(tx.src_node != tx.dest_node);
+                count += 1;
+            }
+        }
+        // 6 pairs at 0.5 payments per second for 1000 seconds
+        assert!(count > 2700 && count < 3300);
+
+        // Amounts above max are picked again instead of piling up at max
+        let mut rng = StdRng::seed_from_u64(1);
+        let pareto = AmountDistribution::Pareto { min: 1000, alpha: 1.0, max: 2000 };
+        let amounts: Vec<u64> = (0..10000).map(|_| pareto.sample(&mut rng)).collect();
+        assert!(amounts.iter().all(|a| *a >= 1000 && *a <= 2000));
+        assert!(amounts.iter().filter(|a| **a == 2000).count() < 100);
+    }
 }
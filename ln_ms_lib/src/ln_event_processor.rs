@@ -1,11 +1,15 @@
 // Project modules
-use crate::sim_event::{SimResultsEvent, SimulationEvent, SimEvent, SimPaymentPath, PathHop};
+use crate::sim_event::{SimResultsEvent, SimulationEvent, SimEvent, SimPaymentPath, PathHop, SimSweep, SimPaymentFailure, SimForward};
+use crate::sim_transaction::SimFailureReason;
 
 // External modules
 use tokio::sync::broadcast;
 
 // Sensei and LDK modules
 use lightning::util::events::Event;
+use lightning::chain::keysinterface::SpendableOutputDescriptor;
+use lightning::routing::gossip::NetworkUpdate;
+use lightning::routing::router::RouteHop;
 use senseicore::hex_utils;
 
 /*
@@ -27,15 +31,15 @@ impl LnEventProcessor {
 
     /*
      * Receive node events and update the results. 
-     * Each node will have its own sender, so sim_receivers is the list of receivers that correspond to the senders.
+     * Each node will have its own sender, so sim_receivers is the list of node names and the receivers that correspond to the senders.
      */
-    pub fn process_events(&self, sim_receivers: Vec<broadcast::Receiver<Event>>, sim_results_sender: broadcast::Sender<SimResultsEvent>, mut sim_event_receiver: broadcast::Receiver<SimEvent>) {
+    pub fn process_events(&self, sim_receivers: Vec<(String, broadcast::Receiver<Event>)>, sim_results_sender: broadcast::Sender<SimResultsEvent>, mut sim_event_receiver: broadcast::Receiver<SimEvent>) {
          tokio::task::block_in_place(move || {
             self.ln_event_runtime_handle.clone().block_on(async move {
                 // Start a thread for each of the node receivers and save the handles
                 let mut handles= Vec::new();
-                for r in sim_receivers {
-                    let h = tokio::spawn(LnEventProcessor::node_receive(r, sim_results_sender.clone()));
+                for (name, r) in sim_receivers {
+                    let h = tokio::spawn(LnEventProcessor::node_receive(name, r, sim_results_sender.clone()));
                     handles.push(h);
                 }
 
@@ -65,7 +69,7 @@ impl LnEventProcessor {
     /*
      * Receives events from node implementations and updates the simulation results as needed
      */
-    async fn node_receive(mut rec: broadcast::Receiver<Event>, sender: broadcast::Sender<SimResultsEvent>) {
+    async fn node_receive(name: String, mut rec: broadcast::Receiver<Event>, sender: broadcast::Sender<SimResultsEvent>) {
         loop {
             // Listen for events coming from the nodes
             let event = match rec.recv().await {
@@ -79,8 +83,8 @@ impl LnEventProcessor {
                 Event::PaymentSent{payment_id: pay_id, payment_preimage: _, payment_hash: _, fee_paid_msat: fee} => {
                     match pay_id {
                         Some(id) => {
-                            // This payment was successful, send the sim event to the network analyzer with the payment_id and amount
-                            let fee_paid = if fee.is_some() { fee.unwrap() / 1000 } else {0};
+                            // This payment was successful, send the sim event to the network analyzer with the payment_id and the fee in msat
+                            let fee_paid = if fee.is_some() { fee.unwrap() } else {0};
                             let simevent = SimulationEvent::PaymentSuccessEvent(hex_utils::hex_str(&id.0), fee_paid);
                             let e = SimResultsEvent {
                                 sim_time: None,
@@ -94,28 +98,39 @@ impl LnEventProcessor {
                 },
                 Event::PaymentFailed{payment_id: id, payment_hash: _} => {
                     // This payment failed, send the sim event to the network analyzer with the payment id
-                    let simevent = SimulationEvent::PaymentSuccessEvent(hex_utils::hex_str(&id.0), 0);
+                    let simevent = SimulationEvent::PaymentFailedEvent(hex_utils::hex_str(&id.0));
                     let e = SimResultsEvent {
                         sim_time: None,
-                        success: true,
+                        success: false,
                         event: simevent
                     };
                     sender.send(e).expect("could not send the event");
                 },
+                Event::PaymentPathFailed { payment_id, payment_failed_permanently, network_update, path, short_channel_id, .. } => {
+                    // An attempt to send a payment along a path failed, the node will retry along another path if it can
+                    match payment_id {
+                        Some(id) => {
+                            let failure = SimPaymentFailure {
+                                payment_id: hex_utils::hex_str(&id.0),
+                                path: LnEventProcessor::get_path_hops(path),
+                                short_channel_id: *short_channel_id,
+                                reason: LnEventProcessor::get_failure_reason(network_update, path, *payment_failed_permanently),
+                                permanent: *payment_failed_permanently
+                            };
+                            let e = SimResultsEvent {
+                                sim_time: None,
+                                success: false,
+                                event: SimulationEvent::PaymentPathFailedEvent(failure)
+                            };
+                            sender.send(e).expect("could not send the event");
+                        },
+                        None => { println!("no payment id supplied, not sending event") }
+                    }
+                },
                 Event::PaymentPathSuccessful { payment_id: id, payment_hash: _, path: p} => {
                     // This event comes after the PaymentSent event and identifies the path that the payment took
-                    let mut path: Vec<PathHop> = Vec::new();
-                    for hop in p {
-                        let path_hop = PathHop {
-                            short_channel_id: hop.short_channel_id,
-                            amount: hop.fee_msat / 1000,
-                            node_pub_key: hop.pubkey.to_string()
-                        };
-                        path.push(path_hop);
-                    }
-
                     // Create a SimPaymentPath that will be used to update the simulation results for all the channels and nodes in the path
-                    let sim_payment_path = SimPaymentPath {path: path, payment_id: hex_utils::hex_str(&id.0)};
+                    let sim_payment_path = SimPaymentPath {path: LnEventProcessor::get_path_hops(p), payment_id: hex_utils::hex_str(&id.0)};
 
                     let simevent = SimulationEvent::PaymentPathSuccessful(sim_payment_path);
                     let e = SimResultsEvent {
@@ -125,6 +140,20 @@ impl LnEventProcessor {
                     };
                     sender.send(e).expect("could not send the event");
                 },
+                Event::PaymentForwarded { prev_channel_id, next_channel_id, fee_earned_msat, .. } => {
+                    // This node forwarded a payment and claimed the fee, send the channels and fee to the network analyzer
+                    let forward = SimForward {
+                        prev_channel_id: prev_channel_id.map(|id| hex_utils::hex_str(&id)),
+                        next_channel_id: next_channel_id.map(|id| hex_utils::hex_str(&id)),
+                        fee_earned_msat: *fee_earned_msat
+                    };
+                    let e = SimResultsEvent {
+                        sim_time: None,
+                        success: true,
+                        event: SimulationEvent::PaymentForwardedEvent(name.clone(), forward)
+                    };
+                    sender.send(e).expect("could not send the event");
+                },
                 Event::ChannelClosed { channel_id, ..} => {
                     // A channel is starting to close, send the id to the network analyzer
                     let simevent = SimulationEvent::CloseChannelSuccessEvent(hex_utils::hex_str(channel_id));
@@ -134,11 +163,82 @@ impl LnEventProcessor {
                         event: simevent
                     };
                     sender.send(e).expect("could not send the event");
-                }
+                },
+                Event::SpendableOutputs { outputs } => {
+                    // Outputs from a closed channel can be swept by this node, the delayed outputs were timelocked by to_self_delay
+                    for o in outputs {
+                        let sweep = match o {
+                            SpendableOutputDescriptor::StaticOutput { outpoint, output } => {
+                                SimSweep { spent_txid: outpoint.txid.to_string(), amount_sats: output.value, to_self_delay: None }
+                            },
+                            SpendableOutputDescriptor::DelayedPaymentOutput(d) => {
+                                SimSweep { spent_txid: d.outpoint.txid.to_string(), amount_sats: d.output.value, to_self_delay: Some(d.to_self_delay) }
+                            },
+                            SpendableOutputDescriptor::StaticPaymentOutput(d) => {
+                                SimSweep { spent_txid: d.outpoint.txid.to_string(), amount_sats: d.output.value, to_self_delay: None }
+                            }
+                        };
+                        let e = SimResultsEvent {
+                            sim_time: None,
+                            success: true,
+                            event: SimulationEvent::SweepEvent(name.clone(), sweep)
+                        };
+                        sender.send(e).expect("could not send the event");
+                    }
+                },
                 _ => {
                     // Ignore all other events
                 }
             }
         }
     }
-}
\ No newline at end of file
+
+    /*
+     * Convert the hops of an LDK route to the hops of a simulation payment path
+     */
+    fn get_path_hops(route: &Vec<RouteHop>) -> Vec<PathHop> {
+        let mut path: Vec<PathHop> = Vec::new();
+        for hop in route {
+            let path_hop = PathHop {
+                short_channel_id: hop.short_channel_id,
+                amount: hop.fee_msat / 1000,
+                amount_msat: hop.fee_msat,
+                node_pub_key: hop.pubkey.to_string()
+            };
+            path.push(path_hop);
+        }
+
+        path
+    }
+
+    /*
+     * Get the reason that a payment attempt failed from the network update that LDK creates from the error that the failing node sent back
+     */
+    fn get_failure_reason(network_update: &Option<NetworkUpdate>, route: &Vec<RouteHop>, payment_failed_permanently: bool) -> SimFailureReason {
+        match network_update {
+            Some(NetworkUpdate::ChannelUpdateMessage { msg }) => {
+                // The failing node sent its current policy for the next channel, check if the fee it took was less than the policy requires
+                match route.iter().position(|hop| hop.short_channel_id == msg.contents.short_channel_id) {
+                    Some(index) if index > 0 => {
+                        let forwarded_msat: u64 = route[index..].iter().map(|hop| hop.fee_msat).sum();
+                        let required_fee_msat = msg.contents.fee_base_msat as u64 + forwarded_msat * msg.contents.fee_proportional_millionths as u64 / 1_000_000;
+                        if route[index - 1].fee_msat < required_fee_msat {
+                            SimFailureReason::FeeInsufficient
+                        } else {
+                            SimFailureReason::ChannelUpdateReceived
+                        }
+                    },
+                    _ => SimFailureReason::ChannelUpdateReceived
+                }
+            },
+            Some(NetworkUpdate::ChannelFailure { is_permanent, .. }) => {
+                if *is_permanent { SimFailureReason::PermanentChannelFailure } else { SimFailureReason::TemporaryChannelFailure }
+            },
+            Some(NetworkUpdate::NodeFailure { .. }) => SimFailureReason::NodeFailure,
+            None => {
+                // The destination does not create a network update when it rejects a payment
+                if payment_failed_permanently { SimFailureReason::RecipientRejected } else { SimFailureReason::Unknown }
+            }
+        }
+    }
+}
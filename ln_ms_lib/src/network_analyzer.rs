@@ -2,13 +2,32 @@
 use crate::sim_event::SimResultsEvent;
 use crate::sim_event::SimulationEvent;
 use crate::sim_results::SimResults;
-use crate::sim_results::Tx;
+use crate::sim_results::{Tx, PaymentPart, FailedAttempt, Forward, FailedForward};
 use crate::sim_runtime_graph::RuntimeNetworkGraph;
 use crate::sensei_controller::SenseiController;
 use crate::sim_channel::SimChannel;
+use crate::sim_channel::SimChannelPolicy;
+use crate::sim_results::ChannelPolicyUpdate;
+use crate::sim_results::{ForceClose, CloseType, Sweep};
+use crate::sim_results::{InvariantViolation, Invariant};
+use crate::sim_results::Partition;
+use crate::sim_results::JammedHtlc;
+use crate::sim_results::BalanceProbe;
+use crate::sim_results::OnChainTx;
+use crate::sim_results::{FeeMarketBlock, ChannelFee, ChannelFeeType, Reorg, ReorgedChannel};
 use crate::sim_transaction::SimTransactionStatus;
+use crate::sim_transaction::SimFailureReason;
 use crate::sim_transaction::SimTransaction;
-use crate::sim_event::PathHop;
+use crate::sim_event::SimPaymentPath;
+use crate::sim_event::SimForward;
+use crate::sim_event::SimSweep;
+use crate::sim_event::SimOnChainTx;
+use crate::sim_event::SimHeldPayment;
+use crate::network_metrics;
+use crate::nigiri_controller;
+use crate::trigger::{Trigger, TriggerConfig};
+use crate::sim_control::SimControl;
+use crate::sim_results::FiredTrigger;
 
 // External modules
 use tokio::sync::broadcast;
@@ -28,18 +47,40 @@ pub struct NetworkAnalyzer {
     analyzer_runtime_handle: tokio::runtime::Handle,
     results: SimResults, // the results object to keep track of what happens in the simulation
     pub_key_map: HashMap<String, String>, // pubkey to node name map
-    bitcoind_client: Arc<BitcoindClient>, // bitcoind client used to get information about on-chain operations
-    finalized_closed_channels: Vec<String> // keeping track of the channels that have been closed
+    bitcoind_client: Option<Arc<BitcoindClient>>, // bitcoind client used to get information about on-chain operations, None if only process_event is used
+    finalized_closed_channels: Vec<String>, // keeping track of the channels that have been closed
+    pending_forwards: Vec<(String, SimForward)>, // forwards reported by the routing nodes before the payment path was reported by the sender
+    invariant_checks: Option<Arc<SenseiController>>, // the node backend to check the results against after every event, None if invariant checking is off
+    initial_supply: u64, // the on-chain + off-chain balance of all the nodes at sim time = 0, plus the funds sent to the nodes from outside of the simulation and minus the funds sent out of it
+    fees_paid: u64, // the on-chain fees paid to open and close channels and to send on-chain payments
+    locked: i64, // funds from force closed channels that have not been swept yet
+    in_flight: HashMap<String, i64>, // key=payment id, value=amount in msat the sender paid that has not been added to the nodes along the path yet
+    off_chain_residue_msat: HashMap<String, u64>, // key=node name, value=the msat of the off-chain balance that are below the whole sats recorded in the results
+    channel_residue_msat: HashMap<u64, (u64, u64)>, // key=channel id, value=the msat of the src and dest balances that are below the whole sats recorded in the results
+    triggers: Vec<Trigger>, // the rules that add events to the simulation when their condition is met
+    held_payments: HashMap<String, SimHeldPayment>, // key=payment id, value=the payments of jamming attacks that have not been failed back yet
+    control: SimControl // the control of the running simulation, used to get the sim time of events that come from the nodes and to add the events of the triggers
 }
 
 impl NetworkAnalyzer {
-    pub fn new(runtime_handle: tokio::runtime::Handle, bitcoind_client: Arc<BitcoindClient>) -> Self {
+    pub fn new(runtime_handle: tokio::runtime::Handle, bitcoind_client: Option<Arc<BitcoindClient>>, control: SimControl) -> Self {
         let analyzer = NetworkAnalyzer {
             analyzer_runtime_handle: runtime_handle,
             results: SimResults::new(),
             pub_key_map: HashMap::new(),
             bitcoind_client: bitcoind_client,
-            finalized_closed_channels: Vec::new()
+            finalized_closed_channels: Vec::new(),
+            pending_forwards: Vec::new(),
+            invariant_checks: None,
+            initial_supply: 0,
+            fees_paid: 0,
+            locked: 0,
+            in_flight: HashMap::new(),
+            off_chain_residue_msat: HashMap::new(),
+            channel_residue_msat: HashMap::new(),
+            triggers: Vec::new(),
+            held_payments: HashMap::new(),
+            control: control
         };
 
         analyzer
@@ -76,15 +117,45 @@ impl NetworkAnalyzer {
                                 short_id: c.short_id.clone(),
                                 run_time_id: Some(c.run_time_id),
                                 funding_tx: c.funding_tx,
-                                penalty_reserve_sats: c.punishment_reserve
+                                penalty_reserve_sats: c.punishment_reserve,
+                                src_policy: NetworkAnalyzer::get_channel_policy(&network.channels, c.id, true),
+                                dest_policy: NetworkAnalyzer::get_channel_policy(&network.channels, c.id, false)
                             };
                             self.results.channels.open_channels.get_mut(&0).unwrap().push(sc);
+                            self.channel_residue_msat.insert(c.id, (c.outbound_capacity % 1000, c.inbound_capacity % 1000));
                         }
                     }
                 },
                 None => {}
             }
         }
+
+        self.initial_supply = self.get_total_supply();
+    }
+
+    /*
+     * Set up the network from results that were already recorded instead of from the nodes, the pub key of each node is its name
+     */
+    #[cfg(test)]
+    pub(crate) fn initialize_results(&mut self, results: SimResults) {
+        self.pub_key_map = results.status.nodes.keys().map(|n| (n.clone(), n.clone())).collect();
+        self.results = results;
+        self.initial_supply = self.get_total_supply();
+    }
+
+    /*
+     * Check the balance invariants after every event that gets processed, the node balances are queried with the sensei controller
+     * This is slow since every node is queried after every event, it is meant for finding mistakes in the results
+     */
+    pub fn enable_invariant_checks(&mut self, sensei_controller: Arc<SenseiController>) {
+        self.invariant_checks = Some(sensei_controller);
+    }
+
+    /*
+     * Check the triggers after every event that gets processed, the events of a trigger that fires are added to the simulation with the control handle
+     */
+    pub fn enable_triggers(&mut self, triggers: Vec<TriggerConfig>) {
+        self.triggers = triggers.into_iter().map(|t| Trigger::new(t)).collect();
     }
 
     /*
@@ -96,213 +167,699 @@ impl NetworkAnalyzer {
                 let mut running = true;
                 while running {
                     let event = results_channel.recv().await.unwrap();
-                    if event.sim_time.is_some() && !self.results.event_times.contains(&event.sim_time.unwrap()){
-                        self.results.event_times.push(event.sim_time.unwrap());
+                    // The on-chain events also need the bitcoind client to find their transactions and fees
+                    self.process_event(&event);
+                    self.process_on_chain_event(&event).await;
+                    running = !matches!(event.event, SimulationEvent::SimulationEndedEvent);
+
+                    // Add the events of the triggers whose condition was met by this event
+                    if running {
+                        self.fire_triggers(&event);
                     }
-                    // Match on the SimulationEvent
-                    match &event.event {
-                        SimulationEvent::StopNodeEvent(name) => {
-                            println!("[=== NetworkAnalyzer === {}] StopNodeEvent for {}", crate::get_current_time(), name);
-                            if event.success {
-                                // The node was successfully stopped, update the status in the results at the event sim time
-                                match self.results.status.nodes.get_mut(name) {
-                                    Some(n) => {
-                                        n.insert(event.sim_time.unwrap(), false);
-                                    },
-                                    None => {}
-                                }
-                            } else {
-                                // The node failed to stop, add the event to the list of failed events
-                                self.results.failed_events.push(event.clone());
-                            }
+
+                    // Make sure the results still add up after this event
+                    match self.invariant_checks.clone() {
+                        Some(sensei_controller) => {
+                            self.check_invariants(&event, &sensei_controller).await;
                         },
-                        SimulationEvent::StartNodeEvent(name) => {
-                            println!("[=== NetworkAnalyzer === {}] StartNodeEvent for {}", crate::get_current_time(), name);
-                            if event.success {
-                                // The node was successfully started, update the status in the results at the event sim time
-                                match self.results.status.nodes.get_mut(name) {
-                                    Some(n) => {
-                                        n.insert(event.sim_time.unwrap(), true);
-                                    },
-                                    None => {}
-                                }
-                            } else {
-                                // The node failed to start, add the event to the list of failed events
-                                self.results.failed_events.push(event.clone());
-                            }
+                        None => {}
+                    }
+                }
+            })
+        });
+    }
+
+    /*
+     * Update the results with an event that does not need to be looked up on-chain
+     */
+    pub(crate) fn process_event(&mut self, event: &SimResultsEvent) {
+        // Snapshots are taken on an interval and are not events on the timeline
+        let snapshot = matches!(event.event, SimulationEvent::SnapshotEvent(_));
+        if event.sim_time.is_some() && !snapshot {
+            self.add_event_time(event.sim_time.unwrap());
+        }
+        // Match on the SimulationEvent
+        match &event.event {
+            SimulationEvent::StopNodeEvent(name) => {
+                println!("[=== NetworkAnalyzer === {}] StopNodeEvent for {}", crate::get_current_time(), name);
+                if event.success {
+                    // The node was successfully stopped, update the status in the results at the event sim time
+                    match self.results.status.nodes.get_mut(name) {
+                        Some(n) => {
+                            n.insert(event.sim_time.unwrap(), false);
                         },
-                        SimulationEvent::CloseChannelEvent(_, id) => {
-                            println!("[=== NetworkAnalyzer === {}] CloseChannelEvent for {}", crate::get_current_time(), id);
-                            if event.success {
-                                // Get the last known status of this channel and save it to the closed channel list
-                                let chan = self.get_last_open_channel_status(id.clone());
-                                match chan {
-                                    Some(c) => {
-                                        self.update_close_channel_results(event.sim_time.unwrap().clone(), &c);
-                                    },
-                                    None => {}
-                                }
-                            } else {
-                                // The channel failed to close, add the event to the list of failed events
-                                self.results.failed_events.push(event.clone());
-                            }
+                        None => {}
+                    }
+                } else {
+                    // The node failed to stop, add the event to the list of failed events
+                    self.results.failed_events.push(event.clone());
+                }
+            },
+            SimulationEvent::StartNodeEvent(name) => {
+                println!("[=== NetworkAnalyzer === {}] StartNodeEvent for {}", crate::get_current_time(), name);
+                if event.success {
+                    // The node was successfully started, update the status in the results at the event sim time
+                    match self.results.status.nodes.get_mut(name) {
+                        Some(n) => {
+                            n.insert(event.sim_time.unwrap(), true);
                         },
-                        SimulationEvent::CloseChannelSuccessEvent(id) => {
-                            println!("[=== NetworkAnalyzer === {}] CloseChannelSuccessEvent for {}", crate::get_current_time(), id);
-                            // Get the closed channel from the closed channel list and use those values to update balances
-                            // This channel was added to the closed list by the CloseChannelEvent that occurs before the CloseChannelSuccessEvent
-                            let chan = self.get_last_closed_channel_status(id.clone());
-                            match chan {
-                                Some((c, t)) => {
-                                    // This channel is closed so subtract the balance of this channel from the overall off-chain balance for the nodes involved
-                                    self.update_off_chain_balance(t, &c.src_node, c.get_src_balance(), true);
-                                    self.update_off_chain_balance(t, &c.dest_node, c.get_dest_balance(), true);
-
-                                    // This channel is in the process of closing, so wait until we find the closing transaction on-chain
-                                    // Then determine the fees and update the on-chain balance for the nodes involved
-                                    // TODO: we should not wait here, the closing transaction might take some time to get included in a block, this needs to happen on another thread
-                                    // TODO: mining in general needs to be modeled and simulated in a separate thread/process.
-                                    let mut pending_channel_closes: Vec<(SimChannel, u64)> = Vec::new();
-                                    pending_channel_closes.push((c.clone(), t));
-                                    while !pending_channel_closes.is_empty() {
-                                        let closes = pending_channel_closes.clone();
-                                        pending_channel_closes.clear();
-                                        for (c, t) in closes {
-                                            match self.get_closing_fees(c.src_balance_sats, c.funding_tx.clone()).await {
-                                                Some(fee) => {
-                                                    self.update_on_chain_balance(t, &c.src_node, c.get_src_balance() - fee.0, true);
-                                                    self.update_on_chain_balance(t, &c.dest_node, c.get_dest_balance() - fee.1, true);
-                                                },
-                                                None => {
-                                                    pending_channel_closes.push((c, t));
-                                                }
-                                            }
-                                        }
-                                        tokio::time::sleep(Duration::from_secs(1)).await;
-                                    }
-                                    self.finalized_closed_channels.push(id.clone());
-                                },
-                                None => {}
-                            }
-                        }
-                        SimulationEvent::OpenChannelEvent(channel) => {
-                            println!("[=== NetworkAnalyzer === {}] OpenChannelEvent for {} <-> {}", crate::get_current_time(), channel.src_node, channel.dest_node);
-                            if event.success {
-                                // Add this channel to the open list
-                                self.update_open_channel_results(event.sim_time.unwrap().clone(), channel);
-
-                                // This channel is opening, so add the balances to the off-chain balance for the nodes involved
-                                self.update_off_chain_balance(event.sim_time.unwrap().clone(), &channel.src_node, channel.get_src_balance(), false);
-                                self.update_off_chain_balance(event.sim_time.unwrap().clone(), &channel.dest_node, channel.get_dest_balance(), false);
-
-                                // Get the on-chain fees for opening this channel and subtract the on-chain balances for the nodes involved
-                                let src_open_fee = self.get_open_fees(channel.funding_tx.clone()).await;
-                                self.update_on_chain_balance(event.sim_time.unwrap().clone(), &channel.src_node, src_open_fee + channel.get_total_balance(), false);
-                            } else {
-                                // The channel failed to open, add the event to the list of failed events
-                                self.results.failed_events.push(event.clone());
-                            }
+                        None => {}
+                    }
+                } else {
+                    // The node failed to start, add the event to the list of failed events
+                    self.results.failed_events.push(event.clone());
+                }
+            },
+            SimulationEvent::CloseChannelEvent(_, id) => {
+                println!("[=== NetworkAnalyzer === {}] CloseChannelEvent for {}", crate::get_current_time(), id);
+                if event.success {
+                    // Get the last known status of this channel and save it to the closed channel list
+                    let chan = self.get_last_open_channel_status(id.clone());
+                    match chan {
+                        Some(c) => {
+                            self.update_close_channel_results(event.sim_time.unwrap().clone(), &c);
                         },
-                        SimulationEvent::TransactionEvent(tx) => {
-                            println!("[=== NetworkAnalyzer === {}] TransactionEvent for {} <-> {}", crate::get_current_time(), tx.src_node, tx.dest_node);
-                            if event.success {
-                                // The payment was sent, add it to the list of transactions (it will be updated later with the details if it is successful)
-                                let new_tx: Tx = Tx {
-                                    time: event.sim_time.unwrap().clone(),
-                                    transaction: tx.clone()
-                                };
-                                self.results.transactions.txs.push(new_tx);
-                            } else {
-                                // The payment failed to send, add the event to the list of failed events
-                                self.results.failed_events.push(event.clone());
-                            }
+                        None => {}
+                    }
+                } else {
+                    // The channel failed to close, add the event to the list of failed events
+                    self.results.failed_events.push(event.clone());
+                }
+            },
+            SimulationEvent::ForceCloseChannelEvent(node, id) | SimulationEvent::BroadcastRevokedStateEvent(node, id) => {
+                println!("[=== NetworkAnalyzer === {}] {} for {}", crate::get_current_time(), event.event, id);
+                if event.success {
+                    // Get the last known status of this channel, save it to the closed channel list and start tracking the force close
+                    let chan = self.get_last_open_channel_status(id.clone());
+                    match chan {
+                        Some(c) => {
+                            self.update_close_channel_results(event.sim_time.unwrap().clone(), &c);
+                            let close_type = match &event.event {
+                                SimulationEvent::BroadcastRevokedStateEvent(_, _) => CloseType::RevokedState,
+                                _ => CloseType::Force
+                            };
+                            self.results.force_closes.push(ForceClose {
+                                channel_id: c.id,
+                                run_time_id: c.run_time_id.clone(),
+                                close_type: close_type,
+                                initiator: node.clone(),
+                                counterparty: if node == &c.src_node { c.dest_node.clone() } else { c.src_node.clone() },
+                                time: event.sim_time.unwrap(),
+                                closing_tx: None,
+                                closing_fee_sats: None,
+                                to_self_delay: None,
+                                sweeps: Vec::new()
+                            });
                         },
-                        SimulationEvent::PaymentPathSuccessful(path) => {
-                            // A payment that was sent went through successfully and was received
-                            // Update the channel and node balances at the time of this payment
-                            // Get the time that this transaction was sent
-                            let mut time_option: Option<u64> = None;
-                            let payment_amount: u64 = path.path.last().unwrap().amount;
-                            for transaction in &self.results.transactions.txs {
-                                match &transaction.transaction.id {
-                                    Some(id) => {
-                                        if id == &path.payment_id {
-                                            time_option = Some(transaction.time.clone());
-                                            break;
-                                        }
-                                    },
-                                    None => {}
-                                }
+                        None => {}
+                    }
+                } else {
+                    // The channel failed to close, add the event to the list of failed events
+                    self.results.failed_events.push(event.clone());
+                }
+            },
+            SimulationEvent::TransactionEvent(tx) | SimulationEvent::KeysendEvent(tx) => {
+                println!("[=== NetworkAnalyzer === {}] {} for {} <-> {}", crate::get_current_time(), event.event, tx.src_node, tx.dest_node);
+                if event.success {
+                    // The payment was sent, add it to the list of transactions (it will be updated later with the details if it is successful)
+                    let new_tx: Tx = Tx {
+                        time: event.sim_time.unwrap().clone(),
+                        transaction: tx.clone(),
+                        parts: Vec::new(),
+                        failed_attempts: Vec::new()
+                    };
+                    self.results.transactions.txs.push(new_tx);
+                } else {
+                    // The payment failed to send, add the event to the list of failed events
+                    self.results.failed_events.push(event.clone());
+                }
+            },
+            SimulationEvent::PaymentPathSuccessful(path) => {
+                // A payment that was sent went through successfully and was received
+                // If the payment was split (MPP) there is one of these events for each part
+                // Update the channel and node balances at the time of this payment
+                // Get the time that this transaction was sent and record this part of the payment
+                let mut time_option: Option<u64> = None;
+                for transaction in &mut self.results.transactions.txs {
+                    match &transaction.transaction.id {
+                        Some(id) => {
+                            if id == &path.payment_id {
+                                time_option = Some(transaction.time.clone());
+                                transaction.parts.push(PaymentPart {
+                                    path: path.path.clone(),
+                                    amount_sats: path.get_amount(),
+                                    fee_sats: path.get_fee()
+                                });
+                                break;
                             }
+                        },
+                        None => {}
+                    }
+                }
 
-                            let time: u64;
-                            match time_option {
-                                Some(t) => {
-                                    time = t;
-                                }
-                                None => {
-                                    println!("transaction not found");
-                                    return;
-                                }
-                            }
+                let time: u64;
+                match time_option {
+                    Some(t) => {
+                        time = t;
+                    }
+                    None => {
+                        println!("transaction not found");
+                        return;
+                    }
+                }
 
-                            // Update the channel balances along the path
-                            let prev_open_list = match self.results.channels.open_channels.keys().copied().max() {
-                                Some(k) => {
-                                    self.results.channels.open_channels.get(&k).unwrap().clone()
-                                },
-                                None => {
-                                    Vec::new()
-                                }
-                            };
-                            self.update_open_channel_balances(time.clone(), prev_open_list, &path.path, payment_amount);
+                // Update the channel balances along the path
+                let prev_open_list = match self.results.channels.open_channels.keys().copied().max() {
+                    Some(k) => {
+                        self.results.channels.open_channels.get(&k).unwrap().clone()
+                    },
+                    None => {
+                        Vec::new()
+                    }
+                };
+                self.update_open_channel_balances(time.clone(), prev_open_list, path);
+
+                // Update the node balances along the path, each node gets its forwarding fee and the destination gets the amount of this part
+                for p in &path.path {
+                    let hop_node_name = self.pub_key_map.get(&p.node_pub_key).unwrap().clone();
+                    self.update_off_chain_balance_msat(time.clone(), &hop_node_name, p.amount_msat, false);
+                    *self.in_flight.entry(path.payment_id.clone()).or_insert(0) -= p.amount_msat as i64;
+                }
+                self.settle_payment(&path.payment_id);
+
+                // Every node along the path except the destination forwarded the payment to the next channel
+                for i in 0..path.path.len().saturating_sub(1) {
+                    let forward = Forward {
+                        time: time,
+                        node: self.pub_key_map.get(&path.path[i].node_pub_key).unwrap().clone(),
+                        short_channel_id_in: path.path[i].short_channel_id,
+                        short_channel_id_out: path.path[i + 1].short_channel_id,
+                        channel_in: self.get_channel_id_by_short_id(path.path[i].short_channel_id),
+                        channel_out: self.get_channel_id_by_short_id(path.path[i + 1].short_channel_id),
+                        amount_in_sats: path.get_forwarded_amount(i),
+                        amount_out_sats: path.get_forwarded_amount(i + 1),
+                        fee_sats: path.path[i].amount,
+                        fee_msat: None
+                    };
+                    self.add_forward(forward);
+                }
+            },
+            SimulationEvent::PaymentPathFailedEvent(failure) if self.is_jamming_payment(&failure.payment_id) => {
+                // A held payment was failed back, if the destination rejected it then it was held along this path
+                if failure.reason == SimFailureReason::RecipientRejected {
+                    let time = self.get_current_sim_time();
+                    let channels: Vec<u64> = failure.path.iter().filter_map(|hop| self.get_channel_id_by_short_id(hop.short_channel_id)).collect();
+                    let expired = match self.results.jamming.htlcs.iter_mut().find(|h| h.payment_id == failure.payment_id) {
+                        Some(h) => {
+                            h.path = failure.path.clone();
+                            h.channels = channels;
 
-                            // Update the node balances along the path
-                            for p in &path.path {
-                                let hop_node_name = self.pub_key_map.get(&p.node_pub_key).unwrap().clone();
-                                self.update_off_chain_balance(time.clone(), &hop_node_name, p.amount, false);
+                            // A payment that was not released by the attacker was failed back by the destination because it was about to expire
+                            let expired = h.released.is_none();
+                            if expired {
+                                h.released = Some(time);
                             }
+                            expired
                         },
-                        SimulationEvent::PaymentFailedEvent(id) => {
-                            // A payment that was sent did not go through successfully, set the status to FAILED
-                            // Do not update any balances
-                            for mut t in &mut self.results.transactions.txs {
-                                if &t.transaction.id.clone().unwrap() == id {
-                                    t.transaction.status = SimTransactionStatus::FAILED;
-                                }
-                            };
+                        None => false
+                    };
+
+                    // Send the payment again with the same id if the attack is still running
+                    match self.held_payments.remove(&failure.payment_id) {
+                        Some(payment) if expired && payment.resend_until.map_or(false, |end| time + 1 < end) => {
+                            let resend = SimHeldPayment { payment_id: None, ..payment };
+                            match self.control.add_event(SimulationEvent::HoldPaymentEvent(resend), None) {
+                                Ok(_) => {},
+                                Err(e) => println!("could not send the held payment again: {}", e)
+                            }
                         },
-                        SimulationEvent::PaymentSuccessEvent(id, fee) => {
-                            // A payment that was sent went through successfully and was received
-                            // Get the transaction that this success event corresponds too and the time it was sent
-                            let mut current_tx: Option<SimTransaction> = None;
-                            let mut time: u64 = 0;
-                            for mut t in &mut self.results.transactions.txs {
-                                if &t.transaction.id.clone().unwrap() == id {
-                                    // Set the status to successful
-                                    t.transaction.status = SimTransactionStatus::SUCCESSFUL;
-                                    current_tx = Some(t.transaction.clone());
-                                    time = t.time.clone();
-                                }
-                            };
+                        _ => {}
+                    }
+                }
+            },
+            SimulationEvent::PaymentPathFailedEvent(failure) => {
+                // An attempt to send a payment failed, record the path and the reason it failed
+                // Do not update any balances
+                let time = self.get_current_sim_time();
+                match self.results.transactions.txs.iter_mut().find(|t| t.transaction.id.as_ref() == Some(&failure.payment_id)) {
+                    Some(t) => {
+                        t.failed_attempts.push(FailedAttempt {
+                            time: time,
+                            path: failure.path.clone(),
+                            short_channel_id: failure.short_channel_id,
+                            reason: failure.reason,
+                            permanent: failure.permanent
+                        });
+                    },
+                    None => {
+                        println!("Transaction for this failed path was not found");
+                    }
+                }
 
-                            // Update the offchain balance for the source node (sending node), the rest of the nodes in the path will be updated in the PaymentPathSuccessful event
-                            match current_tx {
-                                Some(t) => {
-                                    self.update_off_chain_balance(time, &t.src_node, t.amount_sats + fee, true)
-                                },
-                                None => {
-                                    println!("Transaction for this success event was not found");
+                // If the payment failed at a channel after the first hop, the node before that channel failed to forward it
+                match failure.short_channel_id.and_then(|scid| failure.path.iter().position(|hop| hop.short_channel_id == scid)) {
+                    Some(index) if index > 0 => {
+                        let attempt_path = SimPaymentPath { payment_id: failure.payment_id.clone(), path: failure.path.clone() };
+                        let scid_in = failure.path[index - 1].short_channel_id;
+                        let scid_out = failure.path[index].short_channel_id;
+                        match self.pub_key_map.get(&failure.path[index - 1].node_pub_key) {
+                            Some(node) => {
+                                self.results.forwarding.failed_forwards.push(FailedForward {
+                                    time: time,
+                                    node: node.clone(),
+                                    short_channel_id_in: scid_in,
+                                    short_channel_id_out: scid_out,
+                                    channel_in: self.get_channel_id_by_short_id(scid_in),
+                                    channel_out: self.get_channel_id_by_short_id(scid_out),
+                                    amount_sats: attempt_path.get_forwarded_amount(index),
+                                    reason: failure.reason
+                                });
+                            },
+                            None => {}
+                        }
+                    },
+                    _ => {}
+                }
+            },
+            SimulationEvent::PaymentForwardedEvent(node, forward) => {
+                // A routing node claimed the fee for a forward, set the exact fee on the forward that was recorded from the payment path
+                // The sender might not have reported the path yet, so save it until the forward is recorded
+                let channel_in = forward.prev_channel_id.as_ref().and_then(|id| self.get_channel_id_by_run_time_id(id));
+                let channel_out = forward.next_channel_id.as_ref().and_then(|id| self.get_channel_id_by_run_time_id(id));
+                match self.results.forwarding.forwards.iter_mut().find(|f| &f.node == node && f.fee_msat.is_none() && f.channel_in == channel_in && f.channel_out == channel_out) {
+                    Some(f) => {
+                        f.fee_msat = forward.fee_earned_msat;
+                    },
+                    None => {
+                        self.pending_forwards.push((node.clone(), forward.clone()));
+                    }
+                }
+            },
+            SimulationEvent::PaymentFailedEvent(id) => {
+                // A payment that was sent did not go through successfully, set the status to FAILED and save the reason of the last attempt
+                // Do not update any balances
+                for t in &mut self.results.transactions.txs {
+                    if &t.transaction.id.clone().unwrap() == id {
+                        let reason = match t.failed_attempts.last() {
+                            Some(a) => a.reason,
+                            None => SimFailureReason::Unknown
+                        };
+                        t.transaction.status = SimTransactionStatus::FAILED(reason);
+                    }
+                };
+            },
+            SimulationEvent::PaymentSuccessEvent(id, fee) => {
+                // A payment that was sent went through successfully and was received
+                // Get the transaction that this success event corresponds too and the time it was sent
+                let mut current_tx: Option<SimTransaction> = None;
+                let mut time: u64 = 0;
+                for mut t in &mut self.results.transactions.txs {
+                    if &t.transaction.id.clone().unwrap() == id {
+                        // Set the status to successful
+                        t.transaction.status = SimTransactionStatus::SUCCESSFUL;
+                        current_tx = Some(t.transaction.clone());
+                        time = t.time.clone();
+                    }
+                };
+
+                // Update the offchain balance for the source node (sending node), the rest of the nodes in the path will be updated in the PaymentPathSuccessful event
+                match current_tx {
+                    Some(t) => {
+                        // The fee is in msat
+                        self.update_off_chain_balance_msat(time, &t.src_node, t.amount_sats * 1000 + fee, true);
+                        *self.in_flight.entry(id.clone()).or_insert(0) += (t.amount_sats * 1000 + fee) as i64;
+                        self.settle_payment(id);
+                    },
+                    None => {
+                        println!("Transaction for this success event was not found");
+                    }
+                }
+            },
+            SimulationEvent::UpdateChannelPolicyEvent(node, id, policy) => {
+                println!("[=== NetworkAnalyzer === {}] UpdateChannelPolicyEvent for {} on {}", crate::get_current_time(), node, id);
+                if event.success {
+                    // Record the update and set the new policy on the channel in the open list at the event sim time
+                    let time = event.sim_time.unwrap();
+                    self.results.channels.policy_updates.push(ChannelPolicyUpdate {
+                        time: time,
+                        node: node.clone(),
+                        channel_id: id.clone(),
+                        policy: policy.clone()
+                    });
+                    match self.results.channels.open_channels.keys().copied().max() {
+                        Some(k) => {
+                            let mut new_open_list = self.results.channels.open_channels.get(&k).unwrap().clone();
+                            for c in &mut new_open_list {
+                                if &c.id == id {
+                                    // The htlc limits are set when the channel is opened and are kept when the fees change
+                                    let mut new_policy = policy.clone();
+                                    match c.get_policy(node) {
+                                        Some(p) => {
+                                            new_policy.htlc_minimum_msat = p.htlc_minimum_msat;
+                                            new_policy.htlc_maximum_msat = p.htlc_maximum_msat;
+                                        },
+                                        None => {}
+                                    }
+                                    c.set_policy(node, new_policy);
                                 }
                             }
+                            self.results.channels.open_channels.insert(time, new_open_list);
                         },
-                        SimulationEvent::SimulationEndedEvent => {
-                            running = false;
+                        None => {}
+                    }
+                } else {
+                    // The policy failed to update, add the event to the list of failed events
+                    self.results.failed_events.push(event.clone());
+                }
+            },
+            SimulationEvent::SweepEvent(node, sweep) => {
+                println!("[=== NetworkAnalyzer === {}] SweepEvent for {}", crate::get_current_time(), node);
+                // An output of a force close commitment is added here, the outputs of other transactions are looked up by process_on_chain_event
+                match self.results.force_closes.iter().position(|f| f.closing_tx.as_ref() == Some(&sweep.spent_txid)) {
+                    Some(i) => self.add_sweep(i, node, sweep),
+                    None => {}
+                }
+            },
+            SimulationEvent::DisconnectPeersEvent(_, _) | SimulationEvent::PartitionEvent(_, _) => {
+                println!("[=== NetworkAnalyzer === {}] {}", crate::get_current_time(), event.event);
+                if event.success {
+                    // Record the pairs of nodes that can not connect to each other until the partition is healed
+                    let links: Vec<(String, String)> = match &event.event {
+                        SimulationEvent::PartitionEvent(nodes_a, nodes_b) => {
+                            nodes_a.iter().flat_map(|a| nodes_b.iter().filter(move |b| *b != a).map(move |b| (a.clone(), b.clone()))).collect()
+                        },
+                        SimulationEvent::DisconnectPeersEvent(a, b) => vec![(a.clone(), b.clone())],
+                        _ => Vec::new()
+                    };
+                    self.results.partitions.push(Partition { time: event.sim_time.unwrap(), healed: None, links: links });
+                } else {
+                    // The nodes failed to disconnect, add the event to the list of failed events
+                    self.results.failed_events.push(event.clone());
+                }
+            },
+            SimulationEvent::HealPartitionEvent => {
+                println!("[=== NetworkAnalyzer === {}] HealPartitionEvent", crate::get_current_time());
+                for p in &mut self.results.partitions {
+                    if p.healed.is_none() {
+                        p.healed = event.sim_time;
+                    }
+                }
+                if !event.success {
+                    // Some of the nodes failed to connect again, they will reconnect on their own
+                    self.results.failed_events.push(event.clone());
+                }
+            },
+            SimulationEvent::HoldPaymentEvent(payment) => {
+                println!("[=== NetworkAnalyzer === {}] HoldPaymentEvent for {} <-> {}", crate::get_current_time(), payment.src_node, payment.dest_node);
+                if event.success {
+                    // Record the held payment, the path is known once it is failed back
+                    let payment_id = payment.payment_id.clone().unwrap_or_default();
+                    self.held_payments.insert(payment_id, payment.clone());
+                    self.results.jamming.htlcs.push(JammedHtlc {
+                        id: payment.id,
+                        payment_id: payment.payment_id.clone().unwrap_or_default(),
+                        src_node: payment.src_node.clone(),
+                        dest_node: payment.dest_node.clone(),
+                        amount_sats: payment.amount_sats,
+                        sent: event.sim_time.unwrap(),
+                        released: None,
+                        path: Vec::new(),
+                        channels: Vec::new()
+                    });
+                } else {
+                    // The payment failed to send, add the event to the list of failed events
+                    self.results.failed_events.push(event.clone());
+                }
+            },
+            SimulationEvent::ReleaseHeldPaymentEvent(id) => {
+                println!("[=== NetworkAnalyzer === {}] ReleaseHeldPaymentEvent for {}", crate::get_current_time(), id);
+                if event.success {
+                    match self.results.jamming.htlcs.iter_mut().find(|h| &h.id == id && h.released.is_none()) {
+                        Some(h) => h.released = event.sim_time,
+                        None => println!("held payment not found")
+                    }
+                } else {
+                    // The payment failed to release, add the event to the list of failed events
+                    self.results.failed_events.push(event.clone());
+                }
+            },
+            SimulationEvent::ProbeEvent(probe) => {
+                println!("[=== NetworkAnalyzer === {}] ProbeEvent for {} from {}", crate::get_current_time(), probe.channel_id, probe.prober);
+                // The result is reported once the last probe finished, the balances may have changed since the probes were sent at the event time
+                let time = event.sim_time.unwrap();
+                let finished = self.get_current_sim_time().max(time);
+                let channel = match self.results.get_open_channels(finished) {
+                    Some(channels) => channels.into_iter().find(|c| c.id == probe.channel_id),
+                    None => None
+                };
+                match (&probe.result, channel) {
+                    (Some(result), Some(c)) if event.success => {
+                        // Compare the inferred balance to the balance of the probed end of the channel when the last probe finished
+                        let actual = if c.src_node == result.node { c.src_balance_sats } else { c.dest_balance_sats };
+                        self.results.probes.push(BalanceProbe {
+                            time: time,
+                            finished: Some(finished),
+                            prober: probe.prober.clone(),
+                            node: result.node.clone(),
+                            channel_id: probe.channel_id,
+                            lower_sats: result.lower_sats,
+                            upper_sats: result.upper_sats,
+                            probes: result.probes,
+                            actual_sats: actual,
+                            capacity_sats: c.src_balance_sats + c.dest_balance_sats
+                        });
+                    },
+                    _ => {
+                        // The channel could not be probed, add the event to the list of failed events
+                        self.results.failed_events.push(event.clone());
+                    }
+                }
+            },
+            SimulationEvent::SnapshotEvent(statuses) => {
+                // Save the state that the nodes reported next to the results that are inferred from the events
+                self.results.snapshots.insert(event.sim_time.unwrap(), statuses.clone());
+            },
+            SimulationEvent::FundNodeEvent(node, amount) => {
+                println!("[=== NetworkAnalyzer === {}] FundNodeEvent for {}", crate::get_current_time(), node);
+                if event.success {
+                    // The funds come from outside of the simulation, so they add to the supply
+                    let time = event.sim_time.unwrap();
+                    self.update_on_chain_balance(time, node, amount.clone(), true);
+                    self.initial_supply = self.initial_supply + amount;
+                    self.results.on_chain_txs.push(OnChainTx {
+                        time: time,
+                        src_node: None,
+                        dest: node.clone(),
+                        external: false,
+                        amount_sats: amount.clone(),
+                        fee_sats: 0,
+                        txid: None
+                    });
+                } else {
+                    // The node could not be funded, add the event to the list of failed events
+                    self.results.failed_events.push(event.clone());
+                }
+            },
+            SimulationEvent::FeeMarketBlockEvent(block) => {
+                println!("[=== NetworkAnalyzer === {}] FeeMarketBlockEvent at {} sats per vbyte", crate::get_current_time(), block.feerate_sat_per_vbyte);
+                self.results.fee_market.push(FeeMarketBlock {
+                    time: event.sim_time.unwrap(),
+                    feerate_sat_per_vbyte: block.feerate_sat_per_vbyte,
+                    filler_txs: block.filler_txs,
+                    confirmed_txs: block.confirmed_txs.unwrap_or(0),
+                    mempool_txs: block.mempool_txs.unwrap_or(0),
+                    last: block.last
+                });
+                if !event.success {
+                    // The block was mined without all of its filler transactions, add the event to the list of failed events
+                    self.results.failed_events.push(event.clone());
+                }
+            },
+            SimulationEvent::ReorgEvent(reorg) => {
+                println!("[=== NetworkAnalyzer === {}] ReorgEvent for {} blocks", crate::get_current_time(), reorg.depth);
+                if event.success {
+                    // Find the channel transactions that went back to the mempool, the balances do not change until a channel is closed
+                    let channels: Vec<ReorgedChannel> = self.results.channel_fees.iter()
+                        .filter(|f| f.txid.as_ref().map_or(false, |txid| reorg.unconfirmed_txs.contains(txid)))
+                        .map(|f| ReorgedChannel {
+                            channel_id: f.channel_id,
+                            fee_type: f.fee_type,
+                            txid: f.txid.clone().unwrap(),
+                            unusable: reorg.unusable_channels.contains(&f.channel_id),
+                            reconfirmed: None
+                        })
+                        .collect();
+                    self.results.reorgs.push(Reorg {
+                        time: event.sim_time.unwrap(),
+                        depth: reorg.depth,
+                        unconfirmed_txs: reorg.unconfirmed_txs.clone(),
+                        channels: channels
+                    });
+                } else {
+                    self.results.failed_events.push(event.clone());
+                }
+            },
+            SimulationEvent::TxConfirmedEvent(txid) => {
+                println!("[=== NetworkAnalyzer === {}] TxConfirmedEvent for {}", crate::get_current_time(), txid);
+                for r in &mut self.results.reorgs {
+                    for c in &mut r.channels {
+                        if &c.txid == txid && c.reconfirmed.is_none() {
+                            c.reconfirmed = event.sim_time;
                         }
                     }
                 }
-            })
-        });
+            },
+            SimulationEvent::SendOnChainEvent(tx) if !event.success => {
+                println!("[=== NetworkAnalyzer === {}] SendOnChainEvent for {} -> {}", crate::get_current_time(), tx.src_node, tx.dest);
+                // The transaction could not be sent, add the event to the list of failed events
+                self.results.failed_events.push(event.clone());
+            },
+            SimulationEvent::MineBlocksEvent(_) => {
+                // Nothing to update, the sweeps that the new blocks unlock are reported by the nodes
+            },
+            SimulationEvent::SimulationEndedEvent => {
+                self.compute_network_metrics();
+            },
+            SimulationEvent::CloseChannelSuccessEvent(_) | SimulationEvent::OpenChannelEvent(_) | SimulationEvent::SendOnChainEvent(_) => {
+                // The on-chain events are processed by process_on_chain_event
+            }
+        }
+    }
+
+    /*
+     * Update the results with an event whose transaction and fees are looked up with the bitcoind client
+     */
+    async fn process_on_chain_event(&mut self, event: &SimResultsEvent) {
+        match &event.event {
+            SimulationEvent::CloseChannelSuccessEvent(id) if self.is_force_close(id) => {
+                println!("[=== NetworkAnalyzer === {}] CloseChannelSuccessEvent for force closed channel {}", crate::get_current_time(), id);
+                match self.get_last_closed_channel_status(id.clone()) {
+                    Some((c, t)) => {
+                        // This channel is closed so subtract the balance of this channel from the overall off-chain balance for the nodes involved
+                        // The on-chain balances are updated when each node sweeps its outputs
+                        self.update_off_chain_balance(t, &c.src_node, c.get_src_balance(), true);
+                        self.update_off_chain_balance(t, &c.dest_node, c.get_dest_balance(), true);
+                        self.locked = self.locked + (c.get_src_balance() + c.get_dest_balance()) as i64;
+                        self.finalized_closed_channels.push(id.clone());
+
+                        // Wait until the commitment is found on-chain and record its fee
+                        // TODO: we should not wait here, the commitment might take some time to get included in a block, this needs to happen on another thread
+                        let mut closing_tx: Option<String> = None;
+                        while closing_tx.is_none() {
+                            closing_tx = self.get_bitcoind_client().find_input(c.funding_tx.clone().unwrap()).await;
+                            if closing_tx.is_none() {
+                                tokio::time::sleep(Duration::from_secs(1)).await;
+                            }
+                        }
+                        let fee = (self.get_bitcoind_client().get_tx_fees(closing_tx.clone().unwrap()).await * 100000000.0).round() as u64;
+                        self.fees_paid = self.fees_paid + fee;
+                        self.locked = self.locked - fee as i64;
+                        for f in &mut self.results.force_closes {
+                            if f.run_time_id.as_ref() == Some(id) {
+                                f.closing_tx = closing_tx.clone();
+                                f.closing_fee_sats = Some(fee);
+                            }
+                        }
+                        self.add_channel_fee(c.id, ChannelFeeType::ForceClose, &c.src_node, t, fee, closing_tx.clone());
+                    },
+                    None => {}
+                }
+            },
+            SimulationEvent::CloseChannelSuccessEvent(id) => {
+                println!("[=== NetworkAnalyzer === {}] CloseChannelSuccessEvent for {}", crate::get_current_time(), id);
+                // Get the closed channel from the closed channel list and use those values to update balances
+                // This channel was added to the closed list by the CloseChannelEvent that occurs before the CloseChannelSuccessEvent
+                let chan = self.get_last_closed_channel_status(id.clone());
+                match chan {
+                    Some((c, t)) => {
+                        // This channel is closed so subtract the balance of this channel from the overall off-chain balance for the nodes involved
+                        self.update_off_chain_balance(t, &c.src_node, c.get_src_balance(), true);
+                        self.update_off_chain_balance(t, &c.dest_node, c.get_dest_balance(), true);
+
+                        // This channel is in the process of closing, so wait until we find the closing transaction on-chain
+                        // Then determine the fees and update the on-chain balance for the nodes involved
+                        // TODO: we should not wait here, the closing transaction might take some time to get included in a block, this needs to happen on another thread
+                        // TODO: mining in general needs to be modeled and simulated in a separate thread/process.
+                        let mut pending_channel_closes: Vec<(SimChannel, u64)> = Vec::new();
+                        pending_channel_closes.push((c.clone(), t));
+                        while !pending_channel_closes.is_empty() {
+                            let closes = pending_channel_closes.clone();
+                            pending_channel_closes.clear();
+                            for (c, t) in closes {
+                                match self.get_closing_fees(c.src_balance_sats, c.funding_tx.clone()).await {
+                                    Some(fee) => {
+                                        self.update_on_chain_balance(t, &c.src_node, c.get_src_balance() - fee.0, true);
+                                        self.update_on_chain_balance(t, &c.dest_node, c.get_dest_balance() - fee.1, true);
+                                        self.fees_paid = self.fees_paid + fee.0 + fee.1;
+                                        let payer = if fee.0 > 0 { &c.src_node } else { &c.dest_node };
+                                        let closing_tx = self.get_bitcoind_client().find_input(c.funding_tx.clone().unwrap()).await;
+                                        self.add_channel_fee(c.id, ChannelFeeType::Close, payer, t, fee.0 + fee.1, closing_tx);
+                                    },
+                                    None => {
+                                        pending_channel_closes.push((c, t));
+                                    }
+                                }
+                            }
+                            tokio::time::sleep(Duration::from_secs(1)).await;
+                        }
+                        self.finalized_closed_channels.push(id.clone());
+                    },
+                    None => {}
+                }
+            },
+            SimulationEvent::SweepEvent(node, sweep) if !self.results.force_closes.iter().any(|f| f.closing_tx.as_ref() == Some(&sweep.spent_txid)) => {
+                // A justice or htlc transaction spends the commitment of the force close, any other output (e.g. from a cooperative close) is already in the on-chain balance
+                let spent = nigiri_controller::get_input_txids(&sweep.spent_txid);
+                match self.results.force_closes.iter().position(|f| f.closing_tx.as_ref().map_or(false, |c| spent.contains(c))) {
+                    Some(i) => self.add_sweep(i, node, sweep),
+                    None => println!("[=== NetworkAnalyzer === {}] Sweep for {} does not come from a force close, it is ignored", crate::get_current_time(), node)
+                }
+            },
+            SimulationEvent::SendOnChainEvent(tx) if event.success => {
+                println!("[=== NetworkAnalyzer === {}] SendOnChainEvent for {} -> {}", crate::get_current_time(), tx.src_node, tx.dest);
+                // Look up the fee that the broadcast transaction pays, a transaction from a node's wallet always pays a fee so it was not found if it is 0
+                match &tx.txid {
+                    Some(txid) => {
+                        let fee = (self.get_bitcoind_client().get_tx_fees(txid.clone()).await * 100000000.0).round() as u64;
+                        if fee > 0 {
+                            self.add_on_chain_tx(event, tx, fee);
+                        } else {
+                            println!("[=== NetworkAnalyzer === {}] Could not find the fee of on-chain transaction {}", crate::get_current_time(), txid);
+                            self.results.failed_events.push(event.clone());
+                        }
+                    },
+                    None => self.results.failed_events.push(event.clone())
+                }
+            },
+            SimulationEvent::OpenChannelEvent(channel) => {
+                println!("[=== NetworkAnalyzer === {}] OpenChannelEvent for {} <-> {}", crate::get_current_time(), channel.src_node, channel.dest_node);
+                if event.success {
+                    // Add this channel to the open list
+                    self.update_open_channel_results(event.sim_time.unwrap().clone(), channel);
+
+                    // This channel is opening, so add the balances to the off-chain balance for the nodes involved
+                    self.update_off_chain_balance(event.sim_time.unwrap().clone(), &channel.src_node, channel.get_src_balance(), false);
+                    self.update_off_chain_balance(event.sim_time.unwrap().clone(), &channel.dest_node, channel.get_dest_balance(), false);
+
+                    // Get the on-chain fees for opening this channel and subtract the on-chain balances for the nodes involved
+                    let src_open_fee = self.get_open_fees(channel.funding_tx.clone()).await;
+                    self.update_on_chain_balance(event.sim_time.unwrap().clone(), &channel.src_node, src_open_fee + channel.get_total_balance(), false);
+                    self.fees_paid = self.fees_paid + src_open_fee;
+                    self.add_channel_fee(channel.id, ChannelFeeType::Open, &channel.src_node, event.sim_time.unwrap(), src_open_fee, channel.funding_tx.clone());
+                } else {
+                    // The channel failed to open, add the event to the list of failed events
+                    self.results.failed_events.push(event.clone());
+                }
+            },
+            _ => {}
+        }
+    }
+
+    /*
+     * Get the bitcoind client that the on-chain events are looked up with
+     */
+    fn get_bitcoind_client(&self) -> &Arc<BitcoindClient> {
+        self.bitcoind_client.as_ref().expect("the network analyzer needs a bitcoind client to process on-chain events")
     }
 
     /*
@@ -362,6 +919,14 @@ impl NetworkAnalyzer {
      * Update the off chain balance for a node at a certain time
      */
     fn update_off_chain_balance(&mut self, time: u64, node: &String, amount: u64, sent: bool) {
+        self.update_off_chain_balance_msat(time, node, amount * 1000, sent);
+    }
+
+    /*
+     * Update the off chain balance for a node at a certain time with an amount in msat
+     * The results record whole sats, the msat below that are kept so that nothing is lost to rounding
+     */
+    fn update_off_chain_balance_msat(&mut self, time: u64, node: &String, amount_msat: u64, sent: bool) {
         match self.results.balance.off_chain.get_mut(node) {
             Some(hm) => {
                 // Get the previous balance for this node
@@ -373,14 +938,17 @@ impl NetworkAnalyzer {
                         0
                     }
                 };
+                let residue = self.off_chain_residue_msat.entry(node.clone()).or_insert(0);
+                let prev_bal_msat = prev_bal * 1000 + *residue;
 
                 // If this node is sending a payment subtract the amount from the previous balance, otherwise add it.
-                if sent {
-                    hm.insert(time, prev_bal - amount);
+                let bal_msat = if sent {
+                    prev_bal_msat - amount_msat
                 } else {
-                    hm.insert(time, prev_bal + amount);
-                }
-                
+                    prev_bal_msat + amount_msat
+                };
+                hm.insert(time, bal_msat / 1000);
+                *residue = bal_msat % 1000;
             },
             None => {
                 println!("node not found");
@@ -388,6 +956,55 @@ impl NetworkAnalyzer {
         }
     }
 
+    /*
+     * Record the on-chain fee that a node paid to open or close a channel, the transaction was broadcast at time and was found on-chain now
+     * The channel opens are only reported once their funding transaction is mined, so they are confirmed at the time of the open
+     */
+    fn add_channel_fee(&mut self, channel_id: u64, fee_type: ChannelFeeType, node: &String, time: u64, fee: u64, txid: Option<String>) {
+        let confirmed = match fee_type {
+            ChannelFeeType::Open => time,
+            _ => self.get_current_sim_time().max(time)
+        };
+        self.results.channel_fees.push(ChannelFee {
+            channel_id: channel_id,
+            fee_type: fee_type,
+            node: node.clone(),
+            time: time,
+            confirmed: confirmed,
+            fee_sats: fee,
+            market_feerate: self.results.get_market_feerate(time),
+            txid: txid
+        });
+    }
+
+    /*
+     * Check every trigger against the results after an event and add the events of the ones that fire to the running simulation
+     */
+    pub(crate) fn fire_triggers(&mut self, event: &SimResultsEvent) {
+        let time = event.sim_time.unwrap_or(self.get_current_sim_time());
+        for t in &mut self.triggers {
+            let events = t.update(event, &self.results);
+            if events.is_empty() {
+                continue;
+            }
+
+            println!("[=== NetworkAnalyzer === {}] Trigger {} fired after {}", crate::get_current_time(), t.config.name, event.event);
+            let mut added: Vec<SimulationEvent> = Vec::new();
+            for e in events {
+                match self.control.add_event(e.clone(), Some(time + t.config.delay)) {
+                    Ok(_) => added.push(e),
+                    Err(err) => println!("could not add the event from trigger {}: {}", t.config.name, err)
+                }
+            }
+            self.results.triggers.push(FiredTrigger {
+                name: t.config.name.clone(),
+                time: time,
+                send_time: time + t.config.delay,
+                events: added
+            });
+        }
+    }
+
     /*
      * Update the on chain balance for a node at a certain time
      */
@@ -421,50 +1038,52 @@ impl NetworkAnalyzer {
     /*
      * Update the channel balances along a payment path
      */
-    fn update_open_channel_balances(&mut self, time: u64, previous_list: Vec<SimChannel>, path: &Vec<PathHop>, payment_amount: u64) {
+    fn update_open_channel_balances(&mut self, time: u64, previous_list: Vec<SimChannel>, path: &SimPaymentPath) {
         let mut open_list: Vec<SimChannel> = Vec::new();
         let mut updated: bool;
         // Loop through the previous list of open channels and find the channel that is being used
         for prev_channel in previous_list {
             updated = false;
-            for node in path {
-                let amount: u64;
-                if payment_amount == node.amount {
-                    amount = payment_amount;
-                } else {
-                    amount = payment_amount + node.amount;
-                }
-                let hop_node_name = self.pub_key_map.get(&node.node_pub_key).unwrap();
+            for (index, node) in path.path.iter().enumerate() {
+                // The amount that moved through this channel is the amount of this part plus the fees of the rest of the path
+                let amount_msat = path.get_forwarded_amount_msat(index);
+                let hop_node_name = self.pub_key_map.get(&node.node_pub_key).unwrap().clone();
                 if prev_channel.short_id.is_some() && &prev_channel.short_id.unwrap() == &node.short_channel_id {
                     // If the node for this hop is the source node of the channel then increase the src balance and decrease the dest balance
-                    if hop_node_name == &prev_channel.src_node {
+                    if hop_node_name == prev_channel.src_node {
+                        let (src_balance, dest_balance) = self.move_channel_balance(&prev_channel, amount_msat, true);
                         let new_chan = SimChannel {
                             id: prev_channel.id,
                             src_node: prev_channel.src_node.clone(),
                             dest_node: prev_channel.dest_node.clone(),
                             short_id: prev_channel.short_id,
                             run_time_id: prev_channel.run_time_id.clone(),
-                            dest_balance_sats: prev_channel.dest_balance_sats - amount,
-                            src_balance_sats: prev_channel.src_balance_sats + amount,
+                            dest_balance_sats: dest_balance,
+                            src_balance_sats: src_balance,
                             funding_tx: prev_channel.funding_tx.clone(),
-                            penalty_reserve_sats: prev_channel.penalty_reserve_sats.clone()
+                            penalty_reserve_sats: prev_channel.penalty_reserve_sats.clone(),
+                            src_policy: prev_channel.src_policy.clone(),
+                            dest_policy: prev_channel.dest_policy.clone()
                         };
                         open_list.push(new_chan);
                         updated = true;
                     }
 
                     // If the node for this hop is the destination node of the channel then increase the dest balance and decrease the src balance
-                    if hop_node_name == &prev_channel.dest_node {
+                    if hop_node_name == prev_channel.dest_node {
+                        let (src_balance, dest_balance) = self.move_channel_balance(&prev_channel, amount_msat, false);
                         let new_chan = SimChannel {
                             id: prev_channel.id,
                             src_node: prev_channel.src_node.clone(),
                             dest_node: prev_channel.dest_node.clone(),
                             short_id: prev_channel.short_id,
                             run_time_id: prev_channel.run_time_id.clone(),
-                            dest_balance_sats: prev_channel.dest_balance_sats + amount,
-                            src_balance_sats: prev_channel.src_balance_sats - amount,
+                            dest_balance_sats: dest_balance,
+                            src_balance_sats: src_balance,
                             funding_tx: prev_channel.funding_tx.clone(),
-                            penalty_reserve_sats: prev_channel.penalty_reserve_sats.clone()
+                            penalty_reserve_sats: prev_channel.penalty_reserve_sats.clone(),
+                            src_policy: prev_channel.src_policy.clone(),
+                            dest_policy: prev_channel.dest_policy.clone()
                         };
                         open_list.push(new_chan);
                         updated = true;
@@ -481,11 +1100,37 @@ impl NetworkAnalyzer {
         self.results.channels.open_channels.insert(time, open_list);
     }
 
+    /*
+     * Move an amount in msat to the src side of a channel or to its dest side and get the new src and dest balances in sats
+     * The msat below the whole sats of each side are kept so that nothing is lost to rounding
+     */
+    fn move_channel_balance(&mut self, channel: &SimChannel, amount_msat: u64, to_src: bool) -> (u64, u64) {
+        let residue = self.channel_residue_msat.entry(channel.id).or_insert((0, 0));
+        let (mut src_msat, mut dest_msat) = (channel.src_balance_sats * 1000 + residue.0, channel.dest_balance_sats * 1000 + residue.1);
+        if to_src {
+            src_msat = src_msat + amount_msat;
+            dest_msat = dest_msat - amount_msat;
+        } else {
+            src_msat = src_msat - amount_msat;
+            dest_msat = dest_msat + amount_msat;
+        }
+        *residue = (src_msat % 1000, dest_msat % 1000);
+        (src_msat / 1000, dest_msat / 1000)
+    }
+
+    /*
+     * Get the balance of each side of a channel in msat
+     */
+    fn get_channel_balance_msat(&self, channel: &SimChannel) -> (u64, u64) {
+        let residue = self.channel_residue_msat.get(&channel.id).copied().unwrap_or((0, 0));
+        (channel.src_balance_sats * 1000 + residue.0, channel.dest_balance_sats * 1000 + residue.1)
+    }
+
     /*
      * Use the bitcoind client to calculate the on-chain fees for the funding tx of a channel
      */
     async fn get_open_fees(&self, funding_tx: Option<String>) -> u64 {
-        (self.bitcoind_client.get_tx_fees(funding_tx.clone().unwrap()).await * 100000000.0).round() as u64
+        (self.get_bitcoind_client().get_tx_fees(funding_tx.clone().unwrap()).await * 100000000.0).round() as u64
     }
 
     /*
@@ -494,16 +1139,16 @@ impl NetworkAnalyzer {
     async fn get_closing_fees(&self, src_chan_balance: u64, funding_tx: Option<String>) -> Option<(u64, u64)> {
         // Find the transaction where the funding UTXO is used as an input (this means that transaction is the closing tx for this channel)
         // Return an option of a tuple (source_node_fee, dest_node_fee)
-        match self.bitcoind_client.find_input(funding_tx.clone().unwrap()).await {
+        match self.get_bitcoind_client().find_input(funding_tx.clone().unwrap()).await {
             Some(closing_tx) => {
                 let src_bal = src_chan_balance as f64 / 100000000.0;
                 // Determine which node is closing the channel therefore which one is paying the fees
-                if self.bitcoind_client.is_output_value(closing_tx.clone(), src_bal).await {
+                if self.get_bitcoind_client().is_output_value(closing_tx.clone(), src_bal).await {
                     // If the source node's balance is equal to one of the outputs then it is not the one paying the fee
-                    Some((0, (self.bitcoind_client.get_tx_fees(closing_tx.clone()).await * 100000000.0) as u64))
+                    Some((0, (self.get_bitcoind_client().get_tx_fees(closing_tx.clone()).await * 100000000.0) as u64))
                 } else {
                     // If the source node's balance is not equal to any one of the outputs then it is paying the fee
-                    Some(((self.bitcoind_client.get_tx_fees(closing_tx.clone()).await * 100000000.0).round() as u64, 0))
+                    Some(((self.get_bitcoind_client().get_tx_fees(closing_tx.clone()).await * 100000000.0).round() as u64, 0))
                 }
             },
             None => {
@@ -565,6 +1210,282 @@ impl NetworkAnalyzer {
         return None;
     }
 
+    /*
+     * Check that no funds were created or lost by the results and that the channel balances match what the nodes report
+     * Each invariant that does not hold is recorded with the event that was just processed
+     */
+    async fn check_invariants(&mut self, event: &SimResultsEvent, sensei_controller: &SenseiController) {
+        let time = match event.sim_time {
+            Some(t) => t,
+            None => self.get_current_sim_time()
+        };
+
+        // Every msat is either in a node balance, was paid as an on-chain fee, is waiting to be swept or is part of a payment that is being recorded
+        let balances = self.get_total_supply() as i64 * 1000 + self.off_chain_residue_msat.values().sum::<u64>() as i64;
+        let in_flight: i64 = self.in_flight.values().sum();
+        let accounted = balances + self.fees_paid as i64 * 1000 + self.locked * 1000 + in_flight;
+        if accounted != self.initial_supply as i64 * 1000 {
+            let description = format!("balances {} + fees {} + locked {} + in flight {} = {} msat, expected {} msat",
+                balances, self.fees_paid * 1000, self.locked * 1000, in_flight, accounted, self.initial_supply * 1000);
+            self.add_invariant_violation(time, Invariant::SupplyConservation, event, description);
+        }
+
+        // The channels on the paths of payments that are still being recorded and of held payments have HTLCs in them, so they are not compared
+        let mut busy_channels: Vec<u64> = Vec::new();
+        for t in self.results.transactions.txs.iter().filter(|t| t.transaction.id.as_ref().map_or(false, |id| self.in_flight.contains_key(id))) {
+            for p in &t.parts {
+                busy_channels.extend(p.path.iter().filter_map(|hop| self.get_channel_id_by_short_id(hop.short_channel_id)));
+            }
+        }
+        for h in self.results.jamming.htlcs.iter().filter(|h| h.released.is_none()) {
+            busy_channels.extend(h.channels.iter().copied());
+        }
+
+        let open_channels = match self.results.channels.open_channels.keys().copied().max() {
+            Some(k) => self.results.channels.open_channels.get(&k).unwrap().clone(),
+            None => Vec::new()
+        };
+        let mut id_map: HashMap<String, u64> = HashMap::new();
+        let mut funding_map: HashMap<String, String> = HashMap::new();
+        for c in &open_channels {
+            match (&c.run_time_id, &c.funding_tx) {
+                (Some(run_time_id), Some(funding_tx)) => {
+                    id_map.insert(run_time_id.clone(), c.id);
+                    funding_map.insert(run_time_id.clone(), funding_tx.clone());
+                },
+                _ => {}
+            }
+        }
+
+        let mut nodes: Vec<String> = self.results.status.nodes.keys().cloned().collect();
+        nodes.sort();
+        for node in nodes {
+            // A node that is offline can not be queried
+            if !self.results.get_node_status(time, &node) {
+                continue;
+            }
+            let status = match sensei_controller.get_node_status(&node, Some(&id_map), Some(&funding_map)).await {
+                Some(s) => s,
+                None => continue
+            };
+            for nc in status.channels.iter().filter(|nc| nc.is_outbound && !busy_channels.contains(&nc.id)) {
+                match open_channels.iter().find(|c| c.id == nc.id) {
+                    Some(c) => {
+                        // The amounts of pending HTLCs are taken out of both sides of the channel by the node
+                        // So a channel that adds up to less than it does in the results has HTLCs that have not been resolved yet
+                        let (src_balance, dest_balance) = self.get_channel_balance_msat(c);
+                        if nc.outbound_capacity + nc.inbound_capacity < src_balance + dest_balance {
+                            continue;
+                        }
+                        if src_balance != nc.outbound_capacity || dest_balance != nc.inbound_capacity {
+                            let description = format!("channel {} is {} -> {} msat in the results, {} reports {} -> {} msat",
+                                c.id, src_balance, dest_balance, node, nc.outbound_capacity, nc.inbound_capacity);
+                            self.add_invariant_violation(time, Invariant::ChannelBalance, event, description);
+                        }
+                    },
+                    None => {}
+                }
+            }
+        }
+    }
+
+    /*
+     * Stop counting a payment as in flight once the sender was charged and the parts that were recorded add up to the amount
+     * Whatever is left over did not get added to any node, so it shows up as a supply violation
+     */
+    fn settle_payment(&mut self, payment_id: &String) {
+        match self.results.transactions.txs.iter().find(|t| t.transaction.id.as_ref() == Some(payment_id)) {
+            Some(t) => {
+                let received_msat: u64 = t.parts.iter().map(|p| p.path.last().map(|hop| hop.amount_msat).unwrap_or(0)).sum();
+                if matches!(t.transaction.status, SimTransactionStatus::SUCCESSFUL) && received_msat >= t.transaction.amount_sats * 1000 {
+                    self.in_flight.remove(payment_id);
+                }
+            },
+            None => {}
+        }
+    }
+
+    /*
+     * Record an invariant that did not hold
+     */
+    fn add_invariant_violation(&mut self, time: u64, invariant: Invariant, event: &SimResultsEvent, description: String) {
+        println!("[=== NetworkAnalyzer === {}] {:?} invariant violated after {}: {}", crate::get_current_time(), invariant, event.event, description);
+        self.results.invariant_violations.push(InvariantViolation {
+            time: time,
+            invariant: invariant,
+            event: event.clone(),
+            description: description
+        });
+    }
+
+    /*
+     * Get the sum of the latest on-chain and off-chain balances of all the nodes
+     */
+    fn get_total_supply(&self) -> u64 {
+        let latest = |hm: &HashMap<u64, u64>| hm.keys().copied().max().map(|k| hm[&k]).unwrap_or(0);
+        self.results.balance.on_chain.values().map(latest).sum::<u64>() + self.results.balance.off_chain.values().map(latest).sum::<u64>()
+    }
+
+    /*
+     * Compute the graph metrics of the network at each event time from the nodes that were online and the channels that were not partitioned at that time
+     */
+    fn compute_network_metrics(&mut self) {
+        let mut nodes: Vec<String> = self.results.status.nodes.keys().cloned().collect();
+        nodes.sort();
+        for time in self.results.event_times.clone() {
+            let online: Vec<String> = nodes.iter().filter(|n| self.results.get_node_status(time, n)).cloned().collect();
+            let channels = self.results.get_usable_channels(time);
+            let metrics = network_metrics::compute_metrics(&online, &channels);
+            self.results.metrics.insert(time, metrics);
+        }
+    }
+
+    /*
+     * Add a forward that was found in a payment path to the results, using the fee from a forward the node already reported if there is one
+     */
+    fn add_forward(&mut self, mut forward: Forward) {
+        let mut pending_index: Option<usize> = None;
+        for (i, (node, pending)) in self.pending_forwards.iter().enumerate() {
+            let channel_in = pending.prev_channel_id.as_ref().and_then(|id| self.get_channel_id_by_run_time_id(id));
+            let channel_out = pending.next_channel_id.as_ref().and_then(|id| self.get_channel_id_by_run_time_id(id));
+            if node == &forward.node && channel_in == forward.channel_in && channel_out == forward.channel_out {
+                pending_index = Some(i);
+                break;
+            }
+        }
+        if let Some(i) = pending_index {
+            let (_, pending) = self.pending_forwards.remove(i);
+            forward.fee_msat = pending.fee_earned_msat;
+        }
+
+        self.results.forwarding.forwards.push(forward);
+    }
+
+    /*
+     * Get the simulation defined channel id of a channel from its short channel id
+     */
+    fn get_channel_id_by_short_id(&self, short_id: u64) -> Option<u64> {
+        self.find_channel(|c| c.short_id == Some(short_id))
+    }
+
+    /*
+     * Get whether a payment id belongs to a payment that a jamming attacker sent
+     */
+    fn is_jamming_payment(&self, payment_id: &String) -> bool {
+        self.results.jamming.htlcs.iter().any(|h| &h.payment_id == payment_id)
+    }
+
+    /*
+     * Get the simulation defined channel id of a channel from its node implementation channel id
+     */
+    fn get_channel_id_by_run_time_id(&self, run_time_id: &String) -> Option<u64> {
+        self.find_channel(|c| c.run_time_id.as_ref() == Some(run_time_id))
+    }
+
+    /*
+     * Find a channel in the latest open channel list, or in the closed channels if it has been closed, and return its simulation defined id
+     */
+    fn find_channel<F: Fn(&SimChannel) -> bool>(&self, matches: F) -> Option<u64> {
+        let latest_open = self.results.channels.open_channels.keys().copied().max().and_then(|k| self.results.channels.open_channels.get(&k));
+        let open = latest_open.into_iter().flatten();
+        let closed = self.results.channels.closed_channels.values().flatten();
+        open.chain(closed).find(|c| matches(c)).map(|c| c.id)
+    }
+
+    /*
+     * Get whether a node implementation channel id belongs to a channel that was force closed
+     */
+    fn is_force_close(&self, id: &String) -> bool {
+        self.results.force_closes.iter().any(|f| f.run_time_id.as_ref() == Some(id))
+    }
+
+    /*
+     * Add a sim time that something happened at, the times stay sorted since some results (e.g. probes) are reported after later events
+     */
+    fn add_event_time(&mut self, time: u64) {
+        match self.results.event_times.binary_search(&time) {
+            Ok(_) => {},
+            Err(i) => self.results.event_times.insert(i, time)
+        }
+    }
+
+    /*
+     * Add an output that a node can sweep from a force close to the node's on-chain balance at the current sim time
+     */
+    fn add_sweep(&mut self, index: usize, node: &String, sweep: &SimSweep) {
+        let time = self.get_current_sim_time();
+        self.add_event_time(time);
+        self.update_on_chain_balance(time, node, sweep.amount_sats, true);
+        self.locked = self.locked - sweep.amount_sats as i64;
+
+        let force_close = &mut self.results.force_closes[index];
+        // The justice transaction spends the revoked commitment, so the counterparty's output comes from a different transaction
+        let penalty = force_close.close_type == CloseType::RevokedState && &force_close.counterparty == node &&
+            force_close.closing_tx.as_ref() != Some(&sweep.spent_txid);
+        if &force_close.initiator == node && sweep.to_self_delay.is_some() {
+            force_close.to_self_delay = sweep.to_self_delay;
+        }
+        force_close.sweeps.push(Sweep {
+            time: time,
+            node: node.clone(),
+            amount_sats: sweep.amount_sats,
+            delayed: sweep.to_self_delay.is_some(),
+            penalty: penalty
+        });
+    }
+
+    /*
+     * Add an on-chain payment that was broadcast, the sender pays the amount and the fee and the amount only stays in the simulation if it was sent to a node
+     */
+    pub(crate) fn add_on_chain_tx(&mut self, event: &SimResultsEvent, tx: &SimOnChainTx, fee: u64) {
+        let time = event.sim_time.unwrap();
+        self.update_on_chain_balance(time, &tx.src_node, tx.amount_sats + fee, false);
+        self.fees_paid = self.fees_paid + fee;
+        let external = !self.results.balance.on_chain.contains_key(&tx.dest);
+        if external {
+            // The funds left the simulation
+            match self.initial_supply.checked_sub(tx.amount_sats) {
+                Some(supply) => self.initial_supply = supply,
+                None => {
+                    let description = format!("{} sats were sent out of the simulation but the total balance is only {} sats", tx.amount_sats, self.initial_supply);
+                    self.add_invariant_violation(time, Invariant::SupplyConservation, event, description);
+                    self.initial_supply = 0;
+                }
+            }
+        } else {
+            self.update_on_chain_balance(time, &tx.dest, tx.amount_sats, true);
+        }
+        self.results.on_chain_txs.push(OnChainTx {
+            time: time,
+            src_node: Some(tx.src_node.clone()),
+            dest: tx.dest.clone(),
+            external: external,
+            amount_sats: tx.amount_sats,
+            fee_sats: fee,
+            txid: tx.txid.clone()
+        });
+    }
+
+    /*
+     * Get the current sim time from the control of the simulation, it does not move while the simulation is paused
+     */
+    fn get_current_sim_time(&self) -> u64 {
+        self.control.get_time()
+    }
+
+    /*
+     * Get the routing policy that was defined for a channel, for the source node (src=true) or the destination node
+     */
+    fn get_channel_policy(channels: &Vec<SimChannel>, id: u64, src: bool) -> Option<SimChannelPolicy> {
+        for c in channels {
+            if c.id == id {
+                return if src { c.src_policy.clone() } else { c.dest_policy.clone() };
+            }
+        }
+
+        None
+    }
+
     /* 
      * Get the destination node name for a channel
      */
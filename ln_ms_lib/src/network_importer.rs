@@ -0,0 +1,147 @@
+// External Modules
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+
+// Standard Modules
+use std::collections::HashMap;
+use std::fs;
+
+/*
+ * These functions parse the output of LN node commands (lncli describegraph, fwdinghistory, listpayments)
+ * so that the network and transactions can be recreated in a simulation.
+ */
+
+/*
+ * A node found in a network graph file
+ */
+#[derive(Debug, Clone)]
+pub struct ImportedNode {
+    pub pub_key: String,
+    pub alias: String
+}
+
+/*
+ * A channel found in a network graph file
+ */
+#[derive(Debug, Clone)]
+pub struct ImportedChannel {
+    pub chan_id: String,
+    pub node1_pub: String,
+    pub node2_pub: String,
+    pub capacity: u64
+}
+
+/*
+ * A payment found in a forwarding history or payments file
+ */
+#[derive(Debug, Clone)]
+pub struct ImportedTransaction {
+    pub time: u64, // seconds since the first transaction in the file
+    pub src_pub: String,
+    pub dest_pub: String,
+    pub amount_sats: u64
+}
+
+/*
+ * Parse a describegraph file and return the nodes and channels
+ */
+pub fn parse_graph(filename: &String) -> Result<(Vec<ImportedNode>, Vec<ImportedChannel>)> {
+    let contents = fs::read_to_string(filename)?;
+    let graph: Value = serde_json::from_str(&contents)?;
+
+    let mut nodes: Vec<ImportedNode> = Vec::new();
+    for n in graph["nodes"].as_array().ok_or(anyhow!("no nodes found in {}", filename))? {
+        nodes.push(ImportedNode {
+            pub_key: get_string(n, "pub_key")?,
+            alias: get_string(n, "alias").unwrap_or(String::from(""))
+        });
+    }
+
+    let mut channels: Vec<ImportedChannel> = Vec::new();
+    for e in graph["edges"].as_array().ok_or(anyhow!("no edges found in {}", filename))? {
+        channels.push(ImportedChannel {
+            chan_id: get_string(e, "channel_id")?,
+            node1_pub: get_string(e, "node1_pub")?,
+            node2_pub: get_string(e, "node2_pub")?,
+            capacity: get_u64(e, "capacity")?
+        });
+    }
+
+    Ok((nodes, channels))
+}
+
+/*
+ * Parse a fwdinghistory or listpayments file and return the payments
+ * The channels map (channel id to the pubkeys of both nodes) is used to find the nodes involved in each payment
+ */
+pub fn parse_transactions(filename: &String, channels: &HashMap<String, (String, String)>) -> Result<Vec<ImportedTransaction>> {
+    let contents = fs::read_to_string(filename)?;
+    let file: Value = serde_json::from_str(&contents)?;
+    let mut txs: Vec<ImportedTransaction> = Vec::new();
+
+    if let Some(events) = file["forwarding_events"].as_array() {
+        // Each forward is a payment from the peer on the incoming channel to the peer on the outgoing channel
+        for f in events {
+            let chan_in = channels.get(&get_string(f, "chan_id_in")?).ok_or(anyhow!("incoming channel not found in the imported network"))?;
+            let chan_out = channels.get(&get_string(f, "chan_id_out")?).ok_or(anyhow!("outgoing channel not found in the imported network"))?;
+            let forwarding_node = if chan_in.0 == chan_out.0 || chan_in.0 == chan_out.1 { &chan_in.0 } else { &chan_in.1 };
+            let src = if &chan_in.0 == forwarding_node { &chan_in.1 } else { &chan_in.0 };
+            let dest = if &chan_out.0 == forwarding_node { &chan_out.1 } else { &chan_out.0 };
+            txs.push(ImportedTransaction {
+                time: get_u64(f, "timestamp")?,
+                src_pub: src.clone(),
+                dest_pub: dest.clone(),
+                amount_sats: get_u64(f, "amt_out")?
+            });
+        }
+    } else if let Some(payments) = file["payments"].as_array() {
+        // The sender is the node on the first hop channel that is not the first hop, the receiver is the last hop
+        for p in payments {
+            let hops = match p["htlcs"][0]["route"]["hops"].as_array() {
+                Some(h) if !h.is_empty() => h,
+                _ => continue
+            };
+            let first_hop = &hops[0];
+            let first_chan = channels.get(&get_string(first_hop, "chan_id")?).ok_or(anyhow!("payment channel not found in the imported network"))?;
+            let first_pub = get_string(first_hop, "pub_key")?;
+            let src = if first_chan.0 == first_pub { &first_chan.1 } else { &first_chan.0 };
+            txs.push(ImportedTransaction {
+                time: get_u64(p, "creation_date")?,
+                src_pub: src.clone(),
+                dest_pub: get_string(hops.last().unwrap(), "pub_key")?,
+                amount_sats: get_u64(p, "value_sat")?
+            });
+        }
+    } else {
+        return Err(anyhow!("{} is not a fwdinghistory or listpayments file", filename));
+    }
+
+    // Make the times relative to the first transaction
+    let start = txs.iter().map(|t| t.time).min().unwrap_or(0);
+    for t in &mut txs {
+        t.time = t.time - start;
+    }
+
+    Ok(txs)
+}
+
+/*
+ * Get a string field from a json object
+ */
+fn get_string(value: &Value, field: &str) -> Result<String> {
+    match value[field].as_str() {
+        Some(s) => Ok(String::from(s)),
+        None => Err(anyhow!("missing field: {}", field))
+    }
+}
+
+/*
+ * Get a number field from a json object, lncli formats most numbers as strings
+ */
+fn get_u64(value: &Value, field: &str) -> Result<u64> {
+    match &value[field] {
+        Value::String(s) => Ok(s.parse()?),
+        Value::Number(n) => n.as_u64().ok_or(anyhow!("invalid number for field: {}", field)),
+        _ => Err(anyhow!("missing field: {}", field))
+    }
+}
@@ -0,0 +1,302 @@
+// Project Modules
+use crate::sim_channel::SimChannel;
+
+// External Modules
+use serde::{Serialize, Deserialize};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
+
+// Standard Modules
+use std::collections::{HashMap, VecDeque};
+
+/*
+ * Graph metrics of the network at a point in time
+ * Only the nodes that are online are part of the graph, a channel with an offline node can not route payments
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct NetworkMetrics {
+    pub degree: HashMap<String, u64>, // key=node name, value=number of channels with online nodes
+    pub betweenness: HashMap<String, f64>, // key=node name, value=number of shortest paths between other nodes that go through this node
+    pub components: u64, // the number of connected components
+    pub largest_component: u64, // the number of nodes in the largest connected component
+    pub diameter: u64, // the longest shortest path (in hops) between any two connected nodes
+    pub capacity: CapacityDistribution, // the capacity of the channels between online nodes
+    pub balance_ratio: HashMap<u64, f64> // key=simulation defined channel id, value=src balance / capacity (0.5 is perfectly balanced)
+}
+
+/*
+ * Summary of the channel capacities in the network
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct CapacityDistribution {
+    pub channels: u64,
+    pub total_sats: u64,
+    pub min_sats: u64,
+    pub max_sats: u64,
+    pub mean_sats: u64,
+    pub median_sats: u64
+}
+
+/*
+ * Compute the metrics of the network made up of the online nodes and the channels between them
+ */
+pub fn compute_metrics(online_nodes: &Vec<String>, channels: &Vec<SimChannel>) -> NetworkMetrics {
+    let index: HashMap<&String, usize> = online_nodes.iter().enumerate().map(|(i, n)| (n, i)).collect();
+    let mut adjacency: Vec<Vec<usize>> = vec![Vec::new(); online_nodes.len()];
+    let mut capacities: Vec<u64> = Vec::new();
+    let mut balance_ratio: HashMap<u64, f64> = HashMap::new();
+    for c in channels {
+        let (src, dest) = match (index.get(&c.src_node), index.get(&c.dest_node)) {
+            (Some(s), Some(d)) => (*s, *d),
+            _ => continue
+        };
+        adjacency[src].push(dest);
+        adjacency[dest].push(src);
+
+        let capacity = c.src_balance_sats + c.dest_balance_sats;
+        capacities.push(capacity);
+        if capacity > 0 {
+            balance_ratio.insert(c.id, c.src_balance_sats as f64 / capacity as f64);
+        }
+    }
+
+    let (betweenness, diameter) = betweenness_and_diameter(&adjacency);
+    let component_sizes = component_sizes(&adjacency);
+
+    NetworkMetrics {
+        degree: online_nodes.iter().enumerate().map(|(i, n)| (n.clone(), adjacency[i].len() as u64)).collect(),
+        betweenness: online_nodes.iter().enumerate().map(|(i, n)| (n.clone(), betweenness[i])).collect(),
+        components: component_sizes.len() as u64,
+        largest_component: component_sizes.iter().copied().max().unwrap_or(0),
+        diameter: diameter,
+        capacity: capacity_distribution(capacities),
+        balance_ratio: balance_ratio
+    }
+}
+
+/*
+ * Brandes' algorithm: a breadth first search from every node counts the shortest paths through each node
+ * The longest distance found by the searches is the diameter
+ */
+fn betweenness_and_diameter(adjacency: &Vec<Vec<usize>>) -> (Vec<f64>, u64) {
+    let n = adjacency.len();
+    let mut betweenness = vec![0.0; n];
+    let mut diameter: u64 = 0;
+    for s in 0..n {
+        let mut stack: Vec<usize> = Vec::new();
+        let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); n];
+        let mut paths: Vec<f64> = vec![0.0; n];
+        let mut distance: Vec<i64> = vec![-1; n];
+        paths[s] = 1.0;
+        distance[s] = 0;
+        let mut queue: VecDeque<usize> = VecDeque::new();
+        queue.push_back(s);
+        while let Some(v) = queue.pop_front() {
+            stack.push(v);
+            diameter = diameter.max(distance[v] as u64);
+            for &w in &adjacency[v] {
+                if distance[w] < 0 {
+                    distance[w] = distance[v] + 1;
+                    queue.push_back(w);
+                }
+                if distance[w] == distance[v] + 1 {
+                    paths[w] += paths[v];
+                    predecessors[w].push(v);
+                }
+            }
+        }
+
+        let mut dependency: Vec<f64> = vec![0.0; n];
+        while let Some(w) = stack.pop() {
+            for &v in &predecessors[w] {
+                dependency[v] += paths[v] / paths[w] * (1.0 + dependency[w]);
+            }
+            if w != s {
+                betweenness[w] += dependency[w];
+            }
+        }
+    }
+
+    // Each path was counted from both ends
+    (betweenness.iter().map(|b| b / 2.0).collect(), diameter)
+}
+
+/*
+ * Get the number of nodes in each connected component
+ */
+fn component_sizes(adjacency: &Vec<Vec<usize>>) -> Vec<u64> {
+    let mut visited = vec![false; adjacency.len()];
+    let mut sizes: Vec<u64> = Vec::new();
+    for start in 0..adjacency.len() {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let mut size: u64 = 0;
+        let mut stack = vec![start];
+        while let Some(v) = stack.pop() {
+            size += 1;
+            for &w in &adjacency[v] {
+                if !visited[w] {
+                    visited[w] = true;
+                    stack.push(w);
+                }
+            }
+        }
+        sizes.push(size);
+    }
+
+    sizes
+}
+
+/*
+ * Summarize a list of channel capacities
+ */
+fn capacity_distribution(mut capacities: Vec<u64>) -> CapacityDistribution {
+    if capacities.is_empty() {
+        return CapacityDistribution::default();
+    }
+
+    capacities.sort();
+    let total: u64 = capacities.iter().sum();
+    let mid = capacities.len() / 2;
+    let median = if capacities.len() % 2 == 0 { (capacities[mid - 1] + capacities[mid]) / 2 } else { capacities[mid] };
+    CapacityDistribution {
+        channels: capacities.len() as u64,
+        total_sats: total,
+        min_sats: capacities[0],
+        max_sats: capacities[capacities.len() - 1],
+        mean_sats: total / capacities.len() as u64,
+        median_sats: median
+    }
+}
+
+/*
+ * The maximum amount that can be sent between pairs of nodes at a point in time, if a payment can be split across any number of paths (MPP)
+ * Each channel can send its src balance from src to dest and its dest balance from dest to src
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ReachabilityMatrix {
+    pub time: u64,
+    pub max_flow: HashMap<String, HashMap<String, u64>> // key=source node, value=(key=destination node, value=max sendable sats)
+}
+
+impl ReachabilityMatrix {
+    /*
+     * Get the maximum amount that the source can send to the destination, None if the pair was not analyzed
+     */
+    pub fn get_max_flow(&self, src: &String, dest: &String) -> Option<u64> {
+        self.max_flow.get(src).and_then(|m| m.get(dest)).copied()
+    }
+
+    /*
+     * Get whether the source can send any amount to the destination
+     */
+    pub fn is_reachable(&self, src: &String, dest: &String) -> bool {
+        self.get_max_flow(src, dest).unwrap_or(0) > 0
+    }
+
+    /*
+     * Get the number of node pairs that were analyzed
+     */
+    pub fn get_num_pairs(&self) -> u64 {
+        self.max_flow.values().map(|m| m.len() as u64).sum()
+    }
+
+    /*
+     * Get the probability that a payment of this amount between a random pair of the analyzed nodes succeeds
+     * A payment succeeds if the max flow between the pair is at least the amount
+     */
+    pub fn get_success_probability(&self, amount_sats: u64) -> f64 {
+        let pairs = self.get_num_pairs();
+        if pairs == 0 {
+            return 0.0;
+        }
+        let successes = self.max_flow.values().flat_map(|m| m.values()).filter(|f| **f >= amount_sats).count();
+        successes as f64 / pairs as f64
+    }
+}
+
+/*
+ * Compute the max flow between node pairs over the liquidity of the channels between the online nodes
+ * If sample_pairs is set only that many pairs (picked at random with the seed) are analyzed, otherwise every ordered pair is
+ */
+pub fn compute_reachability(time: u64, online_nodes: &Vec<String>, channels: &Vec<SimChannel>, sample_pairs: Option<usize>, seed: u64) -> ReachabilityMatrix {
+    let n = online_nodes.len();
+    let index: HashMap<&String, usize> = online_nodes.iter().enumerate().map(|(i, n)| (n, i)).collect();
+
+    // Directed liquidity between each pair of nodes, parallel channels are added together
+    let mut liquidity: Vec<HashMap<usize, u64>> = vec![HashMap::new(); n];
+    for c in channels {
+        let (src, dest) = match (index.get(&c.src_node), index.get(&c.dest_node)) {
+            (Some(s), Some(d)) => (*s, *d),
+            _ => continue
+        };
+        *liquidity[src].entry(dest).or_insert(0) += c.src_balance_sats;
+        *liquidity[dest].entry(src).or_insert(0) += c.dest_balance_sats;
+    }
+
+    let total_pairs = n * n.saturating_sub(1);
+    let pairs: Vec<(usize, usize)> = match sample_pairs {
+        Some(count) if count < total_pairs => {
+            let mut rng = StdRng::seed_from_u64(seed);
+            sample(&mut rng, total_pairs, count).into_iter().map(|p| {
+                let src = p / (n - 1);
+                let dest = p % (n - 1);
+                (src, if dest >= src { dest + 1 } else { dest })
+            }).collect()
+        },
+        _ => (0..n).flat_map(|s| (0..n).filter(move |d| *d != s).map(move |d| (s, d))).collect()
+    };
+
+    let mut matrix = ReachabilityMatrix { time: time, max_flow: HashMap::new() };
+    for (src, dest) in pairs {
+        let flow = max_flow(&liquidity, src, dest);
+        matrix.max_flow.entry(online_nodes[src].clone()).or_insert(HashMap::new()).insert(online_nodes[dest].clone(), flow);
+    }
+
+    matrix
+}
+
+/*
+ * Edmonds-Karp: keep sending along the shortest path that still has liquidity until there are no paths left
+ */
+fn max_flow(liquidity: &Vec<HashMap<usize, u64>>, src: usize, dest: usize) -> u64 {
+    let mut residual = liquidity.clone();
+    let mut flow: u64 = 0;
+    loop {
+        let mut previous: Vec<Option<usize>> = vec![None; residual.len()];
+        let mut queue: VecDeque<usize> = VecDeque::new();
+        queue.push_back(src);
+        while let Some(v) = queue.pop_front() {
+            if v == dest {
+                break;
+            }
+            for (&w, &capacity) in &residual[v] {
+                if capacity > 0 && w != src && previous[w].is_none() {
+                    previous[w] = Some(v);
+                    queue.push_back(w);
+                }
+            }
+        }
+        if previous[dest].is_none() {
+            return flow;
+        }
+
+        // Find the smallest liquidity along the path and move it
+        let mut amount = u64::MAX;
+        let mut v = dest;
+        while let Some(u) = previous[v] {
+            amount = amount.min(residual[u][&v]);
+            v = u;
+        }
+        let mut v = dest;
+        while let Some(u) = previous[v] {
+            *residual[u].get_mut(&v).unwrap() -= amount;
+            *residual[v].entry(u).or_insert(0) += amount;
+            v = u;
+        }
+        flow += amount;
+    }
+}
@@ -1,6 +1,9 @@
 // Standard Modules
 use std::process::Command;
 
+// External Modules
+use anyhow::{Result, anyhow};
+
 /*
  * These functions controll the nigiri instance that is running bitcoind.
  * TODO: Is there a better way to control the underlying bitcoin blockchain? Instead of running bash commands to control nigiri?
@@ -40,6 +43,26 @@ pub fn mine() {
     .expect("failed to execute mine process");
 }
 
+/*
+ * Mine a number of blocks
+ */
+pub fn mine_blocks(number: u64) {
+    let arg = String::from("nigiri rpc -generate ") + &number.to_string();
+    Command::new("sh")
+    .arg("-c")
+    .arg(&arg)
+    .output()
+    .expect("failed to execute mine process");
+}
+
+/*
+ * Broadcast a raw transaction (hex encoded), returns an error with the reason bitcoind gave if the transaction was rejected
+ */
+pub fn send_raw_transaction(tx_hex: String) -> Result<()> {
+    run_rpc(&(String::from("sendrawtransaction ") + &tx_hex))?;
+    Ok(())
+}
+
 /*
  * Send bitcoin to an address and mine a block
  */ 
@@ -51,4 +74,135 @@ pub fn fund_address(addr: String, amount: u64) {
     .arg(&arg)
     .output()
     .expect("failed to execute fund process");
-}
\ No newline at end of file
+}
+
+/*
+ * Broadcast a number of filler transactions from the nigiri wallet that pay a feerate (sats per vbyte)
+ * The filler transactions pay the wallet back, they only fill the mempool and the blocks so that the fee estimates follow the market feerate
+ * Returns an error if a filler transaction could not be sent, the ones before it are still in the mempool
+ */
+pub fn send_filler_transactions(number: u64, feerate: u64) -> Result<()> {
+    let address = run_rpc("getnewaddress")?;
+    for _ in 0..number {
+        let arg = format!("-named sendtoaddress address={} amount=0.0001 fee_rate={}", address, feerate);
+        run_rpc(&arg)?;
+    }
+
+    Ok(())
+}
+
+/*
+ * Mine a block that only includes the mempool transactions that pay at least a feerate (sats per vbyte), the other transactions keep waiting
+ * A transaction is included if the package with its unconfirmed ancestors pays the feerate, so a child can pay for its parent
+ * Returns the number of transactions that were confirmed and the number that are still waiting
+ */
+pub fn mine_block_above_feerate(feerate: u64) -> (u64, u64) {
+    let mempool: serde_json::Value = serde_json::from_str(&run_rpc("getrawmempool true").unwrap_or_default()).unwrap_or(serde_json::Value::Null);
+    let entries = match mempool.as_object() {
+        Some(e) => e.clone(),
+        None => serde_json::Map::new()
+    };
+
+    // Select the packages that pay the feerate along with all of their ancestors
+    let mut selected: Vec<String> = Vec::new();
+    for (txid, entry) in &entries {
+        let fee = entry["fees"]["ancestor"].as_f64().unwrap_or(0.0) * 100000000.0;
+        let size = entry["ancestorsize"].as_f64().unwrap_or(1.0).max(1.0);
+        if fee / size < feerate as f64 {
+            continue;
+        }
+        let mut stack = vec![txid.clone()];
+        while let Some(id) = stack.pop() {
+            if selected.contains(&id) {
+                continue;
+            }
+            if let Some(parents) = entries.get(&id).and_then(|e| e["depends"].as_array()) {
+                stack.extend(parents.iter().filter_map(|p| p.as_str().map(String::from)));
+            }
+            selected.push(id);
+        }
+    }
+
+    // Parents have fewer ancestors than their children, so this puts them first in the block
+    selected.sort_by_key(|id| entries[id]["ancestorcount"].as_u64().unwrap_or(1));
+    let txids: Vec<String> = selected.iter().map(|id| format!("\"{}\"", id)).collect();
+    let mined = match run_rpc("getnewaddress") {
+        Ok(address) => run_rpc(&format!("generateblock {} '[{}]'", address, txids.join(","))),
+        Err(e) => Err(e)
+    };
+    match mined {
+        Ok(_) => (selected.len() as u64, (entries.len() - selected.len()) as u64),
+        Err(e) => {
+            println!("could not mine block: {:?}", e);
+            (0, entries.len() as u64)
+        }
+    }
+}
+
+/*
+ * Run a bitcoind rpc command with nigiri and return what it printed, returns an error with what bitcoind printed if the command failed
+ */
+fn run_rpc(command: &str) -> Result<String> {
+    let arg = String::from("nigiri rpc ") + command;
+    let output = Command::new("sh")
+    .arg("-c")
+    .arg(&arg)
+    .output()
+    .expect("failed to execute rpc process");
+    if !output.status.success() {
+        return Err(anyhow!("{} failed: {}", command.split(' ').next().unwrap_or(command), String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/*
+ * Replace the last blocks of the chain with a longer chain of empty blocks, the transactions in the replaced blocks go back to the mempool
+ * Returns the txids of the transactions that were unconfirmed (not the coinbases), None if the chain is not that long or the blocks could not be replaced
+ */
+pub fn reorg(depth: u64) -> Option<Vec<String>> {
+    let height: u64 = run_rpc("getblockcount").unwrap_or_default().parse().unwrap_or(0);
+    if depth == 0 || depth > height {
+        return None;
+    }
+
+    // Get the transactions in the blocks that are replaced
+    let mut txids: Vec<String> = Vec::new();
+    let first_hash = run_rpc(&format!("getblockhash {}", height - depth + 1)).ok()?;
+    for h in (height - depth + 1)..=height {
+        let hash = run_rpc(&format!("getblockhash {}", h)).ok()?;
+        let block: serde_json::Value = serde_json::from_str(&run_rpc(&format!("getblock {}", hash)).unwrap_or_default()).unwrap_or(serde_json::Value::Null);
+        match block["tx"].as_array() {
+            Some(txs) => txids.extend(txs.iter().skip(1).filter_map(|t| t.as_str().map(String::from))),
+            None => {}
+        }
+    }
+
+    // Invalidate the first replaced block and mine a longer chain without the transactions
+    run_rpc(&format!("invalidateblock {}", first_hash)).ok()?;
+    let address = run_rpc("getnewaddress").ok()?;
+    for _ in 0..(depth + 1) {
+        run_rpc(&format!("generateblock {} '[]'", address)).ok()?;
+    }
+
+    Some(txids)
+}
+
+/*
+ * Get the number of confirmations of a transaction, 0 if it is in the mempool or not found
+ */
+pub fn get_confirmations(txid: &String) -> u64 {
+    let tx: serde_json::Value = serde_json::from_str(&run_rpc(&format!("getrawtransaction {} true", txid)).unwrap_or_default()).unwrap_or(serde_json::Value::Null);
+    tx["confirmations"].as_u64().unwrap_or(0)
+}
+
+/*
+ * Get the txids of the transactions whose outputs a transaction spends, empty if the transaction is not found
+ */
+pub fn get_input_txids(txid: &String) -> Vec<String> {
+    let tx: serde_json::Value = serde_json::from_str(&run_rpc(&format!("getrawtransaction {} true", txid)).unwrap_or_default()).unwrap_or(serde_json::Value::Null);
+    match tx["vin"].as_array() {
+        Some(inputs) => inputs.iter().filter_map(|i| i["txid"].as_str().map(String::from)).collect(),
+        None => Vec::new()
+    }
+}
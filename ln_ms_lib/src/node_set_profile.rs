@@ -0,0 +1,64 @@
+// External Modules
+use serde::{Serialize, Deserialize};
+use anyhow::{Result, anyhow};
+
+// Standard Modules
+use std::fs;
+
+// The profiles that are built into the library, so they are found no matter where the simulation is run from
+const PROFILES: [(&str, &str); 1] = [
+    ("default", include_str!("node_set_profiles/default.json"))
+];
+
+/*
+ * This struct represents a profile that describes a set of nodes with pre-defined properties
+ * The built in profiles are the json files in the node_set_profiles directory
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NodeSetProfile {
+    pub initial_on_chain: String, // range of on-chain balances for each node in the set, ex: "50000-100000"
+    pub number_channels: String, // range of the number of channels each node in the set will open, ex: "1-10"
+    pub outbound_percentage: u64, // percentage of each channel balance that belongs to the node that opened it
+    pub routing_fee: String // routing fee policy for the channels of each node in the set (empty uses the node defaults)
+}
+
+impl NodeSetProfile {
+    /*
+     * Load a built in profile by name, any other name is the path of a profile json file (relative to the working directory)
+     */
+    pub fn load(name: &String) -> Result<NodeSetProfile> {
+        let contents = match PROFILES.iter().find(|(n, _)| n == name) {
+            Some((_, p)) => String::from(*p),
+            None => fs::read_to_string(name).map_err(|e| anyhow!("profile {} is not built in and could not be read: {}", name, e))?
+        };
+        let profile: NodeSetProfile = serde_json::from_str(&contents)?;
+        Ok(profile)
+    }
+
+    /*
+     * Get the minimum and maximum on-chain balance for a node in this set
+     */
+    pub fn get_initial_on_chain_range(&self) -> Result<(u64, u64)> {
+        NodeSetProfile::parse_range(&self.initial_on_chain)
+    }
+
+    /*
+     * Parse a range string formatted as "min-max" or a single value
+     */
+    fn parse_range(range: &String) -> Result<(u64, u64)> {
+        match range.split_once('-') {
+            Some((min, max)) => {
+                let min_val: u64 = min.trim().parse()?;
+                let max_val: u64 = max.trim().parse()?;
+                if min_val > max_val {
+                    return Err(anyhow!("invalid range: {}", range));
+                }
+                Ok((min_val, max_val))
+            },
+            None => {
+                let val: u64 = range.trim().parse()?;
+                Ok((val, val))
+            }
+        }
+    }
+}
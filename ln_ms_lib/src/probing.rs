@@ -0,0 +1,63 @@
+// Project Modules
+use crate::sim_event::{SimulationEvent, SimProbe};
+
+// External Modules
+use serde::{Serialize, Deserialize};
+
+/*
+ * The configuration of a probing agent that infers channel balances by sending probes that must fail
+ * The prober needs a channel to one end of each probed channel, the balance of that end is probed
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProbingConfig {
+    pub prober: String, // the node that sends the probes
+    pub channels: Vec<u64>, // the simulation defined ids of the channels to probe
+    pub interval: u64, // the number of seconds between probing every channel again
+    pub precision: u64, // the binary search stops once the balance is known within this many sats
+    pub start: u64, // the first second of the simulation that the agent probes
+    pub end: u64 // the last second of the simulation that the agent probes
+}
+
+impl ProbingConfig {
+    /*
+     * Get the probe events that this agent creates and the simulation time of each one
+     */
+    pub fn generate_events(&self) -> Vec<(u64, SimulationEvent)> {
+        let mut events: Vec<(u64, SimulationEvent)> = Vec::new();
+        if self.interval == 0 || self.end < self.start {
+            return events;
+        }
+
+        for time in (self.start..=self.end).step_by(self.interval as usize) {
+            for id in &self.channels {
+                let probe = SimProbe {
+                    prober: self.prober.clone(),
+                    channel_id: *id,
+                    precision_sats: self.precision,
+                    result: None
+                };
+                events.push((time, SimulationEvent::ProbeEvent(probe)));
+            }
+        }
+
+        events
+    }
+
+    /*
+     * Get a list of problems with the parameters of this agent (empty if it is valid)
+     */
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+        if self.channels.is_empty() {
+            problems.push(String::from("there are no channels to probe"));
+        }
+        if self.interval == 0 || self.precision == 0 {
+            problems.push(String::from("interval and precision must be greater than 0"));
+        }
+        if self.end < self.start {
+            problems.push(format!("end time {} must not be before the start time {}", self.end, self.start));
+        }
+
+        problems
+    }
+}
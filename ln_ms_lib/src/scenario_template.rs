@@ -0,0 +1,630 @@
+// Project Modules
+use crate::LnSimulation;
+use crate::traffic_generator::{TrafficModel, AmountDistribution};
+use crate::topology_generator::TopologyModel;
+use crate::churn_model::ChurnModel;
+use crate::jamming::JammingAttack;
+use crate::fee_market::{FeeMarketConfig, FeeratePoint};
+use crate::trigger::TriggerConfig;
+use crate::sim_channel::SimChannelPolicy;
+
+// External Modules
+use anyhow::{Result, anyhow};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+// Standard Modules
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/*
+ * This struct represents a human-authored scenario file (toml or yaml).
+ * A template is compiled down to the LnSimulation builder calls (create_node, create_channel, create_*_event).
+ *
+ * Templates support:
+ * - variables: a table of values that replace "${name}" anywhere in the file
+ * - include: a list of other template files (relative to this file) whose variables, nodes, channels and events are merged in
+ * - node_sets: a number of nodes created from a profile, named {name}1..{name}N and added to a group called {name}
+ * - topologies: a random network of nodes and channels created from a graph model, named and grouped like node sets
+ * - events that repeat "every" n seconds "from" a start time "to" an end time
+ * - amounts that are a range ("1k-10k") sampled for each event and nodes picked at random from a group
+ * - traffic generators that send background payments between nodes or groups
+ * - churn models that stop and start nodes or groups during the simulation
+ * - jamming attacks that hold payments between two attacker nodes
+ * - probing agents that infer channel balances
+ * - an on-chain fee market that follows a scripted feerate schedule or a historical mempool profile
+ * - triggers that create events when a condition is met instead of at a fixed time
+ */
+#[derive(Deserialize, Debug, Clone)]
+pub struct ScenarioTemplate {
+    pub name: String,
+    pub duration: u64,
+    #[serde(default)]
+    pub num_sim_nodes: u64,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub nodes: Vec<NodeTemplate>,
+    #[serde(default)]
+    pub node_sets: Vec<NodeSetTemplate>,
+    #[serde(default)]
+    pub topologies: Vec<TopologyTemplate>,
+    #[serde(default)]
+    pub channels: Vec<ChannelTemplate>,
+    #[serde(default)]
+    pub events: Vec<EventTemplate>,
+    #[serde(default)]
+    pub traffic: Vec<TrafficTemplate>,
+    #[serde(default)]
+    pub churn: Vec<ChurnTemplate>,
+    #[serde(default)]
+    pub jamming: Vec<JammingTemplate>,
+    #[serde(default)]
+    pub probing: Vec<ProbingTemplate>,
+    #[serde(default)]
+    pub fee_market: Option<FeeMarketTemplate>,
+    #[serde(default)]
+    pub triggers: Vec<TriggerConfig>
+}
+
+/*
+ * A single node in a template
+ */
+#[derive(Deserialize, Debug, Clone)]
+pub struct NodeTemplate {
+    pub name: String,
+    #[serde(default)]
+    pub initial_balance: Option<SatsValue>,
+    #[serde(default = "default_running")]
+    pub running: bool,
+    #[serde(default)]
+    pub groups: Vec<String> // groups that this node can be picked from by events
+}
+
+/*
+ * A set of nodes created from a node set profile
+ */
+#[derive(Deserialize, Debug, Clone)]
+pub struct NodeSetTemplate {
+    pub name: String,
+    pub count: u64,
+    #[serde(default = "default_profile")]
+    pub profile: String
+}
+
+/*
+ * A random network of nodes and channels created from a graph model
+ */
+#[derive(Deserialize, Debug, Clone)]
+pub struct TopologyTemplate {
+    pub name: String,
+    pub count: u64,
+    #[serde(flatten)]
+    pub model: TopologyModel,
+    pub channel_amount: AmountDistribution,
+    #[serde(default = "default_profile")]
+    pub profile: String
+}
+
+/*
+ * A channel that is open at the start of the simulation
+ */
+#[derive(Deserialize, Debug, Clone)]
+pub struct ChannelTemplate {
+    pub id: u64,
+    pub src: String,
+    pub dest: String,
+    pub amount: SatsValue,
+    #[serde(default)]
+    pub src_policy: Option<SimChannelPolicy>,
+    #[serde(default)]
+    pub dest_policy: Option<SimChannelPolicy>
+}
+
+/*
+ * An event, or a series of events, on the simulation timeline
+ */
+#[derive(Deserialize, Debug, Clone)]
+pub struct EventTemplate {
+    #[serde(rename = "type")]
+    pub event_type: EventTemplateType,
+    pub time: Option<u64>, // a single event at this time
+    pub every: Option<u64>, // or a series of events every n seconds...
+    pub from: Option<u64>, // ...starting at this time (defaults to 0)...
+    pub to: Option<u64>, // ...until this time (defaults to the duration)
+    pub node: Option<String>, // start_node, stop_node, close_channel, force_close_channel, broadcast_revoked_state, update_channel_policy, disconnect_peers and fund_node
+    pub src: Option<String>, // open_channel, transaction, keysend and send_on_chain
+    pub src_group: Option<String>, // transaction, keysend and send_on_chain: pick a random source node from this group
+    pub dest: Option<String>, // open_channel, transaction, keysend and send_on_chain (a node or a bitcoin address)
+    pub dest_group: Option<String>, // transaction, keysend and send_on_chain: pick a random destination node from this group
+    pub amount: Option<SatsValue>, // open_channel, transaction, keysend, send_on_chain and fund_node
+    pub id: Option<u64>, // open_channel: the new channel id, the close events, update_channel_policy and probe: the channel to close, update or probe
+    pub src_policy: Option<SimChannelPolicy>, // open_channel: the routing policy of the src node
+    pub dest_policy: Option<SimChannelPolicy>, // open_channel: the routing policy of the dest node
+    pub policy: Option<SimChannelPolicy>, // update_channel_policy: the new routing policy of the node
+    pub blocks: Option<u64>, // mine_blocks and reorg: the number of blocks to mine or to replace
+    pub peer: Option<String>, // disconnect_peers: the node to disconnect from
+    pub nodes_a: Option<Vec<String>>, // partition: the nodes (or groups) on one side
+    pub nodes_b: Option<Vec<String>>, // partition: the nodes (or groups) on the other side
+    pub prober: Option<String>, // probe: the node that sends the probes
+    pub precision: Option<u64>, // probe: the balance is found within this many sats (defaults to 1000)
+    pub feerate: Option<u64> // send_on_chain: the fee rate in sats per vbyte (defaults to 1)
+}
+
+/*
+ * A traffic generator that sends background payments from the senders to the receivers (node or group names)
+ */
+#[derive(Deserialize, Debug, Clone)]
+pub struct TrafficTemplate {
+    pub model: TrafficModel,
+    pub senders: Vec<String>,
+    pub receivers: Vec<String>,
+    pub rate: f64,
+    pub amount: AmountDistribution,
+    pub from: Option<u64>, // defaults to 0
+    pub to: Option<u64> // defaults to the duration
+}
+
+/*
+ * An availability model that stops and starts the nodes (node or group names) during the simulation
+ */
+#[derive(Deserialize, Debug, Clone)]
+pub struct ChurnTemplate {
+    #[serde(flatten)]
+    pub model: ChurnModel,
+    pub nodes: Vec<String>,
+    pub from: Option<u64>, // defaults to 0
+    pub to: Option<u64> // defaults to the duration
+}
+
+/*
+ * A jamming attack where the src node pays the dest node and dest holds the payments, both nodes are controlled by the attacker
+ */
+#[derive(Deserialize, Debug, Clone)]
+pub struct JammingTemplate {
+    #[serde(flatten)]
+    pub attack: JammingAttack,
+    pub src: String,
+    pub dest: String,
+    pub amount: SatsValue, // sampled once for the whole attack
+    pub from: Option<u64>, // defaults to 0
+    pub to: Option<u64> // defaults to the duration
+}
+
+/*
+ * A probing agent that infers the balances of the channels every interval seconds
+ */
+#[derive(Deserialize, Debug, Clone)]
+pub struct ProbingTemplate {
+    pub prober: String,
+    pub channels: Vec<u64>,
+    pub interval: u64,
+    #[serde(default = "default_precision")]
+    pub precision: u64, // sats, defaults to 1000
+    pub from: Option<u64>, // defaults to 0
+    pub to: Option<u64> // defaults to the duration
+}
+
+/*
+ * The on-chain fee market, the feerate schedule is either scripted or loaded from a historical mempool profile
+ */
+#[derive(Deserialize, Debug, Clone)]
+pub struct FeeMarketTemplate {
+    pub schedule: Option<Vec<FeeratePoint>>, // the feerate (sats per vbyte) from each time on
+    pub profile: Option<String>, // or a csv file of unix timestamps and feerates (relative to the working directory)...
+    #[serde(default = "default_speedup")]
+    pub speedup: f64, // ...replayed this many times faster than it happened, defaults to 1
+    pub block_interval: u64,
+    #[serde(default = "default_filler_txs")]
+    pub filler_txs: u64, // defaults to 10
+    pub from: Option<u64>, // defaults to 0
+    pub to: Option<u64> // defaults to the duration
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum EventTemplateType {
+    StartNode,
+    StopNode,
+    OpenChannel,
+    CloseChannel,
+    Transaction,
+    Keysend,
+    UpdateChannelPolicy,
+    ForceCloseChannel,
+    BroadcastRevokedState,
+    MineBlocks,
+    DisconnectPeers,
+    Partition,
+    HealPartition,
+    Probe,
+    SendOnChain,
+    FundNode,
+    Reorg
+}
+
+/*
+ * An amount of sats, either a fixed number or a range like "1000-10000" or "1k-10k" that is sampled each time it is used
+ */
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum SatsValue {
+    Fixed(u64),
+    Range(String)
+}
+
+impl SatsValue {
+    /*
+     * Get a value from this amount, picking a random value if it is a range
+     */
+    pub fn sample(&self, rng: &mut StdRng) -> Result<u64> {
+        match self {
+            SatsValue::Fixed(v) => Ok(*v),
+            SatsValue::Range(r) => {
+                let normalized = r.replace('–', "-");
+                match normalized.split_once('-') {
+                    Some((min, max)) => {
+                        let min_val = SatsValue::parse_sats(min)?;
+                        let max_val = SatsValue::parse_sats(max)?;
+                        if min_val > max_val {
+                            return Err(anyhow!("invalid range: {}", r));
+                        }
+                        Ok(rng.gen_range(min_val..=max_val))
+                    },
+                    None => SatsValue::parse_sats(&normalized)
+                }
+            }
+        }
+    }
+
+    /*
+     * Parse a number of sats with an optional k (thousand) or m (million) suffix
+     */
+    fn parse_sats(value: &str) -> Result<u64> {
+        let v = value.trim().to_lowercase();
+        let v = v.trim_end_matches("sats").trim();
+        if let Some(num) = v.strip_suffix('k') {
+            Ok((num.trim().parse::<f64>()? * 1_000.0).round() as u64)
+        } else if let Some(num) = v.strip_suffix('m') {
+            Ok((num.trim().parse::<f64>()? * 1_000_000.0).round() as u64)
+        } else {
+            Ok(v.parse()?)
+        }
+    }
+}
+
+fn default_running() -> bool {
+    true
+}
+
+fn default_precision() -> u64 {
+    1000
+}
+
+fn default_feerate() -> u64 {
+    1
+}
+
+fn default_speedup() -> f64 {
+    1.0
+}
+
+fn default_filler_txs() -> u64 {
+    10
+}
+
+fn default_profile() -> String {
+    String::from("default")
+}
+
+impl ScenarioTemplate {
+    /*
+     * Load a template from a toml or yaml file, resolving includes and variables
+     */
+    pub fn load(filename: &String) -> Result<ScenarioTemplate> {
+        let raw = ScenarioTemplate::load_raw(Path::new(filename), 0)?;
+        let variables = match raw.get("variables") {
+            Some(Value::Object(v)) => v.clone(),
+            _ => Map::new()
+        };
+        let resolved = ScenarioTemplate::substitute(&Value::Object(raw), &variables);
+        let template: ScenarioTemplate = serde_json::from_value(resolved)?;
+        Ok(template)
+    }
+
+    /*
+     * Compile this template into a simulation by making the builder calls for every node, channel and event
+     */
+    pub fn compile(&self) -> Result<LnSimulation> {
+        let seed = self.seed.unwrap_or(0);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut sim = LnSimulation::new(self.name.clone(), self.duration, self.num_sim_nodes);
+        sim.set_seed(seed);
+
+        // Create the nodes and keep track of the groups that each node belongs to
+        let mut groups: HashMap<String, Vec<String>> = HashMap::new();
+        for n in &self.nodes {
+            let balance = match &n.initial_balance {
+                Some(b) => b.sample(&mut rng)?,
+                None => 0
+            };
+            sim.create_node(n.name.clone(), balance, n.running);
+            for g in &n.groups {
+                groups.entry(g.clone()).or_insert(Vec::new()).push(n.name.clone());
+                sim.add_node_to_group(n.name.clone(), g.clone());
+            }
+        }
+        for set in &self.node_sets {
+            let names = sim.create_node_set(set.name.clone(), set.count, set.profile.clone());
+            groups.entry(set.name.clone()).or_insert(Vec::new()).extend(names);
+        }
+        for t in &self.topologies {
+            let names = sim.create_random_network(t.name.clone(), t.count, t.model.clone(), t.channel_amount.clone(), t.profile.clone());
+            if names.is_empty() {
+                return Err(anyhow!("could not create the {} network", t.name));
+            }
+            groups.entry(t.name.clone()).or_insert(Vec::new()).extend(names);
+            if let TopologyModel::HubAndSpoke { .. } = t.model {
+                let hubs_group = t.name.clone() + "_hubs";
+                groups.insert(hubs_group.clone(), sim.get_group_nodes(&hubs_group));
+            }
+        }
+
+        // Create the channels
+        for c in &self.channels {
+            sim.create_channel(c.src.clone(), c.dest.clone(), c.amount.sample(&mut rng)?, c.id);
+            ScenarioTemplate::set_policies(&mut sim, c.id, &c.src, &c.dest, &c.src_policy, &c.dest_policy);
+        }
+
+        // Create the events, expanding the ones that repeat
+        for e in &self.events {
+            let times: Vec<u64> = match (e.time, e.every) {
+                (Some(t), None) => vec![t],
+                (None, Some(every)) if every > 0 => {
+                    let from = e.from.unwrap_or(0);
+                    let to = e.to.unwrap_or(self.duration);
+                    (from..=to).step_by(every as usize).collect()
+                },
+                _ => return Err(anyhow!("{:?} event needs either a time or a non-zero every", e.event_type))
+            };
+
+            for time in times {
+                match e.event_type {
+                    EventTemplateType::StartNode => {
+                        sim.create_start_node_event(ScenarioTemplate::required(&e.node, "node")?, time);
+                    },
+                    EventTemplateType::StopNode => {
+                        sim.create_stop_node_event(ScenarioTemplate::required(&e.node, "node")?, time);
+                    },
+                    EventTemplateType::OpenChannel => {
+                        let amount = ScenarioTemplate::required(&e.amount, "amount")?.sample(&mut rng)?;
+                        let src = ScenarioTemplate::required(&e.src, "src")?;
+                        let dest = ScenarioTemplate::required(&e.dest, "dest")?;
+                        let id = ScenarioTemplate::required(&e.id, "id")?;
+                        sim.create_open_channel_event(src.clone(), dest.clone(), amount, time, id);
+                        ScenarioTemplate::set_policies(&mut sim, id, &src, &dest, &e.src_policy, &e.dest_policy);
+                    },
+                    EventTemplateType::CloseChannel => {
+                        sim.create_close_channel_event(ScenarioTemplate::required(&e.node, "node")?, ScenarioTemplate::required(&e.id, "id")?, time);
+                    },
+                    EventTemplateType::Transaction => {
+                        let src = ScenarioTemplate::pick_node(&e.src, &e.src_group, &groups, None, &mut rng)?;
+                        let dest = ScenarioTemplate::pick_node(&e.dest, &e.dest_group, &groups, Some(&src), &mut rng)?;
+                        let amount = ScenarioTemplate::required(&e.amount, "amount")?.sample(&mut rng)?;
+                        sim.create_transaction_event(src, dest, amount, time);
+                    },
+                    EventTemplateType::Keysend => {
+                        let src = ScenarioTemplate::pick_node(&e.src, &e.src_group, &groups, None, &mut rng)?;
+                        let dest = ScenarioTemplate::pick_node(&e.dest, &e.dest_group, &groups, Some(&src), &mut rng)?;
+                        let amount = ScenarioTemplate::required(&e.amount, "amount")?.sample(&mut rng)?;
+                        sim.create_keysend_event(src, dest, amount, time);
+                    },
+                    EventTemplateType::UpdateChannelPolicy => {
+                        sim.create_update_channel_policy_event(ScenarioTemplate::required(&e.node, "node")?, ScenarioTemplate::required(&e.id, "id")?, ScenarioTemplate::required(&e.policy, "policy")?, time);
+                    },
+                    EventTemplateType::ForceCloseChannel => {
+                        sim.create_force_close_channel_event(ScenarioTemplate::required(&e.node, "node")?, ScenarioTemplate::required(&e.id, "id")?, time);
+                    },
+                    EventTemplateType::BroadcastRevokedState => {
+                        sim.create_broadcast_revoked_state_event(ScenarioTemplate::required(&e.node, "node")?, ScenarioTemplate::required(&e.id, "id")?, time);
+                    },
+                    EventTemplateType::MineBlocks => {
+                        sim.create_mine_blocks_event(ScenarioTemplate::required(&e.blocks, "blocks")?, time);
+                    },
+                    EventTemplateType::Reorg => {
+                        sim.create_reorg_event(ScenarioTemplate::required(&e.blocks, "blocks")?, time);
+                    },
+                    EventTemplateType::DisconnectPeers => {
+                        sim.create_disconnect_peers_event(ScenarioTemplate::required(&e.node, "node")?, ScenarioTemplate::required(&e.peer, "peer")?, time);
+                    },
+                    EventTemplateType::Partition => {
+                        sim.create_partition_event(ScenarioTemplate::required(&e.nodes_a, "nodes_a")?, ScenarioTemplate::required(&e.nodes_b, "nodes_b")?, time);
+                    },
+                    EventTemplateType::HealPartition => {
+                        sim.create_heal_partition_event(time);
+                    },
+                    EventTemplateType::Probe => {
+                        sim.create_probe_event(ScenarioTemplate::required(&e.prober, "prober")?, ScenarioTemplate::required(&e.id, "id")?, e.precision.unwrap_or(default_precision()), time);
+                    },
+                    EventTemplateType::SendOnChain => {
+                        let src = ScenarioTemplate::pick_node(&e.src, &e.src_group, &groups, None, &mut rng)?;
+                        let dest = ScenarioTemplate::pick_node(&e.dest, &e.dest_group, &groups, Some(&src), &mut rng)?;
+                        let amount = ScenarioTemplate::required(&e.amount, "amount")?.sample(&mut rng)?;
+                        sim.create_send_on_chain_event(src, dest, amount, e.feerate.unwrap_or(default_feerate()), time);
+                    },
+                    EventTemplateType::FundNode => {
+                        let amount = ScenarioTemplate::required(&e.amount, "amount")?.sample(&mut rng)?;
+                        sim.create_fund_node_event(ScenarioTemplate::required(&e.node, "node")?, amount, time);
+                    }
+                }
+            }
+        }
+
+        // Create the traffic generators
+        for t in &self.traffic {
+            sim.create_traffic_generator(t.model.clone(), t.senders.clone(), t.receivers.clone(), t.rate, t.amount.clone(), t.from.unwrap_or(0), t.to.unwrap_or(self.duration));
+        }
+
+        // Create the churn models
+        for c in &self.churn {
+            sim.create_churn_model(c.model.clone(), c.nodes.clone(), c.from.unwrap_or(0), c.to.unwrap_or(self.duration));
+        }
+
+        // Create the probing agents
+        for p in &self.probing {
+            sim.create_probing_agent(p.prober.clone(), p.channels.clone(), p.interval, p.precision, p.from.unwrap_or(0), p.to.unwrap_or(self.duration));
+        }
+
+        // Create the fee market
+        match &self.fee_market {
+            Some(m) => {
+                let schedule = match (&m.schedule, &m.profile) {
+                    (Some(s), _) => s.clone(),
+                    (None, Some(profile)) => FeeMarketConfig::load_profile(profile, m.speedup)?,
+                    (None, None) => return Err(anyhow!("fee market needs either a schedule or a profile"))
+                };
+                sim.set_fee_market(schedule, m.block_interval, m.filler_txs, m.from.unwrap_or(0), m.to.unwrap_or(self.duration));
+            },
+            None => {}
+        }
+
+        // Create the jamming attacks
+        for j in &self.jamming {
+            let amount = j.amount.sample(&mut rng)?;
+            sim.create_jamming_attack(j.attack.clone(), j.src.clone(), j.dest.clone(), amount, j.from.unwrap_or(0), j.to.unwrap_or(self.duration));
+        }
+
+        // Create the triggers
+        for t in &self.triggers {
+            sim.create_trigger(t.clone());
+        }
+
+        Ok(sim)
+    }
+
+    /*
+     * Set the routing policies of both nodes of a channel if they are defined
+     */
+    fn set_policies(sim: &mut LnSimulation, id: u64, src: &String, dest: &String, src_policy: &Option<SimChannelPolicy>, dest_policy: &Option<SimChannelPolicy>) {
+        if let Some(p) = src_policy {
+            sim.set_channel_policy(src.clone(), id, p.clone());
+        }
+        if let Some(p) = dest_policy {
+            sim.set_channel_policy(dest.clone(), id, p.clone());
+        }
+    }
+
+    /*
+     * Read a template file into a json object and merge in all of the files that it includes
+     */
+    fn load_raw(path: &Path, depth: u32) -> Result<Map<String, Value>> {
+        if depth > 10 {
+            return Err(anyhow!("too many nested includes at {}", path.display()));
+        }
+
+        let contents = fs::read_to_string(path).map_err(|e| anyhow!("could not read {}: {}", path.display(), e))?;
+        let value: Value = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::from_str(&contents)?,
+            Some("yaml") | Some("yml") => serde_yaml::from_str(&contents)?,
+            Some("json") => serde_json::from_str(&contents)?,
+            _ => return Err(anyhow!("unknown scenario file type: {}", path.display()))
+        };
+        let mut file = match value {
+            Value::Object(m) => m,
+            _ => return Err(anyhow!("{} is not a scenario definition", path.display()))
+        };
+
+        // Merge the included files into this one, values in this file take priority
+        let includes: Vec<String> = match file.remove("include") {
+            Some(Value::Array(a)) => a.iter().filter_map(|i| i.as_str().map(String::from)).collect(),
+            Some(Value::String(s)) => vec![s],
+            _ => Vec::new()
+        };
+        let dir = path.parent().unwrap_or(Path::new("."));
+        for i in includes {
+            let included = ScenarioTemplate::load_raw(&dir.join(i), depth + 1)?;
+            for (key, value) in included {
+                match (file.get_mut(&key), value) {
+                    (Some(Value::Array(mine)), Value::Array(theirs)) => {
+                        let mut merged = theirs;
+                        merged.append(mine);
+                        *mine = merged;
+                    },
+                    (Some(Value::Object(mine)), Value::Object(theirs)) => {
+                        for (k, v) in theirs {
+                            mine.entry(k).or_insert(v);
+                        }
+                    },
+                    (Some(_), _) => {},
+                    (None, v) => {
+                        file.insert(key, v);
+                    }
+                }
+            }
+        }
+
+        Ok(file)
+    }
+
+    /*
+     * Replace "${name}" with the value of the variable called name
+     * A string that is only a variable takes the type of the variable (so numbers stay numbers)
+     */
+    fn substitute(value: &Value, variables: &Map<String, Value>) -> Value {
+        match value {
+            Value::String(s) => {
+                for (name, var) in variables {
+                    if s == &format!("${{{}}}", name) {
+                        return var.clone();
+                    }
+                }
+                let mut new_string = s.clone();
+                for (name, var) in variables {
+                    let var_string = match var {
+                        Value::String(v) => v.clone(),
+                        v => v.to_string()
+                    };
+                    new_string = new_string.replace(&format!("${{{}}}", name), &var_string);
+                }
+                Value::String(new_string)
+            },
+            Value::Array(a) => Value::Array(a.iter().map(|v| ScenarioTemplate::substitute(v, variables)).collect()),
+            Value::Object(m) => Value::Object(m.iter().map(|(k, v)| (k.clone(), ScenarioTemplate::substitute(v, variables))).collect()),
+            v => v.clone()
+        }
+    }
+
+    /*
+     * Get the name of a node for an event, either the given node or a random node from the given group
+     */
+    fn pick_node(node: &Option<String>, group: &Option<String>, groups: &HashMap<String, Vec<String>>, exclude: Option<&String>, rng: &mut StdRng) -> Result<String> {
+        match (node, group) {
+            (Some(n), _) => Ok(n.clone()),
+            (None, Some(g)) => {
+                let members: Vec<&String> = match groups.get(g) {
+                    Some(m) => m.iter().filter(|&n| Some(n) != exclude).collect(),
+                    None => return Err(anyhow!("group {} not found", g))
+                };
+                match members.choose(rng) {
+                    Some(n) => Ok((*n).clone()),
+                    None => Err(anyhow!("group {} does not have any nodes to pick from", g))
+                }
+            },
+            (None, None) => Err(anyhow!("transaction event needs a node or a group"))
+        }
+    }
+
+    /*
+     * Get a required field of an event template
+     */
+    fn required<T: Clone>(field: &Option<T>, name: &str) -> Result<T> {
+        match field {
+            Some(f) => Ok(f.clone()),
+            None => Err(anyhow!("event is missing the {} field", name))
+        }
+    }
+}
@@ -2,8 +2,12 @@
 use crate::sim_event::SimResultsEvent;
 use crate::sim_event::SimulationEvent;
 use crate::sim_event::SimEvent;
+use crate::sim_event::SimHeldPayment;
+use crate::sim_event::SimProbe;
+use crate::sim_event::SimProbeResult;
 use crate::sim_node::SimNode;
 use crate::sim_channel::SimChannel;
+use crate::sim_channel::SimChannelPolicy;
 use crate::nigiri_controller;
 use crate::sim_node_status::SimNodeStatus;
 use crate::sim_node_status::SimNodeChannel;
@@ -14,13 +18,29 @@ use crate::sim_transaction::SimTransactionStatus;
 use std::sync::Arc;
 use std::sync::atomic::Ordering;
 use std::collections::HashMap;
+use std::str::FromStr;
 use std::time::Duration;
+use std::time::SystemTime;
 
 // External modules
 use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
 
 // Sensei and LDK modules
 use lightning::util::events::Event;
+use lightning::util::config::ChannelConfig;
+use lightning::util::config::UserConfig;
+use lightning::ln::channelmanager::{provided_node_features, provided_channel_features, MIN_FINAL_CLTV_EXPIRY};
+use lightning::routing::router::RouteHop;
+use lightning::bitcoin::Transaction;
+use lightning::bitcoin::Address;
+use lightning::bitcoin::consensus::encode::serialize_hex;
+use lightning::ln::PaymentPreimage;
+use lightning::ln::PaymentHash;
+use lightning_invoice::Currency;
+use lightning_invoice::utils::create_invoice_from_channelmanager_and_duration_since_epoch_with_payment_hash;
+use bdk::{FeeRate, SignOptions};
+use senseicore::hex_utils;
 use senseicore::services::admin::{AdminRequest, AdminResponse, AdminService};
 use senseicore::services::admin::Error;
 use senseicore::services::node::{NodeRequest, NodeResponse, OpenChannelRequest, NodeRequestError};
@@ -28,6 +48,17 @@ use senseicore::services::PaginationRequest;
 use senseicore::node::LightningNode;
 use entity::node;
 
+/*
+ * The node that a channel is probed from and the hops that the probes take
+ */
+struct ProbeTarget {
+    prober: Arc<LightningNode>,
+    node: String, // the end of the channel that the prober has a channel with, the balance that is found is this node's
+    hops: Vec<RouteHop>, // prober -> node -> peer, the amounts are set for each probe
+    policy: ChannelConfig, // the routing policy of the probed channel
+    max_sats: u64 // the most that a probe can carry, the capacity of the channel or what the prober can send to the node if that is less
+}
+
 /* 
  * This struct processes simulation events and controls the sensei nodes.
  * It listens for the simulation events and then makes calls to the sensei AdminService and LightningNodes
@@ -37,7 +68,10 @@ pub struct SenseiController {
     sensei_runtime_handle: tokio::runtime::Handle,
     channel_id_map: HashMap<u64, String>,
     rev_channel_id_map: HashMap<String, u64>,
-    channel_funding_map: HashMap<String, String>
+    channel_funding_map: HashMap<String, String>,
+    commitment_snapshots: HashMap<(String, u64), Vec<Transaction>>, // the signed commitment of a node for a channel (key=node name and sim channel id) from when the channel was opened
+    revoked_state_targets: Vec<(String, u64)>, // the node and sim channel id of every scheduled BroadcastRevokedStateEvent, only these channels get a commitment snapshot
+    node_names: Vec<String> // the names of all the nodes that were created
 }
 
 impl SenseiController {
@@ -47,12 +81,23 @@ impl SenseiController {
             sensei_runtime_handle: runtime_handle,
             channel_id_map: HashMap::new(),
             rev_channel_id_map: HashMap::new(),
-            channel_funding_map: HashMap::new()
+            channel_funding_map: HashMap::new(),
+            commitment_snapshots: HashMap::new(),
+            revoked_state_targets: Vec::new(),
+            node_names: Vec::new()
         };
 
         controller
     }
 
+    /*
+     * Set the nodes and channels that will broadcast a revoked state, call this before the network is initialized
+     * The commitment of these channels is saved when they are opened so it can be broadcast once a later payment has revoked it
+     */
+    pub fn set_revoked_state_targets(&mut self, targets: Vec<(String, u64)>) {
+        self.revoked_state_targets = targets;
+    }
+
     /*
      * Receive events and make the appropriate calls to the Sensei library
      */
@@ -61,13 +106,42 @@ impl SenseiController {
         let mut channel_id_map: HashMap<u64, String> = self.channel_id_map.clone();
         let mut rev_channel_id_map: HashMap<String, u64> = self.rev_channel_id_map.clone();
         let mut channel_funding_map: HashMap<String, String> = self.channel_funding_map.clone();
+        let mut commitment_snapshots: HashMap<(String, u64), Vec<Transaction>> = self.commitment_snapshots.clone();
+
+        // The pairs of nodes that are kept disconnected by a partition
+        let mut partitioned_peers: Vec<(String, String)> = Vec::new();
+
+        // The payments that are being held by their destination (key=sim held payment id, value=destination node name and payment hash)
+        let mut held_payments: HashMap<u64, (String, PaymentHash)> = HashMap::new();
+
+        // The feerate that a transaction has to pay to get mined while a fee market is running
+        let mut market_feerate: Option<u64> = None;
+
+        // The transactions that a reorg unconfirmed and that are not in a block again yet
+        let mut reorged_txs: Vec<String> = Vec::new();
+
+        // The channels that were opened while a fee market is running and whose funding transaction is not in a block yet (sim channel, sensei channel id, funding tx)
+        let mut pending_opens: Vec<(SimChannel, String, String)> = Vec::new();
+
+        // The probes that are running on their own tasks, they are finished before the simulation ends so that their results are not lost
+        let mut probe_tasks: Vec<JoinHandle<()>> = Vec::new();
 
         // This is the main thread for processing sim events
         tokio::task::block_in_place(move || {
             self.sensei_runtime_handle.block_on(async move {
                 let mut running = true;
+                let mut partition_check = tokio::time::interval(Duration::from_secs(1));
                 while running {
-                    let event = event_channel.recv().await.unwrap();
+                    let event = tokio::select! {
+                        e = event_channel.recv() => e.unwrap(),
+                        _ = partition_check.tick() => {
+                            // The nodes reconnect to the peers that they have channels with, so disconnect the partitioned nodes again
+                            for (a, b) in &partitioned_peers {
+                                let _ = self.disconnect_peers(a, b).await;
+                            }
+                            continue;
+                        }
+                    };
                     match &event.event {
                         SimulationEvent::StopNodeEvent(name) => {
                             println!("[=== SenseiController === {}] StopNodeEvent for {}", crate::get_current_time(), name);
@@ -110,7 +184,7 @@ impl SenseiController {
                             output_channel.send(sim_event).expect("could not send the event");
                             match channel_id_map.get(id) {
                                 Some(chanid) => {
-                                    match self.close_channel(node, String::from(chanid)).await {
+                                    match self.close_channel(node, String::from(chanid), false, market_feerate).await {
                                         Ok(()) => {},
                                         Err(e) => {
                                             println!("could not close channel: {:?}", e);
@@ -122,42 +196,370 @@ impl SenseiController {
                                 }
                             }
                         },
+                        SimulationEvent::ForceCloseChannelEvent(node, id) => {
+                            println!("[=== SenseiController === {}] ForceCloseChannelEvent for {}", crate::get_current_time(), id);
+                            // Tell the network analyzer that this channel is closing
+                            let sim_event = SimResultsEvent{sim_time: Some(event.sim_time.clone()), success: true, event: event.event.clone()};
+                            output_channel.send(sim_event).expect("could not send the event");
+                            match channel_id_map.get(id) {
+                                Some(chanid) => {
+                                    match self.close_channel(node, String::from(chanid), true, market_feerate).await {
+                                        Ok(()) => {},
+                                        Err(e) => {
+                                            println!("could not force close channel: {:?}", e);
+                                        }
+                                    }
+                                },
+                                None => {
+                                    println!("could not find channel.");
+                                }
+                            }
+                        },
+                        SimulationEvent::BroadcastRevokedStateEvent(node, id) => {
+                            println!("[=== SenseiController === {}] BroadcastRevokedStateEvent for {} on {}", crate::get_current_time(), node, id);
+                            // The saved commitment is only a breach once a later state update has revoked it, otherwise it is the current state
+                            let revoked = match (commitment_snapshots.get(&(node.clone(), id.clone())), channel_id_map.get(id)) {
+                                (Some(txs), Some(chanid)) => {
+                                    match self.get_holder_commitment(node, chanid).await {
+                                        Some(current) if current.first().map(|t| t.txid()) != txs.first().map(|t| t.txid()) => Some(txs),
+                                        _ => None
+                                    }
+                                },
+                                _ => None
+                            };
+                            match revoked {
+                                Some(txs) => {
+                                    // Publish the old commitment so that the counterparty sees it and claims the funds with a justice transaction
+                                    let mut success = true;
+                                    for tx in txs {
+                                        match nigiri_controller::send_raw_transaction(serialize_hex(tx)) {
+                                            Ok(()) => {},
+                                            Err(e) => {
+                                                println!("could not broadcast the revoked commitment: {:?}", e);
+                                                success = false;
+                                                break;
+                                            }
+                                        }
+                                    }
+
+                                    // Tell the network analyzer that this channel is closing or that the revoked state could not be broadcast
+                                    let sim_event = SimResultsEvent{sim_time: Some(event.sim_time.clone()), success: success, event: event.event.clone()};
+                                    output_channel.send(sim_event).expect("could not send the event");
+
+                                    // Mine the commitment, while a fee market is running it waits for a block that it pays the market feerate for
+                                    if success && market_feerate.is_none() {
+                                        nigiri_controller::mine();
+                                        tokio::time::sleep(Duration::from_secs(2)).await;
+                                    }
+                                },
+                                None => {
+                                    println!("could not find a revoked commitment to broadcast, the channel needs a payment after it was opened.");
+
+                                    // Tell the network analyzer that the revoked state could not be broadcast
+                                    let sim_event = SimResultsEvent{sim_time: Some(event.sim_time.clone()), success: false, event: event.event.clone()};
+                                    output_channel.send(sim_event).expect("could not send the event");
+                                }
+                            }
+                        },
+                        SimulationEvent::MineBlocksEvent(number) => {
+                            println!("[=== SenseiController === {}] MineBlocksEvent for {} blocks", crate::get_current_time(), number);
+                            // While a fee market is running the blocks only include the transactions that pay the market feerate
+                            match market_feerate {
+                                Some(feerate) => {
+                                    for _ in 0..*number {
+                                        nigiri_controller::mine_block_above_feerate(feerate);
+                                    }
+                                },
+                                None => nigiri_controller::mine_blocks(number.clone())
+                            }
+
+                            // Tell the network analyzer that the blocks were mined at this time
+                            let sim_event = SimResultsEvent{sim_time: Some(event.sim_time.clone()), success: true, event: event.event.clone()};
+                            output_channel.send(sim_event).expect("could not send the event");
+                        },
+                        SimulationEvent::SendOnChainEvent(tx) => {
+                            println!("[=== SenseiController === {}] SendOnChainEvent for {} -> {}", crate::get_current_time(), tx.src_node, tx.dest);
+                            let result = match self.send_on_chain(&tx.src_node, &tx.dest, tx.amount_sats, tx.feerate_sat_per_vbyte, market_feerate).await {
+                                Ok(sent) => Some(sent),
+                                Err(e) => {
+                                    println!("could not send on-chain: {:?}", e);
+                                    None
+                                }
+                            };
+
+                            // Tell the network analyzer the transaction that was broadcast or that it failed at this time, the analyzer looks up its fee
+                            let success = result.is_some();
+                            let mut sent = tx.clone();
+                            sent.txid = result;
+                            let sim_event = SimResultsEvent{sim_time: Some(event.sim_time.clone()), success: success, event: SimulationEvent::SendOnChainEvent(sent)};
+                            output_channel.send(sim_event).expect("could not send the event");
+                        },
+                        SimulationEvent::FundNodeEvent(node, amount) => {
+                            println!("[=== SenseiController === {}] FundNodeEvent for {}", crate::get_current_time(), node);
+                            let success = match self.get_unused_address(node).await {
+                                Ok(address) => {
+                                    nigiri_controller::fund_address(address, amount.clone());
+                                    true
+                                },
+                                Err(e) => {
+                                    println!("could not fund node: {:?}", e);
+                                    false
+                                }
+                            };
+
+                            // Tell the network analyzer that the node was funded or failed to be funded at this time
+                            let sim_event = SimResultsEvent{sim_time: Some(event.sim_time.clone()), success: success, event: event.event.clone()};
+                            output_channel.send(sim_event).expect("could not send the event");
+                        },
+                        SimulationEvent::FeeMarketBlockEvent(block) => {
+                            println!("[=== SenseiController === {}] FeeMarketBlockEvent at {} sats per vbyte", crate::get_current_time(), block.feerate_sat_per_vbyte);
+                            // Fill the mempool at the market feerate and mine a block with the transactions that pay it, the last block confirms everything that is left
+                            let success = match nigiri_controller::send_filler_transactions(block.filler_txs, block.feerate_sat_per_vbyte) {
+                                Ok(()) => true,
+                                Err(e) => {
+                                    println!("could not send filler transactions: {:?}", e);
+                                    false
+                                }
+                            };
+                            market_feerate = if block.last { None } else { Some(block.feerate_sat_per_vbyte) };
+                            let (confirmed, waiting) = nigiri_controller::mine_block_above_feerate(market_feerate.unwrap_or(0));
+
+                            // Tell the network analyzer how many transactions were confirmed and are still waiting at this time, the event fails if the filler transactions could not be sent
+                            let mut mined = block.clone();
+                            mined.confirmed_txs = Some(confirmed);
+                            mined.mempool_txs = Some(waiting);
+                            let sim_event = SimResultsEvent{sim_time: Some(event.sim_time.clone()), success: success, event: SimulationEvent::FeeMarketBlockEvent(mined)};
+                            output_channel.send(sim_event).expect("could not send the event");
+                        },
+                        SimulationEvent::ReorgEvent(reorg) => {
+                            println!("[=== SenseiController === {}] ReorgEvent for {} blocks", crate::get_current_time(), reorg.depth);
+                            let mut reorged = reorg.clone();
+                            let success = match nigiri_controller::reorg(reorg.depth) {
+                                Some(txids) => {
+                                    // The sensei chain manager updates once a second. We need to wait and make sure the new chain is seen by the chain manager.
+                                    tokio::time::sleep(Duration::from_secs(2)).await;
+
+                                    // Find the channels that lost their funding transaction and are no longer usable
+                                    for (run_time_id, funding_tx) in &channel_funding_map {
+                                        match rev_channel_id_map.get(run_time_id) {
+                                            Some(id) if txids.contains(funding_tx) => {
+                                                if !self.is_channel_usable(run_time_id).await {
+                                                    reorged.unusable_channels.push(id.clone());
+                                                }
+                                            },
+                                            _ => {}
+                                        }
+                                    }
+                                    reorged.unusable_channels.sort();
+                                    reorged_txs.extend(txids.iter().cloned());
+                                    reorged.unconfirmed_txs = txids;
+                                    true
+                                },
+                                None => {
+                                    println!("could not reorg: the chain is not {} blocks long", reorg.depth);
+                                    false
+                                }
+                            };
+
+                            // Tell the network analyzer which transactions and channels the reorg affected at this time
+                            let sim_event = SimResultsEvent{sim_time: Some(event.sim_time.clone()), success: success, event: SimulationEvent::ReorgEvent(reorged)};
+                            output_channel.send(sim_event).expect("could not send the event");
+                        },
+                        SimulationEvent::DisconnectPeersEvent(_, _) | SimulationEvent::PartitionEvent(_, _) => {
+                            println!("[=== SenseiController === {}] {}", crate::get_current_time(), event.event);
+                            let pairs: Vec<(String, String)> = match &event.event {
+                                SimulationEvent::PartitionEvent(nodes_a, nodes_b) => {
+                                    nodes_a.iter().flat_map(|a| nodes_b.iter().filter(move |b| *b != a).map(move |b| (a.clone(), b.clone()))).collect()
+                                },
+                                SimulationEvent::DisconnectPeersEvent(a, b) => vec![(a.clone(), b.clone())],
+                                _ => Vec::new()
+                            };
+
+                            // Disconnect each pair and keep them disconnected until the partition is healed
+                            let mut success = true;
+                            for (a, b) in pairs {
+                                match self.disconnect_peers(&a, &b).await {
+                                    Ok(()) => {},
+                                    Err(e) => {
+                                        println!("could not disconnect peers: {:?}", e);
+                                        success = false;
+                                    }
+                                }
+                                if !partitioned_peers.contains(&(a.clone(), b.clone())) {
+                                    partitioned_peers.push((a, b));
+                                }
+                            }
+
+                            // Tell the network analyzer that the nodes were disconnected or failed to disconnect at this time
+                            let sim_event = SimResultsEvent{sim_time: Some(event.sim_time.clone()), success: success, event: event.event.clone()};
+                            output_channel.send(sim_event).expect("could not send the event");
+                        },
+                        SimulationEvent::HealPartitionEvent => {
+                            println!("[=== SenseiController === {}] HealPartitionEvent", crate::get_current_time());
+                            // Stop keeping the nodes apart and connect them again right away instead of waiting for the nodes to reconnect on their own
+                            let mut success = true;
+                            for (a, b) in partitioned_peers.drain(..) {
+                                match self.connect_peers(&a, &b).await {
+                                    Ok(()) => {},
+                                    Err(e) => {
+                                        println!("could not connect peers: {:?}", e);
+                                        success = false;
+                                    }
+                                }
+                            }
+
+                            // Tell the network analyzer that the partition was healed at this time
+                            let sim_event = SimResultsEvent{sim_time: Some(event.sim_time.clone()), success: success, event: event.event.clone()};
+                            output_channel.send(sim_event).expect("could not send the event");
+                        },
+                        SimulationEvent::HoldPaymentEvent(payment) => {
+                            println!("[=== SenseiController === {}] HoldPaymentEvent for {} <-> {}", crate::get_current_time(), payment.src_node, payment.dest_node);
+                            match self.send_held_payment(payment).await {
+                                Ok((id, payment_hash)) => {
+                                    held_payments.insert(payment.id, (payment.dest_node.clone(), payment_hash));
+
+                                    // Tell the network analyzer that the payment was sent with this payment id
+                                    let held_payment = SimHeldPayment {
+                                        id: payment.id,
+                                        src_node: payment.src_node.clone(),
+                                        dest_node: payment.dest_node.clone(),
+                                        amount_sats: payment.amount_sats,
+                                        payment_id: Some(id),
+                                        resend_until: payment.resend_until
+                                    };
+                                    let sim_event = SimResultsEvent{sim_time: Some(event.sim_time.clone()), success: true, event: SimulationEvent::HoldPaymentEvent(held_payment)};
+                                    output_channel.send(sim_event).expect("could not send the event");
+                                },
+                                Err(e) => {
+                                    println!("could not send held payment: {:?}", e);
+
+                                    // Tell the network analyzer that this payment failed to send
+                                    let sim_event = SimResultsEvent{sim_time: Some(event.sim_time.clone()), success: false, event: event.event.clone()};
+                                    output_channel.send(sim_event).expect("could not send the event");
+                                }
+                            }
+                        },
+                        SimulationEvent::ReleaseHeldPaymentEvent(id) => {
+                            println!("[=== SenseiController === {}] ReleaseHeldPaymentEvent for {}", crate::get_current_time(), id);
+                            let success = match held_payments.remove(id) {
+                                Some((dest, payment_hash)) => {
+                                    match self.get_sensei_node(&dest).await {
+                                        Ok(node) => {
+                                            // The destination does not know the preimage, so it fails the payment back to the sender
+                                            node.channel_manager.fail_htlc_backwards(&payment_hash);
+                                            true
+                                        },
+                                        Err(e) => {
+                                            println!("could not release held payment: {}", e);
+                                            false
+                                        }
+                                    }
+                                },
+                                None => {
+                                    println!("could not find held payment.");
+                                    false
+                                }
+                            };
+
+                            // Tell the network analyzer that the payment was released or failed to release at this time
+                            let sim_event = SimResultsEvent{sim_time: Some(event.sim_time.clone()), success: success, event: event.event.clone()};
+                            output_channel.send(sim_event).expect("could not send the event");
+                        },
+                        SimulationEvent::ProbeEvent(probe) => {
+                            println!("[=== SenseiController === {}] ProbeEvent for {} from {}", crate::get_current_time(), probe.channel_id, probe.prober);
+                            let target = match channel_id_map.get(&probe.channel_id) {
+                                Some(chanid) => {
+                                    match self.get_probe_target(&probe.prober, chanid).await {
+                                        Ok(t) => Some(t),
+                                        Err(e) => {
+                                            println!("could not probe channel: {:?}", e);
+                                            None
+                                        }
+                                    }
+                                },
+                                None => {
+                                    println!("could not find channel.");
+                                    None
+                                }
+                            };
+
+                            // The probes wait for each result, so they are sent on another task to keep processing the events that come after this one
+                            // The result is reported at the time of the event, the network analyzer compares it to the channel balances when it gets the result
+                            let probe = probe.clone();
+                            let sim_time = event.sim_time.clone();
+                            let probe_output = output_channel.clone();
+                            let task = self.sensei_runtime_handle.spawn(async move {
+                                let result = match target {
+                                    Some(t) => {
+                                        match SenseiController::probe_channel(t, probe.precision_sats).await {
+                                            Ok(r) => Some(r),
+                                            Err(e) => {
+                                                println!("could not probe channel: {:?}", e);
+                                                None
+                                            }
+                                        }
+                                    },
+                                    None => None
+                                };
+
+                                // Tell the network analyzer the balance range that was found or that the probes failed at the time of the event
+                                let success = result.is_some();
+                                let probe_event = SimulationEvent::ProbeEvent(SimProbe {
+                                    prober: probe.prober.clone(),
+                                    channel_id: probe.channel_id,
+                                    precision_sats: probe.precision_sats,
+                                    result: result
+                                });
+                                let sim_event = SimResultsEvent{sim_time: Some(sim_time), success: success, event: probe_event};
+                                match probe_output.send(sim_event) {
+                                    Ok(_) => {},
+                                    Err(_) => println!("could not send the probe result, the network analyzer stopped")
+                                }
+                            });
+                            probe_tasks.push(task);
+                        },
+                        SimulationEvent::SnapshotEvent(_) => {
+                            println!("[=== SenseiController === {}] SnapshotEvent", crate::get_current_time());
+                            // Get the state that each node reports, nodes that are stopped can not be queried
+                            let mut statuses: HashMap<String, SimNodeStatus> = HashMap::new();
+                            for name in &self.node_names {
+                                match self.get_node_status(name, Some(&rev_channel_id_map), Some(&channel_funding_map)).await {
+                                    Some(status) => {
+                                        statuses.insert(name.clone(), status);
+                                    },
+                                    None => {}
+                                }
+                            }
+
+                            // Send the snapshot to the network analyzer
+                            let sim_event = SimResultsEvent{sim_time: Some(event.sim_time.clone()), success: true, event: SimulationEvent::SnapshotEvent(statuses)};
+                            output_channel.send(sim_event).expect("could not send the event");
+                        },
                         SimulationEvent::OpenChannelEvent(channel) => {
                             println!("[=== SenseiController === {}] OpenChannelEvent for {} <-> {}", crate::get_current_time(), channel.src_node, channel.dest_node);
-                            match self.open_channel(&channel.src_node, &channel.dest_node, channel.src_balance_sats, channel.dest_balance_sats, channel.id).await {
+                            match self.open_channel(&channel.src_node, &channel.dest_node, channel.src_balance_sats, channel.dest_balance_sats, channel.id, &channel.src_policy, &channel.dest_policy, market_feerate).await {
                                 Ok(res) => {
                                     // Establish the relationship between sensei channel id and sim channel id for the new channel
                                     channel_id_map.insert(channel.id, res.0.clone());
                                     rev_channel_id_map.insert(res.0.clone(), channel.id);
                                     channel_funding_map.insert(res.0.clone(), res.1.clone());
-                                    
-                                    // Get the node status from sensei and set the short id that was assigned to this channel
-                                    let node_status = self.get_node_status(&channel.src_node, Some(&rev_channel_id_map), Some(&channel_funding_map)).await;
-                                    match node_status {
-                                        Some(status) => {
-                                            match status.get_channel(channel.id) {
-                                                Some(sc) => {
-                                                    // Create a new SimChannel with the same values as the "channel" variable and the short id of the "SimNodeChannel" that we got from sensei
-                                                    let simchan = SimChannel {
-                                                        id: channel.id.clone(),
-                                                        short_id: sc.short_id,
-                                                        run_time_id: Some(res.0),
-                                                        src_node: channel.src_node.clone(),
-                                                        dest_node: channel.dest_node.clone(),
-                                                        src_balance_sats: sc.outbound_capacity / 1000,
-                                                        dest_balance_sats: sc.inbound_capacity / 1000,
-                                                        funding_tx: Some(res.1.clone()),
-                                                        penalty_reserve_sats: sc.punishment_reserve
-                                                    };
-                                                    // Tell the network analyzer that this channel was opened and pass the new channel object to use
-                                                    let channel_event = SimulationEvent::OpenChannelEvent(simchan);
-                                                    let sim_event = SimResultsEvent{sim_time: Some(event.sim_time.clone()), success: true, event: channel_event.clone()};
-                                                    output_channel.send(sim_event).expect("could not send the event");
-                                                },
-                                                None => {}
+
+                                    // Save the commitment of the nodes that will broadcast it after it has been revoked
+                                    for n in [&channel.src_node, &channel.dest_node] {
+                                        if self.revoked_state_targets.contains(&(n.clone(), channel.id)) {
+                                            match self.get_holder_commitment(n, &res.0).await {
+                                                Some(txs) => { commitment_snapshots.insert((n.clone(), channel.id), txs); },
+                                                None => println!("could not get the commitment for {}", n)
                                             }
-                                        },
-                                        None => { println!("node status not found, not updating the network analyzer.") }
+                                        }
+                                    }
+                                    
+                                    // While a fee market is running the funding transaction waits for a block that it pays the market feerate for
+                                    // The channel is reported to the network analyzer once it is confirmed and has a short id
+                                    if market_feerate.is_some() {
+                                        pending_opens.push((channel.clone(), res.0.clone(), res.1.clone()));
+                                    } else {
+                                        self.report_open_channel(channel, &res.0, event.sim_time.clone(), &rev_channel_id_map, &channel_funding_map, &output_channel).await;
                                     }
                                 },
                                 Err(e) => {
@@ -182,7 +584,8 @@ impl SenseiController {
                                                 src_node: tx.src_node.clone(),
                                                 dest_node: tx.dest_node.clone(),
                                                 amount_sats: tx.amount_sats,
-                                                status: SimTransactionStatus::PENDING
+                                                status: SimTransactionStatus::PENDING,
+                                                keysend: false
                                             };
 
                                             // Tell the network analyzer that we sent this payment successfully (it still might fail to get received though, so it is PENDING)
@@ -208,8 +611,72 @@ impl SenseiController {
                                 }
                             }
                         },
+                        SimulationEvent::KeysendEvent(tx) => {
+                            println!("[=== SenseiController === {}] KeysendEvent for {} <-> {}", crate::get_current_time(), tx.src_node, tx.dest_node);
+                            match self.send_keysend(&tx.src_node, &tx.dest_node, tx.amount_sats).await {
+                                Ok(id) => {
+                                    // Payment was sent and now we can set the payment id of this SimTransaction
+                                    let transaction = SimTransaction {
+                                        id: Some(id.clone()),
+                                        src_node: tx.src_node.clone(),
+                                        dest_node: tx.dest_node.clone(),
+                                        amount_sats: tx.amount_sats,
+                                        status: SimTransactionStatus::PENDING,
+                                        keysend: true
+                                    };
+
+                                    // Tell the network analyzer that we sent this payment successfully (it still might fail to get received though, so it is PENDING)
+                                    let keysend_event = SimulationEvent::KeysendEvent(transaction);
+                                    let sim_event_src = SimResultsEvent{sim_time: Some(event.sim_time.clone()), success: true, event: keysend_event};
+                                    output_channel.send(sim_event_src).expect("could not send the event");
+                                },
+                                Err(e) => {
+                                    println!("could not send keysend payment: {:?}", e);
+
+                                    // Tell the network analyzer that this keysend event failed
+                                    let sim_event_src = SimResultsEvent{sim_time: Some(event.sim_time.clone()), success: false, event: event.event.clone()};
+                                    output_channel.send(sim_event_src).expect("could not send the event");
+                                }
+                            }
+                        },
+                        SimulationEvent::UpdateChannelPolicyEvent(node, id, policy) => {
+                            println!("[=== SenseiController === {}] UpdateChannelPolicyEvent for {} on {}", crate::get_current_time(), node, id);
+                            let success: bool;
+                            match channel_id_map.get(id) {
+                                Some(_) if policy.has_htlc_limits() => {
+                                    println!("could not update channel policy: htlc limits can only be set when the channel is opened");
+                                    success = false;
+                                },
+                                Some(chanid) => {
+                                    match self.set_channel_policy(node, chanid, policy).await {
+                                        Ok(()) => {
+                                            success = true;
+                                        },
+                                        Err(e) => {
+                                            println!("could not update channel policy: {:?}", e);
+                                            success = false;
+                                        }
+                                    }
+                                },
+                                None => {
+                                    println!("could not find channel.");
+                                    success = false;
+                                }
+                            }
+
+                            // Tell the network analyzer that the policy has been updated or failed to update at this time
+                            let sim_event = SimResultsEvent{sim_time: Some(event.sim_time.clone()), success: success, event: event.event.clone()};
+                            output_channel.send(sim_event).expect("could not send the event");
+                        },
                         SimulationEvent::SimulationEndedEvent => {
                             println!("[=== SenseiController === {}] SimulationEndedEvent", crate::get_current_time());
+                            // Wait for the probes that are still running, they need the nodes and the network analyzer stops after this event
+                            for task in probe_tasks.drain(..) {
+                                match task.await {
+                                    Ok(()) => {},
+                                    Err(e) => println!("could not finish probe: {:?}", e)
+                                }
+                            }
                             self.sensei_admin_service.stop_signal.store(true, Ordering::Release);
                             match self.sensei_admin_service.stop().await {
                                 Ok(_) => {},
@@ -225,6 +692,29 @@ impl SenseiController {
                             // Ignore all other events
                         }
                     }
+
+                    // Blocks could have been mined by this event, tell the network analyzer about the channels whose funding transaction is confirmed now
+                    if running && !pending_opens.is_empty() {
+                        let confirmed: Vec<(SimChannel, String, String)> = pending_opens.iter().filter(|(_, _, txid)| nigiri_controller::get_confirmations(txid) > 0).cloned().collect();
+                        if !confirmed.is_empty() {
+                            // The sensei chain manager updates once a second, wait for it to see the block so that the channels have a short id
+                            tokio::time::sleep(Duration::from_secs(2)).await;
+                        }
+                        for (channel, chanid, funding_tx) in confirmed {
+                            pending_opens.retain(|(_, _, txid)| txid != &funding_tx);
+                            self.report_open_channel(&channel, &chanid, event.sim_time.clone(), &rev_channel_id_map, &channel_funding_map, &output_channel).await;
+                        }
+                    }
+
+                    // Tell the network analyzer about the reorged transactions that are confirmed again
+                    if running && !reorged_txs.is_empty() {
+                        let confirmed: Vec<String> = reorged_txs.iter().filter(|txid| nigiri_controller::get_confirmations(txid) > 0).cloned().collect();
+                        for txid in confirmed {
+                            reorged_txs.retain(|t| t != &txid);
+                            let sim_event = SimResultsEvent{sim_time: Some(event.sim_time.clone()), success: true, event: SimulationEvent::TxConfirmedEvent(txid)};
+                            output_channel.send(sim_event).expect("could not send the event");
+                        }
+                    }
                 }
             })
         });
@@ -235,7 +725,7 @@ impl SenseiController {
      * TODO: This function is slow because creating sensei nodes is slow
      * - needs to be re-worked to speed up if the simulation framework is going to allow for large networks
      */
-    pub async fn initialize_network(&mut self, nodes: &HashMap<String, SimNode>, channels: &Vec<SimChannel>, num_nodes: u64, nigiri: bool) -> Vec<broadcast::Receiver<Event>> {
+    pub async fn initialize_network(&mut self, nodes: &HashMap<String, SimNode>, channels: &Vec<SimChannel>, num_nodes: u64, nigiri: bool) -> Vec<(String, broadcast::Receiver<Event>)> {
         println!("[=== SenseiController === {}] Creating simulation nodes", crate::get_current_time());
         let mut sim_receivers = Vec::new();
         let num = num_nodes + 1;
@@ -270,7 +760,8 @@ impl SenseiController {
                                     }
                                 }
                                 let r = node.sim_sender.subscribe();
-                                sim_receivers.push(r);
+                                sim_receivers.push((node_name.clone(), r));
+                                self.node_names.push(node_name.clone());
                             },
                             Err(e) => {
                                 println!("node not found: {}", e);
@@ -316,7 +807,8 @@ impl SenseiController {
                                     }
                                 }
                                 let r = node.sim_sender.subscribe();
-                                sim_receivers.push(r);
+                                sim_receivers.push((n.0.clone(), r));
+                                self.node_names.push(n.0.clone());
                             },
                             Err(e) => {
                                 println!("node not found: {}", e);
@@ -335,12 +827,22 @@ impl SenseiController {
         
         println!("[=== SenseiController === {}] Creating channels", crate::get_current_time());
         for c in channels {
-            match self.open_channel(&c.src_node, &c.dest_node, c.src_balance_sats, c.dest_balance_sats, c.id).await {
+            match self.open_channel(&c.src_node, &c.dest_node, c.src_balance_sats, c.dest_balance_sats, c.id, &c.src_policy, &c.dest_policy, None).await {
                 Ok(res) => {
                     // Establish the relationship between sensei channel id and sim channel id for the new channel
                     self.channel_id_map.insert(c.id, res.0.clone());
                     self.rev_channel_id_map.insert(res.0.clone(), c.id);
                     self.channel_funding_map.insert(res.0.clone(), res.1);
+
+                    // Save the commitment of the nodes that will broadcast it after it has been revoked
+                    for n in [&c.src_node, &c.dest_node] {
+                        if self.revoked_state_targets.contains(&(n.clone(), c.id)) {
+                            match self.get_holder_commitment(n, &res.0).await {
+                                Some(txs) => { self.commitment_snapshots.insert((n.clone(), c.id), txs); },
+                                None => println!("could not get the commitment for {}", n)
+                            }
+                        }
+                    }
                 },
                 Err(e) => {
                     println!("failed to open channel: {:?}", e);
@@ -407,21 +909,27 @@ impl SenseiController {
                             for chan in c.into_iter() {
                                 let id = match id_map {
                                     Some(map) => {
-                                        map.get(&chan.channel_id).unwrap().clone()
+                                        map.get(&chan.channel_id).cloned()
                                     },
                                     None => {
-                                        self.rev_channel_id_map.get(&chan.channel_id).unwrap().clone()
+                                        self.rev_channel_id_map.get(&chan.channel_id).cloned()
                                     }
                                 };
 
                                 let funding_id = match funding_map {
                                     Some(map) => {
-                                        map.get(&chan.channel_id).unwrap().clone()
+                                        map.get(&chan.channel_id).cloned()
                                     },
                                     None => {
-                                        self.channel_funding_map.get(&chan.channel_id).unwrap().clone()
+                                        self.channel_funding_map.get(&chan.channel_id).cloned()
                                     }
                                 };
+
+                                // Skip channels that are not in the maps (the node can list a channel that is still opening or already closing)
+                                let (id, funding_id) = match (id, funding_id) {
+                                    (Some(i), Some(f)) => (i, f),
+                                    _ => continue
+                                };
                                 status.channels.push(SimNodeChannel::new(id, chan.short_channel_id, 
                                     chan.channel_id.clone(), chan.confirmations_required.unwrap(), chan.is_usable, chan.is_public, chan.is_outbound, chan.balance_msat,
                                     chan.outbound_capacity_msat, chan.inbound_capacity_msat, chan.is_channel_ready, Some(funding_id), chan.unspendable_punishment_reserve));
@@ -486,6 +994,237 @@ impl SenseiController {
         }
     }
 
+    /*
+     * Check if any running node reports a channel as usable, a channel that was closed is not reported by the nodes
+     */
+    async fn is_channel_usable(&self, channel_id: &String) -> bool {
+        for name in &self.node_names {
+            match self.get_sensei_node(name).await {
+                Ok(node) => {
+                    match node.channel_manager.list_channels().iter().find(|c| &hex_utils::hex_str(&c.channel_id) == channel_id) {
+                        Some(details) => return details.is_usable,
+                        None => {}
+                    }
+                },
+                Err(_) => {}
+            }
+        }
+
+        false
+    }
+
+    /*
+     * Get a new address from a node's on-chain wallet
+     */
+    async fn get_unused_address(&self, node_name: &String) -> Result<String, Error> {
+        let node = self.get_sensei_node(node_name).await.map_err(|e| Error::Generic(String::from(e)))?;
+        match node.call(NodeRequest::GetUnusedAddress {}).await {
+            Ok(NodeResponse::GetUnusedAddress { address }) => Ok(address),
+            Err(e) => Err(Error::Generic(format!("{:?}", e))),
+            _ => Err(Error::Generic(String::from("unexpected response from get unused address")))
+        }
+    }
+
+    /*
+     * Send on-chain funds from a node's wallet to another node or to an address, returns the txid
+     * The node's wallet builds and signs the transaction, it is broadcast and mined with nigiri the same way the nodes are funded
+     * While a fee market is running the transaction is not mined right away, it waits for a block that it pays the market feerate for
+     */
+    async fn send_on_chain(&self, src_name: &String, dest: &String, amount: u64, feerate: u64, market_feerate: Option<u64>) -> Result<String, Error> {
+        let src = self.get_sensei_node(src_name).await.map_err(|e| Error::Generic(String::from(e)))?;
+        let address = if self.node_names.contains(dest) {
+            self.get_unused_address(dest).await?
+        } else {
+            dest.clone()
+        };
+        let script = match Address::from_str(&address) {
+            Ok(a) => a.script_pubkey(),
+            Err(e) => return Err(Error::Generic(format!("invalid address {}: {:?}", address, e)))
+        };
+
+        let tx = {
+            let wallet = src.wallet.lock().unwrap();
+            let mut builder = wallet.build_tx();
+            builder.add_recipient(script, amount).fee_rate(FeeRate::from_sat_per_vb(feerate as f32));
+            let (mut psbt, _) = builder.finish().map_err(|e| Error::Generic(format!("{:?}", e)))?;
+            wallet.sign(&mut psbt, SignOptions::default()).map_err(|e| Error::Generic(format!("{:?}", e)))?;
+            psbt.extract_tx()
+        };
+        nigiri_controller::send_raw_transaction(serialize_hex(&tx)).map_err(|e| Error::Generic(format!("could not broadcast the transaction: {:?}", e)))?;
+        if market_feerate.is_none() {
+            nigiri_controller::mine_blocks(1);
+        }
+        Ok(tx.txid().to_string())
+    }
+
+    /*
+     * Send a payment that the destination can not claim, the destination creates an invoice for a random payment hash that nobody knows the preimage of
+     * The destination holds the HTLC until it is failed back, returns the payment id and payment hash
+     */
+    async fn send_held_payment(&self, payment: &SimHeldPayment) -> Result<(String, PaymentHash), Error> {
+        let src = self.get_sensei_node(&payment.src_node).await.map_err(|e| Error::Generic(String::from(e)))?;
+        let dest = self.get_sensei_node(&payment.dest_node).await.map_err(|e| Error::Generic(String::from(e)))?;
+        let payment_hash = PaymentHash(rand::random::<[u8; 32]>());
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or(Duration::from_secs(0));
+        let invoice = match create_invoice_from_channelmanager_and_duration_since_epoch_with_payment_hash(&dest.channel_manager, dest.keys_manager.clone(), dest.logger.clone(), Currency::Regtest, Some(payment.amount_sats * 1000), String::from(""), now, 3600, payment_hash) {
+            Ok(i) => i,
+            Err(e) => return Err(Error::Generic(format!("{:?}", e)))
+        };
+        match src.invoice_payer.pay_invoice(&invoice) {
+            Ok(payment_id) => Ok((hex_utils::hex_str(&payment_id.0), payment_hash)),
+            Err(e) => Err(Error::Generic(format!("{:?}", e)))
+        }
+    }
+
+    /*
+     * Find the end of a channel that the prober has a usable channel with and the hops that the probes take, the probes go prober -> node -> peer
+     * Only information that other nodes could learn is used to build the probes: the capacity, the short channel id and the routing policy of the channel
+     * The last hop uses the final cltv expiry that LDK nodes require of the payments they receive
+     */
+    async fn get_probe_target(&self, prober_name: &String, channel_id: &String) -> Result<ProbeTarget, Error> {
+        let prober = self.get_sensei_node(prober_name).await.map_err(|e| Error::Generic(String::from(e)))?;
+        let prober_channels = prober.channel_manager.list_channels();
+
+        // Find the end of the channel that the prober has a usable channel with
+        for name in &self.node_names {
+            if name == prober_name {
+                continue;
+            }
+            let node = match self.get_sensei_node(name).await {
+                Ok(n) => n,
+                Err(_) => continue
+            };
+            let target = match node.channel_manager.list_channels().into_iter().find(|c| &hex_utils::hex_str(&c.channel_id) == channel_id) {
+                Some(c) => c,
+                None => continue
+            };
+            let node_id = node.channel_manager.get_our_node_id();
+            let first = match prober_channels.iter().find(|c| c.counterparty.node_id == node_id && c.is_usable) {
+                Some(c) => c,
+                None => continue
+            };
+            let first_scid = first.short_channel_id.ok_or(Error::Generic(String::from("the prober's channel has no short channel id")))?;
+            let target_scid = target.short_channel_id.ok_or(Error::Generic(String::from("the probed channel has no short channel id")))?;
+            let policy = target.config.unwrap_or(ChannelConfig::default());
+            let hops = vec![
+                RouteHop {
+                    pubkey: node_id.clone(),
+                    node_features: provided_node_features(&UserConfig::default()),
+                    short_channel_id: first_scid,
+                    channel_features: provided_channel_features(&UserConfig::default()),
+                    fee_msat: 0,
+                    cltv_expiry_delta: policy.cltv_expiry_delta as u32
+                },
+                RouteHop {
+                    pubkey: target.counterparty.node_id.clone(),
+                    node_features: provided_node_features(&UserConfig::default()),
+                    short_channel_id: target_scid,
+                    channel_features: provided_channel_features(&UserConfig::default()),
+                    fee_msat: 0,
+                    cltv_expiry_delta: MIN_FINAL_CLTV_EXPIRY
+                }
+            ];
+
+            // A probe for more than the prober can send over its own channel would fail before it got to the probed channel
+            return Ok(ProbeTarget {
+                prober: prober.clone(),
+                node: name.clone(),
+                hops: hops,
+                policy: policy,
+                max_sats: target.channel_value_satoshis.min(first.outbound_capacity_msat / 1000)
+            });
+        }
+
+        Err(Error::Generic(String::from("the prober does not have a channel to either end of the channel")))
+    }
+
+    /*
+     * Binary search the balance of one end of a channel with probes from the prober
+     * This waits for the result of each probe, so it runs on its own task
+     */
+    async fn probe_channel(target: ProbeTarget, precision: u64) -> Result<SimProbeResult, Error> {
+        // Each probe shows whether the node can send the amount over the channel
+        let mut events = target.prober.sim_sender.subscribe();
+        let target_scid = target.hops[1].short_channel_id;
+        let mut lower: u64 = 0;
+        let mut upper: u64 = target.max_sats;
+        let mut probes: u64 = 0;
+        while upper - lower > precision.max(1) {
+            let amount_msat = (lower + upper) / 2 * 1000;
+            let mut hops = target.hops.clone();
+            hops[0].fee_msat = target.policy.forwarding_fee_base_msat as u64 + amount_msat * target.policy.forwarding_fee_proportional_millionths as u64 / 1_000_000;
+            hops[1].fee_msat = amount_msat;
+            let (_, payment_id) = target.prober.channel_manager.send_probe(hops).map_err(|e| Error::Generic(format!("could not send probe: {:?}", e)))?;
+            probes = probes + 1;
+            if SenseiController::wait_for_probe(&mut events, payment_id.0, target_scid).await? {
+                lower = amount_msat / 1000;
+            } else {
+                upper = amount_msat / 1000;
+            }
+        }
+
+        Ok(SimProbeResult { node: target.node, lower_sats: lower, upper_sats: upper, probes: probes })
+    }
+
+    /*
+     * Wait for the result of a probe, returns true if the probe got through the probed channel and false if it failed there
+     */
+    async fn wait_for_probe(events: &mut broadcast::Receiver<Event>, payment_id: [u8; 32], probed_scid: u64) -> Result<bool, Error> {
+        let result = async {
+            loop {
+                match events.recv().await {
+                    Ok(Event::ProbeSuccessful { payment_id: id, .. }) if id.0 == payment_id => return Ok(true),
+                    Ok(Event::ProbeFailed { payment_id: id, short_channel_id, .. }) if id.0 == payment_id => {
+                        if short_channel_id == Some(probed_scid) {
+                            return Ok(false);
+                        }
+                        return Err(Error::Generic(String::from("the probe failed before it got to the probed channel")));
+                    },
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {},
+                    Err(_) => return Err(Error::Generic(String::from("the prober stopped")))
+                }
+            }
+        };
+        match tokio::time::timeout(Duration::from_secs(30), result).await {
+            Ok(r) => r,
+            Err(_) => Err(Error::Generic(String::from("timed out waiting for the probe result")))
+        }
+    }
+
+    /*
+     * Close the p2p connection between two nodes, the nodes keep running and their channels are unusable while they are disconnected
+     */
+    async fn disconnect_peers(&self, node_name: &String, peer_name: &String) -> Result<(), Error> {
+        let node = self.get_sensei_node(node_name).await.map_err(|e| Error::Generic(String::from(e)))?;
+        let peer_pubkey = match self.get_sensei_node_model(peer_name).await {
+            Some(model) => hex_utils::to_compressed_pubkey(&model.id),
+            None => None
+        };
+        match peer_pubkey {
+            Some(pk) => {
+                node.peer_manager.disconnect_by_node_id(pk, false);
+                Ok(())
+            },
+            None => Err(Error::Generic(String::from("peer not found")))
+        }
+    }
+
+    /*
+     * Open a p2p connection from a node to a peer
+     */
+    async fn connect_peers(&self, node_name: &String, peer_name: &String) -> Result<(), Error> {
+        let node = self.get_sensei_node(node_name).await.map_err(|e| Error::Generic(String::from(e)))?;
+        let connection = match self.get_sensei_node_model(peer_name).await {
+            Some(model) => model.id.clone() + "@" + &model.listen_addr + ":" + &model.listen_port.to_string(),
+            None => return Err(Error::Generic(String::from("peer not found")))
+        };
+        match node.call(NodeRequest::ConnectPeer { node_connection_string: connection }).await {
+            Ok(NodeResponse::ConnectPeer {}) => Ok(()),
+            Ok(_) => Err(Error::Generic(String::from("unexpected response from connect peer"))),
+            Err(e) => Err(Error::Generic(format!("{:?}", e)))
+        }
+    }
+
     /*
      * Stop a sensei node
      */
@@ -532,18 +1271,22 @@ impl SenseiController {
     }
 
     /*
-     * Close a sensei channel
+     * Close a sensei channel, a force close publishes the latest commitment of this node
+     * While a fee market is running the closing transaction is not mined right away, it waits for a block that it pays the market feerate for
      */
-    async fn close_channel(&self, node_name: &String, id: String) -> Result<(), NodeRequestError> {
+    async fn close_channel(&self, node_name: &String, id: String, force: bool, market_feerate: Option<u64>) -> Result<(), NodeRequestError> {
         match self.get_sensei_node(node_name).await {
             Ok(node) => {
                 let close_chan = NodeRequest::CloseChannel {
                     channel_id: String::from(&id),
-                    force: false,
+                    force: force,
                 };
 
                 match node.call(close_chan).await {
                     Ok(NodeResponse::CloseChannel {}) => {
+                        if market_feerate.is_some() {
+                            return Ok(());
+                        }
                         //TODO: mining should be on a separate thread and continually generating new blocks.
                         //      that will simulate accurate channel closing... you have to wait until the closing tx is included in a block
                         //      this needs to be removed, makes it easier for testing purposes right now... immediatly making the closing transaction valid by mining blocks and sleeping to let the chain manager update
@@ -561,10 +1304,80 @@ impl SenseiController {
         }
     }
 
+    /*
+     * Tell the network analyzer that a channel was opened at a sim time, with the short id and balances that the src node reports for it
+     */
+    async fn report_open_channel(&self, channel: &SimChannel, chanid: &String, sim_time: u64, rev_channel_id_map: &HashMap<String, u64>, channel_funding_map: &HashMap<String, String>, output_channel: &broadcast::Sender<SimResultsEvent>) {
+        // Get the node status from sensei and set the short id that was assigned to this channel
+        let node_status = self.get_node_status(&channel.src_node, Some(rev_channel_id_map), Some(channel_funding_map)).await;
+        match node_status {
+            Some(status) => {
+                match status.get_channel(channel.id) {
+                    Some(sc) => {
+                        // Create a new SimChannel with the same values as the "channel" variable and the short id of the "SimNodeChannel" that we got from sensei
+                        let simchan = SimChannel {
+                            id: channel.id.clone(),
+                            short_id: sc.short_id,
+                            run_time_id: Some(chanid.clone()),
+                            src_node: channel.src_node.clone(),
+                            dest_node: channel.dest_node.clone(),
+                            src_balance_sats: sc.outbound_capacity / 1000,
+                            dest_balance_sats: sc.inbound_capacity / 1000,
+                            funding_tx: channel_funding_map.get(chanid).cloned(),
+                            penalty_reserve_sats: sc.punishment_reserve,
+                            src_policy: channel.src_policy.clone(),
+                            dest_policy: channel.dest_policy.clone()
+                        };
+                        // Tell the network analyzer that this channel was opened and pass the new channel object to use
+                        let channel_event = SimulationEvent::OpenChannelEvent(simchan);
+                        let sim_event = SimResultsEvent{sim_time: Some(sim_time), success: true, event: channel_event.clone()};
+                        output_channel.send(sim_event).expect("could not send the event");
+                    },
+                    None => {}
+                }
+            },
+            None => { println!("node status not found, not updating the network analyzer.") }
+        }
+    }
+
     /*
      * Open a sensei channel
+     * The src policy is set when the channel is opened and the dest policy is set by the dest node once the channel is open
+     * While a fee market is running the funding transaction is not mined right away, it waits for a block that it pays the market feerate for
      */
-    async fn open_channel(&self, src_node_name: &String, dest_node_name: &String, src_amount: u64, dest_amount: u64, id: u64) -> Result<(String, String), Error> {
+    async fn open_channel(&self, src_node_name: &String, dest_node_name: &String, src_amount: u64, dest_amount: u64, id: u64, src_policy: &Option<SimChannelPolicy>, dest_policy: &Option<SimChannelPolicy>, market_feerate: Option<u64>) -> Result<(String, String), Error> {
+        // The htlc limits of the dest policy can only be chosen by the src node when it creates the channel, sensei's open channel request does not set them
+        let (chanid, fundingtx) = match dest_policy {
+            Some(p) if p.has_htlc_limits() => self.create_channel_with_htlc_limits(src_node_name, dest_node_name, src_amount, dest_amount, id, src_policy, p, market_feerate).await?,
+            _ => self.create_sensei_channel(src_node_name, dest_node_name, src_amount, dest_amount, id, src_policy).await?
+        };
+
+        //TODO: mining should be on a separate thread and continually generating new blocks.
+        //      that will simulate accurate channel opening... you have to wait until the funding tx is included in a block
+        //      this needs to be removed, makes it easier for testing purposes right now... immediatly making the closing transaction valid by mining blocks and sleeping to let the chain manager update
+        //      in order to be more realistic, the simulation should have to wait to see the confirmed funding transaction before using the channel
+        // The sensei chain manager updates once a second. We need to wait and make sure all funding txs are seen by the chain manager.
+        if market_feerate.is_none() {
+            nigiri_controller::mine();
+            tokio::time::sleep(Duration::from_secs(2)).await;
+        }
+        match dest_policy {
+            Some(p) => {
+                match self.set_channel_policy(dest_node_name, &chanid, p).await {
+                    Ok(()) => {},
+                    Err(e) => println!("could not set the dest node channel policy: {:?}", e)
+                }
+            },
+            None => {}
+        }
+
+        Ok((chanid, fundingtx))
+    }
+
+    /*
+     * Create a channel with a sensei open channel request, returns the channel id and the funding txid
+     */
+    async fn create_sensei_channel(&self, src_node_name: &String, dest_node_name: &String, src_amount: u64, dest_amount: u64, id: u64, src_policy: &Option<SimChannelPolicy>) -> Result<(String, String), Error> {
         let dest_pubkey: String;
         let dest_connection: String;
         match self.get_sensei_node_model(dest_node_name).await {
@@ -596,9 +1409,9 @@ impl SenseiController {
                     custom_id: Some(id),
                     push_amount_msats: Some(dest_amount*1000),
                     counterparty_host_port: Some(dest_connection),
-                    forwarding_fee_proportional_millionths: None,
-                    forwarding_fee_base_msat: None,
-                    cltv_expiry_delta: None,
+                    forwarding_fee_proportional_millionths: src_policy.as_ref().map(|p| p.fee_rate_ppm),
+                    forwarding_fee_base_msat: src_policy.as_ref().map(|p| p.base_fee_msat),
+                    cltv_expiry_delta: src_policy.as_ref().map(|p| p.cltv_expiry_delta),
                     max_dust_htlc_exposure_msat: None,
                     force_close_avoidance_max_fee_satoshis: None
                 };
@@ -608,25 +1421,11 @@ impl SenseiController {
                 };
                 match node.call(open_chan).await {
                     Ok(NodeResponse::OpenChannels {requests: _, results: r}) => {
-                        match &r[0].channel_id {
-                            Some(chanid) => {
-                                //TODO: mining should be on a separate thread and continually generating new blocks.
-                                //      that will simulate accurate channel opening... you have to wait until the funding tx is included in a block
-                                //      this needs to be removed, makes it easier for testing purposes right now... immediatly making the closing transaction valid by mining blocks and sleeping to let the chain manager update
-                                //      in order to be more realistic, the simulation should have to wait to see the confirmed funding transaction before using the channel
-                                // The sensei chain manager updates once a second. We need to wait and make sure all funding txs are seen by the chain manager.
-                                nigiri_controller::mine();
-                                tokio::time::sleep(Duration::from_secs(2)).await;
-                                match &r[0].funding_txid {
-                                    Some(fundingtx) => {
-                                        return Ok((String::from(chanid), String::from(fundingtx)));
-                                    },
-                                    None => {
-                                        return Err(Error::Generic(String::from("could not open channel for: ") + src_node_name + " " + dest_node_name));
-                                    }
-                                }
+                        match (&r[0].channel_id, &r[0].funding_txid) {
+                            (Some(chanid), Some(fundingtx)) => {
+                                return Ok((String::from(chanid), String::from(fundingtx)));
                             },
-                            None => {
+                            _ => {
                                 return Err(Error::Generic(String::from("could not open channel for: ") + src_node_name + " " + dest_node_name));
                             }
                         }
@@ -645,6 +1444,147 @@ impl SenseiController {
         }
     }
 
+    /*
+     * Create a channel with LDK directly so that the htlc limits of the dest policy are set, returns the channel id and the funding txid
+     * LDK lets the node that opens a channel choose the smallest htlc and the largest amount in flight that it accepts, these are the limits of the payments that the dest node forwards over the channel
+     * The src node creates the channel with its own config and funds it from its wallet once LDK is ready for the funding transaction
+     */
+    async fn create_channel_with_htlc_limits(&self, src_node_name: &String, dest_node_name: &String, src_amount: u64, dest_amount: u64, id: u64, src_policy: &Option<SimChannelPolicy>, dest_policy: &SimChannelPolicy, market_feerate: Option<u64>) -> Result<(String, String), Error> {
+        self.connect_peers(src_node_name, dest_node_name).await?;
+        let src = self.get_sensei_node(src_node_name).await.map_err(|e| Error::Generic(String::from(e)))?;
+        let dest_pubkey = match self.get_sensei_node_model(dest_node_name).await {
+            Some(model) => hex_utils::to_compressed_pubkey(&model.id).ok_or(Error::Generic(String::from("invalid dest node pubkey")))?,
+            None => return Err(Error::Generic(String::from("dest node not found")))
+        };
+
+        let capacity = src_amount + dest_amount;
+        let mut config = UserConfig::default();
+        config.channel_handshake_config.announced_channel = true;
+        match dest_policy.htlc_minimum_msat {
+            Some(min) => config.channel_handshake_config.our_htlc_minimum_msat = min,
+            None => {}
+        }
+        match dest_policy.get_max_in_flight_percent(capacity) {
+            Some(percent) => config.channel_handshake_config.max_inbound_htlc_value_in_flight_percent_of_channel = percent,
+            None => {}
+        }
+        match src_policy {
+            Some(p) => {
+                config.channel_config.forwarding_fee_base_msat = p.base_fee_msat;
+                config.channel_config.forwarding_fee_proportional_millionths = p.fee_rate_ppm;
+                config.channel_config.cltv_expiry_delta = p.cltv_expiry_delta;
+            },
+            None => {}
+        }
+
+        let mut ln_events = src.sim_sender.subscribe();
+        src.channel_manager.create_channel(dest_pubkey, capacity, dest_amount * 1000, id.into(), Some(config)).map_err(|e| Error::Generic(format!("could not create channel: {:?}", e)))?;
+
+        // Fund the channel from the src node's wallet, if sensei already funded it the channel keeps that funding transaction
+        let funding = async {
+            loop {
+                match ln_events.recv().await {
+                    Ok(Event::FundingGenerationReady { temporary_channel_id, counterparty_node_id, channel_value_satoshis, output_script, user_channel_id, .. }) if user_channel_id == id.into() => {
+                        let tx = {
+                            let wallet = src.wallet.lock().unwrap();
+                            let mut builder = wallet.build_tx();
+                            builder.add_recipient(output_script, channel_value_satoshis).fee_rate(FeeRate::from_sat_per_vb(market_feerate.unwrap_or(1) as f32));
+                            let (mut psbt, _) = builder.finish().map_err(|e| Error::Generic(format!("{:?}", e)))?;
+                            wallet.sign(&mut psbt, SignOptions::default()).map_err(|e| Error::Generic(format!("{:?}", e)))?;
+                            psbt.extract_tx()
+                        };
+                        match src.channel_manager.funding_transaction_generated(&temporary_channel_id, &counterparty_node_id, tx) {
+                            Ok(()) => {},
+                            Err(e) => println!("funding transaction not used: {:?}", e)
+                        }
+                        return Ok(());
+                    },
+                    Ok(_) => {},
+                    Err(e) => return Err(Error::Generic(format!("could not receive the funding event: {:?}", e)))
+                }
+            }
+        };
+        match tokio::time::timeout(Duration::from_secs(30), funding).await {
+            Ok(r) => r?,
+            Err(_) => return Err(Error::Generic(String::from("timed out waiting to fund the channel")))
+        }
+
+        // The channel id is final once the funding transaction is known
+        for _ in 0..30 {
+            for details in src.channel_manager.list_channels() {
+                if details.user_channel_id == id.into() {
+                    match details.funding_txo {
+                        Some(txo) => return Ok((hex_utils::hex_str(&details.channel_id), txo.txid.to_string())),
+                        None => {}
+                    }
+                }
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+        Err(Error::Generic(String::from("could not open channel for: ") + src_node_name + " " + dest_node_name))
+    }
+
+    /*
+     * Set the routing policy of a node for one of its channels
+     */
+    async fn set_channel_policy(&self, node_name: &String, channel_id: &String, policy: &SimChannelPolicy) -> Result<(), Error> {
+        match self.get_sensei_node(node_name).await {
+            Ok(node) => {
+                for details in node.channel_manager.list_channels() {
+                    if &hex_utils::hex_str(&details.channel_id) == channel_id {
+                        let mut config = details.config.unwrap_or(ChannelConfig::default());
+                        config.forwarding_fee_base_msat = policy.base_fee_msat;
+                        config.forwarding_fee_proportional_millionths = policy.fee_rate_ppm;
+                        config.cltv_expiry_delta = policy.cltv_expiry_delta;
+                        match node.channel_manager.update_channel_config(&details.counterparty.node_id, &[details.channel_id], &config) {
+                            Ok(()) => return Ok(()),
+                            Err(e) => return Err(Error::Generic(format!("could not update channel config: {:?}", e)))
+                        }
+                    }
+                }
+                Err(Error::Generic(String::from("channel not found: ") + channel_id))
+            },
+            Err(e) => {
+                Err(Error::Generic(String::from("node not found: ") + e))
+            }
+        }
+    }
+
+    /*
+     * Get the signed commitment transaction(s) that a node would publish to force close a channel in its current state
+     * This uses the LDK test signing path, the monitor's force close api would stop the channel from accepting any later state updates
+     */
+    #[cfg(feature = "revoked_state")]
+    async fn get_holder_commitment(&self, node_name: &String, channel_id: &String) -> Option<Vec<Transaction>> {
+        match self.get_sensei_node(node_name).await {
+            Ok(node) => {
+                for details in node.channel_manager.list_channels() {
+                    if &hex_utils::hex_str(&details.channel_id) == channel_id {
+                        return match details.funding_txo {
+                            Some(funding_txo) => {
+                                match node.chain_monitor.get_monitor(funding_txo) {
+                                    Ok(monitor) => Some(monitor.unsafe_get_latest_holder_commitment_txn(&node.logger)),
+                                    Err(_) => None
+                                }
+                            },
+                            None => None
+                        };
+                    }
+                }
+                None
+            },
+            Err(_) => None
+        }
+    }
+
+    /*
+     * The commitment can only be signed after it is revoked with the revoked_state feature, no channel gets a commitment snapshot without it
+     */
+    #[cfg(not(feature = "revoked_state"))]
+    async fn get_holder_commitment(&self, _node_name: &String, _channel_id: &String) -> Option<Vec<Transaction>> {
+        None
+    }
+
     /*
      * Create and return an invoice string for a node
      */
@@ -700,4 +1640,35 @@ impl SenseiController {
             }
         }
     }
-}
\ No newline at end of file
+
+    /*
+     * Send a spontaneous (keysend) payment from a node to the pubkey of another node
+     * The preimage is created here so the payment id is known when it is sent, the same way sensei sends keysend payments
+     * There is no invoice to give the final cltv expiry, so the one that LDK nodes require of the payments they receive is used
+     */
+    async fn send_keysend(&self, src_name: &String, dest_name: &String, amount: u64) -> Result<String, Error> {
+        // The destination does not need to be running to get its pubkey, the node id in the database is the pubkey
+        let dest_pubkey = match self.get_sensei_node_model(dest_name).await {
+            Some(model) => {
+                match hex_utils::to_compressed_pubkey(&model.id) {
+                    Some(pk) => pk,
+                    None => return Err(Error::Generic(String::from("invalid destination pubkey")))
+                }
+            },
+            None => return Err(Error::Generic(String::from("destination node not found")))
+        };
+
+        match self.get_sensei_node(src_name).await {
+            Ok(node) => {
+                let payment_preimage = PaymentPreimage(rand::random::<[u8; 32]>());
+                match node.invoice_payer.pay_pubkey(dest_pubkey, payment_preimage, amount * 1000, MIN_FINAL_CLTV_EXPIRY) {
+                    Ok(payment_id) => Ok(hex_utils::hex_str(&payment_id.0)),
+                    Err(e) => Err(Error::Generic(format!("{:?}", e)))
+                }
+            },
+            Err(e) => {
+                return Err(Error::Generic(String::from(e)));
+            }
+        }
+    }
+}
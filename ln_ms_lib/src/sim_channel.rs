@@ -14,7 +14,61 @@ pub struct SimChannel {
     pub dest_node: String, // the node that accepted the incoming channel
     pub src_balance_sats: u64, // the outbound liquidity from the source node, inbound liquidity of the dest node
     pub dest_balance_sats: u64, // the outbound liquidity from the dest node, inbound liquidity of the src node
-    pub penalty_reserve_sats: Option<u64> // the reserve amount that the src node must hold back, set by the node implementation when opening the channel
+    pub penalty_reserve_sats: Option<u64>, // the reserve amount that the src node must hold back, set by the node implementation when opening the channel
+    #[serde(default)]
+    pub src_policy: Option<SimChannelPolicy>, // the routing policy of the src node for payments forwarded from src to dest (None uses the node defaults)
+    #[serde(default)]
+    pub dest_policy: Option<SimChannelPolicy> // the routing policy of the dest node for payments forwarded from dest to src (None uses the node defaults)
+}
+
+/*
+ * The routing policy that a node sets for one direction of a channel
+ * The htlc limits are set when the channel is opened and can not be updated later, LDK only lets the node that opens a channel choose them
+ * (the smallest htlc and the largest amount in flight that the opener accepts are the limits of the payments that its peer forwards to it),
+ * so only the dest policy of a channel can have htlc limits
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimChannelPolicy {
+    pub base_fee_msat: u32, // the fixed fee charged for forwarding a payment
+    pub fee_rate_ppm: u32, // the proportional fee charged for forwarding a payment, in millionths of the amount
+    pub cltv_expiry_delta: u16, // the number of blocks added to the timelock of a forwarded payment
+    pub htlc_minimum_msat: Option<u64>, // the smallest payment that will be forwarded (None uses the LDK default of 1 msat)
+    pub htlc_maximum_msat: Option<u64> // the largest amount that can be in flight, LDK sets it as a whole percent of the channel capacity so it is rounded down (None uses the LDK default of 10%)
+}
+
+impl Default for SimChannelPolicy {
+    /*
+     * The LDK default channel config
+     */
+    fn default() -> Self {
+        SimChannelPolicy {
+            base_fee_msat: 1000,
+            fee_rate_ppm: 0,
+            cltv_expiry_delta: 72,
+            htlc_minimum_msat: None,
+            htlc_maximum_msat: None
+        }
+    }
+}
+
+impl SimChannelPolicy {
+    /*
+     * Check if this policy sets either of the htlc limits
+     */
+    pub fn has_htlc_limits(&self) -> bool {
+        self.htlc_minimum_msat.is_some() || self.htlc_maximum_msat.is_some()
+    }
+
+    /*
+     * Get the percent of a channel's capacity that can be in flight for the htlc maximum of this policy, rounded down to a whole percent between 1 and 100
+     */
+    pub fn get_max_in_flight_percent(&self, capacity_sats: u64) -> Option<u8> {
+        match self.htlc_maximum_msat {
+            Some(max) if capacity_sats > 0 => Some((max / 10 / capacity_sats).clamp(1, 100) as u8),
+            _ => None
+        }
+    }
 }
 
 impl SimChannel {
@@ -43,4 +97,32 @@ impl SimChannel {
     pub fn get_dest_balance(&self) -> u64 {
         self.dest_balance_sats
     }
+
+    /*
+     * Get the routing policy that a node has set for this channel, None if the node is not in this channel or uses the node defaults
+     */
+    pub fn get_policy(&self, node: &String) -> Option<SimChannelPolicy> {
+        if node == &self.src_node {
+            self.src_policy.clone()
+        } else if node == &self.dest_node {
+            self.dest_policy.clone()
+        } else {
+            None
+        }
+    }
+
+    /*
+     * Set the routing policy of a node in this channel, returns false if the node is not in this channel
+     */
+    pub fn set_policy(&mut self, node: &String, policy: SimChannelPolicy) -> bool {
+        if node == &self.src_node {
+            self.src_policy = Some(policy);
+            true
+        } else if node == &self.dest_node {
+            self.dest_policy = Some(policy);
+            true
+        } else {
+            false
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,211 @@
+// Project Modules
+use crate::sim_event::SimulationEvent;
+
+// Standard Modules
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Condvar};
+
+// External Modules
+use anyhow::{Result, anyhow};
+
+/*
+ * The state of a simulation that is controlled by the user while it runs
+ */
+struct ControlState {
+    paused: bool,
+    step_to: Option<u64>, // the last sim time to run before pausing again, None if the simulation is not stepping
+    current_time: u64, // the sim time that the event manager is at, the events at this time are sent once the simulation is not paused
+    next_time: u64, // the first sim time whose events have not been sent yet
+    duration: u64,
+    running: bool,
+    stopped: bool, // the user stopped the simulation, no more events are sent
+    injected: HashMap<u64, Vec<SimulationEvent>> // key=sim time, value=the events that were added while the simulation was running
+}
+
+/*
+ * SimControl is a handle to pause, resume, step, stop and add events to a running simulation from another thread
+ * Get it from LnSimulation::get_control() before running the simulation or from the SimulationHandle that start() returns, every clone controls the same simulation
+ * While the simulation is paused no events are sent but the nodes keep running, so the network can be inspected or a user-controlled node can connect to it
+ */
+#[derive(Clone)]
+pub struct SimControl {
+    state: Arc<(Mutex<ControlState>, Condvar)>
+}
+
+impl SimControl {
+    pub fn new() -> Self {
+        let control = SimControl {
+            state: Arc::new((Mutex::new(ControlState {
+                paused: false,
+                step_to: None,
+                current_time: 0,
+                next_time: 0,
+                duration: 0,
+                running: false,
+                stopped: false,
+                injected: HashMap::new()
+            }), Condvar::new()))
+        };
+
+        control
+    }
+
+    /*
+     * Pause the simulation before the next second of sim time, a simulation that is paused before it is run starts paused
+     */
+    pub fn pause(&self) {
+        let (lock, _) = &*self.state;
+        lock.lock().unwrap().paused = true;
+    }
+
+    /*
+     * Resume the simulation and stop stepping
+     */
+    pub fn resume(&self) {
+        let (lock, cvar) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        state.paused = false;
+        state.step_to = None;
+        cvar.notify_all();
+    }
+
+    /*
+     * Run the simulation until all the events up to and including a sim time have been sent, then pause it
+     * If that time has already passed the simulation pauses before the next second
+     */
+    pub fn step_to(&self, time: u64) {
+        let (lock, cvar) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        state.paused = false;
+        state.step_to = Some(time);
+        cvar.notify_all();
+    }
+
+    /*
+     * Stop the simulation before the next second of sim time, the results include the events that were sent before it stopped
+     */
+    pub fn stop(&self) {
+        let (lock, cvar) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        if state.running {
+            state.stopped = true;
+            cvar.notify_all();
+        }
+    }
+
+    /*
+     * Check if the simulation is paused
+     */
+    pub fn is_paused(&self) -> bool {
+        let (lock, _) = &*self.state;
+        lock.lock().unwrap().paused
+    }
+
+    /*
+     * Get the current sim time of the simulation
+     */
+    pub fn get_time(&self) -> u64 {
+        let (lock, _) = &*self.state;
+        lock.lock().unwrap().current_time
+    }
+
+    /*
+     * Add an event to the running simulation at a sim time, or as soon as possible if the time is None or has already passed
+     * Returns the sim time that the event will be sent at
+     * A BroadcastRevokedStateEvent can not be added, the commitment it broadcasts is only saved for the events that were scheduled before the simulation started
+     */
+    pub fn add_event(&self, event: SimulationEvent, time: Option<u64>) -> Result<u64> {
+        if !event.is_user_event() {
+            return Err(anyhow!("{} is sent by the simulation and can not be added", event));
+        }
+        match event {
+            SimulationEvent::BroadcastRevokedStateEvent(_, _) => {
+                return Err(anyhow!("{} can not be added to a running simulation, the commitment to broadcast is saved when the channel is opened so the event must be scheduled before the simulation starts", event));
+            },
+            _ => {}
+        }
+        let (lock, _) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        if !state.running {
+            return Err(anyhow!("the simulation is not running"));
+        }
+        let t = time.unwrap_or(state.next_time).max(state.next_time);
+        if t > state.duration {
+            return Err(anyhow!("{} seconds is after the end of the simulation at {} seconds", t, state.duration));
+        }
+        println!("[=== SimControl === {}] Add {} at {} seconds", crate::get_current_time(), event, t);
+        state.injected.entry(t).or_insert(Vec::new()).push(event);
+        Ok(t)
+    }
+
+    /*
+     * Called by the event manager when it starts running a simulation for a number of seconds
+     */
+    pub(crate) fn start(&self, duration: u64) {
+        let (lock, _) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        state.current_time = 0;
+        state.next_time = 0;
+        state.duration = duration;
+        state.running = true;
+        state.injected.clear();
+    }
+
+    /*
+     * Called by the event manager after the last events have been sent, events can not be added after this
+     */
+    pub(crate) fn end(&self) {
+        let (lock, _) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        state.running = false;
+        state.stopped = false;
+        state.injected.clear();
+    }
+
+    /*
+     * Called by the event manager to get the added events that should be sent at a sim time
+     */
+    pub(crate) fn take_events(&self, time: u64) -> Vec<SimulationEvent> {
+        let (lock, _) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        state.next_time = time + 1;
+        let mut times: Vec<u64> = state.injected.keys().copied().filter(|t| *t <= time).collect();
+        times.sort();
+        let mut events: Vec<SimulationEvent> = Vec::new();
+        for t in times {
+            events.append(&mut state.injected.remove(&t).unwrap());
+        }
+        events
+    }
+
+    /*
+     * Called by the event manager before it sends the events for a sim time, blocks while the simulation is paused
+     * Returns false if the simulation was stopped and the events should not be sent
+     */
+    pub(crate) fn wait(&self, time: u64) -> bool {
+        let (lock, cvar) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        state.current_time = time;
+        state.next_time = time;
+        loop {
+            if state.stopped {
+                println!("[=== SimControl === {}] Stopped at {} seconds", crate::get_current_time(), time);
+                return false;
+            }
+            match state.step_to {
+                Some(t) if time > t => {
+                    println!("[=== SimControl === {}] Paused at {} seconds", crate::get_current_time(), time);
+                    state.paused = true;
+                    state.step_to = None;
+                },
+                _ => {}
+            }
+            if !state.paused {
+                break;
+            }
+            state = cvar.wait(state).unwrap();
+        }
+
+        true
+    }
+}
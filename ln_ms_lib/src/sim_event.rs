@@ -1,27 +1,69 @@
 // Project Modules
 use crate::sim_channel::SimChannel;
+use crate::sim_channel::SimChannelPolicy;
 use crate::sim_transaction::SimTransaction;
+use crate::sim_transaction::SimFailureReason;
+use crate::sim_node_status::SimNodeStatus;
 
 // Standard Modules
+use std::collections::HashMap;
 use std::fmt;
 
+// External Modules
+use serde::{Serialize, Deserialize};
+
 /*
  * This enum represents all of the events that can be added to a simulation
  */
-#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum SimulationEvent {
     StartNodeEvent(String), // param: node name to start
     StopNodeEvent(String), // param: node name to stop
     OpenChannelEvent(SimChannel), // param: the details of the channel to open
     CloseChannelEvent(String, u64), // param: node name and simulation defined channel id of the channel to close
     TransactionEvent(SimTransaction), // param: the details of the transaction to attempt
+    KeysendEvent(SimTransaction), // param: the details of the spontaneous payment to attempt, it is sent to the destination's pubkey without an invoice
+    UpdateChannelPolicyEvent(String, u64, SimChannelPolicy), // param: node name, simulation defined channel id and the new routing policy of the node for that channel
+    ForceCloseChannelEvent(String, u64), // param: node name and simulation defined channel id of the channel to force close
+    BroadcastRevokedStateEvent(String, u64), // param: node name and simulation defined channel id, the node publishes the commitment from when the channel was opened
+    MineBlocksEvent(u64), // param: the number of blocks to mine
+    SendOnChainEvent(SimOnChainTx), // param: the details of an on-chain payment from a node's wallet, the sensei controller fills in the txid
+    FundNodeEvent(String, u64), // param: node name and the amount in sats that is sent to its on-chain wallet from outside of the simulation
+    FeeMarketBlockEvent(SimFeeMarketBlock), // param: the market feerate of the next block, the sensei controller fills in the number of transactions that were confirmed and are still waiting
+    ReorgEvent(SimReorg), // param: the number of blocks to replace with a competing chain, the sensei controller fills in the transactions and channels that were affected
+    DisconnectPeersEvent(String, String), // param: the names of two nodes that can not connect to each other until the next HealPartitionEvent, both nodes keep running
+    PartitionEvent(Vec<String>, Vec<String>), // param: two sets of node names, no node in one set can connect to a node in the other set until the next HealPartitionEvent
+    HealPartitionEvent, // every pair of nodes that was disconnected by a DisconnectPeersEvent or PartitionEvent connects again
+    HoldPaymentEvent(SimHeldPayment), // param: the details of a payment that the destination holds instead of claiming, used by jamming attacks
+    ReleaseHeldPaymentEvent(u64), // param: the id of a held payment, the destination fails it back to the sender
+    ProbeEvent(SimProbe), // param: the channel to probe and the node that probes it, the sensei controller fills in the inferred balance
+    SnapshotEvent(HashMap<String, SimNodeStatus>), // sent by the event manager every snapshot interval, the sensei controller fills in the status that each running node reports. Param: node name to status map
+    SweepEvent(String, SimSweep), // sent from ln_event_processor when a node can spend an output from a closed channel. Param: node name and the output
     PaymentPathSuccessful(SimPaymentPath), // sent from ln_event_processor when the node notifies us that a payment was successful
+    PaymentPathFailedEvent(SimPaymentFailure), // sent from ln_event_processor when an attempt to send a payment along a path failed, the node might retry along another path
     PaymentFailedEvent(String), // sent from ln_event_processor when the node notifies us that a payment failed. Param: payment id that failed
-    PaymentSuccessEvent(String, u64), // sent from ln_event_processor when the node notifies us that a payment was successful. Param: payment Id and the fee paid
+    PaymentForwardedEvent(String, SimForward), // sent from ln_event_processor when a node forwarded a payment and claimed its fee. Param: node name and the forward
+    PaymentSuccessEvent(String, u64), // sent from ln_event_processor when the node notifies us that a payment was successful. Param: payment Id and the fee paid in msat
     CloseChannelSuccessEvent(String), // sent from ln_event_processor when the node notifies us that a channel closed. Param: node implementation channel id
+    TxConfirmedEvent(String), // sent from the sensei controller when a transaction that was unconfirmed by a reorg is in a block again. Param: txid
     SimulationEndedEvent // simulation has ended
 }
 
+impl SimulationEvent {
+    /*
+     * Check if this event can be scheduled by a user, the other events are only sent by the simulation while it runs
+     */
+    pub fn is_user_event(&self) -> bool {
+        match self {
+            SimulationEvent::SnapshotEvent(_) | SimulationEvent::SweepEvent(_, _) | SimulationEvent::PaymentPathSuccessful(_) |
+            SimulationEvent::PaymentPathFailedEvent(_) | SimulationEvent::PaymentFailedEvent(_) | SimulationEvent::PaymentForwardedEvent(_, _) |
+            SimulationEvent::PaymentSuccessEvent(_, _) | SimulationEvent::CloseChannelSuccessEvent(_) | SimulationEvent::TxConfirmedEvent(_) |
+            SimulationEvent::SimulationEndedEvent => false,
+            _ => true
+        }
+    }
+}
+
 impl fmt::Display for SimulationEvent {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -30,10 +72,30 @@ impl fmt::Display for SimulationEvent {
             SimulationEvent::OpenChannelEvent(_) => write!(f, "OpenChannelEvent"),
             SimulationEvent::CloseChannelEvent(_,_) => write!(f, "CloseChannelEvent"),
             SimulationEvent::TransactionEvent(_) => write!(f, "TransactionEvent"),
+            SimulationEvent::KeysendEvent(_) => write!(f, "KeysendEvent"),
+            SimulationEvent::UpdateChannelPolicyEvent(_, _, _) => write!(f, "UpdateChannelPolicyEvent"),
+            SimulationEvent::ForceCloseChannelEvent(_, _) => write!(f, "ForceCloseChannelEvent"),
+            SimulationEvent::BroadcastRevokedStateEvent(_, _) => write!(f, "BroadcastRevokedStateEvent"),
+            SimulationEvent::MineBlocksEvent(_) => write!(f, "MineBlocksEvent"),
+            SimulationEvent::SendOnChainEvent(_) => write!(f, "SendOnChainEvent"),
+            SimulationEvent::FundNodeEvent(_, _) => write!(f, "FundNodeEvent"),
+            SimulationEvent::FeeMarketBlockEvent(_) => write!(f, "FeeMarketBlockEvent"),
+            SimulationEvent::ReorgEvent(_) => write!(f, "ReorgEvent"),
+            SimulationEvent::DisconnectPeersEvent(_, _) => write!(f, "DisconnectPeersEvent"),
+            SimulationEvent::PartitionEvent(_, _) => write!(f, "PartitionEvent"),
+            SimulationEvent::HealPartitionEvent => write!(f, "HealPartitionEvent"),
+            SimulationEvent::HoldPaymentEvent(_) => write!(f, "HoldPaymentEvent"),
+            SimulationEvent::ReleaseHeldPaymentEvent(_) => write!(f, "ReleaseHeldPaymentEvent"),
+            SimulationEvent::ProbeEvent(_) => write!(f, "ProbeEvent"),
+            SimulationEvent::SnapshotEvent(_) => write!(f, "SnapshotEvent"),
+            SimulationEvent::SweepEvent(_, _) => write!(f, "SweepEvent"),
             SimulationEvent::PaymentPathSuccessful(_) => write!(f, "PaymentPathSuccessful"),
+            SimulationEvent::PaymentPathFailedEvent(_) => write!(f, "PaymentPathFailedEvent"),
             SimulationEvent::PaymentFailedEvent(_) => write!(f, "PaymentFailedEvent"),
+            SimulationEvent::PaymentForwardedEvent(_, _) => write!(f, "PaymentForwardedEvent"),
             SimulationEvent::PaymentSuccessEvent(_, _) => write!(f, "PaymentSuccessEvent"),
             SimulationEvent::CloseChannelSuccessEvent(_) => write!(f, "CloseChannelSuccessEvent"),
+            SimulationEvent::TxConfirmedEvent(_) => write!(f, "TxConfirmedEvent"),
             SimulationEvent::SimulationEndedEvent => write!(f, "SimulationEndedEvent"),
         }
     }
@@ -42,26 +104,177 @@ impl fmt::Display for SimulationEvent {
 /*
  * The path that a successful payment took
  */
-#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct SimPaymentPath {
     pub payment_id: String,
     pub path: Vec<PathHop>
 }
 
+impl SimPaymentPath {
+    /*
+     * Get the amount that was delivered to the destination by this path, when a payment is split across several paths (MPP) this is the amount of this part
+     */
+    pub fn get_amount(&self) -> u64 {
+        match self.path.last() {
+            Some(hop) => hop.amount,
+            None => 0
+        }
+    }
+
+    /*
+     * Get the routing fees paid to the nodes along this path
+     */
+    pub fn get_fee(&self) -> u64 {
+        if self.path.is_empty() {
+            return 0;
+        }
+        self.path[..self.path.len() - 1].iter().map(|hop| hop.amount).sum()
+    }
+
+    /*
+     * Get the amount that moved through the channel of the hop at the given index, this is the amount of this part plus the fees of the hops after it
+     */
+    pub fn get_forwarded_amount(&self, index: usize) -> u64 {
+        if index >= self.path.len() {
+            return 0;
+        }
+        let fees: u64 = self.path[index..self.path.len() - 1].iter().map(|hop| hop.amount).sum();
+        self.get_amount() + fees
+    }
+
+    /*
+     * Get the amount in msat that moved through the channel of the hop at the given index
+     */
+    pub fn get_forwarded_amount_msat(&self, index: usize) -> u64 {
+        if index >= self.path.len() {
+            return 0;
+        }
+        self.path[index..].iter().map(|hop| hop.amount_msat).sum()
+    }
+}
+
+/*
+ * A payment that the destination holds until it is released, the destination does not know the preimage so it can only fail the payment back
+ * The destination's node fails the payment back on its own a few blocks before the HTLC expires
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SimHeldPayment {
+    pub id: u64, // the simulation defined id that is used to release the payment
+    pub src_node: String,
+    pub dest_node: String,
+    pub amount_sats: u64,
+    pub payment_id: Option<String>, // set to none until the payment is sent by the node
+    #[serde(default)]
+    pub resend_until: Option<u64> // the payment is sent again with the same id when it expires before this sim time, none to only send it once
+}
+
+/*
+ * A binary search for the balance of one end of a channel, each probe is a payment with a random payment hash that must fail
+ * A probe that reaches the other end of the channel is rejected there and shows that the balance is at least the amount of the probe
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SimProbe {
+    pub prober: String, // the node that sends the probes, it needs a channel to one end of the probed channel
+    pub channel_id: u64, // the simulation defined id of the probed channel
+    pub precision_sats: u64, // the search stops once the balance is known within this many sats
+    pub result: Option<SimProbeResult> // set to none until the probes are done
+}
+
+/*
+ * The balance range that a probe found
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SimProbeResult {
+    pub node: String, // the end of the channel whose balance was probed (the end that the prober has a channel with)
+    pub lower_sats: u64, // the largest probe that got through the channel
+    pub upper_sats: u64, // the smallest probe that failed at the channel, or the capacity if none did
+    pub probes: u64 // the number of probes that were sent
+}
+
+/*
+ * A path that a payment attempt failed along
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SimPaymentFailure {
+    pub payment_id: String,
+    pub path: Vec<PathHop>,
+    pub short_channel_id: Option<u64>, // the channel that the payment failed at, None if the failure could not be attributed to a channel
+    pub reason: SimFailureReason,
+    pub permanent: bool // true if the payment can not succeed along any path, so it will not be retried
+}
+
+/*
+ * A payment that a node forwarded, the channels are node implementation channel ids
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SimForward {
+    pub prev_channel_id: Option<String>, // the channel that the payment came in on
+    pub next_channel_id: Option<String>, // the channel that the payment went out on
+    pub fee_earned_msat: Option<u64>
+}
+
 /*
  * A node along a successful payment path
+ * The amount is the fee that the node took for forwarding the payment, or the amount received for the last hop
  */
-#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PathHop {
     pub short_channel_id: u64,
     pub amount: u64,
+    #[serde(default)]
+    pub amount_msat: u64, // the exact amount, the sats amount is rounded down
     pub node_pub_key: String
 }
 
+/*
+ * An output from a closed channel that a node is able to spend (sweep) to its on-chain wallet
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SimSweep {
+    pub spent_txid: String, // the transaction that created the output (a commitment, justice, htlc or cooperative close transaction)
+    pub amount_sats: u64,
+    pub to_self_delay: Option<u16> // the number of blocks the output was locked for after the commitment confirmed, None if it was not timelocked
+}
+
+/*
+ * An on-chain payment from a node's wallet, the destination is either the name of another node or an address outside of the simulation
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SimOnChainTx {
+    pub src_node: String,
+    pub dest: String, // a node name (the node's wallet gives a new address) or a bitcoin address
+    pub amount_sats: u64,
+    pub feerate_sat_per_vbyte: u64,
+    pub txid: Option<String> // set to none until the transaction is broadcast, the network analyzer looks up the fee that it pays
+}
+
+/*
+ * A block mined by the fee market, it only includes the transactions that pay at least the market feerate
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SimFeeMarketBlock {
+    pub feerate_sat_per_vbyte: u64, // the market feerate
+    pub filler_txs: u64, // the number of filler transactions that are broadcast at the market feerate before the block is mined
+    pub last: bool, // the last block of the fee market confirms every transaction that is still waiting
+    pub confirmed_txs: Option<u64>, // set to none until the block is mined
+    pub mempool_txs: Option<u64> // the number of transactions still waiting after the block, set to none until the block is mined
+}
+
+/*
+ * A reorg that replaces the last blocks of the chain with a longer chain of empty blocks
+ * The transactions in the replaced blocks go back to the mempool and are confirmed again by the next block that is mined
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SimReorg {
+    pub depth: u64, // the number of blocks that are replaced
+    pub unconfirmed_txs: Vec<String>, // the transactions that were in the replaced blocks, empty until the reorg happens
+    pub unusable_channels: Vec<u64> // the simulation defined ids of the channels whose funding transaction was unconfirmed and that the nodes no longer report as usable
+}
+
 /*
  * An event that should take place at a given time
  */
-#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct SimEvent {
     pub sim_time: u64,
     pub event: SimulationEvent
@@ -70,7 +283,7 @@ pub struct SimEvent {
 /*
  * An event that reports the results of a SimEvent taking place
  */
-#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct SimResultsEvent {
     pub sim_time: Option<u64>, // set to none for events that occur in response to another event: PaymentPathSuccessful, PaymentFailedEvent, PaymentSuccessEvent, CloseChannelSuccessEvent
     pub success: bool,
@@ -1,9 +1,12 @@
 // Project Modules
 use crate::sim_event::{SimulationEvent, SimEvent};
+use crate::traffic_generator::TrafficGenerator;
+use crate::sim_control::SimControl;
 
 // Standard Modules
 use std::collections::HashMap;
 use std::{thread, time};
+use std::sync::Mutex;
 
 // External Modules
 use tokio::sync::broadcast;
@@ -12,15 +15,20 @@ use tokio::sync::broadcast;
  * This struct holds all of the events that will take place over the duration of the simulation
  * and executes them at the correct time
  */ 
-#[derive(Clone)]
 pub struct SimEventManager {
-    events: HashMap<u64, Vec<SimulationEvent>> 
+    events: HashMap<u64, Vec<SimulationEvent>>,
+    traffic_generators: Mutex<Vec<Box<dyn TrafficGenerator>>>, // generators that create background payments while the simulation runs
+    snapshot_interval: Option<u64>, // the number of seconds between snapshots of the node state, None if no snapshots are taken
+    control: SimControl // lets the user pause, resume and step the simulation while it runs
 }
 
 impl SimEventManager {
-    pub fn new(sim_events: HashMap<u64, Vec<SimulationEvent>>) -> Self {
+    pub fn new(sim_events: HashMap<u64, Vec<SimulationEvent>>, traffic_generators: Vec<Box<dyn TrafficGenerator>>, snapshot_interval: Option<u64>, control: SimControl) -> Self {
         let event_manager = SimEventManager {
-            events: sim_events
+            events: sim_events,
+            traffic_generators: Mutex::new(traffic_generators),
+            snapshot_interval: snapshot_interval,
+            control: control
         };
 
         event_manager
@@ -38,7 +46,22 @@ impl SimEventManager {
         println!("[=== SimEventManager === {}] Running SimEventManager for {} seconds", crate::get_current_time(), duration);
         let one_sec = time::Duration::from_secs(1);
         let mut current_sec = 0;
+        self.control.start(duration);
         while current_sec <= duration {
+            // Wait here while the user has the simulation paused, end it early if the user stopped it
+            if !self.control.wait(current_sec) {
+                break;
+            }
+
+            // Take a snapshot of the node state before the events at this time
+            match self.snapshot_interval {
+                Some(interval) if interval > 0 && current_sec % interval == 0 => {
+                    let sim_event = SimEvent{sim_time: current_sec.clone(), event: SimulationEvent::SnapshotEvent(HashMap::new())};
+                    event_channel.send(sim_event).expect("could not send the event");
+                },
+                _ => {}
+            }
+
             if self.events.contains_key(&current_sec) {
                 let current_events = &self.events[&current_sec];
                 let current_events_iter = current_events.iter();
@@ -48,10 +71,32 @@ impl SimEventManager {
                 }
             }
 
+            // Send the events that were added while the simulation was running
+            for e in self.control.take_events(current_sec) {
+                let sim_event = SimEvent{sim_time: current_sec.clone(), event: e};
+                event_channel.send(sim_event).expect("could not send the event");
+            }
+
+            // Send the background payments from the traffic generators
+            for g in self.traffic_generators.lock().unwrap().iter_mut() {
+                for tx in g.generate(current_sec) {
+                    let sim_event = SimEvent{sim_time: current_sec.clone(), event: SimulationEvent::TransactionEvent(tx)};
+                    event_channel.send(sim_event).expect("could not send the event");
+                }
+            }
+
             current_sec += 1;
             thread::sleep(one_sec);
         }
-        let sim_event = SimEvent{sim_time: duration, event: SimulationEvent::SimulationEndedEvent};
+        self.control.end();
+        let sim_event = SimEvent{sim_time: current_sec.min(duration), event: SimulationEvent::SimulationEndedEvent};
         event_channel.send(sim_event).expect("could not send the simulation ended event");
     }
+
+    /*
+     * Get the traffic generators back from the event manager after the simulation has ended
+     */
+    pub fn take_traffic_generators(&self) -> Vec<Box<dyn TrafficGenerator>> {
+        std::mem::take(&mut *self.traffic_generators.lock().unwrap())
+    }
 }
\ No newline at end of file
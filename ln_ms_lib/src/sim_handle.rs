@@ -0,0 +1,171 @@
+// Project Modules
+use crate::LnSimulation;
+use crate::sim_control::SimControl;
+use crate::sim_event::{SimulationEvent, SimResultsEvent};
+use crate::sim_results::SimResults;
+use crate::sim_runtime_graph::RuntimeNetworkGraph;
+
+// Standard Modules
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// External Modules
+use anyhow::{Result, anyhow};
+use tokio::sync::{broadcast, oneshot};
+
+/*
+ * The options for running a simulation with LnSimulation::start()
+ */
+#[derive(Debug, Clone, Default)]
+pub struct RunConfig {
+    pub nigiri: bool // start and stop bitcoind with nigiri
+}
+
+/*
+ * SimulationHandle owns a simulation that was started with LnSimulation::start() and is running on its own thread
+ * The caller is not blocked, it can await the results from an async service or inspect and control the simulation while it runs
+ */
+pub struct SimulationHandle {
+    name: String,
+    control: SimControl,
+    network_graph: Arc<Mutex<RuntimeNetworkGraph>>,
+    results_events: broadcast::Sender<SimResultsEvent>,
+    finished: oneshot::Receiver<(LnSimulation, thread::Result<Result<SimResults>>)>, // the simulation is sent back with its results when it is done, or with the panic if it panicked
+    thread: thread::JoinHandle<()>
+}
+
+impl SimulationHandle {
+    pub(crate) fn new(sim: LnSimulation, config: RunConfig) -> Self {
+        let name = sim.get_name();
+        let control = sim.get_control();
+        let network_graph = sim.network_graph.clone();
+        let results_events = sim.results_events.clone();
+        let (finished_sender, finished) = oneshot::channel();
+        let thread = thread::spawn(move || {
+            let mut sim = sim;
+            let results = panic::catch_unwind(AssertUnwindSafe(|| sim.execute(config.nigiri)));
+            if finished_sender.send((sim, results)).is_err() {
+                println!("could not send the results, the simulation handle was dropped");
+            }
+        });
+
+        let handle = SimulationHandle {
+            name: name,
+            control: control,
+            network_graph: network_graph,
+            results_events: results_events,
+            finished: finished,
+            thread: thread
+        };
+
+        handle
+    }
+
+    /*
+     * Wait for the simulation to end without blocking the caller's runtime and get its results
+     */
+    pub async fn await_results(self) -> Result<SimResults> {
+        let name = self.name.clone();
+        match self.await_simulation().await {
+            Some((_, results)) => results,
+            None => Err(anyhow!("simulation {} stopped without results", name))
+        }
+    }
+
+    /*
+     * Wait for the simulation to end and get the simulation back with its results so that it can be changed or run again
+     * A panic in the simulation is returned as an error, None is only returned if the simulation thread stopped without sending the simulation back
+     */
+    pub async fn await_simulation(self) -> Option<(LnSimulation, Result<SimResults>)> {
+        let name = self.name.clone();
+        match self.finished.await {
+            Ok((sim, Ok(results))) => Some((sim, results)),
+            Ok((sim, Err(e))) => Some((sim, Err(anyhow!("simulation {} panicked: {}", name, SimulationHandle::panic_message(&e))))),
+            Err(_) => None
+        }
+    }
+
+    /*
+     * Block the current thread until the simulation ends and get the simulation back with its results
+     * The simulation is sent back even if it panicked so the caller can restore it before passing the panic on
+     */
+    pub(crate) fn join(self) -> (LnSimulation, thread::Result<Result<SimResults>>) {
+        match self.finished.blocking_recv() {
+            Ok(finished) => finished,
+            Err(_) => match self.thread.join() {
+                Ok(()) => panic!("simulation {} stopped without results", self.name),
+                Err(e) => panic::resume_unwind(e)
+            }
+        }
+    }
+
+    /*
+     * Get the message of a panic payload
+     */
+    fn panic_message(payload: &Box<dyn Any + Send>) -> String {
+        match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+            (Some(m), _) => m.to_string(),
+            (_, Some(m)) => m.clone(),
+            _ => String::from("unknown panic")
+        }
+    }
+
+    /*
+     * Get the name of the simulation
+     */
+    pub fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    /*
+     * Get the current sim time of the simulation
+     */
+    pub fn current_time(&self) -> u64 {
+        self.control.get_time()
+    }
+
+    /*
+     * Get the current network graph of the simulation
+     */
+    pub fn runtime_graph(&self) -> String {
+        self.network_graph.lock().unwrap().to_json()
+    }
+
+    /*
+     * Receive the results events (the simulation events and whether they succeeded) as the simulation runs
+     */
+    pub fn subscribe_events(&self) -> broadcast::Receiver<SimResultsEvent> {
+        self.results_events.subscribe()
+    }
+
+    /*
+     * Stop the simulation before the next second of sim time, the results of the events that were sent can still be awaited
+     */
+    pub fn stop(&self) {
+        self.control.stop();
+    }
+
+    /*
+     * Add an event to the running simulation at a sim time, or as soon as possible if the time is None or has already passed
+     * Returns the sim time that the event will be sent at
+     */
+    pub fn add_event(&self, event: SimulationEvent, time: Option<u64>) -> Result<u64> {
+        self.control.add_event(event, time)
+    }
+
+    /*
+     * Get the handle that pauses, resumes, steps and adds events to the simulation
+     */
+    pub fn get_control(&self) -> SimControl {
+        self.control.clone()
+    }
+
+    /*
+     * Check if the simulation has ended and its results are ready
+     */
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+}
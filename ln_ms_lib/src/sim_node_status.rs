@@ -1,8 +1,11 @@
+// External Modules
+use serde::{Serialize, Deserialize};
+
 /*
  * This struct represents the status of a node in the simulation as reported by the node implementation.
  * These values are set by querying the node library and getting info on the node.
  */
-#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct SimNodeStatus {
     pub pub_key: String,
     pub balance: SimNodeBalance,
@@ -34,7 +37,7 @@ impl SimNodeStatus {
 /*
  * The balance of a node as reported by the node
  */
-#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct SimNodeBalance {
     pub total: u64,
     pub onchain: u64,
@@ -56,7 +59,7 @@ impl SimNodeBalance {
 /*
  * The information about a channel as reported by the node
  */
-#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct SimNodeChannel {
     pub id: u64,
     pub short_id: Option<u64>,
@@ -1,10 +1,21 @@
 // Project Modules
 use crate::sim_transaction::SimTransaction;
+use crate::sim_transaction::SimTransactionStatus;
+use crate::sim_transaction::SimFailureReason;
 use crate::sim_channel::SimChannel;
+use crate::sim_channel::SimChannelPolicy;
 use crate::sim_event::SimResultsEvent;
+use crate::sim_event::SimulationEvent;
+use crate::sim_event::PathHop;
+use crate::network_metrics;
+use crate::network_metrics::NetworkMetrics;
+use crate::network_metrics::ReachabilityMatrix;
+use crate::sim_node_status::SimNodeStatus;
 
 // External Modules
 use build_html::{Container, ContainerType, HtmlContainer, Html};
+use serde::{Serialize, Deserialize};
+use anyhow::Result;
 
 // Standard Modules
 use std::collections::HashMap;
@@ -13,14 +24,40 @@ use std::fs;
 /*
  * The results of the simulation are stored in this struct and returned at the end of a run
  */
-#[derive(Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct SimResults {
     pub balance: BalanceResults,
     pub transactions: TxResults,
     pub channels: ChannelResults,
     pub status: StatusResults,
     pub failed_events: Vec<SimResultsEvent>,
-    pub event_times: Vec<u64>
+    pub event_times: Vec<u64>,
+    #[serde(default)]
+    pub force_closes: Vec<ForceClose>,
+    #[serde(default)]
+    pub forwarding: ForwardingResults,
+    #[serde(default)]
+    pub metrics: HashMap<u64, NetworkMetrics>, // key=sim time, value=graph metrics of the network at that time
+    #[serde(default)]
+    pub invariant_violations: Vec<InvariantViolation>, // only recorded when invariant checking is turned on
+    #[serde(default)]
+    pub snapshots: HashMap<u64, HashMap<String, SimNodeStatus>>, // key=sim time, value=(key=node name, value=the state that the node reported), only recorded when a snapshot interval is set
+    #[serde(default)]
+    pub partitions: Vec<Partition>,
+    #[serde(default)]
+    pub jamming: JammingResults,
+    #[serde(default)]
+    pub probes: Vec<BalanceProbe>,
+    #[serde(default)]
+    pub on_chain_txs: Vec<OnChainTx>, // on-chain payments between wallets and funds sent to the nodes from outside of the simulation
+    #[serde(default)]
+    pub fee_market: Vec<FeeMarketBlock>, // the blocks mined by the fee market, only recorded when a fee market is running
+    #[serde(default)]
+    pub channel_fees: Vec<ChannelFee>, // the on-chain fees paid to open and close the channels
+    #[serde(default)]
+    pub reorgs: Vec<Reorg>,
+    #[serde(default)]
+    pub triggers: Vec<FiredTrigger> // the triggers that fired and the events that they added
 }
 
 impl SimResults {
@@ -28,15 +65,44 @@ impl SimResults {
         let r = SimResults {
             balance: BalanceResults { on_chain: HashMap::new(), off_chain: HashMap::new() },
             transactions: TxResults { txs: Vec::new() },
-            channels: ChannelResults { open_channels: HashMap::new(), closed_channels: HashMap::new() },
+            channels: ChannelResults { open_channels: HashMap::new(), closed_channels: HashMap::new(), policy_updates: Vec::new() },
             status: StatusResults { nodes: HashMap::new() },
             failed_events: Vec::new(),
-            event_times: Vec::new()
+            event_times: Vec::new(),
+            force_closes: Vec::new(),
+            forwarding: ForwardingResults { forwards: Vec::new(), failed_forwards: Vec::new() },
+            metrics: HashMap::new(),
+            invariant_violations: Vec::new(),
+            snapshots: HashMap::new(),
+            partitions: Vec::new(),
+            jamming: JammingResults { htlcs: Vec::new() },
+            probes: Vec::new(),
+            on_chain_txs: Vec::new(),
+            fee_market: Vec::new(),
+            channel_fees: Vec::new(),
+            reorgs: Vec::new(),
+            triggers: Vec::new()
         };
 
         r
     }
 
+    /*
+     * Load the results of a previous simulation from a json string
+     */
+    pub fn from_json(json: &str) -> Result<SimResults> {
+        let results: SimResults = serde_json::from_str(json)?;
+        Ok(results)
+    }
+
+    /*
+     * Get the results of this simulation as a json string that can be saved and loaded later
+     */
+    pub fn to_json(&self) -> Result<String> {
+        let json = serde_json::to_string_pretty(self)?;
+        Ok(json)
+    }
+
     /*
      * Get the on chain balance of a node at a given time in the simulation
      */
@@ -119,6 +185,43 @@ impl SimResults {
         Some(filtered_txs)
     }
 
+    /*
+     * Get all the transactions that failed
+     */
+    pub fn get_failed_transactions(&self) -> Vec<Tx> {
+        self.transactions.txs.iter().filter(|t| matches!(t.transaction.status, SimTransactionStatus::FAILED(_))).cloned().collect()
+    }
+
+    /*
+     * Get the number of failed payment attempts for each failure reason
+     */
+    pub fn get_failure_reason_counts(&self) -> HashMap<SimFailureReason, u64> {
+        let mut counts: HashMap<SimFailureReason, u64> = HashMap::new();
+        for t in &self.transactions.txs {
+            for a in &t.failed_attempts {
+                *counts.entry(a.reason).or_insert(0) += 1;
+            }
+        }
+
+        counts
+    }
+
+    /*
+     * Get the number of failed payment attempts at each channel (key=short channel id)
+     */
+    pub fn get_failing_channel_counts(&self) -> HashMap<u64, u64> {
+        let mut counts: HashMap<u64, u64> = HashMap::new();
+        for t in &self.transactions.txs {
+            for a in &t.failed_attempts {
+                if let Some(scid) = a.short_channel_id {
+                    *counts.entry(scid).or_insert(0) += 1;
+                }
+            }
+        }
+
+        counts
+    }
+
     /*
      * Get all the transactions from the simulation
      */
@@ -156,6 +259,398 @@ impl SimResults {
         }
     }
 
+    /*
+     * Get the routing policy that a node had set for a channel at a given time in the simulation
+     * Returns None if the channel was not open or the node was using its default policy
+     */
+    pub fn get_channel_policy(&self, time: u64, channel_id: u64, node: &String) -> Option<SimChannelPolicy> {
+        match self.get_open_channels(time) {
+            Some(channels) => {
+                for c in channels {
+                    if c.id == channel_id {
+                        return c.get_policy(node);
+                    }
+                }
+                None
+            },
+            None => None
+        }
+    }
+
+    /*
+     * Get all the routing policy changes that were made during the simulation
+     */
+    pub fn get_policy_updates(&self) -> Vec<ChannelPolicyUpdate> {
+        self.channels.policy_updates.clone()
+    }
+
+    /*
+     * Get all the force closes and revoked state broadcasts that took place during the simulation
+     */
+    pub fn get_force_closes(&self) -> Vec<ForceClose> {
+        self.force_closes.clone()
+    }
+
+    /*
+     * Get the forwarding stats of a routing node from the start of the simulation up to a given time
+     */
+    pub fn get_node_forwarding_stats(&self, time: u64, node: &String) -> ForwardingStats {
+        let mut stats = ForwardingStats::default();
+        for f in self.forwarding.forwards.iter().filter(|f| f.time <= time && &f.node == node) {
+            stats.forwards += 1;
+            stats.volume_sats += f.amount_out_sats;
+            stats.fee_sats += f.fee_sats;
+        }
+        stats.failed_forwards = self.forwarding.failed_forwards.iter().filter(|f| f.time <= time && &f.node == node).count() as u64;
+
+        stats
+    }
+
+    /*
+     * Get the forwarding stats of a channel from the start of the simulation up to a given time
+     * A forward counts for both the incoming and outgoing channel, the fee is credited to the outgoing channel (the one the node charges for)
+     */
+    pub fn get_channel_forwarding_stats(&self, time: u64, channel_id: u64) -> ForwardingStats {
+        let mut stats = ForwardingStats::default();
+        for f in self.forwarding.forwards.iter().filter(|f| f.time <= time) {
+            if f.channel_in == Some(channel_id) {
+                stats.forwards += 1;
+                stats.volume_sats += f.amount_in_sats;
+            }
+            if f.channel_out == Some(channel_id) {
+                stats.forwards += 1;
+                stats.volume_sats += f.amount_out_sats;
+                stats.fee_sats += f.fee_sats;
+            }
+        }
+        stats.failed_forwards = self.forwarding.failed_forwards.iter().filter(|f| f.time <= time && f.channel_out == Some(channel_id)).count() as u64;
+
+        stats
+    }
+
+    /*
+     * Get the total fees that a routing node has earned at each event time
+     */
+    pub fn get_node_fee_revenue(&self, node: &String) -> Vec<(u64, u64)> {
+        let mut times = self.event_times.clone();
+        times.sort();
+        times.iter().map(|t| (*t, self.get_node_forwarding_stats(*t, node).fee_sats)).collect()
+    }
+
+    /*
+     * Get the forwards of a routing node in the same format as lncli fwdinghistory, the timestamps are sim times
+     */
+    pub fn get_forwarding_history(&self, node: &String) -> Result<String> {
+        let mut events: Vec<serde_json::Value> = Vec::new();
+        for f in self.forwarding.forwards.iter().filter(|f| &f.node == node) {
+            let fee_msat = f.get_fee_msat();
+            events.push(serde_json::json!({
+                "timestamp": f.time.to_string(),
+                "chan_id_in": f.short_channel_id_in.to_string(),
+                "chan_id_out": f.short_channel_id_out.to_string(),
+                "amt_in": f.amount_in_sats.to_string(),
+                "amt_out": f.amount_out_sats.to_string(),
+                "fee": (fee_msat / 1000).to_string(),
+                "fee_msat": fee_msat.to_string(),
+                "amt_in_msat": (f.amount_in_sats * 1000).to_string(),
+                "amt_out_msat": (f.amount_out_sats * 1000).to_string(),
+                "timestamp_ns": (f.time as u128 * 1_000_000_000).to_string()
+            }));
+        }
+        let history = serde_json::json!({
+            "last_offset_index": events.len(),
+            "forwarding_events": events
+        });
+
+        Ok(serde_json::to_string_pretty(&history)?)
+    }
+
+    /*
+     * Get the graph metrics of the network at a given time in the simulation
+     */
+    pub fn get_network_metrics(&self, time: u64) -> Option<NetworkMetrics> {
+        let keys: Vec<&u64> = self.metrics.keys().collect();
+        match SimResults::find_closest_less(keys, &time) {
+            Some(k) => Some(self.metrics.get(&k).unwrap().clone()),
+            None => None
+        }
+    }
+
+    /*
+     * Get a time series of one of the network metrics, for example get_metric_series(|m| m.components)
+     */
+    pub fn get_metric_series<T, F: Fn(&NetworkMetrics) -> T>(&self, metric: F) -> Vec<(u64, T)> {
+        let mut times: Vec<u64> = self.metrics.keys().copied().collect();
+        times.sort();
+        times.iter().map(|t| (*t, metric(self.metrics.get(t).unwrap()))).collect()
+    }
+
+    /*
+     * Get the maximum amount that can be sent between pairs of the online nodes at a given time, using the balances of the channels that are not partitioned at that time
+     * This can take a long time for large networks, set sample_pairs to only analyze that many random pairs (picked with the seed)
+     */
+    pub fn get_reachability(&self, time: u64, sample_pairs: Option<usize>, seed: u64) -> ReachabilityMatrix {
+        let mut nodes = self.get_nodes();
+        nodes.sort();
+        let online: Vec<String> = nodes.into_iter().filter(|n| self.get_node_status(time, n)).collect();
+        let channels = self.get_usable_channels(time);
+        network_metrics::compute_reachability(time, &online, &channels, sample_pairs, seed)
+    }
+
+    /*
+     * Get the balance invariants that did not hold during the simulation and the events that broke them
+     */
+    pub fn get_invariant_violations(&self) -> Vec<InvariantViolation> {
+        self.invariant_violations.clone()
+    }
+
+    /*
+     * Get the last state that a node reported at or before a given time in the simulation
+     * Returns None if no snapshot was taken yet or the node was stopped when the snapshot was taken
+     */
+    pub fn get_node_snapshot(&self, time: u64, node: &String) -> Option<SimNodeStatus> {
+        let keys: Vec<&u64> = self.snapshots.keys().collect();
+        match SimResults::find_closest_less(keys, &time) {
+            Some(k) => self.snapshots.get(&k).unwrap().get(node).cloned(),
+            None => None
+        }
+    }
+
+    /*
+     * Get a time series of a value from the snapshots of a node, for example get_snapshot_series(&node, |s| s.balance.offchain)
+     */
+    pub fn get_snapshot_series<T, F: Fn(&SimNodeStatus) -> T>(&self, node: &String, value: F) -> Vec<(u64, T)> {
+        let mut times: Vec<u64> = self.snapshots.keys().copied().collect();
+        times.sort();
+        times.iter().filter_map(|t| self.snapshots.get(t).unwrap().get(node).map(|s| (*t, value(s)))).collect()
+    }
+
+    /*
+     * Get the difference between the balances that a node reported and the balances in the results at each snapshot time
+     * Each value is (time, reported on-chain - on-chain in the results, reported off-chain - off-chain in the results)
+     */
+    pub fn get_balance_drift(&self, node: &String) -> Vec<(u64, i64, i64)> {
+        self.get_snapshot_series(node, |s| (s.balance.onchain, s.balance.offchain)).into_iter().map(|(t, (on_chain, off_chain))| {
+            let on_chain_results = self.get_on_chain_bal(t, node).unwrap_or(0);
+            let off_chain_results = self.get_off_chain_bal(t, node).unwrap_or(0);
+            (t, on_chain as i64 - on_chain_results as i64, off_chain as i64 - off_chain_results as i64)
+        }).collect()
+    }
+
+    /*
+     * Get the times that nodes were disconnected from each other while they were running
+     */
+    pub fn get_partitions(&self) -> Vec<Partition> {
+        self.partitions.clone()
+    }
+
+    /*
+     * Get whether two nodes were kept from connecting to each other at a given time in the simulation
+     */
+    pub fn is_partitioned(&self, time: u64, node_a: &String, node_b: &String) -> bool {
+        self.partitions.iter().any(|p| p.is_active(time) && p.links.iter().any(|(a, b)| (a == node_a && b == node_b) || (a == node_b && b == node_a)))
+    }
+
+    /*
+     * Get the open channels that could not be used at a given time because the two nodes were disconnected
+     */
+    pub fn get_partitioned_channels(&self, time: u64) -> Vec<SimChannel> {
+        match self.get_open_channels(time) {
+            Some(channels) => channels.into_iter().filter(|c| self.is_partitioned(time, &c.src_node, &c.dest_node)).collect(),
+            None => Vec::new()
+        }
+    }
+
+    /*
+     * Get the open channels at a given time whose nodes can connect to each other, the channels cut by a partition are left out
+     */
+    pub fn get_usable_channels(&self, time: u64) -> Vec<SimChannel> {
+        match self.get_open_channels(time) {
+            Some(channels) => channels.into_iter().filter(|c| !self.is_partitioned(time, &c.src_node, &c.dest_node)).collect(),
+            None => Vec::new()
+        }
+    }
+
+    /*
+     * Get the payments that jamming attackers sent and held
+     */
+    pub fn get_jammed_htlcs(&self) -> Vec<JammedHtlc> {
+        self.jamming.htlcs.clone()
+    }
+
+    /*
+     * Get the number of HTLC slots that held payments were using in each channel at a given time (key=channel id)
+     * An LDK node accepts up to MAX_ACCEPTED_HTLCS on each channel, payments fail once the slots are full
+     */
+    pub fn get_htlc_slot_occupancy(&self, time: u64) -> HashMap<u64, u64> {
+        let mut occupancy: HashMap<u64, u64> = HashMap::new();
+        for h in self.jamming.htlcs.iter().filter(|h| h.is_held(time)) {
+            for c in &h.channels {
+                *occupancy.entry(*c).or_insert(0) += 1;
+            }
+        }
+
+        occupancy
+    }
+
+    /*
+     * Get the most HTLC slots that held payments used in each channel at any time in the simulation, as a percentage of MAX_ACCEPTED_HTLCS (key=channel id)
+     */
+    pub fn get_peak_htlc_slot_occupancy(&self) -> HashMap<u64, f64> {
+        let mut peaks: HashMap<u64, f64> = HashMap::new();
+        for h in &self.jamming.htlcs {
+            for (c, slots) in self.get_htlc_slot_occupancy(h.sent) {
+                let percent = slots as f64 * 100.0 / MAX_ACCEPTED_HTLCS as f64;
+                let peak = peaks.entry(c).or_insert(0.0);
+                if percent > *peak {
+                    *peak = percent;
+                }
+            }
+        }
+
+        peaks
+    }
+
+    /*
+     * Get the liquidity (sats) that held payments were locking up at a given time, a payment locks its amount in every channel along its path
+     */
+    pub fn get_jammed_liquidity(&self, time: u64) -> u64 {
+        self.jamming.htlcs.iter().filter(|h| h.is_held(time)).map(|h| h.amount_sats * h.channels.len() as u64).sum()
+    }
+
+    /*
+     * Get the jammed liquidity at each event time in the simulation
+     */
+    pub fn get_jammed_liquidity_series(&self) -> Vec<(u64, u64)> {
+        let mut times = self.event_times.clone();
+        times.sort();
+        times.iter().map(|t| (*t, self.get_jammed_liquidity(*t))).collect()
+    }
+
+    /*
+     * Get the percentage of the payments sent between two times (inclusive) that failed, None if none of them have finished
+     * Jamming payments are not transactions, so this is the failure rate of the honest payments
+     */
+    pub fn get_honest_failure_rate(&self, from: u64, to: u64) -> Option<f64> {
+        let finished: Vec<&Tx> = self.transactions.txs.iter()
+            .filter(|t| t.time >= from && t.time <= to)
+            .filter(|t| matches!(t.transaction.status, SimTransactionStatus::SUCCESSFUL | SimTransactionStatus::FAILED(_)))
+            .collect();
+        if finished.is_empty() {
+            return None;
+        }
+        let failed = finished.iter().filter(|t| matches!(t.transaction.status, SimTransactionStatus::FAILED(_))).count();
+        Some(failed as f64 * 100.0 / finished.len() as f64)
+    }
+
+    /*
+     * Get the channel balances that were inferred by probing
+     */
+    pub fn get_probes(&self) -> Vec<BalanceProbe> {
+        self.probes.clone()
+    }
+
+    /*
+     * Get how accurately the probes inferred the channel balances compared to the balances in the open channels, None if there were no probes
+     */
+    pub fn get_probe_accuracy(&self) -> Option<ProbeAccuracy> {
+        if self.probes.is_empty() {
+            return None;
+        }
+        let count = self.probes.len() as f64;
+        let mean_error_sats = self.probes.iter().map(|p| p.get_error_sats().abs() as f64).sum::<f64>() / count;
+        let mean_error_percent = self.probes.iter().map(|p| p.get_error_sats().abs() as f64 * 100.0 / p.capacity_sats.max(1) as f64).sum::<f64>() / count;
+        let within_range = self.probes.iter().filter(|p| p.actual_sats >= p.lower_sats && p.actual_sats <= p.upper_sats).count() as f64;
+        let mean_probes = self.probes.iter().map(|p| p.probes as f64).sum::<f64>() / count;
+        Some(ProbeAccuracy {
+            channels_probed: self.probes.len() as u64,
+            mean_error_sats: mean_error_sats,
+            mean_error_percent: mean_error_percent,
+            within_range_percent: within_range * 100.0 / count,
+            mean_probes: mean_probes
+        })
+    }
+
+    /*
+     * Get the on-chain payments and funding that happened during the simulation
+     */
+    pub fn get_on_chain_txs(&self) -> Vec<OnChainTx> {
+        self.on_chain_txs.clone()
+    }
+
+    /*
+     * Get the on-chain payments that a node sent or received
+     */
+    pub fn get_node_on_chain_txs(&self, node: &String) -> Vec<OnChainTx> {
+        self.on_chain_txs.iter().filter(|t| t.src_node.as_ref() == Some(node) || &t.dest == node).cloned().collect()
+    }
+
+    /*
+     * Get the blocks that the fee market mined
+     */
+    pub fn get_fee_market_blocks(&self) -> Vec<FeeMarketBlock> {
+        self.fee_market.clone()
+    }
+
+    /*
+     * Get the market feerate (sats per vbyte) of the last block mined at or before a time, None if the fee market did not mine a block yet or has stopped
+     */
+    pub fn get_market_feerate(&self, time: u64) -> Option<u64> {
+        match self.fee_market.iter().filter(|b| b.time <= time).max_by_key(|b| b.time) {
+            Some(b) if !b.last => Some(b.feerate_sat_per_vbyte),
+            _ => None
+        }
+    }
+
+    /*
+     * Get the on-chain fees that were paid to open and close the channels
+     */
+    pub fn get_channel_fees(&self) -> Vec<ChannelFee> {
+        self.channel_fees.clone()
+    }
+
+    /*
+     * Get the mean on-chain fee and the mean number of seconds it took to confirm for one type of channel transaction, None if there were none
+     */
+    pub fn get_mean_channel_fee(&self, fee_type: ChannelFeeType) -> Option<(f64, f64)> {
+        let fees: Vec<&ChannelFee> = self.channel_fees.iter().filter(|f| f.fee_type == fee_type).collect();
+        if fees.is_empty() {
+            return None;
+        }
+        let count = fees.len() as f64;
+        let mean_fee = fees.iter().map(|f| f.fee_sats as f64).sum::<f64>() / count;
+        let mean_delay = fees.iter().map(|f| f.get_delay() as f64).sum::<f64>() / count;
+        Some((mean_fee, mean_delay))
+    }
+
+    /*
+     * Get the reorgs that happened during the simulation
+     */
+    pub fn get_reorgs(&self) -> Vec<Reorg> {
+        self.reorgs.clone()
+    }
+
+    /*
+     * Get the ids of the channels that a reorg made unusable and that were not re-confirmed by the end of the simulation
+     */
+    pub fn get_unusable_channels(&self) -> Vec<u64> {
+        let mut ids: Vec<u64> = self.reorgs.iter()
+            .flat_map(|r| r.channels.iter())
+            .filter(|c| c.unusable && c.reconfirmed.is_none())
+            .map(|c| c.channel_id)
+            .collect();
+        ids.sort();
+        ids.dedup();
+        ids
+    }
+
+    /*
+     * Get the times that a trigger fired and the events that it added
+     */
+    pub fn get_fired_triggers(&self, name: &String) -> Vec<FiredTrigger> {
+        self.triggers.iter().filter(|t| &t.name == name).cloned().collect()
+    }
+
     /*
      * Get the on/off status of a node at a given time in the simulation
      */
@@ -190,6 +685,38 @@ impl SimResults {
         }
     }
 
+    /*
+     * Get the percentage of the simulation (0 to 100) that a node was online, the simulation ends at the last event time
+     * Returns None if the node was not found
+     */
+    pub fn get_node_availability(&self, node: &String) -> Option<f64> {
+        let status = self.status.nodes.get(node)?;
+        let end = self.event_times.iter().copied().max().unwrap_or(0);
+        if end == 0 {
+            return Some(if self.get_node_status(0, node) { 100.0 } else { 0.0 });
+        }
+
+        // Add up the time between each status change that the node was online
+        let mut times: Vec<u64> = status.keys().copied().filter(|t| *t < end).collect();
+        times.sort();
+        let mut online: u64 = 0;
+        for (i, t) in times.iter().enumerate() {
+            let next = if i + 1 < times.len() { times[i + 1] } else { end };
+            if status[t] {
+                online = online + (next - t);
+            }
+        }
+
+        Some(online as f64 / end as f64 * 100.0)
+    }
+
+    /*
+     * Get the percentage of the simulation that each node was online (key=node name, value=availability from 0 to 100)
+     */
+    pub fn get_availability(&self) -> HashMap<String, f64> {
+        self.get_nodes().iter().filter_map(|n| self.get_node_availability(n).map(|a| (n.clone(), a))).collect()
+    }
+
     /*
      * Get the results of this simulation formatted in an HTML webpage
      */
@@ -247,7 +774,9 @@ impl SimResults {
         }
 
         // Read in the template HTML page and replace the relevant sections with the sections created above
-        let template = fs::read_to_string("/home/blake/Projects/ln-ms-framework/ln_ms_lib/src/results_html/index.html");
+        let this_file = file!();
+        let template_file = this_file.replace("sim_results.rs", "results_html/index.html");
+        let template = fs::read_to_string(template_file);
         match template {
             Ok(html_page) => {
                 let page1 = html_page.as_str().replace("<!--NODE_LIST-->", node_list.to_html_string().as_str());
@@ -264,6 +793,39 @@ impl SimResults {
 
     }
 
+    /*
+     * Get the results of this simulation formatted as csv
+     * Each row is the state of a node at a time in the simulation where an event occurred
+     */
+    pub fn get_results_csv(&self) -> String {
+        let mut csv = String::from("time,node,status,on_chain_balance,off_chain_balance,open_channels\n");
+        let mut times = self.event_times.clone();
+        times.sort();
+        let mut nodes = self.get_nodes();
+        nodes.sort();
+        for time in &times {
+            let open_channels = match self.get_open_channels(time.clone()) {
+                Some(channels) => channels,
+                None => Vec::new()
+            };
+            for node in &nodes {
+                let status = if self.get_node_status(time.clone(), node) {"ONLINE"} else {"OFFLINE"};
+                let onchain = match self.get_on_chain_bal(time.clone(), node) {
+                    Some(b) => {b},
+                    None => 0
+                };
+                let offchain = match self.get_off_chain_bal(time.clone(), node) {
+                    Some(b) => {b},
+                    None => 0
+                };
+                let num_channels = open_channels.iter().filter(|&c| &c.src_node == node || &c.dest_node == node).count();
+                csv = csv + &format!("{time},{node},{status},{onchain},{offchain},{num_channels}\n");
+            }
+        }
+
+        csv
+    }
+
     /*
      * Get an html element containing a formatted string with the details of a given node at a given time
      */
@@ -297,8 +859,24 @@ impl SimResults {
             None => {}
         };
         
+        // Get the payments this node has forwarded up to this time in the simulation
+        let fwd = self.get_node_forwarding_stats(time.clone(), node);
+        let forwarding = format!("{} forwarded ({} sats), {} sats in fees, {} failed", fwd.forwards, fwd.volume_sats, fwd.fee_sats, fwd.failed_forwards);
+
+        // Get the balances that the node reported in the last snapshot
+        let reported = match self.get_node_snapshot(time.clone(), node) {
+            Some(s) => format!("{} on-chain, {} LN, {} channels", s.balance.onchain, s.balance.offchain, s.channels.len()),
+            None => String::from("")
+        };
+
+        // Get the percentage of the whole simulation that the node was online
+        let availability = match self.get_node_availability(node) {
+            Some(a) => format!("{:.1}%", a),
+            None => String::from("")
+        };
+
         // Create the container
-        let desc = format!("TIME: {time}\n\nSTATUS: {status}\n\nONCHAIN BALANCE: {onchain}\n\nLN BALANCE: {offchain}\n\nCHANNELS: \n\t{channels_string}");
+        let desc = format!("TIME: {time}\n\nSTATUS: {status} (online {availability} of the simulation)\n\nONCHAIN BALANCE: {onchain}\n\nLN BALANCE: {offchain}\n\nREPORTED BY NODE: {reported}\n\nFORWARDING: {forwarding}\n\nCHANNELS: \n\t{channels_string}");
         let node_details = Container::new(ContainerType::Div)
                                         .with_attributes([("class", "details")])
                                         .with_paragraph_attr(format!("NODE: {node}"), [("id", title_id.as_str())])
@@ -331,7 +909,13 @@ impl SimResults {
         for t in self.get_all_transactions().unwrap() {
             if t.time == time.clone() {
                 let amount = t.transaction.amount_sats.clone();
-                transactions = transactions + &t.transaction.src_node + " " + &String::from("&#8594") + " " + &t.transaction.dest_node + " (" + &format!("{amount}") + ")\n\t";
+                let keysend = if t.transaction.keysend { " keysend" } else { "" };
+                let parts = if t.is_multi_path() { format!(" in {} parts", t.parts.len()) } else { String::from("") };
+                let failed = match &t.transaction.status {
+                    SimTransactionStatus::FAILED(reason) => format!(" failed: {:?} after {} retries", reason, t.get_retry_count()),
+                    _ => String::from("")
+                };
+                transactions = transactions + &t.transaction.src_node + " " + &String::from("&#8594") + " " + &t.transaction.dest_node + " (" + &format!("{amount}") + keysend + &parts + &failed + ")\n\t";
             }
         }
 
@@ -348,6 +932,89 @@ impl SimResults {
             None => {}
         }
 
+        // Get the routing policy changes that occurred at this time in the simulation
+        let mut policies = String::from("");
+        for p in &self.channels.policy_updates {
+            if p.time == time.clone() {
+                policies = policies + &p.node + " channel " + &format!("{}", p.channel_id) + " (" + &format!("base {} msat, {} ppm, cltv delta {}", p.policy.base_fee_msat, p.policy.fee_rate_ppm, p.policy.cltv_expiry_delta) + ")\n\t";
+            }
+        }
+
+        // Get the force closes that occurred at this time in the simulation
+        let mut forceclosed = String::from("");
+        for c in &self.force_closes {
+            if c.time == time.clone() {
+                forceclosed = forceclosed + &c.initiator + " " + &String::from("&#8594") + " " + &c.counterparty + " (" + &format!("{:?}", c.close_type) + ")\n\t";
+            }
+        }
+
+        // Get the partitions that started or were healed at this time in the simulation
+        let mut partitions = String::from("");
+        for p in &self.partitions {
+            if p.time == time.clone() {
+                partitions = partitions + &format!("{} links cut: ", p.links.len()) + &p.links.iter().map(|(a, b)| format!("{} / {}", a, b)).collect::<Vec<String>>().join(", ") + "\n\t";
+            }
+            if p.healed == Some(time.clone()) {
+                partitions = partitions + &format!("{} links healed", p.links.len()) + "\n\t";
+            }
+        }
+
+        // Get the channels that were probed at this time in the simulation
+        let mut probes = String::from("");
+        for p in &self.probes {
+            if p.time == time.clone() {
+                probes = probes + &format!("{} probed {} in channel {}: {}-{} sats (actual {} sats, {} probes)", p.prober, p.node, p.channel_id, p.lower_sats, p.upper_sats, p.actual_sats, p.probes) + "\n\t";
+            }
+        }
+
+        // Get the on-chain payments that were sent at this time in the simulation
+        let mut onchain = String::from("");
+        for t in &self.on_chain_txs {
+            if t.time == time.clone() {
+                let src = match &t.src_node {
+                    Some(n) => n.clone(),
+                    None => String::from("faucet")
+                };
+                onchain = onchain + &src + " " + &String::from("&#8594") + " " + &t.dest + " (" + &format!("{} sats, {} sats fee", t.amount_sats, t.fee_sats) + ")\n\t";
+            }
+        }
+
+        // Get the block that the fee market mined at this time in the simulation
+        let feemarket = match self.fee_market.iter().find(|b| b.time == time.clone()) {
+            Some(b) => format!("{} sats per vbyte, {} transactions confirmed, {} waiting{}", b.feerate_sat_per_vbyte, b.confirmed_txs, b.mempool_txs, if b.last { ", the fee market stopped" } else { "" }),
+            None => String::from("")
+        };
+
+        // Get the reorgs that happened at this time in the simulation and the channel transactions that were re-confirmed
+        let mut reorgs = String::from("");
+        for r in &self.reorgs {
+            if r.time == time.clone() {
+                reorgs = reorgs + &format!("{} blocks replaced, {} transactions unconfirmed", r.depth, r.unconfirmed_txs.len()) + "\n\t";
+                for c in &r.channels {
+                    let status = if c.unusable { "unusable" } else { "still usable" };
+                    reorgs = reorgs + &format!("channel {} lost its {:?} transaction ({})", c.channel_id, c.fee_type, status) + "\n\t";
+                }
+            }
+            for c in &r.channels {
+                if c.reconfirmed == Some(time.clone()) {
+                    reorgs = reorgs + &format!("channel {} {:?} transaction re-confirmed", c.channel_id, c.fee_type) + "\n\t";
+                }
+            }
+        }
+
+        // Get the triggers that fired at this time in the simulation
+        let mut triggers = String::from("");
+        for t in &self.triggers {
+            if t.time == time.clone() {
+                let events: Vec<String> = t.events.iter().map(|e| e.to_string()).collect();
+                triggers = triggers + &format!("{} fired, sending at {} seconds: {}", t.name, t.send_time, events.join(", ")) + "\n\t";
+            }
+        }
+
+        // Get the payments that jamming attackers were holding at this time in the simulation
+        let held = self.jamming.htlcs.iter().filter(|h| h.is_held(time.clone())).count();
+        let jamming = format!("{} HTLCs held, {} sats of liquidity locked", held, self.get_jammed_liquidity(time.clone()));
+
         // Get the failed events at this time in the simulation
         let mut failed = String::from("");
         for f in &self.failed_events {
@@ -356,8 +1023,22 @@ impl SimResults {
             }
         }
 
+        // Get the invariants that did not hold at this time in the simulation
+        let mut violations = String::from("");
+        for v in &self.invariant_violations {
+            if v.time == time.clone() {
+                violations = violations + &format!("{:?} after {}: {}", v.invariant, v.event.event, v.description) + "\n\t";
+            }
+        }
+
         // Create the container
-        let desc = format!("TRANSACTIONS:\n\t{transactions}\n\nOPEN CHANNELS:\n\t{openchannels}\n\nCLOSED CHANNELS:\n\t{closedchannels}\n\nFAILED EVENTS:\n\t{failed}");
+        // Get the graph metrics of the network at this time in the simulation
+        let network = match self.metrics.get(time) {
+            Some(m) => format!("{} components (largest has {} nodes), diameter {}, {} channels ({} sats median capacity)", m.components, m.largest_component, m.diameter, m.capacity.channels, m.capacity.median_sats),
+            None => String::from("")
+        };
+
+        let desc = format!("NETWORK: {network}\n\nTRANSACTIONS:\n\t{transactions}\n\nOPEN CHANNELS:\n\t{openchannels}\n\nCLOSED CHANNELS:\n\t{closedchannels}\n\nFORCE CLOSED CHANNELS:\n\t{forceclosed}\n\nPOLICY UPDATES:\n\t{policies}\n\nPARTITIONS:\n\t{partitions}\n\nJAMMING: {jamming}\n\nPROBES:\n\t{probes}\n\nON-CHAIN:\n\t{onchain}\n\nFEE MARKET: {feemarket}\n\nREORGS:\n\t{reorgs}\n\nTRIGGERS:\n\t{triggers}\n\nFAILED EVENTS:\n\t{failed}\n\nINVARIANT VIOLATIONS:\n\t{violations}");
         let time_details = Container::new(ContainerType::Div)
                                         .with_attributes([("class", "details")])
                                         .with_paragraph_attr(format!("SIM TIME: {time}"), [("id", title_id.as_str())])
@@ -403,7 +1084,7 @@ impl SimResults {
  * The on_chain and off_chain balances for a node at a given sim time
  * key=node name, value=map of time to balance
  */
-#[derive(Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct BalanceResults {
     pub on_chain: HashMap<String, HashMap<u64, u64>>,
     pub off_chain: HashMap<String, HashMap<u64, u64>>,
@@ -412,7 +1093,7 @@ pub struct BalanceResults {
 /*
  * A list of all transactions that occurred in the sim
  */
-#[derive(Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct TxResults {
     pub txs: Vec<Tx>
 }
@@ -420,27 +1101,453 @@ pub struct TxResults {
 /*
  * Details about each transaction
  */
-#[derive(Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Tx {
     pub time: u64,
-    pub transaction: SimTransaction
+    pub transaction: SimTransaction,
+    #[serde(default)]
+    pub parts: Vec<PaymentPart>, // the paths that the payment took once it was successful, there is more than one if the payment was split (MPP)
+    #[serde(default)]
+    pub failed_attempts: Vec<FailedAttempt> // the paths that the payment was attempted along and failed
+}
+
+impl Tx {
+    /*
+     * Get the parts of this payment, one for each path that the payment took
+     */
+    pub fn get_parts(&self) -> Vec<PaymentPart> {
+        self.parts.clone()
+    }
+
+    /*
+     * Get whether this payment was split across more than one path
+     */
+    pub fn is_multi_path(&self) -> bool {
+        self.parts.len() > 1
+    }
+
+    /*
+     * Get the total routing fees paid by all the parts of this payment
+     */
+    pub fn get_fee_sats(&self) -> u64 {
+        self.parts.iter().map(|p| p.fee_sats).sum()
+    }
+
+    /*
+     * Get the attempts to send this payment that failed
+     */
+    pub fn get_failed_attempts(&self) -> Vec<FailedAttempt> {
+        self.failed_attempts.clone()
+    }
+
+    /*
+     * Get the number of times the node retried this payment along another path
+     * Every failed attempt was retried except the last one of a payment that failed
+     */
+    pub fn get_retry_count(&self) -> u64 {
+        let attempts = self.failed_attempts.len() as u64;
+        match self.transaction.status {
+            SimTransactionStatus::FAILED(_) if attempts > 0 => attempts - 1,
+            _ => attempts
+        }
+    }
+}
+
+/*
+ * An attempt to send a payment along a path that failed
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FailedAttempt {
+    pub time: u64, // the sim time that the failure was reported
+    pub path: Vec<PathHop>,
+    pub short_channel_id: Option<u64>, // the channel that the payment failed at
+    pub reason: SimFailureReason,
+    pub permanent: bool // true if the payment could not succeed along any path
+}
+
+/*
+ * One part of a successful payment and the path that it took
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PaymentPart {
+    pub path: Vec<PathHop>,
+    pub amount_sats: u64, // the amount that this part delivered to the destination
+    pub fee_sats: u64 // the routing fees paid for this part
+}
+
+/*
+ * The payments that routing nodes forwarded and failed to forward
+ */
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ForwardingResults {
+    pub forwards: Vec<Forward>,
+    pub failed_forwards: Vec<FailedForward>
+}
+
+/*
+ * A payment that a routing node forwarded from one of its channels to another
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Forward {
+    pub time: u64, // the sim time that the payment was sent
+    pub node: String,
+    pub short_channel_id_in: u64,
+    pub short_channel_id_out: u64,
+    pub channel_in: Option<u64>, // the simulation defined channel ids, None if the channel was not found
+    pub channel_out: Option<u64>,
+    pub amount_in_sats: u64,
+    pub amount_out_sats: u64,
+    pub fee_sats: u64,
+    pub fee_msat: Option<u64> // the exact fee that the node reported when it claimed the payment, None until it is reported
+}
+
+impl Forward {
+    /*
+     * Get the fee of this forward in msats, using the fee the node reported if it is known
+     */
+    pub fn get_fee_msat(&self) -> u64 {
+        self.fee_msat.unwrap_or(self.fee_sats * 1000)
+    }
+}
+
+/*
+ * A payment that a routing node failed to forward to the next channel in the path
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FailedForward {
+    pub time: u64, // the sim time that the failure was reported
+    pub node: String,
+    pub short_channel_id_in: u64,
+    pub short_channel_id_out: u64,
+    pub channel_in: Option<u64>,
+    pub channel_out: Option<u64>,
+    pub amount_sats: u64, // the amount that the node was asked to forward
+    pub reason: SimFailureReason
+}
+
+/*
+ * Totals of the forwards for a node or channel
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ForwardingStats {
+    pub forwards: u64,
+    pub volume_sats: u64,
+    pub fee_sats: u64,
+    pub failed_forwards: u64
 }
 
 /*
  * The open and closed channels in the simulation at a given sim time
  * key=sim time, value=list of channels at that time
  */
-#[derive(Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct ChannelResults {
     pub open_channels: HashMap<u64, Vec<SimChannel>>,
-    pub closed_channels: HashMap<u64, Vec<SimChannel>>
+    pub closed_channels: HashMap<u64, Vec<SimChannel>>,
+    #[serde(default)]
+    pub policy_updates: Vec<ChannelPolicyUpdate>
+}
+
+/*
+ * A change to the routing policy of a node for a channel
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChannelPolicyUpdate {
+    pub time: u64,
+    pub node: String,
+    pub channel_id: u64,
+    pub policy: SimChannelPolicy
+}
+
+/*
+ * A channel that was closed by publishing a commitment transaction and what happened to the funds on-chain afterwards
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ForceClose {
+    pub channel_id: u64,
+    pub run_time_id: Option<String>,
+    pub close_type: CloseType,
+    pub initiator: String, // the node that published the commitment
+    pub counterparty: String,
+    pub time: u64, // the sim time that the commitment was published
+    pub closing_tx: Option<String>, // the commitment transaction, set when it is found on-chain
+    pub closing_fee_sats: Option<u64>, // the on-chain fee of the commitment transaction, paid by the node that opened the channel
+    pub to_self_delay: Option<u16>, // the number of blocks that the initiator's funds were locked for, set when they are swept
+    pub sweeps: Vec<Sweep> // the outputs that each node swept back to its on-chain wallet
+}
+
+impl ForceClose {
+    /*
+     * Get the number of seconds that the initiator's funds were locked from the close until they were swept
+     * Returns None if the initiator's funds have not been swept yet
+     */
+    pub fn get_locked_time(&self) -> Option<u64> {
+        self.sweeps.iter().find(|s| s.node == self.initiator && s.delayed).map(|s| s.time - self.time)
+    }
+
+    /*
+     * Get whether a node that published a revoked state was penalized (the counterparty claimed the funds with a justice transaction)
+     * Returns None if this was not a revoked state broadcast or the outputs have not been swept yet
+     */
+    pub fn is_penalized(&self) -> Option<bool> {
+        if self.close_type != CloseType::RevokedState {
+            return None;
+        }
+
+        if self.sweeps.iter().any(|s| s.penalty) {
+            Some(true)
+        } else if self.sweeps.iter().any(|s| s.node == self.initiator && s.delayed) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+/*
+ * How a channel was force closed
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum CloseType {
+    Force, // the initiator published its latest commitment
+    RevokedState // the initiator published an old (revoked) commitment
+}
+
+/*
+ * An output from a force closed channel that was swept to a node's on-chain wallet
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Sweep {
+    pub time: u64,
+    pub node: String,
+    pub amount_sats: u64,
+    pub delayed: bool, // true if the output was timelocked by to_self_delay
+    pub penalty: bool // true if the output was claimed from a revoked commitment with a justice transaction
+}
+
+/*
+ * An on-chain payment to a node's wallet or from a node's wallet to another node or an address outside of the simulation
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OnChainTx {
+    pub time: u64,
+    pub src_node: Option<String>, // None if the funds came from outside of the simulation
+    pub dest: String, // the receiving node name or address
+    pub external: bool, // true if the funds left the simulation
+    pub amount_sats: u64,
+    pub fee_sats: u64, // the on-chain fee paid by the sender, 0 for funds from outside of the simulation
+    pub txid: Option<String>
+}
+
+/*
+ * A block that the fee market mined
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FeeMarketBlock {
+    pub time: u64,
+    pub feerate_sat_per_vbyte: u64, // the market feerate, only transactions that paid at least this much were confirmed
+    pub filler_txs: u64,
+    pub confirmed_txs: u64,
+    pub mempool_txs: u64, // the transactions that were still waiting after the block
+    #[serde(default)]
+    pub last: bool // the fee market stopped after this block, it confirmed every transaction that was still waiting
+}
+
+/*
+ * The kind of transaction that a channel fee was paid for
+ */
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ChannelFeeType {
+    Open,
+    Close,
+    ForceClose
+}
+
+/*
+ * The on-chain fee that a node paid to open or close a channel
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChannelFee {
+    pub channel_id: u64,
+    pub fee_type: ChannelFeeType,
+    pub node: String, // the node that paid the fee
+    pub time: u64, // the sim time that the transaction was broadcast
+    pub confirmed: u64, // the sim time that the transaction was found on-chain
+    pub fee_sats: u64,
+    pub market_feerate: Option<u64>, // the fee market feerate when the transaction was broadcast, None if no fee market was running
+    #[serde(default)]
+    pub txid: Option<String> // the funding or closing transaction
+}
+
+impl ChannelFee {
+    /*
+     * Get the number of seconds that the transaction waited to be confirmed
+     */
+    pub fn get_delay(&self) -> u64 {
+        self.confirmed.saturating_sub(self.time)
+    }
+}
+
+/*
+ * A trigger that fired and the events that it added to the simulation
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FiredTrigger {
+    pub name: String,
+    pub time: u64, // the sim time that the condition was met
+    pub send_time: u64, // the sim time that the events were scheduled for
+    pub events: Vec<SimulationEvent>
+}
+
+/*
+ * A reorg that replaced the last blocks of the chain and the channels whose funding or closing transaction went back to the mempool
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Reorg {
+    pub time: u64,
+    pub depth: u64, // the number of blocks that were replaced
+    pub unconfirmed_txs: Vec<String>,
+    pub channels: Vec<ReorgedChannel>
+}
+
+/*
+ * A channel transaction that a reorg unconfirmed
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReorgedChannel {
+    pub channel_id: u64,
+    pub fee_type: ChannelFeeType, // which of the channel's transactions was unconfirmed
+    pub txid: String,
+    pub unusable: bool, // the nodes stopped using the channel after the reorg
+    pub reconfirmed: Option<u64> // the sim time that the transaction was found in a block again, None if it was not re-confirmed
+}
+
+/*
+ * The balance range of one end of a channel that a node inferred by probing and the balance that the end actually had
+ * The nodes keep a channel reserve that can not be sent, so the inferred balance is usually below the actual balance by about the reserve
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BalanceProbe {
+    pub time: u64, // the sim time that the channel was probed
+    #[serde(default)]
+    pub finished: Option<u64>, // the sim time that the last probe finished, None for results saved before it was recorded
+    pub prober: String,
+    pub node: String, // the end of the channel whose balance was probed
+    pub channel_id: u64,
+    pub lower_sats: u64,
+    pub upper_sats: u64,
+    pub probes: u64, // the number of probes that were sent
+    pub actual_sats: u64, // the balance of the node in the channel when the last probe finished
+    pub capacity_sats: u64
+}
+
+impl BalanceProbe {
+    /*
+     * Get the inferred balance, the middle of the range that the probes found
+     */
+    pub fn get_estimate(&self) -> u64 {
+        (self.lower_sats + self.upper_sats) / 2
+    }
+
+    /*
+     * Get how far the inferred balance was from the actual balance (negative if it was below)
+     */
+    pub fn get_error_sats(&self) -> i64 {
+        self.get_estimate() as i64 - self.actual_sats as i64
+    }
+}
+
+/*
+ * How much the probes leaked about the channel balances
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProbeAccuracy {
+    pub channels_probed: u64,
+    pub mean_error_sats: f64, // the mean distance between the inferred and actual balances
+    pub mean_error_percent: f64, // the mean distance as a percentage of the channel capacity
+    pub within_range_percent: f64, // the percentage of probes where the actual balance was inside the inferred range
+    pub mean_probes: f64 // the mean number of probes that were sent to infer a balance
+}
+
+/*
+ * The number of HTLCs that an LDK node accepts on a channel by default
+ */
+pub const MAX_ACCEPTED_HTLCS: u64 = 50;
+
+/*
+ * The payments that jamming attackers sent to their own nodes and held
+ */
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct JammingResults {
+    pub htlcs: Vec<JammedHtlc>
+}
+
+/*
+ * A payment that was held by an attacker controlled destination, it used an HTLC slot and its amount in every channel along the path
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JammedHtlc {
+    pub id: u64, // the simulation defined id of the held payment
+    pub payment_id: String,
+    pub src_node: String,
+    pub dest_node: String,
+    pub amount_sats: u64,
+    pub sent: u64, // the sim time that the payment was sent
+    pub released: Option<u64>, // the sim time that the destination failed the payment back, None if it was held until the end of the simulation
+    pub path: Vec<PathHop>, // the path that the payment was held along, empty if it never reached the destination
+    pub channels: Vec<u64> // the simulation defined ids of the channels along the path
+}
+
+impl JammedHtlc {
+    /*
+     * Get whether the payment was held at the destination at a given time
+     */
+    pub fn is_held(&self, time: u64) -> bool {
+        !self.path.is_empty() && time >= self.sent && self.released.map_or(true, |r| time < r)
+    }
+}
+
+/*
+ * Pairs of running nodes that were kept from connecting to each other, their channels could not be used until the partition was healed
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Partition {
+    pub time: u64, // the sim time that the nodes were disconnected
+    pub healed: Option<u64>, // the sim time that the nodes were connected again, None if they were disconnected until the end of the simulation
+    pub links: Vec<(String, String)> // the pairs of nodes that could not connect to each other
+}
+
+impl Partition {
+    /*
+     * Get whether the nodes were disconnected at a given time
+     */
+    pub fn is_active(&self, time: u64) -> bool {
+        time >= self.time && self.healed.map_or(true, |h| time < h)
+    }
+}
+
+/*
+ * A balance invariant that did not hold after the network analyzer processed an event
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InvariantViolation {
+    pub time: u64, // the sim time that the event was processed
+    pub invariant: Invariant,
+    pub event: SimResultsEvent, // the event that was processed right before the invariant was found broken
+    pub description: String
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Invariant {
+    SupplyConservation, // on-chain + off-chain balances + fees paid + funds in flight equal the total balance at the start of the simulation
+    ChannelBalance // the balances of an open channel match the balances that the node reports
 }
 
 /*
  * Node status at a given sim time
  * key=node name, value=map of time to status (true=online, false=offline)
  */
-#[derive(Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct StatusResults {
     pub nodes: HashMap<String, HashMap<u64, bool>>
 }
\ No newline at end of file
@@ -5,6 +5,9 @@ use crate::sim_channel::SimChannel;
 // Standard Modules
 use std::collections::HashMap;
 
+// External Modules
+use serde_json::{Map, Value};
+
 /*
  * This struct holds a runtime graph that can be used by the library to show the overall status of the network.
  */
@@ -33,7 +36,8 @@ impl RuntimeNetworkGraph {
         
         for c in channels {
             self.channels.push(SimChannel { src_node: String::from(&c.src_node), dest_node: String::from(&c.dest_node), src_balance_sats: c.src_balance_sats, 
-                dest_balance_sats: c.dest_balance_sats, id: c.id, short_id: None, run_time_id: None, funding_tx: None, penalty_reserve_sats: c.penalty_reserve_sats });
+                dest_balance_sats: c.dest_balance_sats, id: c.id, short_id: None, run_time_id: None, funding_tx: None, penalty_reserve_sats: c.penalty_reserve_sats,
+                src_policy: c.src_policy.clone(), dest_policy: c.dest_policy.clone() });
         }
 
         let num = num_nodes + 1;
@@ -43,4 +47,16 @@ impl RuntimeNetworkGraph {
             self.nodes.push(SimNode { name: String::from(&node_name), initial_balance: 1_000_000_000, running: true });
         }
     }
-}
\ No newline at end of file
+
+    /*
+     * Serialize the nodes and channels of the graph
+     */
+    pub fn to_json(&self) -> String {
+        let serialized_nodes = serde_json::to_string(&self.nodes).unwrap();
+        let serialized_channels = serde_json::to_string(&self.channels).unwrap();
+        let mut map = Map::new();
+        map.insert(String::from("nodes"), Value::String(serialized_nodes));
+        map.insert(String::from("channels"), Value::String(serialized_channels));
+        serde_json::to_string(&map).unwrap()
+    }
+}
@@ -0,0 +1,406 @@
+// Project Modules
+use crate::sim_node::SimNode;
+use crate::sim_channel::SimChannel;
+use crate::sim_event::SimulationEvent;
+use crate::traffic_generator::TrafficConfig;
+use crate::churn_model::ChurnConfig;
+use crate::jamming::JammingConfig;
+use crate::probing::ProbingConfig;
+use crate::fee_market::FeeMarketConfig;
+use crate::trigger::{TriggerConfig, TriggerCondition, TriggerAction};
+
+// External Modules
+use serde::{Serialize, Deserialize};
+use anyhow::Result;
+
+// Standard Modules
+use std::collections::HashMap;
+use std::fs;
+
+/*
+ * This struct represents the full definition of a simulation (nodes, channels and events).
+ * A scenario can be saved to a json file and loaded later to re-run the same simulation.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SimScenario {
+    pub name: String,
+    pub duration: u64,
+    pub num_sim_nodes: u64,
+    pub nodes: Vec<SimNode>,
+    pub channels: Vec<SimChannel>,
+    pub events: Vec<ScenarioEvent>,
+    #[serde(default)]
+    pub traffic: Vec<TrafficConfig>,
+    #[serde(default)]
+    pub churn: Vec<ChurnConfig>,
+    #[serde(default)]
+    pub jamming: Vec<JammingConfig>,
+    #[serde(default)]
+    pub probing: Vec<ProbingConfig>,
+    #[serde(default)]
+    pub fee_market: Option<FeeMarketConfig>,
+    #[serde(default)]
+    pub triggers: Vec<TriggerConfig>
+}
+
+/*
+ * An event in a scenario and the simulation time that it should take place
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScenarioEvent {
+    pub time: u64,
+    pub event: SimulationEvent
+}
+
+impl SimScenario {
+    /*
+     * Load a scenario from a json file
+     */
+    pub fn from_file(filename: &String) -> Result<SimScenario> {
+        let contents = fs::read_to_string(filename)?;
+        let scenario: SimScenario = serde_json::from_str(&contents)?;
+        Ok(scenario)
+    }
+
+    /*
+     * Save this scenario to a json file
+     */
+    pub fn save(&self, filename: &String) -> Result<()> {
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(filename, contents)?;
+        Ok(())
+    }
+
+    /*
+     * Check the scenario for problems that would keep it from running as defined
+     * Returns a list of descriptions of each problem that was found (empty if the scenario is valid)
+     */
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+
+        // Collect all of the node names, including the generated simulation nodes
+        let mut node_balances: HashMap<String, u64> = HashMap::new();
+        let mut node_running: HashMap<String, bool> = HashMap::new();
+        for n in &self.nodes {
+            if node_balances.contains_key(&n.name) {
+                problems.push(format!("node {} is defined more than once", n.name));
+            }
+            node_balances.insert(n.name.clone(), n.initial_balance);
+            node_running.insert(n.name.clone(), n.running);
+        }
+        for number in 1..(self.num_sim_nodes + 1) {
+            let node_name = String::from("simnode")+&number.to_string();
+            node_balances.insert(node_name.clone(), 1_000_000_000);
+            node_running.insert(node_name, true);
+        }
+
+        // Check the initial channels
+        let mut channel_ids: Vec<u64> = Vec::new();
+        for c in &self.channels {
+            if channel_ids.contains(&c.id) {
+                problems.push(format!("channel id {} is used more than once", c.id));
+            }
+            channel_ids.push(c.id);
+
+            match node_balances.get_mut(&c.src_node) {
+                Some(balance) => {
+                    if !node_running.get(&c.src_node).unwrap() {
+                        problems.push(format!("channel {}: source node {} is not running at the start of the simulation", c.id, c.src_node));
+                    }
+                    if *balance < c.src_balance_sats {
+                        problems.push(format!("channel {}: source node {} does not have enough of an initial balance to open this channel", c.id, c.src_node));
+                    } else {
+                        *balance -= c.src_balance_sats;
+                    }
+                },
+                None => problems.push(format!("channel {}: source node {} not found", c.id, c.src_node))
+            }
+
+            match node_running.get(&c.dest_node) {
+                Some(running) => {
+                    if !running {
+                        problems.push(format!("channel {}: destination node {} is not running at the start of the simulation", c.id, c.dest_node));
+                    }
+                },
+                None => problems.push(format!("channel {}: destination node {} not found", c.id, c.dest_node))
+            }
+
+            if c.src_balance_sats < 20000 {
+                problems.push(format!("channel {}: amount must be at least 20000", c.id));
+            }
+            if c.src_policy.as_ref().map_or(false, |p| p.has_htlc_limits()) {
+                problems.push(format!("channel {}: htlc limits can only be set on the dest policy, the limits of the src node are chosen by the dest node when it accepts the channel", c.id));
+            }
+        }
+
+        // Check the events
+        for e in &self.events {
+            if e.time > self.duration {
+                problems.push(format!("{} at {} seconds is after the end of the simulation", e.event, e.time));
+            }
+
+            match &e.event {
+                SimulationEvent::StartNodeEvent(name) | SimulationEvent::StopNodeEvent(name) => {
+                    if !node_balances.contains_key(name) {
+                        problems.push(format!("{} at {} seconds: node {} not found", e.event, e.time, name));
+                    }
+                },
+                SimulationEvent::OpenChannelEvent(c) => {
+                    if channel_ids.contains(&c.id) {
+                        problems.push(format!("{} at {} seconds: channel id {} is used more than once", e.event, e.time, c.id));
+                    }
+                    channel_ids.push(c.id);
+                    if !node_balances.contains_key(&c.src_node) {
+                        problems.push(format!("{} at {} seconds: source node {} not found", e.event, e.time, c.src_node));
+                    }
+                    if !node_balances.contains_key(&c.dest_node) {
+                        problems.push(format!("{} at {} seconds: destination node {} not found", e.event, e.time, c.dest_node));
+                    }
+                    if c.src_policy.as_ref().map_or(false, |p| p.has_htlc_limits()) {
+                        problems.push(format!("{} at {} seconds: htlc limits can only be set on the dest policy", e.event, e.time));
+                    }
+                },
+                SimulationEvent::TransactionEvent(tx) | SimulationEvent::KeysendEvent(tx) => {
+                    if !node_balances.contains_key(&tx.src_node) {
+                        problems.push(format!("{} at {} seconds: source node {} not found", e.event, e.time, tx.src_node));
+                    }
+                    if !node_balances.contains_key(&tx.dest_node) {
+                        problems.push(format!("{} at {} seconds: destination node {} not found", e.event, e.time, tx.dest_node));
+                    }
+                },
+                SimulationEvent::CloseChannelEvent(_, _) | SimulationEvent::UpdateChannelPolicyEvent(_, _, _) |
+                SimulationEvent::ForceCloseChannelEvent(_, _) | SimulationEvent::BroadcastRevokedStateEvent(_, _) => {
+                    // Close and policy events are checked after all the channel ids are known
+                },
+                SimulationEvent::MineBlocksEvent(blocks) => {
+                    if *blocks == 0 {
+                        problems.push(format!("{} at {} seconds: number of blocks must be greater than 0", e.event, e.time));
+                    }
+                },
+                SimulationEvent::ReorgEvent(reorg) => {
+                    if reorg.depth == 0 {
+                        problems.push(format!("{} at {} seconds: reorg depth must be greater than 0", e.event, e.time));
+                    }
+                },
+                SimulationEvent::SendOnChainEvent(tx) => {
+                    // The destination can be an address, so only the source has to be a node
+                    if !node_balances.contains_key(&tx.src_node) {
+                        problems.push(format!("{} at {} seconds: source node {} not found", e.event, e.time, tx.src_node));
+                    }
+                    if tx.src_node == tx.dest {
+                        problems.push(format!("{} at {} seconds: a node can not send to itself", e.event, e.time));
+                    }
+                    if tx.amount_sats == 0 || tx.feerate_sat_per_vbyte == 0 {
+                        problems.push(format!("{} at {} seconds: amount and feerate must be greater than 0", e.event, e.time));
+                    }
+                },
+                SimulationEvent::FundNodeEvent(node, amount) => {
+                    if !node_balances.contains_key(node) {
+                        problems.push(format!("{} at {} seconds: node {} not found", e.event, e.time, node));
+                    }
+                    if *amount == 0 {
+                        problems.push(format!("{} at {} seconds: amount must be greater than 0", e.event, e.time));
+                    }
+                },
+                SimulationEvent::DisconnectPeersEvent(node, peer) => {
+                    for n in [node, peer] {
+                        if !node_balances.contains_key(n) {
+                            problems.push(format!("{} at {} seconds: node {} not found", e.event, e.time, n));
+                        }
+                    }
+                    if node == peer {
+                        problems.push(format!("{} at {} seconds: a node can not be disconnected from itself", e.event, e.time));
+                    }
+                },
+                SimulationEvent::PartitionEvent(nodes_a, nodes_b) => {
+                    if nodes_a.is_empty() || nodes_b.is_empty() {
+                        problems.push(format!("{} at {} seconds: both sides of the partition need at least one node", e.event, e.time));
+                    }
+                    for n in nodes_a.iter().chain(nodes_b.iter()) {
+                        if !node_balances.contains_key(n) {
+                            problems.push(format!("{} at {} seconds: node {} not found", e.event, e.time, n));
+                        }
+                    }
+                    for n in nodes_a {
+                        if nodes_b.contains(n) {
+                            problems.push(format!("{} at {} seconds: node {} is on both sides of the partition", e.event, e.time, n));
+                        }
+                    }
+                },
+                SimulationEvent::HealPartitionEvent => {},
+                SimulationEvent::ProbeEvent(probe) => {
+                    // The channel is checked after all the channel ids are known
+                    if !node_balances.contains_key(&probe.prober) {
+                        problems.push(format!("{} at {} seconds: node {} not found", e.event, e.time, probe.prober));
+                    }
+                    if probe.precision_sats == 0 {
+                        problems.push(format!("{} at {} seconds: precision must be greater than 0", e.event, e.time));
+                    }
+                },
+                _ => {
+                    problems.push(format!("{} at {} seconds can not be scheduled in a scenario", e.event, e.time));
+                }
+            }
+        }
+
+        // Check the traffic generators
+        for t in &self.traffic {
+            for n in t.senders.iter().chain(t.receivers.iter()) {
+                if !node_balances.contains_key(n) {
+                    problems.push(format!("{:?} traffic generator: node {} not found", t.model, n));
+                }
+            }
+            for p in t.validate() {
+                problems.push(format!("{:?} traffic generator: {}", t.model, p));
+            }
+            if t.end > self.duration {
+                problems.push(format!("{:?} traffic generator: end time {} is after the end of the simulation", t.model, t.end));
+            }
+        }
+
+        // Check the churn models
+        for c in &self.churn {
+            for n in &c.nodes {
+                if !node_balances.contains_key(n) {
+                    problems.push(format!("{:?} churn model: node {} not found", c.model, n));
+                }
+            }
+            for p in c.validate() {
+                problems.push(format!("{:?} churn model: {}", c.model, p));
+            }
+            if c.end > self.duration {
+                problems.push(format!("{:?} churn model: end time {} is after the end of the simulation", c.model, c.end));
+            }
+        }
+
+        // Check the jamming attacks
+        for j in &self.jamming {
+            for n in [&j.src, &j.dest] {
+                if !node_balances.contains_key(n) {
+                    problems.push(format!("{:?} jamming attack: node {} not found", j.attack, n));
+                }
+            }
+            for p in j.validate() {
+                problems.push(format!("{:?} jamming attack: {}", j.attack, p));
+            }
+            if j.end > self.duration {
+                problems.push(format!("{:?} jamming attack: end time {} is after the end of the simulation", j.attack, j.end));
+            }
+        }
+
+        // Check the probing agents
+        for p in &self.probing {
+            if !node_balances.contains_key(&p.prober) {
+                problems.push(format!("probing agent: node {} not found", p.prober));
+            }
+            for id in &p.channels {
+                if !channel_ids.contains(id) {
+                    problems.push(format!("probing agent {}: channel id {} not found", p.prober, id));
+                }
+            }
+            for problem in p.validate() {
+                problems.push(format!("probing agent {}: {}", p.prober, problem));
+            }
+            if p.end > self.duration {
+                problems.push(format!("probing agent {}: end time {} is after the end of the simulation", p.prober, p.end));
+            }
+        }
+
+        // Check the fee market
+        match &self.fee_market {
+            Some(m) => {
+                for problem in m.validate() {
+                    problems.push(format!("fee market: {}", problem));
+                }
+                if m.end > self.duration {
+                    problems.push(format!("fee market: end time {} is after the end of the simulation", m.end));
+                }
+            },
+            None => {}
+        }
+
+        // Check the triggers, the channels that they open can be used by the conditions of other triggers
+        for t in &self.triggers {
+            match &t.then {
+                TriggerAction::OpenChannel { src, dest, id, .. } => {
+                    if channel_ids.contains(id) {
+                        problems.push(format!("trigger {}: channel id {} is used more than once", t.name, id));
+                    }
+                    channel_ids.push(*id);
+                    for n in [src, dest] {
+                        if !node_balances.contains_key(n) {
+                            problems.push(format!("trigger {}: node {} not found", t.name, n));
+                        }
+                    }
+                },
+                TriggerAction::CloseChannels { node, .. } | TriggerAction::RetryPayment { src: node } => {
+                    if !node_balances.contains_key(node) {
+                        problems.push(format!("trigger {}: node {} not found", t.name, node));
+                    }
+                }
+            }
+        }
+        for t in &self.triggers {
+            match &t.when {
+                TriggerCondition::ChannelBalanceBelow { channel, node, .. } => {
+                    if !channel_ids.contains(channel) {
+                        problems.push(format!("trigger {}: channel id {} not found", t.name, channel));
+                    }
+                    if !node_balances.contains_key(node) {
+                        problems.push(format!("trigger {}: node {} not found", t.name, node));
+                    }
+                },
+                TriggerCondition::NodeOffline { node } => {
+                    if !node_balances.contains_key(node) {
+                        problems.push(format!("trigger {}: node {} not found", t.name, node));
+                    }
+                },
+                TriggerCondition::PaymentFailed { src, dest } => {
+                    for n in src.iter().chain(dest.iter()) {
+                        if !node_balances.contains_key(n) {
+                            problems.push(format!("trigger {}: node {} not found", t.name, n));
+                        }
+                    }
+                }
+            }
+            for problem in t.validate() {
+                problems.push(format!("trigger {}: {}", t.name, problem));
+            }
+            if t.delay > self.duration {
+                problems.push(format!("trigger {}: delay {} is longer than the simulation", t.name, t.delay));
+            }
+        }
+
+        // Check that every channel being closed, updated or probed is defined somewhere in the scenario
+        for e in &self.events {
+            match &e.event {
+                SimulationEvent::ProbeEvent(probe) => {
+                    if !channel_ids.contains(&probe.channel_id) {
+                        problems.push(format!("{} at {} seconds: channel id {} not found", e.event, e.time, probe.channel_id));
+                    }
+                },
+                SimulationEvent::CloseChannelEvent(name, id) | SimulationEvent::UpdateChannelPolicyEvent(name, id, _) |
+                SimulationEvent::ForceCloseChannelEvent(name, id) | SimulationEvent::BroadcastRevokedStateEvent(name, id) => {
+                    match &e.event {
+                        SimulationEvent::UpdateChannelPolicyEvent(_, _, policy) if policy.has_htlc_limits() => {
+                            problems.push(format!("{} at {} seconds: htlc limits are set when the channel is opened and can not be updated", e.event, e.time));
+                        },
+                        SimulationEvent::BroadcastRevokedStateEvent(_, _) if !cfg!(feature = "revoked_state") => {
+                            problems.push(format!("{} at {} seconds: ln_ms_lib needs to be built with the revoked_state feature to broadcast a revoked state", e.event, e.time));
+                        },
+                        _ => {}
+                    }
+                    if !node_balances.contains_key(name) {
+                        problems.push(format!("{} at {} seconds: node {} not found", e.event, e.time, name));
+                    }
+                    if !channel_ids.contains(id) {
+                        problems.push(format!("{} at {} seconds: channel id {} not found", e.event, e.time, id));
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        problems
+    }
+}
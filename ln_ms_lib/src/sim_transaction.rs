@@ -10,16 +10,67 @@ pub struct SimTransaction {
     pub src_node: String, // the node that is sending the payment
     pub dest_node: String, // the node that is receiving the payment
     pub amount_sats: u64, // amount in sats
-    pub status: SimTransactionStatus // set to none until the transaction is sent
+    pub status: SimTransactionStatus, // set to none until the transaction is sent
+    #[serde(default)]
+    pub keysend: bool // true if the payment is sent to the destination's pubkey without an invoice
 }
 
 /*
  * The status of a transaction.
  */
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(try_from = "SavedTransactionStatus")]
 pub enum SimTransactionStatus {
     NONE, // the transaction has been created
     PENDING, // the transaction  has been initiated
     SUCCESSFUL, // the transaction was received and successful
-    FAILED // the transaction failed
-}
\ No newline at end of file
+    FAILED(SimFailureReason) // the transaction failed, the reason is from the last path that was attempted
+}
+
+/*
+ * A status as it is saved in the results, a failed transaction saved before it had a reason is just "FAILED"
+ */
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedTransactionStatus {
+    Name(String),
+    Failed {
+        #[serde(rename = "FAILED")]
+        reason: SimFailureReason
+    }
+}
+
+impl TryFrom<SavedTransactionStatus> for SimTransactionStatus {
+    type Error = String;
+
+    fn try_from(status: SavedTransactionStatus) -> Result<Self, Self::Error> {
+        match status {
+            SavedTransactionStatus::Name(name) => {
+                match name.as_str() {
+                    "NONE" => Ok(SimTransactionStatus::NONE),
+                    "PENDING" => Ok(SimTransactionStatus::PENDING),
+                    "SUCCESSFUL" => Ok(SimTransactionStatus::SUCCESSFUL),
+                    "FAILED" => Ok(SimTransactionStatus::FAILED(SimFailureReason::Unknown)),
+                    _ => Err(format!("unknown transaction status: {}", name))
+                }
+            },
+            SavedTransactionStatus::Failed { reason } => Ok(SimTransactionStatus::FAILED(reason))
+        }
+    }
+}
+
+/*
+ * The reason that a payment attempt failed, from the network update that LDK creates from the error that the failing node sent back
+ * LDK does not give the failure code itself, so errors that cause the same update can not be told apart and the reasons say which update was received
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SimFailureReason {
+    ChannelUpdateReceived, // a node along the path sent its policy for the next channel, e.g. for a temporary channel failure (not enough liquidity), a disabled channel or a wrong cltv expiry
+    FeeInsufficient, // a node along the path sent its policy for the next channel and the fee that was paid is less than the policy requires
+    TemporaryChannelFailure, // a node along the path could not use the next channel for now and did not send its policy
+    #[serde(alias = "UnknownNextPeer")]
+    PermanentChannelFailure, // the next channel can not be used any more, e.g. the node does not know the channel (unknown next peer) or it was closed
+    NodeFailure, // a node along the path failed
+    RecipientRejected, // the destination rejected the payment, for example the payment details were wrong
+    Unknown // the payment failed without a reason, for example no route was found
+}
@@ -0,0 +1,223 @@
+// Project Modules
+use crate::network_importer;
+use crate::traffic_generator::AmountDistribution;
+
+// External Modules
+use anyhow::{Result, anyhow};
+use rand::Rng;
+use rand::rngs::StdRng;
+use rand::distributions::{Distribution, WeightedIndex};
+use serde::{Serialize, Deserialize};
+
+// Standard Modules
+use std::collections::{HashMap, HashSet};
+
+/*
+ * These functions build a random starting network from well-known graph models.
+ * Each model creates a list of channels between node indexes 0..N, the first node of each channel is the node that opens (funds) it.
+ */
+
+/*
+ * The graph model used to create the channels of a random network
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "model", rename_all = "snake_case")]
+pub enum TopologyModel {
+    BarabasiAlbert { edges_per_node: u64 }, // scale-free, each new node opens channels to nodes that already have many channels
+    ErdosRenyi { probability: f64 }, // every pair of nodes has a channel with the same probability
+    WattsStrogatz { neighbors: u64, rewire_probability: f64 }, // small-world, a ring where each node has channels to its nearest neighbors and some are rewired at random
+    HubAndSpoke { hubs: u64 }, // LSP-style, the hubs have channels with each other and open a channel to each of the other nodes
+    MainnetLike { graph: String } // the degree and capacity distributions are sampled from a describegraph file
+}
+
+/*
+ * Create the channels for a network with number_of_nodes nodes, returns (src index, dest index, capacity) for each channel
+ * The capacity of each channel is sampled from channel_amount, except for the mainnet-like model which samples the capacities from the graph file
+ */
+pub fn generate(model: &TopologyModel, number_of_nodes: u64, channel_amount: &AmountDistribution, rng: &mut StdRng) -> Result<Vec<(usize, usize, u64)>> {
+    let problems = channel_amount.validate();
+    if !problems.is_empty() {
+        return Err(anyhow!("invalid channel amount: {}", problems.join(", ")));
+    }
+    let n = number_of_nodes as usize;
+    let (edges, capacities) = match model {
+        TopologyModel::BarabasiAlbert { edges_per_node } => (barabasi_albert(n, *edges_per_node as usize, rng)?, Vec::new()),
+        TopologyModel::ErdosRenyi { probability } => (erdos_renyi(n, *probability, rng)?, Vec::new()),
+        TopologyModel::WattsStrogatz { neighbors, rewire_probability } => (watts_strogatz(n, *neighbors as usize, *rewire_probability, rng)?, Vec::new()),
+        TopologyModel::HubAndSpoke { hubs } => (hub_and_spoke(n, *hubs as usize)?, Vec::new()),
+        TopologyModel::MainnetLike { graph } => mainnet_like(n, graph, rng)?
+    };
+
+    // Channels need at least 20000 sats to be opened
+    let channels = edges.into_iter().map(|(src, dest)| {
+        let capacity = if capacities.is_empty() { channel_amount.sample(rng) } else { capacities[rng.gen_range(0..capacities.len())] };
+        (src, dest, capacity.max(20000))
+    }).collect();
+
+    Ok(channels)
+}
+
+/*
+ * Barabási–Albert: start with edges_per_node + 1 fully connected nodes, then each new node opens edges_per_node channels
+ * to existing nodes picked with a probability proportional to the number of channels they have
+ */
+fn barabasi_albert(n: usize, m: usize, rng: &mut StdRng) -> Result<Vec<(usize, usize)>> {
+    if m == 0 || n <= m {
+        return Err(anyhow!("barabasi_albert needs edges_per_node > 0 and more than edges_per_node nodes"));
+    }
+
+    let mut edges: Vec<(usize, usize)> = Vec::new();
+    // Every node appears in this list once for each of its channels, so picking from it is preferential attachment
+    let mut endpoints: Vec<usize> = Vec::new();
+    for i in 0..(m + 1) {
+        for j in (i + 1)..(m + 1) {
+            edges.push((i, j));
+            endpoints.push(i);
+            endpoints.push(j);
+        }
+    }
+
+    for new_node in (m + 1)..n {
+        let mut targets: HashSet<usize> = HashSet::new();
+        while targets.len() < m {
+            targets.insert(endpoints[rng.gen_range(0..endpoints.len())]);
+        }
+        let mut targets: Vec<usize> = targets.into_iter().collect();
+        targets.sort();
+        for t in targets {
+            edges.push((new_node, t));
+            endpoints.push(new_node);
+            endpoints.push(t);
+        }
+    }
+
+    Ok(edges)
+}
+
+/*
+ * Erdős–Rényi: every pair of nodes has a channel with the given probability, the node that opens it is picked at random
+ */
+fn erdos_renyi(n: usize, probability: f64, rng: &mut StdRng) -> Result<Vec<(usize, usize)>> {
+    if !(0.0..=1.0).contains(&probability) {
+        return Err(anyhow!("erdos_renyi probability must be between 0 and 1"));
+    }
+
+    let mut edges: Vec<(usize, usize)> = Vec::new();
+    for i in 0..n {
+        for j in (i + 1)..n {
+            if rng.gen_bool(probability) {
+                edges.push(if rng.gen_bool(0.5) { (i, j) } else { (j, i) });
+            }
+        }
+    }
+
+    Ok(edges)
+}
+
+/*
+ * Watts–Strogatz: a ring where each node has channels to its neighbors/2 nearest nodes on each side,
+ * then the far end of each channel is moved to a random node with the given probability
+ */
+fn watts_strogatz(n: usize, neighbors: usize, rewire_probability: f64, rng: &mut StdRng) -> Result<Vec<(usize, usize)>> {
+    let k = neighbors / 2;
+    if k == 0 || n <= neighbors {
+        return Err(anyhow!("watts_strogatz needs neighbors >= 2 and more than neighbors nodes"));
+    }
+    if !(0.0..=1.0).contains(&rewire_probability) {
+        return Err(anyhow!("watts_strogatz rewire_probability must be between 0 and 1"));
+    }
+
+    let key = |a: usize, b: usize| if a < b { (a, b) } else { (b, a) };
+    let mut edges: Vec<(usize, usize)> = Vec::new();
+    let mut existing: HashSet<(usize, usize)> = HashSet::new();
+    for i in 0..n {
+        for j in 1..(k + 1) {
+            let dest = (i + j) % n;
+            edges.push((i, dest));
+            existing.insert(key(i, dest));
+        }
+    }
+
+    for e in edges.iter_mut() {
+        if rng.gen_bool(rewire_probability) {
+            let src = e.0;
+            // Skip the rewire if the node already has a channel with every other node
+            if existing.iter().filter(|(a, b)| *a == src || *b == src).count() >= n - 1 {
+                continue;
+            }
+            let mut dest = rng.gen_range(0..n);
+            while dest == src || existing.contains(&key(src, dest)) {
+                dest = rng.gen_range(0..n);
+            }
+            existing.remove(&key(e.0, e.1));
+            existing.insert(key(src, dest));
+            e.1 = dest;
+        }
+    }
+
+    Ok(edges)
+}
+
+/*
+ * Hub-and-spoke: the first nodes are hubs that have channels with each other,
+ * every other node is a spoke that the hubs take turns opening a channel to (like an LSP providing inbound liquidity)
+ */
+fn hub_and_spoke(n: usize, hubs: usize) -> Result<Vec<(usize, usize)>> {
+    if hubs == 0 || n <= hubs {
+        return Err(anyhow!("hub_and_spoke needs at least one hub and more nodes than hubs"));
+    }
+
+    let mut edges: Vec<(usize, usize)> = Vec::new();
+    for i in 0..hubs {
+        for j in (i + 1)..hubs {
+            edges.push((i, j));
+        }
+    }
+    for spoke in hubs..n {
+        edges.push((spoke % hubs, spoke));
+    }
+
+    Ok(edges)
+}
+
+/*
+ * Mainnet-like: each node is given a target number of channels sampled from the degree distribution of a describegraph file,
+ * then channels are created between nodes picked with a probability proportional to their target (Chung–Lu)
+ * Returns the channels and the capacities from the file that channel capacities are sampled from
+ */
+fn mainnet_like(n: usize, graph: &String, rng: &mut StdRng) -> Result<(Vec<(usize, usize)>, Vec<u64>)> {
+    let (_, graph_channels) = network_importer::parse_graph(graph)?;
+    let mut graph_degrees: HashMap<String, u64> = HashMap::new();
+    for c in &graph_channels {
+        *graph_degrees.entry(c.node1_pub.clone()).or_insert(0) += 1;
+        *graph_degrees.entry(c.node2_pub.clone()).or_insert(0) += 1;
+    }
+    let mut degrees: Vec<u64> = graph_degrees.into_values().collect();
+    degrees.sort();
+    let capacities: Vec<u64> = graph_channels.iter().map(|c| c.capacity).collect();
+    if degrees.is_empty() || n < 2 {
+        return Err(anyhow!("mainnet_like needs a graph with channels and at least 2 nodes"));
+    }
+
+    // A node can not have more channels than there are other nodes
+    let weights: Vec<u64> = (0..n).map(|_| degrees[rng.gen_range(0..degrees.len())].min(n as u64 - 1)).collect();
+    let num_edges = (weights.iter().sum::<u64>() / 2) as usize;
+    let picker = WeightedIndex::new(&weights)?;
+
+    let mut edges: Vec<(usize, usize)> = Vec::new();
+    let mut existing: HashSet<(usize, usize)> = HashSet::new();
+    let mut attempts = 0;
+    while edges.len() < num_edges && attempts < num_edges * 10 {
+        attempts += 1;
+        let src = picker.sample(rng);
+        let dest = picker.sample(rng);
+        let key = if src < dest { (src, dest) } else { (dest, src) };
+        if src == dest || existing.contains(&key) {
+            continue;
+        }
+        existing.insert(key);
+        edges.push((src, dest));
+    }
+
+    Ok((edges, capacities))
+}
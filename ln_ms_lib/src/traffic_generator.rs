@@ -0,0 +1,270 @@
+// Project Modules
+use crate::sim_transaction::{SimTransaction, SimTransactionStatus};
+
+// External Modules
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::distributions::WeightedIndex;
+use rand_distr::{Distribution, Poisson, Pareto, LogNormal};
+use serde::{Serialize, Deserialize};
+
+// Standard Modules
+use std::collections::HashMap;
+
+/*
+ * A traffic generator creates payments in the background while the simulation is running.
+ * The event manager asks each generator for the payments to send at every second of the simulation.
+ * Implement this trait and add it with LnSimulation::add_traffic_generator to plug in a custom traffic model.
+ */
+pub trait TrafficGenerator: Send {
+    /*
+     * Called at the start of the simulation with the total channel capacity of each node (key=node name, value=capacity in sats)
+     */
+    fn initialize(&mut self, capacities: &HashMap<String, u64>);
+
+    /*
+     * Get the payments that should be sent at this time in the simulation
+     */
+    fn generate(&mut self, time: u64) -> Vec<SimTransaction>;
+}
+
+/*
+ * How the sender and receiver of each payment are picked
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TrafficModel {
+    Poisson, // every sender/receiver pair (a node never pays itself) sends payments with poisson arrivals at the configured rate
+    Gravity // payments arrive at the configured rate and each pair is picked with a probability weighted by the capacity of both nodes
+}
+
+/*
+ * The distribution that the amount of each payment is picked from
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "distribution", rename_all = "snake_case")]
+pub enum AmountDistribution {
+    Fixed { amount: u64 },
+    Uniform { min: u64, max: u64 },
+    Pareto { min: u64, alpha: f64, max: u64 }, // heavy-tailed, most payments are close to min and a few are very large, truncated at max
+    LogNormal { median: u64, sigma: f64, max: u64 } // heavy-tailed, centered around the median, truncated at max
+}
+
+impl AmountDistribution {
+    /*
+     * Pick a payment amount from this distribution
+     * The heavy-tailed distributions are truncated at max: amounts above it are picked again instead of being set to max
+     */
+    pub fn sample(&self, rng: &mut StdRng) -> u64 {
+        match self {
+            AmountDistribution::Fixed { amount } => *amount,
+            AmountDistribution::Uniform { min, max } => rng.gen_range(*min..=*max),
+            AmountDistribution::Pareto { min, alpha, max } => {
+                match Pareto::new(*min as f64, *alpha) {
+                    Ok(d) => AmountDistribution::sample_below(rng, &d, *max).unwrap_or(*min),
+                    Err(_) => *min
+                }
+            },
+            AmountDistribution::LogNormal { median, sigma, max } => {
+                match LogNormal::new((*median as f64).ln(), *sigma) {
+                    Ok(d) => AmountDistribution::sample_below(rng, &d, *max).unwrap_or((*median).min(*max)),
+                    Err(_) => *median
+                }
+            }
+        }
+    }
+
+    /*
+     * Check the parameters of the distribution, returns a description of each problem that was found
+     */
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+        match self {
+            AmountDistribution::Fixed { amount } => {
+                if *amount == 0 {
+                    problems.push(String::from("amount must be greater than 0"));
+                }
+            },
+            AmountDistribution::Uniform { min, max } => {
+                if *min == 0 || max < min {
+                    problems.push(format!("uniform amount range {}-{} must be greater than 0 with min not above max", min, max));
+                }
+            },
+            AmountDistribution::Pareto { min, alpha, max } => {
+                if *min == 0 || *alpha <= 0.0 || max < min {
+                    problems.push(String::from("pareto amount needs a min and alpha greater than 0 and a max not below min"));
+                }
+            },
+            AmountDistribution::LogNormal { median, sigma, max } => {
+                if *median == 0 || *sigma < 0.0 || max < median {
+                    problems.push(String::from("log-normal amount needs a median greater than 0, a sigma not below 0 and a max not below the median"));
+                }
+            }
+        }
+
+        problems
+    }
+
+    /*
+     * Pick an amount from a distribution until it is at most max, returns None if max is too far in the tail to reach
+     */
+    fn sample_below<D: Distribution<f64>>(rng: &mut StdRng, distribution: &D, max: u64) -> Option<u64> {
+        for _ in 0..1000 {
+            let amount = (distribution.sample(rng).round() as u64).max(1);
+            if amount <= max {
+                return Some(amount);
+            }
+        }
+
+        None
+    }
+}
+
+/*
+ * The configuration of one of the built-in traffic generators
+ * Senders and receivers can be used to model roles, for example consumers paying merchants
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TrafficConfig {
+    pub model: TrafficModel,
+    pub senders: Vec<String>, // the nodes that send payments
+    pub receivers: Vec<String>, // the nodes that receive payments
+    pub rate: f64, // Poisson: payments per second for each sender/receiver pair where the sender is not the receiver, Gravity: payments per second for the whole network
+    pub amount: AmountDistribution,
+    pub start: u64, // the first second of the simulation that payments are sent
+    pub end: u64, // the last second of the simulation that payments are sent
+    pub seed: u64 // the seed for the random number generator so that the same payments are generated each run
+}
+
+impl TrafficConfig {
+    /*
+     * Check the parameters of the traffic generator, returns a description of each problem that was found
+     */
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+        if self.senders.is_empty() || self.receivers.is_empty() {
+            problems.push(String::from("there must be at least one sender and one receiver"));
+        }
+        if self.rate <= 0.0 {
+            problems.push(String::from("rate must be greater than 0"));
+        }
+        if self.end < self.start {
+            problems.push(format!("end time {} must not be before the start time {}", self.end, self.start));
+        }
+        problems.extend(self.amount.validate());
+
+        problems
+    }
+}
+
+/*
+ * The built-in traffic generator, creates payments from a TrafficConfig
+ */
+pub struct SimTrafficGenerator {
+    config: TrafficConfig,
+    rng: StdRng,
+    sender_weights: Vec<u64>,
+    receiver_weights: Vec<u64>
+}
+
+impl SimTrafficGenerator {
+    pub fn new(config: TrafficConfig) -> Self {
+        let generator = SimTrafficGenerator {
+            rng: StdRng::seed_from_u64(config.seed),
+            config: config,
+            sender_weights: Vec::new(),
+            receiver_weights: Vec::new()
+        };
+
+        generator
+    }
+
+    /*
+     * Get the number of sender/receiver pairs where the sender is not the receiver
+     */
+    fn num_pairs(&self) -> usize {
+        self.config.senders.iter().map(|s| self.config.receivers.iter().filter(|r| *r != s).count()).sum()
+    }
+
+    /*
+     * Pick a sender and receiver for a payment, returns None if there is no pair to pick from
+     * The receiver is picked from the receivers other than the sender so a payment is never dropped
+     */
+    fn pick_pair(&mut self) -> Option<(String, String)> {
+        // Poisson: each sender is weighted by the number of receivers it can pay so that every pair is equally likely
+        let senders: Vec<usize> = (0..self.config.senders.len()).filter(|i| self.config.receivers.iter().any(|r| r != &self.config.senders[*i])).collect();
+        if senders.is_empty() {
+            return None;
+        }
+        let weights: Vec<u64> = senders.iter().map(|i| match self.config.model {
+            TrafficModel::Poisson => self.config.receivers.iter().filter(|r| *r != &self.config.senders[*i]).count() as u64,
+            TrafficModel::Gravity => self.sender_weights[*i]
+        }).collect();
+        let s = self.pick_weighted(&senders, &weights);
+        let src = self.config.senders[s].clone();
+
+        let receivers: Vec<usize> = (0..self.config.receivers.len()).filter(|i| self.config.receivers[*i] != src).collect();
+        let weights: Vec<u64> = receivers.iter().map(|i| match self.config.model {
+            TrafficModel::Poisson => 1,
+            TrafficModel::Gravity => self.receiver_weights[*i]
+        }).collect();
+        let r = self.pick_weighted(&receivers, &weights);
+        let dest = self.config.receivers[r].clone();
+
+        Some((src, dest))
+    }
+
+    /*
+     * Pick one of the candidate indexes with a probability weighted by its weight, or uniformly if all of the weights are 0
+     */
+    fn pick_weighted(&mut self, candidates: &Vec<usize>, weights: &Vec<u64>) -> usize {
+        match WeightedIndex::new(weights) {
+            Ok(w) => candidates[w.sample(&mut self.rng)],
+            Err(_) => candidates[self.rng.gen_range(0..candidates.len())]
+        }
+    }
+}
+
+impl TrafficGenerator for SimTrafficGenerator {
+    fn initialize(&mut self, capacities: &HashMap<String, u64>) {
+        self.rng = StdRng::seed_from_u64(self.config.seed);
+        self.sender_weights = self.config.senders.iter().map(|n| *capacities.get(n).unwrap_or(&0)).collect();
+        self.receiver_weights = self.config.receivers.iter().map(|n| *capacities.get(n).unwrap_or(&0)).collect();
+    }
+
+    fn generate(&mut self, time: u64) -> Vec<SimTransaction> {
+        let mut txs: Vec<SimTransaction> = Vec::new();
+        if time < self.config.start || time > self.config.end || self.config.rate <= 0.0 {
+            return txs;
+        }
+
+        // The sum of independent poisson arrivals for every pair is a single poisson arrival with the combined rate,
+        // so pick the number of payments this second first and then pick the pair for each one
+        let rate = match self.config.model {
+            TrafficModel::Poisson => self.config.rate * self.num_pairs() as f64,
+            TrafficModel::Gravity => self.config.rate
+        };
+        let count = match Poisson::new(rate) {
+            Ok(d) => d.sample(&mut self.rng) as u64,
+            Err(_) => 0
+        };
+
+        for _ in 0..count {
+            match self.pick_pair() {
+                Some((src, dest)) => {
+                    txs.push(SimTransaction {
+                        id: None,
+                        src_node: src,
+                        dest_node: dest,
+                        amount_sats: self.config.amount.sample(&mut self.rng),
+                        status: SimTransactionStatus::NONE,
+                        keysend: false
+                    });
+                },
+                None => {}
+            }
+        }
+
+        txs
+    }
+}
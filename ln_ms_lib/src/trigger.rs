@@ -0,0 +1,186 @@
+// Project Modules
+use crate::sim_event::{SimulationEvent, SimResultsEvent};
+use crate::sim_results::SimResults;
+use crate::sim_channel::SimChannel;
+use crate::sim_transaction::{SimTransaction, SimTransactionStatus};
+
+// External Modules
+use serde::{Serialize, Deserialize};
+
+/*
+ * The condition that fires a trigger, it is checked against the results after every event that the network analyzer processes
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TriggerCondition {
+    ChannelBalanceBelow { channel: u64, node: String, percent: f64 }, // the node's balance in the channel drops below a percent of the channel capacity
+    NodeOffline { node: String }, // the node is stopped
+    PaymentFailed { src: Option<String>, dest: Option<String> } // a payment from src to dest fails, any node matches if one is not set
+}
+
+/*
+ * The events that a trigger creates when it fires
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TriggerAction {
+    OpenChannel { src: String, dest: String, amount: u64, id: u64 }, // open a new channel
+    CloseChannels { node: String, force: bool }, // the peers of the node close every channel they have open with it
+    RetryPayment { src: String } // send the payment that failed again from another node, only for a payment_failed condition
+}
+
+/*
+ * The configuration of a trigger, a rule that creates events when a condition is met instead of at a fixed time
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TriggerConfig {
+    pub name: String,
+    pub when: TriggerCondition,
+    pub then: TriggerAction,
+    #[serde(default)]
+    pub delay: u64, // the number of seconds after the condition is met that the events are sent
+    #[serde(default)]
+    pub repeat: bool // fire every time the condition is met again, otherwise the trigger only fires once
+}
+
+impl TriggerConfig {
+    /*
+     * Get a list of problems with the parameters of this trigger (empty if it is valid)
+     */
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+        match &self.when {
+            TriggerCondition::ChannelBalanceBelow { percent, .. } if *percent <= 0.0 || *percent > 100.0 => {
+                problems.push(String::from("percent must be greater than 0 and at most 100"));
+            },
+            _ => {}
+        }
+        match (&self.when, &self.then) {
+            (TriggerCondition::PaymentFailed { .. }, TriggerAction::RetryPayment { .. }) => {},
+            (_, TriggerAction::RetryPayment { .. }) => {
+                problems.push(String::from("retry_payment can only be used with a payment_failed condition"));
+            },
+            (_, TriggerAction::OpenChannel { .. }) if self.repeat => {
+                problems.push(String::from("a trigger that opens a channel can not repeat, the channel id can only be used once"));
+            },
+            _ => {}
+        }
+
+        problems
+    }
+}
+
+/*
+ * A trigger while the simulation is running
+ */
+pub struct Trigger {
+    pub config: TriggerConfig,
+    met: bool, // the condition was met after the last event, a balance condition has to stop being met before it can fire again
+    fired: bool
+}
+
+impl Trigger {
+    pub fn new(config: TriggerConfig) -> Self {
+        let trigger = Trigger {
+            config: config,
+            met: false,
+            fired: false
+        };
+
+        trigger
+    }
+
+    /*
+     * Check the condition after an event has been added to the results
+     * Returns the events to send if the trigger fires, the caller sends them delay seconds later
+     */
+    pub fn update(&mut self, event: &SimResultsEvent, results: &SimResults) -> Vec<SimulationEvent> {
+        let (met, failed_payment) = match &self.config.when {
+            TriggerCondition::ChannelBalanceBelow { channel, node, percent } => {
+                let open = results.get_open_channels(u64::MAX).unwrap_or(Vec::new());
+                let met = match open.iter().find(|c| c.id == *channel) {
+                    Some(c) if &c.src_node == node => (c.get_src_balance() as f64) < (c.get_total_balance() as f64) * percent / 100.0,
+                    Some(c) if &c.dest_node == node => (c.get_dest_balance() as f64) < (c.get_total_balance() as f64) * percent / 100.0,
+                    _ => false
+                };
+
+                // The balance has to go back above the threshold before the trigger fires again
+                let rising = met && !self.met;
+                self.met = met;
+                (rising, None)
+            },
+            TriggerCondition::NodeOffline { node } => {
+                (event.success && matches!(&event.event, SimulationEvent::StopNodeEvent(name) if name == node), None)
+            },
+            TriggerCondition::PaymentFailed { src, dest } => {
+                match &event.event {
+                    SimulationEvent::PaymentFailedEvent(id) => {
+                        match results.transactions.txs.iter().find(|t| t.transaction.id.as_ref() == Some(id)) {
+                            Some(t) => {
+                                let matches = src.as_ref().map_or(true, |s| s == &t.transaction.src_node) && dest.as_ref().map_or(true, |d| d == &t.transaction.dest_node);
+                                (matches, Some(t.transaction.clone()))
+                            },
+                            None => (false, None)
+                        }
+                    },
+                    _ => (false, None)
+                }
+            }
+        };
+
+        if !met || (self.fired && !self.config.repeat) {
+            return Vec::new();
+        }
+        self.fired = true;
+
+        match &self.config.then {
+            TriggerAction::OpenChannel { src, dest, amount, id } => {
+                let channel = SimChannel {
+                    src_node: src.clone(),
+                    dest_node: dest.clone(),
+                    src_balance_sats: *amount,
+                    dest_balance_sats: 0,
+                    id: *id,
+                    short_id: None,
+                    run_time_id: None,
+                    funding_tx: None,
+                    penalty_reserve_sats: None,
+                    src_policy: None,
+                    dest_policy: None
+                };
+                vec![SimulationEvent::OpenChannelEvent(channel)]
+            },
+            TriggerAction::CloseChannels { node, force } => {
+                let open = results.get_open_channels(u64::MAX).unwrap_or(Vec::new());
+                open.iter().filter(|c| &c.src_node == node || &c.dest_node == node).map(|c| {
+                    let peer = if &c.src_node == node { c.dest_node.clone() } else { c.src_node.clone() };
+                    if *force {
+                        SimulationEvent::ForceCloseChannelEvent(peer, c.id)
+                    } else {
+                        SimulationEvent::CloseChannelEvent(peer, c.id)
+                    }
+                }).collect()
+            },
+            TriggerAction::RetryPayment { src } => {
+                match failed_payment {
+                    Some(tx) => {
+                        let retry = SimTransaction {
+                            id: None,
+                            src_node: src.clone(),
+                            dest_node: tx.dest_node.clone(),
+                            amount_sats: tx.amount_sats,
+                            status: SimTransactionStatus::NONE,
+                            keysend: tx.keysend
+                        };
+                        if tx.keysend {
+                            vec![SimulationEvent::KeysendEvent(retry)]
+                        } else {
+                            vec![SimulationEvent::TransactionEvent(retry)]
+                        }
+                    },
+                    None => Vec::new()
+                }
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 // External Modules
 use actix_files as fs;
-use actix_web::{App, HttpServer};
+use actix_web::{App, HttpServer, web};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::{SwaggerUi, Url};
 
@@ -20,6 +20,11 @@ async fn main() -> std::io::Result<()> {
             api::create_channel,
             api::create_event,
             api::run_sim,
+            api::pause_sim,
+            api::resume_sim,
+            api::step_sim,
+            api::stop_sim,
+            api::add_run_event,
             api::import_network,
             api::export_network,
             api::import_transactions
@@ -30,6 +35,8 @@ async fn main() -> std::io::Result<()> {
             api::CreateChannelRequest,
             api::CreateEventRequest,
             api::RunSimulationRequest,
+            api::StepSimulationRequest,
+            api::AddRunEventRequest,
             api::ImportNetworkRequest,
             api::ExportNetworkRequest,
             api::ImportTransactionsRequest
@@ -42,8 +49,10 @@ async fn main() -> std::io::Result<()> {
     println!("View simulated network here: http://localhost:8080/network_monitor");
     println!("View simulation results here: http://localhost:8080/results");
 
+    let state = web::Data::new(api::SimState::new());
     HttpServer::new(move || {
         App::new()
+            .app_data(state.clone())
             .service(fs::Files::new("/static", "./static").show_files_listing())
             .service(api::network_monitor)
             .service(api::get_sim)
@@ -53,6 +62,11 @@ async fn main() -> std::io::Result<()> {
             .service(api::create_channel)
             .service(api::create_event)
             .service(api::run_sim)
+            .service(api::pause_sim)
+            .service(api::resume_sim)
+            .service(api::step_sim)
+            .service(api::stop_sim)
+            .service(api::add_run_event)
             .service(api::import_network)
             .service(api::results)
             .service(SwaggerUi::new("/swagger-ui/{_:.*}").urls(vec![
@@ -72,12 +86,15 @@ pub mod api {
     // Project Modules
     use ln_ms_lib::LnSimulation;
     use ln_ms_lib::sim_results::SimResults;
+    use ln_ms_lib::sim_event::SimulationEvent;
+    use ln_ms_lib::sim_control::SimControl;
+    use ln_ms_lib::sim_handle::{SimulationHandle, RunConfig};
 
     // Standard Modules
-    use std::thread;
+    use std::sync::Mutex;
 
     // External Modules
-    use actix_web::{get, post, HttpResponse, Responder, Result, web::{Json, Path}};
+    use actix_web::{get, post, HttpResponse, Responder, Result, web::{Data, Json, Path}};
     use serde::{Deserialize, Serialize};
     use utoipa::{ToSchema};
 
@@ -89,27 +106,82 @@ pub mod api {
             .body(include_str!("../static/network_monitor.html")))
     }
 
-    // Get the results of a simulation
-    #[get("/results")]
-    pub async fn results() -> Result<HttpResponse> {
-        unsafe {
-            match &RESULTS {
-                Some(res) => {
-                    let html = res.get_results_page();
-                    Ok(HttpResponse::Ok()
-                        .content_type("text/html; charset=utf-8")
-                        .body(html))
+    /* 
+     * TODO: the simulations will not be kept in memory, each endpoint will get the LnSimulation object from the database
+     * - for simplicity the server holds one simulation right now in order to demonstate the use case
+     */
+    pub struct SimState {
+        sim: Mutex<Option<LnSimulation>>, // the simulation that is being defined, it is moved into the run while it is running
+        run: Mutex<Option<SimulationHandle>>, // the running simulation
+        results: Mutex<Option<SimResults>> // the results of the last run
+    }
+
+    impl SimState {
+        pub fn new() -> Self {
+            let state = SimState {
+                sim: Mutex::new(None),
+                run: Mutex::new(None),
+                results: Mutex::new(None)
+            };
+
+            state
+        }
+
+        /*
+         * Get the simulation and its results back from the run once it has ended
+         */
+        async fn finish_run(&self) {
+            let handle = {
+                let mut run = self.run.lock().unwrap();
+                match run.as_ref() {
+                    Some(h) if h.is_finished() => run.take(),
+                    _ => None
+                }
+            };
+            match handle {
+                Some(h) => {
+                    match h.await_simulation().await {
+                        Some((sim, Ok(res))) => {
+                            *self.sim.lock().unwrap() = Some(sim);
+                            *self.results.lock().unwrap() = Some(res);
+                        },
+                        Some((sim, Err(e))) => {
+                            println!("could not run the simulation: {:?}", e);
+                            *self.sim.lock().unwrap() = Some(sim);
+                        },
+                        None => println!("could not get the simulation back from the run")
+                    }
                 },
-                None => Ok(HttpResponse::Ok().content_type("text/html; charset=utf-8").body("could not get results"))
+                None => {}
             }
         }
+
+        /*
+         * Get the control of the running simulation, or of the simulation that is being defined so it can be paused before it starts
+         */
+        fn get_control(&self) -> Option<SimControl> {
+            match self.run.lock().unwrap().as_ref() {
+                Some(h) => return Some(h.get_control()),
+                None => {}
+            }
+            self.sim.lock().unwrap().as_ref().map(|s| s.get_control())
+        }
+    }
+
+    // Get the results of a simulation
+    #[get("/results")]
+    pub async fn results(state: Data<SimState>) -> Result<HttpResponse> {
+        state.finish_run().await;
+        match state.results.lock().unwrap().as_ref() {
+            Some(res) => {
+                let html = res.get_results_page();
+                Ok(HttpResponse::Ok()
+                    .content_type("text/html; charset=utf-8")
+                    .body(html))
+            },
+            None => Ok(HttpResponse::Ok().content_type("text/html; charset=utf-8").body("could not get results"))
+        }
     }
-    /* 
-     * TODO: this will not be a global variable, each endpoint will get the LnSimulation object from the database
-     * - for simplicity it will be used as a global variable right now in order to demonstate the use case
-     */
-    static mut SIM: Option<LnSimulation> = None;
-    static mut RESULTS: Option<SimResults> = None;
 
     // TODO: implement
     #[utoipa::path(
@@ -137,17 +209,22 @@ pub mod api {
         )
     )]
     #[get("/get_network/{sim_name}")]
-    pub async fn get_network(sim_name: Path<String>) -> impl Responder {
+    pub async fn get_network(sim_name: Path<String>, state: Data<SimState>) -> impl Responder {
         let _sim_name = sim_name.into_inner();
-        unsafe {
-            // TODO: get the simulation object from a database and do not use a static global unsafe variable
-            match SIM.as_ref() {
-                Some(s) => { 
-                    let network_json = s.get_runtime_network_graph();
-                    HttpResponse::Ok().content_type("text/json; charset=utf-8").body(network_json)
-                }
-                None => HttpResponse::Ok().body("")
+        state.finish_run().await;
+        // TODO: get the simulation object from a database
+        match state.run.lock().unwrap().as_ref() {
+            Some(h) => {
+                return HttpResponse::Ok().content_type("text/json; charset=utf-8").body(h.runtime_graph());
             }
+            None => {}
+        }
+        match state.sim.lock().unwrap().as_ref() {
+            Some(s) => { 
+                let network_json = s.get_runtime_network_graph();
+                HttpResponse::Ok().content_type("text/json; charset=utf-8").body(network_json)
+            }
+            None => HttpResponse::Ok().body("")
         }
     }
 
@@ -166,12 +243,10 @@ pub mod api {
         )
     )]
     #[post("/create_sim")]
-    pub async fn create_sim(req: Json<CreateSimRequest>) -> impl Responder {
+    pub async fn create_sim(req: Json<CreateSimRequest>, state: Data<SimState>) -> impl Responder {
         let create_sim_req = req.into_inner();
-        unsafe {
-            // TODO: create the simulation object and save it to a database and do not use a static global unsafe variable
-            SIM = Option::Some(LnSimulation::new(create_sim_req.name, create_sim_req.duration, create_sim_req.num_nodes));
-        }
+        // TODO: create the simulation object and save it to a database
+        *state.sim.lock().unwrap() = Option::Some(LnSimulation::new(create_sim_req.name, create_sim_req.duration, create_sim_req.num_nodes));
         HttpResponse::Ok().body("Created Simulation")
     }
 
@@ -191,17 +266,16 @@ pub mod api {
         )
     )]
     #[post("/create_node")]
-    pub async fn create_node(req: Json<CreateNodeRequest>) -> impl Responder {
+    pub async fn create_node(req: Json<CreateNodeRequest>, state: Data<SimState>) -> impl Responder {
         let create_node_req = req.into_inner();
-        unsafe {
-            // TODO: get the simulation object from a database and do not use a static global unsafe variable
-            match SIM.as_mut() {
-                Some(s) => { 
-                    s.create_node(create_node_req.name, create_node_req.initial_balance, create_node_req.running);
-                    HttpResponse::Ok().body("Created Node")
-                }
-                None => HttpResponse::NotFound().body("Simulation not found, try creating a new simulation before creating a node")
+        state.finish_run().await;
+        // TODO: get the simulation object from a database
+        match state.sim.lock().unwrap().as_mut() {
+            Some(s) => { 
+                s.create_node(create_node_req.name, create_node_req.initial_balance, create_node_req.running);
+                HttpResponse::Ok().body("Created Node")
             }
+            None => HttpResponse::NotFound().body("Simulation not found, try creating a new simulation before creating a node")
         }
     }
 
@@ -222,17 +296,16 @@ pub mod api {
         )
     )]
     #[post("/create_channel")]
-    pub async fn create_channel(req: Json<CreateChannelRequest>) -> impl Responder {
+    pub async fn create_channel(req: Json<CreateChannelRequest>, state: Data<SimState>) -> impl Responder {
         let create_channel_req = req.into_inner();
-        unsafe {
-            // TODO: get the simulation object from a database and do not use a static global unsafe variable
-            match SIM.as_mut() {
-                Some(s) => {
-                    s.create_channel(create_channel_req.src_name, create_channel_req.dest_name, create_channel_req.amount, create_channel_req.id);
-                    HttpResponse::Ok().body("Created Channel")
-                }
-                None => HttpResponse::NotFound().body("Simulation not found, try creating a new simulation before creating a channel")
+        state.finish_run().await;
+        // TODO: get the simulation object from a database
+        match state.sim.lock().unwrap().as_mut() {
+            Some(s) => {
+                s.create_channel(create_channel_req.src_name, create_channel_req.dest_name, create_channel_req.amount, create_channel_req.id);
+                HttpResponse::Ok().body("Created Channel")
             }
+            None => HttpResponse::NotFound().body("Simulation not found, try creating a new simulation before creating a channel")
         }
     }
 
@@ -255,28 +328,27 @@ pub mod api {
         )
     )]
     #[post("/create_event")]
-    pub async fn create_event(req: Json<CreateEventRequest>) -> impl Responder {
+    pub async fn create_event(req: Json<CreateEventRequest>, state: Data<SimState>) -> impl Responder {
         let create_event_req = req.into_inner();
-        unsafe {
-            // TODO: get the simulation object from a database and do not use a static global unsafe variable
-            match SIM.as_mut() {
-                Some(s) => {
-                    // TODO: this will need to be much more generic and the request will need to only allow supported events
-                    if create_event_req.event_type == "NodeOfflineEvent" {
-                        s.create_stop_node_event(create_event_req.src_name, create_event_req.time);
-                    } else if create_event_req.event_type == "NodeOnlineEvent"{
-                        s.create_start_node_event(create_event_req.src_name, create_event_req.time);
-                    } else if create_event_req.event_type == "OpenChannelEvent"{
-                        s.create_open_channel_event(create_event_req.src_name, create_event_req.dest_name, create_event_req.amount, create_event_req.time, create_event_req.channel_id);
-                    } else if create_event_req.event_type == "CloseChannelEvent"{
-                        s.create_close_channel_event(create_event_req.src_name, create_event_req.channel_id, create_event_req.time);
-                    } else if create_event_req.event_type == "TransactionEvent" {
-                        s.create_transaction_event(create_event_req.src_name, create_event_req.dest_name, create_event_req.amount, create_event_req.time);
-                    }
-                    HttpResponse::Ok().body("Event Created")
+        state.finish_run().await;
+        // TODO: get the simulation object from a database
+        match state.sim.lock().unwrap().as_mut() {
+            Some(s) => {
+                // TODO: this will need to be much more generic and the request will need to only allow supported events
+                if create_event_req.event_type == "NodeOfflineEvent" {
+                    s.create_stop_node_event(create_event_req.src_name, create_event_req.time);
+                } else if create_event_req.event_type == "NodeOnlineEvent"{
+                    s.create_start_node_event(create_event_req.src_name, create_event_req.time);
+                } else if create_event_req.event_type == "OpenChannelEvent"{
+                    s.create_open_channel_event(create_event_req.src_name, create_event_req.dest_name, create_event_req.amount, create_event_req.time, create_event_req.channel_id);
+                } else if create_event_req.event_type == "CloseChannelEvent"{
+                    s.create_close_channel_event(create_event_req.src_name, create_event_req.channel_id, create_event_req.time);
+                } else if create_event_req.event_type == "TransactionEvent" {
+                    s.create_transaction_event(create_event_req.src_name, create_event_req.dest_name, create_event_req.amount, create_event_req.time);
                 }
-                None => HttpResponse::NotFound().body("Simulation not found, try creating a new simulation before creating an event")
+                HttpResponse::Ok().body("Event Created")
             }
+            None => HttpResponse::NotFound().body("Simulation not found, try creating a new simulation before creating an event")
         }
     }
 
@@ -291,29 +363,135 @@ pub mod api {
         request_body = RunSimulationRequest,
         responses(
             (status = 200, description = "Successfully started a simulation", body = String),
+            (status = 400, description = "A simulation is already running", body = String),
             (status = 404, description = "Simulation not found", body = String)
         )
     )]
     #[post("/run_sim")]
-    pub async fn run_sim(req: Json<RunSimulationRequest>) -> impl Responder {
+    pub async fn run_sim(req: Json<RunSimulationRequest>, state: Data<SimState>) -> impl Responder {
         let run_sim_request = req.into_inner();
-        unsafe {
-            // TODO: get the simulation object from a database and do not use a static global unsafe variable
-            match SIM.as_mut() {
-                Some(s) => {
-                    thread::spawn(move || {
-                        let res = s.run(run_sim_request.nigiri);
-                        match res {
-                            Ok(r) => {
-                                RESULTS = Some(r);
-                            },
-                            Err(_) => {}
-                        }
-                    });
-                    HttpResponse::Ok().body(String::from("Running Simulation: ") + &run_sim_request.name)
+        state.finish_run().await;
+        let mut run = state.run.lock().unwrap();
+        if run.is_some() {
+            return HttpResponse::BadRequest().body("A simulation is already running, stop it or wait for it to end before running a simulation");
+        }
+        // TODO: get the simulation object from a database
+        match state.sim.lock().unwrap().take() {
+            Some(s) => {
+                *run = Some(s.start(RunConfig { nigiri: run_sim_request.nigiri }));
+                HttpResponse::Ok().body(String::from("Running Simulation: ") + &run_sim_request.name)
+            }
+            None => HttpResponse::NotFound().body("Simulation not found, try creating a new simulation before running a simulation")
+        }
+    }
+
+    #[utoipa::path(
+        responses(
+            (status = 200, description = "Successfully paused the simulation", body = String),
+            (status = 404, description = "Simulation not found", body = String)
+        )
+    )]
+    #[post("/pause_sim")]
+    pub async fn pause_sim(state: Data<SimState>) -> impl Responder {
+        match state.get_control() {
+            Some(control) => {
+                control.pause();
+                HttpResponse::Ok().body(format!("Paused Simulation at {} seconds", control.get_time()))
+            }
+            None => HttpResponse::NotFound().body("Simulation not found, try creating a new simulation before pausing a simulation")
+        }
+    }
+
+    #[utoipa::path(
+        responses(
+            (status = 200, description = "Successfully resumed the simulation", body = String),
+            (status = 404, description = "Simulation not found", body = String)
+        )
+    )]
+    #[post("/resume_sim")]
+    pub async fn resume_sim(state: Data<SimState>) -> impl Responder {
+        match state.get_control() {
+            Some(control) => {
+                control.resume();
+                HttpResponse::Ok().body("Resumed Simulation")
+            }
+            None => HttpResponse::NotFound().body("Simulation not found, try creating a new simulation before resuming a simulation")
+        }
+    }
+
+    // A request to run the simulation until a sim time and then pause it
+    #[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+    pub struct StepSimulationRequest {
+        time: u64
+    }
+
+    #[utoipa::path(
+        request_body = StepSimulationRequest,
+        responses(
+            (status = 200, description = "Successfully stepped the simulation", body = String),
+            (status = 404, description = "Simulation not found", body = String)
+        )
+    )]
+    #[post("/step_sim")]
+    pub async fn step_sim(req: Json<StepSimulationRequest>, state: Data<SimState>) -> impl Responder {
+        let step_sim_request = req.into_inner();
+        match state.get_control() {
+            Some(control) => {
+                control.step_to(step_sim_request.time);
+                HttpResponse::Ok().body(format!("Stepping Simulation to {} seconds", step_sim_request.time))
+            }
+            None => HttpResponse::NotFound().body("Simulation not found, try creating a new simulation before stepping a simulation")
+        }
+    }
+
+    #[utoipa::path(
+        responses(
+            (status = 200, description = "Successfully stopped the simulation", body = String),
+            (status = 404, description = "No simulation is running", body = String)
+        )
+    )]
+    #[post("/stop_sim")]
+    pub async fn stop_sim(state: Data<SimState>) -> impl Responder {
+        match state.run.lock().unwrap().as_ref() {
+            Some(h) => {
+                h.stop();
+                HttpResponse::Ok().body(format!("Stopping Simulation at {} seconds", h.current_time()))
+            }
+            None => HttpResponse::NotFound().body("No simulation is running")
+        }
+    }
+
+    // A request to add an event to a running simulation, at a sim time or as soon as possible if no time is given
+    #[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+    pub struct AddRunEventRequest {
+        #[schema(value_type = Object)]
+        event: SimulationEvent,
+        time: Option<u64>
+    }
+
+    #[utoipa::path(
+        params(
+            ("id", description = "The name of the running simulation")
+        ),
+        request_body = AddRunEventRequest,
+        responses(
+            (status = 200, description = "Successfully added the event to the running simulation", body = String),
+            (status = 400, description = "The event could not be added", body = String),
+            (status = 404, description = "Simulation not found", body = String)
+        )
+    )]
+    #[post("/runs/{id}/events")]
+    pub async fn add_run_event(id: Path<String>, req: Json<AddRunEventRequest>, state: Data<SimState>) -> impl Responder {
+        let id = id.into_inner();
+        let add_event_request = req.into_inner();
+        match state.run.lock().unwrap().as_ref() {
+            Some(h) if h.get_name() == id => {
+                match h.add_event(add_event_request.event, add_event_request.time) {
+                    Ok(time) => HttpResponse::Ok().body(format!("Event Added at {} seconds", time)),
+                    Err(e) => HttpResponse::BadRequest().body(format!("could not add the event: {}", e))
                 }
-                None => HttpResponse::NotFound().body("Simulation not found, try creating a new simulation before running a simulation")
             }
+            _ => HttpResponse::NotFound().body(String::from("Simulation not found: ") + &id)
         }
     }
 
@@ -333,18 +511,17 @@ pub mod api {
         )
     )]
     #[post("/import_network")]
-    pub async fn import_network(req: Json<ImportNetworkRequest>) -> impl Responder {
+    pub async fn import_network(req: Json<ImportNetworkRequest>, state: Data<SimState>) -> impl Responder {
         let import_request = req.into_inner();
-        unsafe {
-            // TODO: get the simulation object from a database and do not use a static global unsafe variable
-            match SIM.as_mut() {
-                Some(s) => {
-                    s.import_network(import_request.filename, import_request.import_map);
-                    HttpResponse::Ok().body("Network Imported")
-                }
-                None => HttpResponse::NotFound().body("Simulation not found, try creating a new simulation before importing a network definition")
-            }   
-        }
+        state.finish_run().await;
+        // TODO: get the simulation object from a database
+        match state.sim.lock().unwrap().as_mut() {
+            Some(s) => {
+                s.import_network(import_request.filename, import_request.import_map);
+                HttpResponse::Ok().body("Network Imported")
+            }
+            None => HttpResponse::NotFound().body("Simulation not found, try creating a new simulation before importing a network definition")
+        }   
     }
 
     // A request to export a network definition from a file
@@ -362,18 +539,17 @@ pub mod api {
         )
     )]
     #[post("/export_network")]
-    pub async fn export_network(req: Json<ExportNetworkRequest>) -> impl Responder {
+    pub async fn export_network(req: Json<ExportNetworkRequest>, state: Data<SimState>) -> impl Responder {
         let export_request = req.into_inner();
-        unsafe {
-            // TODO: get the simulation object from a database and do not use a static global unsafe variable
-            match SIM.as_mut() {
-                Some(s) => {
-                    s.export_network(export_request.filename);
-                    HttpResponse::Ok().body("Network Imported")
-                }
-                None => HttpResponse::NotFound().body("Simulation not found, try creating a new simulation before importing a network definition")
-            }   
-        }
+        state.finish_run().await;
+        // TODO: get the simulation object from a database
+        match state.sim.lock().unwrap().as_mut() {
+            Some(s) => {
+                s.export_network(export_request.filename);
+                HttpResponse::Ok().body("Network Imported")
+            }
+            None => HttpResponse::NotFound().body("Simulation not found, try creating a new simulation before importing a network definition")
+        }   
     }
 
     // A request to import a list of transactions from a file
@@ -391,17 +567,16 @@ pub mod api {
         )
     )]
     #[post("/import_transactions")]
-    pub async fn import_transactions(req: Json<ImportTransactionsRequest>) -> impl Responder {
+    pub async fn import_transactions(req: Json<ImportTransactionsRequest>, state: Data<SimState>) -> impl Responder {
         let import_request = req.into_inner();
-        unsafe {
-            // TODO: get the simulation object from a database and do not use a static global unsafe variable
-            match SIM.as_mut() {
-                Some(s) => {
-                    s.import_transactions(import_request.filename);
-                    HttpResponse::Ok().body("Transactions Imported")
-                }
-                None => HttpResponse::NotFound().body("Simulation not found, try creating a new simulation before importing transactions")
-            }   
-        }
+        state.finish_run().await;
+        // TODO: get the simulation object from a database
+        match state.sim.lock().unwrap().as_mut() {
+            Some(s) => {
+                s.import_transactions(import_request.filename);
+                HttpResponse::Ok().body("Transactions Imported")
+            }
+            None => HttpResponse::NotFound().body("Simulation not found, try creating a new simulation before importing transactions")
+        }   
     }    
 }